@@ -9,4 +9,16 @@ cfg_alloc! {
     pub fn ax_dealloc(ptr: NonNull<u8>, layout: Layout) {
         axalloc::global_allocator().dealloc(ptr, layout)
     }
+
+    pub fn ax_memory_usage() -> (usize, usize) {
+        // The page size of the page allocator in `axalloc`.
+        const PAGE_SIZE: usize = 0x1000;
+        let allocator = axalloc::global_allocator();
+        // The byte allocator takes its memory from the page allocator, so
+        // its free bytes are part of the used pages.
+        let free_bytes = allocator.available_bytes();
+        let used = (allocator.used_pages() * PAGE_SIZE).saturating_sub(free_bytes);
+        let available = allocator.available_pages() * PAGE_SIZE + free_bytes;
+        (used, available)
+    }
 }
@@ -33,8 +33,28 @@ mod stdio {
     }
 
     pub fn ax_console_write_fmt_debug(level: u8, args: fmt::Arguments) -> fmt::Result {
-        axhal::console::print_debug(level, args)
+        axhal::console::print_debug(level, args).map(|_| ())
     }
+
+    pub fn ax_console_max_debug_level() -> u8 {
+        axhal::console::max_level()
+    }
+
+    pub fn ax_console_set_max_debug_level(level: u8) -> crate::AxResult {
+        axhal::console::set_max_level(level).or_else(|_| {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_console_set_max_debug_level: invalid debug level"
+            )
+        })
+    }
+
+    pub fn ax_console_set_debug_prefix(enable: bool) {
+        axhal::console::set_debug_prefix(enable)
+    }
+
+    #[cfg(feature = "log-buffer")]
+    pub use axhal::log_buffer::{read_since as ax_console_read_log, LogRecord as AxLogRecord};
 }
 
 pub use self::mem::*;
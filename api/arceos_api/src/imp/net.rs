@@ -2,6 +2,7 @@ use crate::io::AxPollState;
 use axerrno::AxResult;
 use axnet::{UdpSocket, TcpSocket};
 use core::net::{IpAddr, SocketAddr};
+use core::time::Duration;
 
 /// A handle to a TCP socket.
 pub struct AxTcpSocketHandle(TcpSocket);
@@ -30,6 +31,16 @@ pub fn ax_tcp_set_nonblocking(socket: &AxTcpSocketHandle, nonblocking: bool) ->
     Ok(())
 }
 
+pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_recv_timeout(timeout);
+    Ok(())
+}
+
+pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_send_timeout(timeout);
+    Ok(())
+}
+
 pub fn ax_tcp_connect(socket: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult {
     socket.0.connect(addr)
 }
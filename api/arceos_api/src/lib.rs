@@ -63,6 +63,9 @@ pub mod mem {
         /// Deallocate the memory block at the given `ptr` pointer with the given
         /// `layout`, which should be allocated by [`ax_alloc`].
         pub fn ax_dealloc(ptr: NonNull<u8>, layout: Layout);
+        /// Returns the numbers of bytes in use and still available in the
+        /// global allocator.
+        pub fn ax_memory_usage() -> (usize, usize);
     }
 }
 
@@ -76,8 +79,29 @@ pub mod stdio {
         pub fn ax_console_write_bytes(buf: &[u8]) -> crate::AxResult<usize>;
         /// Writes a formatted string to the console.
         pub fn ax_console_write_fmt(args: fmt::Arguments) -> fmt::Result;
-        /// Writes a formatted string to the console(debug).
+        /// Writes a formatted string to the console(debug), unless its debug
+        /// level is filtered out, which is not an error.
         pub fn ax_console_write_fmt_debug(level: u8, args: fmt::Arguments) -> fmt::Result;
+        /// Returns the maximum debug level of the strings written.
+        pub fn ax_console_max_debug_level() -> u8;
+        /// Sets the maximum debug level of the strings written, from 0 to 4.
+        pub fn ax_console_set_max_debug_level(level: u8) -> crate::AxResult;
+        /// Sets whether the strings written(debug) start with the time since
+        /// boot and the current CPU.
+        pub fn ax_console_set_debug_prefix(enable: bool);
+    }
+
+    define_api_type! {
+        @cfg "log-buffer";
+        pub type AxLogRecord;
+    }
+
+    define_api! {
+        @cfg "log-buffer";
+        /// Returns the records of the console output kept since `cursor`,
+        /// oldest first, with the cursor to read those kept after them; 0
+        /// reads all those kept.
+        pub fn ax_console_read_log(cursor: usize) -> (alloc::vec::Vec<AxLogRecord>, usize);
     }
 }
 
@@ -227,6 +251,7 @@ pub mod fs {
 pub mod net {
     use crate::{io::AxPollState, AxResult};
     use core::net::{IpAddr, SocketAddr};
+    use core::time::Duration;
 
     define_api_type! {
         @cfg "net";
@@ -247,6 +272,12 @@ pub mod net {
         pub fn ax_tcp_peer_addr(socket: &AxTcpSocketHandle) -> AxResult<SocketAddr>;
         /// Moves this TCP socket into or out of nonblocking mode.
         pub fn ax_tcp_set_nonblocking(socket: &AxTcpSocketHandle, nonblocking: bool) -> AxResult;
+        /// Sets the timeout of receiving or accepting on the TCP socket, or
+        /// `None` for no timeout.
+        pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Sets the timeout of sending or connecting on the TCP socket, or
+        /// `None` for no timeout.
+        pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
 
         /// Connects the TCP socket to the given address and port.
         pub fn ax_tcp_connect(handle: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult;
@@ -257,7 +288,8 @@ pub mod net {
         /// Accepts a new connection on the TCP socket.
         ///
         /// This function will block the calling thread until a new TCP connection
-        /// is established. When established, a new TCP socket is returned.
+        /// is established, or the receive timeout of the socket has elapsed.
+        /// When established, a new TCP socket is returned.
         pub fn ax_tcp_accept(socket: &AxTcpSocketHandle) -> AxResult<(AxTcpSocketHandle, SocketAddr)>;
 
         /// Transmits data in the given buffer on the TCP socket.
@@ -0,0 +1,84 @@
+//! Console output benchmark, measuring what writing a line costs when it is
+//! written a byte at a time, taking the console lock for each byte as `print!`
+//! once did, and when it is written at once, taking the lock once.
+//!
+//! Each way, `LINES` lines of `LINE_LEN` bytes, a full row of the VGA text
+//! screen with the newline, are written, and the time per line is reported,
+//! in cycles of the time-stamp counter on x86_64 and in nanoseconds
+//! elsewhere, e.g. with `make A=apps/consolebench run`.
+//!
+//! The VGA console takes its lock once per write since it moves the hardware
+//! cursor after each write; this only measures the difference. Numbers are
+//! only meaningful under QEMU or on hardware, where the port writes of the
+//! cursor and the serial mirror cost what they do.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::io::{self, prelude::*};
+
+/// The number of lines written each way.
+const LINES: u64 = 200;
+/// The length of a line, with its newline.
+const LINE_LEN: usize = 80;
+
+/// The unit of `measure`.
+#[cfg(target_arch = "x86_64")]
+const UNIT: &str = "cycles";
+#[cfg(not(target_arch = "x86_64"))]
+const UNIT: &str = "ns";
+
+/// Returns the time running `f` takes.
+#[cfg(target_arch = "x86_64")]
+fn measure(f: impl FnOnce() -> io::Result<()>) -> io::Result<u64> {
+    use core::arch::x86_64::_rdtsc;
+
+    let start = unsafe { _rdtsc() };
+    f()?;
+    Ok(unsafe { _rdtsc() } - start)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn measure(f: impl FnOnce() -> io::Result<()>) -> io::Result<u64> {
+    let start = std::time::Instant::now();
+    f()?;
+    Ok(start.elapsed().as_nanos() as u64)
+}
+
+/// Writes `LINES` times `line`, in writes of `chunk` bytes, returning the
+/// time it takes per line.
+fn write_lines(line: &[u8], chunk: usize) -> io::Result<u64> {
+    let mut out = io::stdout();
+    let total = measure(|| {
+        for _ in 0..LINES {
+            for part in line.chunks(chunk) {
+                out.write_all(part)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(total / LINES)
+}
+
+fn run() -> io::Result<()> {
+    let mut line = [b'.'; LINE_LEN];
+    line[LINE_LEN - 1] = b'\n';
+    let per_byte = write_lines(&line, 1)?;
+    let per_line = write_lines(&line, LINE_LEN)?;
+    println!("{} lines of {} bytes, time per line:", LINES, LINE_LEN);
+    println!("  a byte at a time: {:>10} {}", per_byte, UNIT);
+    println!("  a line at a time: {:>10} {}", per_line, UNIT);
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, console benchmark!");
+    if let Err(e) = run() {
+        println!("benchmark failed: {}", e);
+    }
+}
@@ -0,0 +1,187 @@
+//! Chunked transfer encoding for bodies of unknown length.
+
+use std::io::{self, prelude::*};
+
+use crate::{invalid_data, read_line, MAX_LINE_LEN};
+
+/// A writer that sends everything written to it as HTTP chunks.
+///
+/// Each non-empty `write` call becomes one chunk. The terminating zero-length
+/// chunk is sent by [`ChunkedWriter::finish`], or on drop if `finish` was not
+/// called.
+pub struct ChunkedWriter<'a, W: Write + ?Sized> {
+    inner: &'a mut W,
+    finished: bool,
+}
+
+impl<'a, W: Write + ?Sized> ChunkedWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            finished: false,
+        }
+    }
+
+    /// Sends the last chunk, ending the body.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + ?Sized> Write for ChunkedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty chunk would end the body.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:x}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + ?Sized> Drop for ChunkedWriter<'_, W> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.inner.write_all(b"0\r\n\r\n");
+        }
+    }
+}
+
+/// A reader that decodes a chunked body from `inner`.
+///
+/// Chunk extensions and trailer fields are skipped. The reader ends after the
+/// last chunk, leaving the bytes after it in `inner`; a body cut short fails
+/// with an `InvalidData` error.
+pub struct ChunkedReader<'a, R: BufRead + ?Sized> {
+    inner: &'a mut R,
+    /// The bytes left in the current chunk.
+    remaining: u64,
+    done: bool,
+}
+
+impl<'a, R: BufRead + ?Sized> ChunkedReader<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    /// Reads the size line of the next chunk, and the trailer section after
+    /// the last one.
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let line = read_line(self.inner, MAX_LINE_LEN)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        self.remaining = u64::from_str_radix(size, 16).map_err(|_| invalid_data())?;
+        if self.remaining == 0 {
+            while !read_line(self.inner, MAX_LINE_LEN)?.is_empty() {}
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead + ?Sized> Read for ChunkedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 && !self.done {
+            self.next_chunk()?;
+        }
+        if self.done {
+            return Ok(0);
+        }
+        let len = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(invalid_data());
+        }
+        self.remaining -= n as u64;
+        if self.remaining == 0 && !read_line(self.inner, 0)?.is_empty() {
+            return Err(invalid_data());
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framing() {
+        let mut out = Vec::new();
+        let mut writer = ChunkedWriter::new(&mut out);
+        writer.write_all(b"hello, ").unwrap();
+        writer.write_all(b"").unwrap();
+        writer.write_all(b"chunked world!").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(out, b"7\r\nhello, \r\ne\r\nchunked world!\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_finish_on_drop() {
+        let mut out = Vec::new();
+        {
+            let mut writer = ChunkedWriter::new(&mut out);
+            write!(writer, "{}", 42).unwrap();
+        }
+        assert_eq!(out, b"2\r\n42\r\n0\r\n\r\n");
+    }
+
+    fn decode(encoded: &[u8]) -> io::Result<Vec<u8>> {
+        let mut body = Vec::new();
+        ChunkedReader::new(&mut &encoded[..]).read_to_end(&mut body)?;
+        Ok(body)
+    }
+
+    #[test]
+    fn test_decode() {
+        let body =
+            decode(b"7\r\nhello, \r\nE;ext=1\r\nchunked world!\r\n0\r\nX-Trailer: 1\r\n\r\n");
+        assert_eq!(body.unwrap(), b"hello, chunked world!");
+
+        // The reader stops after the body.
+        let mut encoded: &[u8] = b"2\r\n42\r\n0\r\n\r\nnext";
+        let mut body = Vec::new();
+        ChunkedReader::new(&mut encoded)
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, b"42");
+        assert_eq!(encoded, b"next");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode(b"5\r\nhel").is_err());
+        assert!(decode(b"5\r\nhello").is_err());
+        assert!(decode(b"2\r\n42xx\r\n0\r\n\r\n").is_err());
+        assert!(decode(b"z\r\n").is_err());
+        assert!(decode(b"0\r\n").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut encoded = Vec::new();
+        let mut writer = ChunkedWriter::new(&mut encoded);
+        for i in 0..100 {
+            writeln!(writer, "line {}", i).unwrap();
+        }
+        writer.finish().unwrap();
+        let body = String::from_utf8(decode(&encoded).unwrap()).unwrap();
+        assert_eq!(body.lines().count(), 100);
+        assert!(body.ends_with("line 99\n"));
+    }
+}
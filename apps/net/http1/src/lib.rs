@@ -0,0 +1,131 @@
+//! HTTP/1.1 message framing shared by the network apps: `http://` URLs,
+//! `GET` requests, reading lines and response heads, and the chunked
+//! transfer encoding.
+//!
+//! It builds against the host `std` by default, and against `axstd` with the
+//! `axstd` feature, which the apps enable along with their own.
+
+#![cfg_attr(feature = "axstd", no_std)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+mod chunked;
+mod request;
+mod response;
+mod url;
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::vec::Vec;
+
+pub use self::chunked::{ChunkedReader, ChunkedWriter};
+pub use self::request::get_request;
+pub use self::response::{copy_body, read_response_head, ResponseHead};
+pub use self::url::Url;
+
+/// The maximum length of the status line, a header line or a chunk size line.
+pub const MAX_LINE_LEN: usize = 8 * 1024;
+/// The maximum number of header fields in a response.
+pub const MAX_HEADERS: usize = 64;
+/// The size of the buffer bodies are copied through.
+pub const COPY_CHUNK: usize = 4096;
+
+#[cfg(feature = "axstd")]
+pub fn invalid_data() -> io::Error {
+    io::Error::InvalidData
+}
+
+#[cfg(not(feature = "axstd"))]
+pub fn invalid_data() -> io::Error {
+    io::Error::from(io::ErrorKind::InvalidData)
+}
+
+/// Reads a line from `reader` and returns it without the line ending.
+///
+/// Lines longer than `max_len`, lines cut short by the end of the stream and
+/// lines that are not valid UTF-8 are rejected with an `InvalidData` error.
+pub fn read_line<R: BufRead + ?Sized>(reader: &mut R, max_len: usize) -> io::Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Err(invalid_data());
+        }
+        let (len, end) = match available.iter().position(|&b| b == b'\n') {
+            Some(pos) => (pos, true),
+            None => (available.len(), false),
+        };
+        line.extend_from_slice(&available[..len]);
+        reader.consume(if end { len + 1 } else { len });
+        // Leaves room for the `\r` before the `\n`.
+        if line.len() > max_len + 1 {
+            return Err(invalid_data());
+        }
+        if end {
+            break;
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    if line.len() > max_len {
+        return Err(invalid_data());
+    }
+    String::from_utf8(line).map_err(|_| invalid_data())
+}
+
+/// Copies `reader` to `out` through a fixed-size buffer, up to `limit` bytes
+/// if given, which must then all be there. Returns the number of bytes
+/// copied.
+pub fn copy<R: Read + ?Sized, W: Write + ?Sized>(
+    reader: &mut R,
+    out: &mut W,
+    limit: Option<u64>,
+) -> io::Result<u64> {
+    let mut buf = [0u8; COPY_CHUNK];
+    let mut copied = 0;
+    let mut remaining = limit.unwrap_or(u64::MAX);
+    while remaining > 0 {
+        let len = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+        let n = reader.read(&mut buf[..len])?;
+        if n == 0 {
+            return match limit {
+                Some(_) => Err(invalid_data()),
+                None => Ok(copied),
+            };
+        }
+        out.write_all(&buf[..n])?;
+        copied += n as u64;
+        remaining -= n as u64;
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_line() {
+        let mut raw: &[u8] = b"a line\r\nbare\nlast";
+        assert_eq!(read_line(&mut raw, 16).unwrap(), "a line");
+        assert_eq!(read_line(&mut raw, 4).unwrap(), "bare");
+        assert!(read_line(&mut raw, 16).is_err());
+
+        assert_eq!(read_line(&mut &b"1234\r\n"[..], 4).unwrap(), "1234");
+        assert!(read_line(&mut &b"12345\r\n"[..], 4).is_err());
+        assert!(read_line(&mut &b"\xff\r\n"[..], 4).is_err());
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut out = Vec::new();
+        let mut raw: &[u8] = b"hello, extra";
+        assert_eq!(copy(&mut raw, &mut out, Some(5)).unwrap(), 5);
+        assert_eq!((out.as_slice(), raw), (&b"hello"[..], &b", extra"[..]));
+        assert_eq!(copy(&mut raw, &mut out, None).unwrap(), 7);
+        assert!(copy(&mut &b"hel"[..], &mut out, Some(5)).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+//! Building `GET` requests.
+
+use std::string::String;
+
+use crate::url::Url;
+
+/// Builds a `GET` request for `url`, asking the server to keep the
+/// connection open afterwards if `keep_alive` is set.
+///
+/// Otherwise the request asks the server to close the connection, so a body
+/// without framing headers ends when the connection does.
+pub fn get_request(url: &Url, keep_alive: bool) -> String {
+    format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: arceos/0.1\r\n\
+         Accept: */*\r\nConnection: {}\r\n\r\n",
+        url.target,
+        url.host_header(),
+        if keep_alive { "keep-alive" } else { "close" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_request() {
+        let url = Url::parse("http://10.0.2.2:5555/a?b").unwrap();
+        let request = get_request(&url, false);
+        assert!(request.starts_with("GET /a?b HTTP/1.1\r\nHost: 10.0.2.2:5555\r\n"));
+        assert!(request.ends_with("Connection: close\r\n\r\n"));
+        assert!(get_request(&url, true).ends_with("Connection: keep-alive\r\n\r\n"));
+    }
+}
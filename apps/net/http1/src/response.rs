@@ -0,0 +1,205 @@
+//! Reading the head and body of a response.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::vec::Vec;
+
+use crate::{copy, invalid_data, read_line, ChunkedReader, MAX_HEADERS, MAX_LINE_LEN};
+
+/// The status line and headers of a response.
+pub struct ResponseHead {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl ResponseHead {
+    /// Returns the value of the header `name`, which is case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns whether the response has a `2xx` status.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Returns whether the response is a redirect the client should follow
+    /// with another `GET` request.
+    pub fn is_redirect(&self) -> bool {
+        matches!(self.status, 301 | 302 | 303 | 307 | 308)
+    }
+
+    /// Returns whether the response never has a body, whatever its headers
+    /// say (RFC 9112, section 6.3).
+    pub fn has_no_body(&self) -> bool {
+        matches!(self.status, 204 | 304)
+    }
+
+    /// Returns whether the connection may carry another request after this
+    /// response: the server did not ask to close it, and the body has a
+    /// length, so that it does not end with the connection.
+    pub fn keeps_alive(&self) -> bool {
+        let close = self.header("Connection").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|option| option.trim().eq_ignore_ascii_case("close"))
+        });
+        !close
+            && (self.has_no_body()
+                || self.header("Transfer-Encoding").is_some()
+                || self.header("Content-Length").is_some())
+    }
+}
+
+/// Reads the status line and headers of a response, skipping interim `1xx`
+/// responses.
+pub fn read_response_head<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<ResponseHead> {
+    loop {
+        let line = read_line(reader, MAX_LINE_LEN)?;
+        let mut parts = line.splitn(3, ' ');
+        let status = match (parts.next(), parts.next()) {
+            (Some(version), Some(code)) if version.starts_with("HTTP/1.") && code.len() == 3 => {
+                code.parse::<u16>().map_err(|_| invalid_data())?
+            }
+            _ => return Err(invalid_data()),
+        };
+        if !(100..600).contains(&status) {
+            return Err(invalid_data());
+        }
+        let reason = String::from(parts.next().unwrap_or(""));
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(reader, MAX_LINE_LEN)?;
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(invalid_data());
+            }
+            let (name, value) = line.split_once(':').ok_or_else(invalid_data)?;
+            if name.is_empty() || name.contains(|c: char| c.is_ascii_whitespace()) {
+                return Err(invalid_data());
+            }
+            headers.push((String::from(name), String::from(value.trim())));
+        }
+        if !(100..200).contains(&status) {
+            return Ok(ResponseHead {
+                status,
+                reason,
+                headers,
+            });
+        }
+    }
+}
+
+/// Copies the body of the response with `head` from `reader` to `out`,
+/// returning its length.
+///
+/// The body is chunked if `Transfer-Encoding` says so, has the length given
+/// by `Content-Length` otherwise, and ends with the connection if neither is
+/// there. A body cut short fails with an `InvalidData` error.
+pub fn copy_body<R: BufRead + ?Sized, W: Write + ?Sized>(
+    head: &ResponseHead,
+    reader: &mut R,
+    out: &mut W,
+) -> io::Result<u64> {
+    if head.has_no_body() {
+        return Ok(0);
+    }
+    if head.header("Transfer-Encoding").is_some() {
+        return copy(&mut ChunkedReader::new(reader), out, None);
+    }
+    match head.header("Content-Length") {
+        Some(len) => {
+            let len = len.parse().map_err(|_| invalid_data())?;
+            copy(reader, out, Some(len))
+        }
+        None => copy(reader, out, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::COPY_CHUNK;
+
+    /// Reads a whole response, returning its status and body.
+    fn read(raw: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let mut reader = raw;
+        let head = read_response_head(&mut reader)?;
+        let mut body = Vec::new();
+        copy_body(&head, &mut reader, &mut body)?;
+        Ok((head.status, body))
+    }
+
+    #[test]
+    fn test_response_head() {
+        let mut raw: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 302 Found\r\nlocation: /x\r\nContent-Length: 0\r\n\r\nrest";
+        let head = read_response_head(&mut raw).unwrap();
+        assert_eq!((head.status, head.reason.as_str()), (302, "Found"));
+        assert_eq!(head.header("Location"), Some("/x"));
+        assert!(head.is_redirect());
+        assert!(!head.is_success());
+        assert_eq!(raw, b"rest");
+
+        for raw in [
+            &b"SSH-2.0-OpenSSH\r\n\r\n"[..],
+            b"HTTP/1.1 99 Low\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nBad Header: 1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\n",
+        ] {
+            assert!(read_response_head(&mut &raw[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_bodies() {
+        let (status, body) =
+            read(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello, extra").unwrap();
+        assert_eq!((status, body.as_slice()), (200, &b"hello"[..]));
+
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            7\r\nhello, \r\nE;ext=1\r\nchunked world!\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        assert_eq!(read(raw).unwrap().1, b"hello, chunked world!");
+
+        let raw = b"HTTP/1.0 200 OK\r\n\r\nuntil the connection closes";
+        assert_eq!(read(raw).unwrap().1, b"until the connection closes");
+
+        let raw = b"HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n";
+        assert_eq!(read(raw).unwrap().1, b"");
+
+        let big = vec![b'x'; COPY_CHUNK * 3 + 1];
+        let mut raw = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", big.len());
+        raw.push_str(std::str::from_utf8(&big).unwrap());
+        assert_eq!(read(raw.as_bytes()).unwrap().1, big);
+    }
+
+    #[test]
+    fn test_keeps_alive() {
+        let head = |raw: &str| read_response_head(&mut raw.as_bytes()).unwrap();
+        assert!(head("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n").keeps_alive());
+        assert!(head("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n").keeps_alive());
+        assert!(head("HTTP/1.1 304 Not Modified\r\n\r\n").keeps_alive());
+        assert!(!head("HTTP/1.1 200 OK\r\n\r\n").keeps_alive());
+        assert!(
+            !head("HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\n")
+                .keeps_alive()
+        );
+    }
+
+    #[test]
+    fn test_truncated_bodies() {
+        assert!(read(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel").is_err());
+        assert!(read(b"HTTP/1.1 200 OK\r\nContent-Length: x\r\n\r\n").is_err());
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel";
+        assert!(read(raw).is_err());
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n42xx\r\n";
+        assert!(read(raw).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Parsing `http://` URLs and resolving redirect targets against them.
+
+use std::fmt;
+use std::string::String;
+
+/// The default port of the `http` scheme.
+const DEFAULT_PORT: u16 = 80;
+
+/// An `http://` URL, split into the parts needed to send a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    /// The host as written in the URL, with the brackets of an IPv6 address.
+    pub host: String,
+    pub port: u16,
+    /// The path and query, starting with `/`. The fragment is dropped.
+    pub target: String,
+}
+
+impl Url {
+    /// Parses an absolute `http://` URL.
+    ///
+    /// Returns `None` for other schemes, for URLs with user information, and
+    /// for malformed hosts or ports.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (scheme, rest) = s.trim().split_once("://")?;
+        if !scheme.eq_ignore_ascii_case("http") {
+            return None;
+        }
+        let rest = rest.split('#').next().unwrap_or("");
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, ""),
+        };
+        let (host, port) = split_authority(authority)?;
+        let target = match target {
+            "" => String::from("/"),
+            t if t.starts_with('?') => format!("/{}", t),
+            t => String::from(t),
+        };
+        Some(Self {
+            host: String::from(host),
+            port,
+            target,
+        })
+    }
+
+    /// Returns the host without the brackets of an IPv6 address, as needed to
+    /// connect to it.
+    pub fn connect_host(&self) -> &str {
+        self.host.trim_start_matches('[').trim_end_matches(']')
+    }
+
+    /// Returns the value of the `Host` header, which leaves out the default
+    /// port.
+    pub fn host_header(&self) -> String {
+        match self.port {
+            DEFAULT_PORT => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        }
+    }
+
+    /// Resolves `location`, the target of a redirect, against this URL.
+    ///
+    /// `location` may be an absolute URL, a URL without a scheme, an absolute
+    /// path or a path relative to the directory of this URL's path.
+    pub fn join(&self, location: &str) -> Option<Self> {
+        let location = location.trim();
+        if location.contains("://") {
+            return Self::parse(location);
+        }
+        if let Some(rest) = location.strip_prefix("//") {
+            return Self::parse(&format!("http://{}", rest));
+        }
+        let location = location.split('#').next().unwrap_or("");
+        let target = if location.starts_with('/') {
+            String::from(location)
+        } else if location.is_empty() {
+            self.target.clone()
+        } else if location.starts_with('?') {
+            let path = self.target.split('?').next().unwrap_or("/");
+            format!("{}{}", path, location)
+        } else {
+            let path = self.target.split('?').next().unwrap_or("/");
+            let dir = &path[..path.rfind('/').map_or(0, |pos| pos + 1)];
+            format!("{}{}", dir, location)
+        };
+        Some(Self {
+            host: self.host.clone(),
+            port: self.port,
+            target,
+        })
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "http://{}{}", self.host_header(), self.target)
+    }
+}
+
+/// Splits the authority of a URL into its host and port.
+fn split_authority(authority: &str) -> Option<(&str, u16)> {
+    if authority.contains('@') {
+        return None;
+    }
+    let (host, port) = if authority.starts_with('[') {
+        let end = authority.find(']')? + 1;
+        match &authority[end..] {
+            "" => (&authority[..end], None),
+            rest => (&authority[..end], Some(rest.strip_prefix(':')?)),
+        }
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() || host.contains(|c: char| c.is_ascii_whitespace()) {
+        return None;
+    }
+    let port = match port {
+        None | Some("") => DEFAULT_PORT,
+        Some(port) => port.parse().ok()?,
+    };
+    Some((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let u = url("http://example.com");
+        assert_eq!((u.host.as_str(), u.port), ("example.com", 80));
+        assert_eq!(u.target, "/");
+
+        let u = url("HTTP://10.0.2.2:5555/a/b?x=1#top");
+        assert_eq!((u.host.as_str(), u.port), ("10.0.2.2", 5555));
+        assert_eq!(u.target, "/a/b?x=1");
+        assert_eq!(u.host_header(), "10.0.2.2:5555");
+
+        let u = url("http://[::1]:8080?q");
+        assert_eq!((u.connect_host(), u.port), ("::1", 8080));
+        assert_eq!(u.target, "/?q");
+        assert_eq!(u.to_string(), "http://[::1]:8080/?q");
+
+        for s in [
+            "https://example.com/",
+            "example.com/",
+            "http:///x",
+            "http://user@host/",
+            "http://host:99999/",
+            "http://[::1/",
+        ] {
+            assert_eq!(Url::parse(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_join() {
+        let base = url("http://host:8000/a/b?x=1");
+        let join = |location: &str| base.join(location).unwrap().to_string();
+        assert_eq!(join("http://other/c"), "http://other/c");
+        assert_eq!(join("//other:81/c"), "http://other:81/c");
+        assert_eq!(join("/c"), "http://host:8000/c");
+        assert_eq!(join("c/d"), "http://host:8000/a/c/d");
+        assert_eq!(join("?y=2"), "http://host:8000/a/b?y=2");
+        assert_eq!(join(""), "http://host:8000/a/b?x=1");
+        assert!(base.join("https://other/").is_none());
+    }
+}
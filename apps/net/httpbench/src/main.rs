@@ -0,0 +1,342 @@
+//! HTTP benchmarking client, in the manner of `ab`.
+//!
+//! Opens concurrent connections to a URL, one per thread, makes the same
+//! number of `GET` requests on each, and reports the throughput and the
+//! distribution of request times, timed with the monotonic clock, in a format
+//! close to that of `ab` so that the results can be compared.
+//!
+//! The options are `-c <connections>` (10 by default), `-n <requests>`, the
+//! number of requests of each connection rather than in total as with `ab`
+//! (100 by default), and `-k` to keep connections open between requests,
+//! which are otherwise made on a new connection each. They are given on the
+//! command line when running on a host, e.g.
+//! `cargo run --release -- -c 20 -k http://127.0.0.1:5555/`, and with
+//! environment variables when building for ArceOS, e.g.
+//! `make A=apps/net/httpbench NET=y BENCH_URL=http://10.0.2.2:5555/
+//! BENCH_CONNECTIONS=20 BENCH_REQUESTS=500 BENCH_KEEPALIVE=y run`.
+//! Only `http://` URLs are supported; host names need the `dns` feature on
+//! ArceOS.
+//!
+//! Requests that fail do not stop the benchmark. Those that could not
+//! connect are counted apart from those that failed afterwards.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+mod report;
+
+use std::io::{self, prelude::*, BufReader};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use std::vec::Vec;
+
+use http1::{ResponseHead, Url};
+
+use crate::report::{Report, Stats};
+
+/// The number of connections made at the same time, unless given.
+const DEFAULT_CONNECTIONS: usize = 10;
+/// The number of requests made on each connection, unless given.
+const DEFAULT_REQUESTS: usize = 100;
+
+/// What to benchmark, and how.
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    url: Url,
+    connections: usize,
+    requests: usize,
+    keep_alive: bool,
+}
+
+/// Parses the options and the URL, returning why not if they are bad.
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut url = None;
+    let mut connections = DEFAULT_CONNECTIONS;
+    let mut requests = DEFAULT_REQUESTS;
+    let mut keep_alive = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "-n" => {
+                let count = match args.next().map(|value| value.parse::<usize>()) {
+                    Some(Ok(count)) if count > 0 => count,
+                    _ => return Err(format!("{} needs a number of at least 1", arg)),
+                };
+                if arg == "-c" {
+                    connections = count;
+                } else {
+                    requests = count;
+                }
+            }
+            "-k" => keep_alive = true,
+            _ if url.is_none() && !arg.starts_with('-') => {
+                url = Some(Url::parse(&arg).ok_or_else(|| format!("unsupported URL {:?}", arg))?)
+            }
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    Ok(Options {
+        url: url.ok_or_else(|| String::from("no URL given"))?,
+        connections,
+        requests,
+        keep_alive,
+    })
+}
+
+/// Returns the command-line arguments, or the equivalent ones from the
+/// environment variables the app was built with on ArceOS.
+fn args() -> Vec<String> {
+    #[cfg(not(feature = "axstd"))]
+    let args = std::env::args().skip(1).collect();
+    #[cfg(feature = "axstd")]
+    let args = {
+        let mut args = Vec::new();
+        for (option, var) in [
+            ("-c", option_env!("BENCH_CONNECTIONS")),
+            ("-n", option_env!("BENCH_REQUESTS")),
+        ] {
+            if let Some(value) = var {
+                args.push(String::from(option));
+                args.push(String::from(value));
+            }
+        }
+        if option_env!("BENCH_KEEPALIVE") == Some("y") {
+            args.push(String::from("-k"));
+        }
+        args.extend(option_env!("BENCH_URL").map(String::from));
+        args
+    };
+    args
+}
+
+/// A reader counting the bytes read from `inner`.
+struct Counted<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// A writer that drops everything written to it, for the response bodies.
+struct Discard;
+
+impl Write for Discard {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A connection to the server, counting the bytes received on it.
+type Connection = BufReader<Counted<TcpStream>>;
+
+/// Sends `request` on `conn` and reads the response, returning its head and
+/// the length of its body.
+fn exchange(conn: &mut Connection, request: &[u8]) -> io::Result<(ResponseHead, u64)> {
+    conn.get_mut().inner.write_all(request)?;
+    let head = http1::read_response_head(conn)?;
+    let len = http1::copy_body(&head, conn, &mut Discard)?;
+    Ok((head, len))
+}
+
+/// Sends `request` to `addr` `requests` times, on one connection as long as
+/// the server keeps it open if `keep_alive` is set, or on a new connection
+/// each time otherwise.
+fn worker(addr: SocketAddr, request: &[u8], requests: usize, keep_alive: bool) -> Stats {
+    let mut stats = Stats::default();
+    let mut kept: Option<Connection> = None;
+    for _ in 0..requests {
+        let start = Instant::now();
+        let reused = kept.is_some();
+        let mut conn = match kept.take() {
+            Some(conn) => conn,
+            None => match TcpStream::connect(addr) {
+                Ok(stream) => BufReader::new(Counted {
+                    inner: stream,
+                    count: 0,
+                }),
+                Err(_) => {
+                    stats.connect_errors += 1;
+                    continue;
+                }
+            },
+        };
+        let received = conn.get_ref().count;
+        let result = exchange(&mut conn, request);
+        stats.total_bytes += conn.get_ref().count - received;
+        let (head, len) = match result {
+            Ok(response) => response,
+            Err(_) => {
+                stats.failed += 1;
+                continue;
+            }
+        };
+        stats.record(start.elapsed());
+        stats.body_bytes += len;
+        if !head.is_success() {
+            stats.non_2xx += 1;
+        }
+        if reused {
+            stats.keep_alive += 1;
+        }
+        if stats.doc_len.is_none() {
+            stats.doc_len = Some(len);
+            stats.server = head.header("Server").map(String::from);
+        }
+        if keep_alive && head.keeps_alive() {
+            kept = Some(conn);
+        }
+    }
+    stats
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let Options {
+        url,
+        connections,
+        requests,
+        keep_alive,
+    } = options;
+    let Some(addr) = (url.connect_host(), url.port).to_socket_addrs()?.next() else {
+        println!("cannot resolve {}", url.host);
+        return Ok(());
+    };
+    println!(
+        "Benchmarking {} ({}) with {} connections of {} requests{}",
+        url,
+        addr,
+        connections,
+        requests,
+        if keep_alive { ", kept alive" } else { "" }
+    );
+    let request = Arc::new(http1::get_request(&url, keep_alive));
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..connections)
+        .map(|_| {
+            let request = request.clone();
+            thread::spawn(move || worker(addr, request.as_bytes(), requests, keep_alive))
+        })
+        .collect();
+    let mut stats = Stats::default();
+    for worker in workers {
+        match worker.join() {
+            Ok(worker_stats) => stats.merge(worker_stats),
+            Err(_) => println!("a worker failed, its requests are not counted"),
+        }
+    }
+    let elapsed = start.elapsed();
+    println!();
+    print!("{}", Report::new(&url, connections, elapsed, stats));
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, HTTP benchmarking client!");
+    match parse_options(args().into_iter()) {
+        Ok(options) => {
+            if let Err(e) = run(options) {
+                println!("benchmark failed: {}", e);
+            }
+        }
+        Err(reason) => {
+            println!("{}", reason);
+            println!(
+                "usage: httpbench [-c connections] [-n requests] [-k] <http://host[:port]/path>"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        parse_options(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse("http://10.0.2.2:5555/").unwrap();
+        assert_eq!(
+            (options.connections, options.requests, options.keep_alive),
+            (DEFAULT_CONNECTIONS, DEFAULT_REQUESTS, false)
+        );
+        let options = parse("-k -c 4 http://10.0.2.2/x -n 7").unwrap();
+        assert_eq!(options.url.target, "/x");
+        assert_eq!(
+            (options.connections, options.requests, options.keep_alive),
+            (4, 7, true)
+        );
+
+        for args in [
+            "",
+            "-c 4",
+            "-c 0 http://host/",
+            "-n http://host/",
+            "-x http://host/",
+            "https://host/",
+            "http://host/ http://other/",
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    /// Answers the requests of `connections` connections on `listener`,
+    /// closing each after `per_connection` responses.
+    fn serve(listener: TcpListener, connections: usize, per_connection: usize) {
+        for _ in 0..connections {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            for _ in 0..per_connection {
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && !line.ends_with("\r\n\r\n") {}
+                if line.is_empty() {
+                    break;
+                }
+                let response = b"HTTP/1.1 200 OK\r\nServer: test\r\nContent-Length: 5\r\n\r\nhello";
+                reader.get_mut().write_all(response).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // The server closes the connection after 3 responses, so the fourth
+        // request fails, and the fifth is made on a new connection.
+        let server = thread::spawn(move || serve(listener, 2, 3));
+        let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+        let request = http1::get_request(&url, true);
+        let stats = worker(addr, request.as_bytes(), 5, true);
+        server.join().unwrap();
+        assert_eq!(stats.completed(), 4);
+        assert_eq!((stats.failed, stats.connect_errors), (1, 0));
+        assert_eq!(stats.keep_alive, 2);
+        assert_eq!((stats.body_bytes, stats.doc_len), (20, Some(5)));
+        assert_eq!(stats.server.as_deref(), Some("test"));
+
+        // Nothing listens on the port any more.
+        let stats = worker(addr, request.as_bytes(), 3, false);
+        assert_eq!((stats.completed(), stats.connect_errors), (0, 3));
+    }
+}
@@ -0,0 +1,277 @@
+//! Collecting the results of a benchmark and reporting them in the format of
+//! `ab`, the Apache HTTP server benchmarking tool.
+
+use std::fmt;
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use http1::Url;
+
+/// The percentages of requests listed with the time they were served within,
+/// as by `ab`.
+const PERCENTAGES: &[usize] = &[50, 66, 75, 80, 90, 95, 98, 99, 100];
+
+/// The results of the requests made by one worker, or by all of them once
+/// merged.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// The time each completed request took, in microseconds, from
+    /// connecting if it needed a new connection to reading the whole
+    /// response.
+    latencies: Vec<u64>,
+    /// The requests that were not sent because connecting failed.
+    pub connect_errors: usize,
+    /// The requests that failed after connecting, such as when the
+    /// connection was reset or the response was malformed.
+    pub failed: usize,
+    /// The completed requests answered with a status other than `2xx`.
+    pub non_2xx: usize,
+    /// The completed requests sent on a connection that was kept open after
+    /// an earlier one.
+    pub keep_alive: usize,
+    /// The bytes received, response heads included.
+    pub total_bytes: u64,
+    /// The bytes of response bodies received.
+    pub body_bytes: u64,
+    /// The `Server` header and the body length of the first response.
+    pub server: Option<String>,
+    pub doc_len: Option<u64>,
+}
+
+impl Stats {
+    /// Records a completed request that took `latency`.
+    pub fn record(&mut self, latency: Duration) {
+        self.latencies.push(latency.as_micros() as u64);
+    }
+
+    /// Returns the number of completed requests.
+    pub fn completed(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Adds the results of `other` to these.
+    pub fn merge(&mut self, other: Stats) {
+        self.latencies.extend_from_slice(&other.latencies);
+        self.connect_errors += other.connect_errors;
+        self.failed += other.failed;
+        self.non_2xx += other.non_2xx;
+        self.keep_alive += other.keep_alive;
+        self.total_bytes += other.total_bytes;
+        self.body_bytes += other.body_bytes;
+        self.server = self.server.take().or(other.server);
+        self.doc_len = self.doc_len.or(other.doc_len);
+    }
+}
+
+/// Returns the latency within which `percent` percent of the requests with
+/// the `sorted` latencies were served, by the nearest-rank method.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Formats a latency in microseconds as milliseconds.
+fn ms(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+/// The report of a finished benchmark, displayed like that of `ab`.
+pub struct Report<'a> {
+    url: &'a Url,
+    concurrency: usize,
+    elapsed: Duration,
+    stats: Stats,
+}
+
+impl<'a> Report<'a> {
+    /// Reports the results `stats` of benchmarking `url` with `concurrency`
+    /// connections for `elapsed`.
+    pub fn new(url: &'a Url, concurrency: usize, elapsed: Duration, mut stats: Stats) -> Self {
+        stats.latencies.sort_unstable();
+        Self {
+            url,
+            concurrency,
+            elapsed,
+            stats,
+        }
+    }
+
+    fn write_times(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sorted = &self.stats.latencies;
+        let mean = sorted.iter().sum::<u64>() / sorted.len() as u64;
+        writeln!(f, "Request times (ms)")?;
+        writeln!(
+            f,
+            "{:8}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "", "min", "mean", "median", "p95", "p99", "max"
+        )?;
+        writeln!(
+            f,
+            "{:8}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10.3}",
+            "Total:",
+            ms(sorted[0]),
+            ms(mean),
+            ms(percentile(sorted, 50)),
+            ms(percentile(sorted, 95)),
+            ms(percentile(sorted, 99)),
+            ms(sorted[sorted.len() - 1])
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Percentage of the requests served within a certain time (ms)"
+        )?;
+        for &percent in PERCENTAGES {
+            write!(
+                f,
+                " {:>3}% {:>9.3}",
+                percent,
+                ms(percentile(sorted, percent))
+            )?;
+            if percent == 100 {
+                write!(f, " (longest request)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stats = &self.stats;
+        let completed = stats.completed();
+        let secs = self.elapsed.as_secs_f64();
+        writeln!(
+            f,
+            "Server Software:        {}",
+            stats.server.as_deref().unwrap_or("")
+        )?;
+        writeln!(f, "Server Hostname:        {}", self.url.host)?;
+        writeln!(f, "Server Port:            {}", self.url.port)?;
+        writeln!(f)?;
+        writeln!(f, "Document Path:          {}", self.url.target)?;
+        writeln!(
+            f,
+            "Document Length:        {} bytes",
+            stats.doc_len.unwrap_or(0)
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Concurrency Level:      {}", self.concurrency)?;
+        writeln!(f, "Time taken for tests:   {:.3} seconds", secs)?;
+        writeln!(f, "Complete requests:      {}", completed)?;
+        writeln!(
+            f,
+            "Failed requests:        {}",
+            stats.connect_errors + stats.failed
+        )?;
+        if stats.connect_errors + stats.failed > 0 {
+            writeln!(
+                f,
+                "   (Connect: {}, Exceptions: {})",
+                stats.connect_errors, stats.failed
+            )?;
+        }
+        if stats.non_2xx > 0 {
+            writeln!(f, "Non-2xx responses:      {}", stats.non_2xx)?;
+        }
+        writeln!(f, "Keep-Alive requests:    {}", stats.keep_alive)?;
+        writeln!(f, "Total transferred:      {} bytes", stats.total_bytes)?;
+        writeln!(f, "HTML transferred:       {} bytes", stats.body_bytes)?;
+        if completed == 0 || secs == 0.0 {
+            return writeln!(f, "\nNo request completed.");
+        }
+        writeln!(
+            f,
+            "Requests per second:    {:.2} [#/sec] (mean)",
+            completed as f64 / secs
+        )?;
+        writeln!(
+            f,
+            "Time per request:       {:.3} [ms] (mean)",
+            secs * 1000.0 * self.concurrency as f64 / completed as f64
+        )?;
+        writeln!(
+            f,
+            "Time per request:       {:.3} [ms] (mean, across all concurrent requests)",
+            secs * 1000.0 / completed as f64
+        )?;
+        writeln!(
+            f,
+            "Transfer rate:          {:.2} [Kbytes/sec] received",
+            stats.total_bytes as f64 / 1024.0 / secs
+        )?;
+        writeln!(f)?;
+        self.write_times(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<u64> = (1..=200).collect();
+        assert_eq!(percentile(&sorted, 50), 100);
+        assert_eq!(percentile(&sorted, 95), 190);
+        assert_eq!(percentile(&sorted, 99), 198);
+        assert_eq!(percentile(&sorted, 100), 200);
+        assert_eq!(percentile(&[7], 50), 7);
+        assert_eq!(percentile(&[1, 2], 0), 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Stats::default();
+        a.record(Duration::from_micros(300));
+        a.failed = 1;
+        let mut b = Stats {
+            connect_errors: 2,
+            server: Some(String::from("test")),
+            doc_len: Some(5),
+            ..Stats::default()
+        };
+        b.record(Duration::from_micros(100));
+        b.record(Duration::from_micros(200));
+        a.merge(b);
+        assert_eq!(a.completed(), 3);
+        assert_eq!((a.failed, a.connect_errors), (1, 2));
+        assert_eq!((a.server.as_deref(), a.doc_len), (Some("test"), Some(5)));
+    }
+
+    #[test]
+    fn test_report() {
+        let url = Url::parse("http://10.0.2.2:5555/index.html").unwrap();
+        let mut stats = Stats {
+            connect_errors: 1,
+            total_bytes: 4096,
+            body_bytes: 1000,
+            doc_len: Some(10),
+            ..Stats::default()
+        };
+        for micros in (1..=100).rev() {
+            stats.record(Duration::from_micros(micros * 10));
+        }
+        let report = Report::new(&url, 4, Duration::from_millis(500), stats).to_string();
+        for line in [
+            "Server Hostname:        10.0.2.2\n",
+            "Document Path:          /index.html\n",
+            "Document Length:        10 bytes\n",
+            "Complete requests:      100\n",
+            "Failed requests:        1\n   (Connect: 1, Exceptions: 0)\n",
+            "Requests per second:    200.00 [#/sec] (mean)\n",
+            "Time per request:       20.000 [ms] (mean)\n",
+            "Transfer rate:          8.00 [Kbytes/sec] received\n",
+            "Total:       0.010     0.505     0.500     0.950     0.990     1.000\n",
+            "  95%     0.950\n",
+            " 100%     1.000 (longest request)\n",
+        ] {
+            assert!(report.contains(line), "{:?} not in\n{}", line, report);
+        }
+
+        let report = Report::new(&url, 1, Duration::from_secs(1), Stats::default()).to_string();
+        assert!(report.ends_with("No request completed.\n"));
+    }
+}
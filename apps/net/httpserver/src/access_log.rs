@@ -0,0 +1,127 @@
+//! Per-request access log lines.
+
+use core::fmt::Debug;
+use std::io::{self, prelude::*};
+use std::net::SocketAddr;
+use std::string::String;
+use std::time::Duration;
+
+use crate::log_file;
+use crate::request::Request;
+use crate::response::Status;
+
+/// Counts the bytes written through it to the inner writer.
+pub struct CountingWriter<'a, W: Write + ?Sized> {
+    inner: &'a mut W,
+    count: usize,
+}
+
+impl<'a, W: Write + ?Sized> CountingWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<W: Write + ?Sized> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Formats a log line in the spirit of the common log format, with the time
+/// taken instead of the date:
+///
+/// ```text
+/// 10.0.2.2:53412 "GET /index.html HTTP/1.1" 200 1840 412us
+/// ```
+fn format_line(
+    peer: SocketAddr,
+    request: &Request,
+    status: Status,
+    bytes: usize,
+    elapsed: Duration,
+) -> String {
+    format!(
+        "{} \"{} {}{}{} {}\" {} {} {}us",
+        peer,
+        request.method,
+        request.path,
+        if request.query.is_some() { "?" } else { "" },
+        request.query.as_deref().unwrap_or(""),
+        request.version,
+        status.code(),
+        bytes,
+        elapsed.as_micros()
+    )
+}
+
+/// Logs a completed request, to the log file if there is one and to the
+/// console otherwise.
+pub fn log_request(
+    peer: SocketAddr,
+    request: &Request,
+    status: Status,
+    bytes: usize,
+    elapsed: Duration,
+) {
+    let line = format_line(peer, request, status, bytes, elapsed);
+    if let Err(line) = log_file::send(line) {
+        info!("{}", line);
+    }
+}
+
+/// Returns the kind of an I/O error, for logging.
+#[cfg(feature = "axstd")]
+pub fn error_kind(e: &io::Error) -> impl Debug + '_ {
+    e
+}
+
+/// Returns the kind of an I/O error, for logging.
+#[cfg(not(feature = "axstd"))]
+pub fn error_kind(e: &io::Error) -> impl Debug {
+    e.kind()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    #[test]
+    fn test_counting_writer() {
+        let mut out = Vec::new();
+        let mut w = CountingWriter::new(&mut out);
+        w.write_all(b"hello ").unwrap();
+        write!(w, "{}", 42).unwrap();
+        assert_eq!(w.count(), 8);
+        assert_eq!(out, b"hello 42");
+    }
+
+    #[test]
+    fn test_format_line() {
+        let mut raw: &[u8] = b"GET /a%20b?x=1 HTTP/1.0\r\n\r\n";
+        let request = RequestReader::default().read_request(&mut raw).unwrap();
+        let line = format_line(
+            "10.0.2.2:53412".parse().unwrap(),
+            &request,
+            Status::NotFound,
+            123,
+            Duration::from_micros(1500),
+        );
+        assert_eq!(
+            line,
+            "10.0.2.2:53412 \"GET /a%20b?x=1 HTTP/1.0\" 404 123 1500us"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Administrative endpoints, such as `/shutdown` and `/admin/reload`.
+//!
+//! They are routed with Basic authentication, using the user name and password
+//! set with the `HTTPD_ADMIN_USER` and `HTTPD_ADMIN_PASSWORD` environment
+//! variables at build time. Without them the endpoints are disabled.
+
+use crate::auth::Credentials;
+use crate::embedded;
+use crate::error_pages;
+use crate::files;
+use crate::request::Request;
+use crate::response::{Response, Status};
+
+/// The credentials that authorize administrative requests.
+pub static CREDENTIALS: Option<Credentials> = match (
+    option_env!("HTTPD_ADMIN_USER"),
+    option_env!("HTTPD_ADMIN_PASSWORD"),
+) {
+    (Some(user), Some(password)) if !user.is_empty() && !password.is_empty() => {
+        Some(Credentials { user, password })
+    }
+    _ => None,
+};
+
+/// Checks that `request` may use an administrative endpoint, returning the
+/// error response to send if not.
+///
+/// The credentials are checked by the router, since the routes of these
+/// endpoints require them.
+pub fn check(request: &Request) -> Result<(), Response> {
+    if request.method != "GET" {
+        // Answering `HEAD` must not have side effects.
+        return Err(Response::error(Status::MethodNotAllowed).with_header("Allow", "GET"));
+    }
+    if CREDENTIALS.is_none() {
+        return Err(Response::error(Status::NotFound));
+    }
+    Ok(())
+}
+
+/// Reads the content kept in memory from the document root `doc_root`: the
+/// error pages and the compressed index. Cached files are dropped, to be read
+/// again when requested, and the built-in site is served while the document
+/// root is missing.
+///
+/// Requests being served keep the content they already have; later requests
+/// see the new content once it has been read completely.
+pub fn load_content(doc_root: &str) {
+    embedded::check_root(doc_root);
+    files::clear_cache();
+    files::precompress_index(doc_root);
+    error_pages::load(doc_root);
+}
+
+/// Handles `GET /admin/reload`, for the document root `doc_root`.
+///
+/// Like the other administrative endpoints, it only exists when the server
+/// is built with `HTTPD_ADMIN_USER` and `HTTPD_ADMIN_PASSWORD`, and answers
+/// `404 Not Found` otherwise.
+pub fn reload(request: &Request, doc_root: &str) -> Response {
+    if let Err(response) = check(request) {
+        return response;
+    }
+    info!("reloading content");
+    load_content(doc_root);
+    Response::new(Status::Ok, "text/plain", b"reloaded\n".to_vec())
+}
@@ -0,0 +1,100 @@
+//! HTTP Basic authentication (RFC 7617).
+
+use crate::base64;
+use crate::response::{Response, Status};
+
+/// The protection space sent in challenges.
+const REALM: &str = "arceos";
+
+/// A user name and password accepted by protected routes.
+pub struct Credentials {
+    pub user: &'static str,
+    pub password: &'static str,
+}
+
+/// Compares `a` and `b` in a time that depends only on their lengths, so
+/// that the time taken does not reveal how much of a guess is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = (a.len() != b.len()) as u8;
+    for i in 0..a.len().max(b.len()) {
+        diff |= a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0);
+    }
+    diff == 0
+}
+
+impl Credentials {
+    /// Returns whether the `Authorization` header carries these credentials.
+    pub fn verify(&self, authorization: Option<&str>) -> bool {
+        let Some((scheme, value)) = authorization.and_then(|v| v.trim().split_once(' ')) else {
+            return false;
+        };
+        if !scheme.eq_ignore_ascii_case("Basic") {
+            return false;
+        }
+        let Some(decoded) = base64::decode(value.trim()) else {
+            return false;
+        };
+        // The user name cannot contain a colon, but the password can.
+        let Some(colon) = decoded.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        let user_ok = constant_time_eq(&decoded[..colon], self.user.as_bytes());
+        let password_ok = constant_time_eq(&decoded[colon + 1..], self.password.as_bytes());
+        user_ok & password_ok
+    }
+}
+
+/// Returns the `401 Unauthorized` response asking for credentials.
+pub fn challenge() -> Response {
+    Response::error(Status::Unauthorized)
+        .with_header("WWW-Authenticate", &format!("Basic realm=\"{}\"", REALM))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADMIN: Credentials = Credentials {
+        user: "admin",
+        password: "s3:cret",
+    };
+
+    fn basic(user_pass: &str) -> String {
+        format!("Basic {}", base64::encode(user_pass.as_bytes()))
+    }
+
+    #[test]
+    fn test_verify() {
+        assert!(ADMIN.verify(Some(&basic("admin:s3:cret"))));
+        assert!(ADMIN.verify(Some(&basic("admin:s3:cret").replace("Basic", "basic"))));
+        assert!(!ADMIN.verify(Some(&basic("admin:s3:cre"))));
+        assert!(!ADMIN.verify(Some(&basic("admin:s3:crett"))));
+        assert!(!ADMIN.verify(Some(&basic("root:s3:cret"))));
+        assert!(!ADMIN.verify(Some(&basic("admins3:cret"))));
+        assert!(!ADMIN.verify(Some("Basic !!!!")));
+        assert!(!ADMIN.verify(Some("Bearer s3:cret")));
+        assert!(!ADMIN.verify(Some("Basic")));
+        assert!(!ADMIN.verify(None));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abc\0"));
+        assert!(!constant_time_eq(b"", b"a"));
+    }
+
+    #[test]
+    fn test_challenge() {
+        let response = challenge();
+        assert_eq!(response.status, Status::Unauthorized);
+        let (_, value) = response
+            .headers
+            .iter()
+            .find(|(n, _)| *n == "WWW-Authenticate")
+            .unwrap();
+        assert_eq!(value, "Basic realm=\"arceos\"");
+    }
+}
@@ -0,0 +1,89 @@
+//! Base64 with the standard alphabet and padding (RFC 4648, section 4).
+
+use std::string::String;
+use std::vec::Vec;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `data` as Base64.
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes padded Base64, returning `None` if `s` is not valid.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return None;
+    }
+    let value = |c: u8| ALPHABET.iter().position(|&a| a == c).map(|v| v as u32);
+
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, chunk) in s.chunks(4).enumerate() {
+        let last = i == s.len() / 4 - 1;
+        let padding = match chunk {
+            [_, _, b'=', b'='] if last => 2,
+            [_, _, _, b'='] if last => 1,
+            _ => 0,
+        };
+        let mut n = 0;
+        for &c in chunk[..4 - padding].iter() {
+            n = n << 6 | value(c)?;
+        }
+        n <<= 6 * padding;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: &[(&str, &str)] = &[
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn test_encode() {
+        for &(plain, encoded) in VECTORS {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+        }
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn test_decode() {
+        for &(plain, encoded) in VECTORS {
+            assert_eq!(decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(decode("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode("Zm9"), None);
+        assert_eq!(decode("Zm9v!A=="), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Z==="), None);
+    }
+}
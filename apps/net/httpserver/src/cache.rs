@@ -0,0 +1,146 @@
+//! An in-memory cache of file contents with least-recently-used eviction.
+
+use std::string::String;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::sync::lock;
+
+/// A cached file.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// The file contents, shared with the responses being sent.
+    pub content: Arc<Vec<u8>>,
+    pub content_type: &'static str,
+    pub etag: String,
+    /// When the file was last modified as it was read, if the filesystem
+    /// records it.
+    pub modified: Option<u64>,
+}
+
+/// Caches files by path, up to a total size.
+///
+/// Entries are kept in order of use, least recently used first, which makes
+/// lookups linear; the cache is meant to hold a modest number of files.
+pub struct FileCache {
+    entries: Mutex<Vec<(String, Entry)>>,
+    /// The maximum total size of the cached contents.
+    budget: usize,
+    /// The maximum size of a single cached file.
+    max_entry_len: usize,
+}
+
+impl FileCache {
+    pub const fn new(budget: usize, max_entry_len: usize) -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+            budget,
+            max_entry_len,
+        }
+    }
+
+    /// Returns the entry for `path`, if it is cached and its file is still
+    /// `len` bytes long and was last modified at `modified`.
+    ///
+    /// Entries whose file has changed are dropped. Without modification
+    /// times, a file changed in place to the same length cannot be told
+    /// apart, so callers must compare the contents.
+    pub fn get(&self, path: &str, len: u64, modified: Option<u64>) -> Option<Entry> {
+        let mut entries = lock(&self.entries);
+        let pos = entries.iter().position(|(p, _)| p == path)?;
+        let (path, entry) = entries.remove(pos);
+        if entry.content.len() as u64 != len || entry.modified != modified {
+            return None;
+        }
+        entries.push((path, entry.clone()));
+        Some(entry)
+    }
+
+    /// Adds the entry for `path`, evicting the least recently used entries
+    /// to stay within the budget.
+    ///
+    /// Files larger than the per-entry limit are not cached.
+    pub fn insert(&self, path: &str, entry: Entry) {
+        let len = entry.content.len();
+        if len > self.max_entry_len || len > self.budget {
+            return;
+        }
+        let mut entries = lock(&self.entries);
+        entries.retain(|(p, _)| p != path);
+        let mut used: usize = entries.iter().map(|(_, e)| e.content.len()).sum();
+        while used + len > self.budget {
+            let (_, evicted) = entries.remove(0);
+            used -= evicted.content.len();
+        }
+        entries.push((String::from(path), entry));
+    }
+
+    /// Drops the entry for `path`, such as after the file has been written.
+    pub fn remove(&self, path: &str) {
+        lock(&self.entries).retain(|(p, _)| p != path);
+    }
+
+    /// Drops every entry.
+    pub fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(len: usize) -> Entry {
+        Entry {
+            content: Arc::new(vec![0; len]),
+            content_type: "text/plain",
+            etag: format!("\"{}\"", len),
+            modified: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let cache = FileCache::new(100, 50);
+        cache.insert("/a", entry(10));
+        assert_eq!(cache.get("/a", 10, Some(1)).unwrap().etag, "\"10\"");
+        assert!(cache.get("/b", 10, Some(1)).is_none());
+        // A changed length invalidates the entry.
+        assert!(cache.get("/a", 11, Some(1)).is_none());
+        assert!(cache.get("/a", 10, Some(1)).is_none());
+
+        // So does a changed modification time, with the same length.
+        cache.insert("/a", entry(10));
+        assert!(cache.get("/a", 10, Some(2)).is_none());
+        assert!(cache.get("/a", 10, Some(1)).is_none());
+    }
+
+    #[test]
+    fn test_eviction() {
+        let cache = FileCache::new(100, 50);
+        cache.insert("/a", entry(40));
+        cache.insert("/b", entry(40));
+        // Using `/a` makes `/b` the least recently used.
+        assert!(cache.get("/a", 40, Some(1)).is_some());
+        cache.insert("/c", entry(40));
+        assert!(cache.get("/b", 40, Some(1)).is_none());
+        assert!(cache.get("/a", 40, Some(1)).is_some());
+        assert!(cache.get("/c", 40, Some(1)).is_some());
+
+        // Replacing an entry does not count it twice.
+        cache.insert("/c", entry(40));
+        assert!(cache.get("/a", 40, Some(1)).is_some());
+
+        cache.clear();
+        assert!(cache.get("/a", 40, Some(1)).is_none());
+    }
+
+    #[test]
+    fn test_large_file() {
+        let cache = FileCache::new(100, 50);
+        cache.insert("/big", entry(51));
+        assert!(cache.get("/big", 51, Some(1)).is_none());
+        cache.insert("/limit", entry(50));
+        assert!(cache.get("/limit", 50, Some(1)).is_some());
+    }
+}
@@ -0,0 +1,343 @@
+//! Response compression with the `gzip` and `deflate` content codings.
+
+use std::string::String;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::deflate;
+use crate::request::Request;
+use crate::response::{Body, Response, Status};
+use crate::sync::lock;
+
+/// Bodies shorter than this are sent as they are, since the framing overhead
+/// would eat most of the savings.
+const MIN_COMPRESS_LEN: usize = 256;
+
+/// Bodies compressed ahead of time, keyed by their ETag and coding.
+static PRECOMPRESSED: Mutex<Vec<Precompressed>> = Mutex::new(Vec::new());
+
+/// A body compressed ahead of time.
+struct Precompressed {
+    etag: String,
+    coding: Coding,
+    /// Shared, so that the lock is only held to look it up.
+    body: Arc<Vec<u8>>,
+}
+
+/// A content coding supported by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coding {
+    Gzip,
+    Deflate,
+}
+
+impl Coding {
+    /// Returns the name used in `Accept-Encoding` and `Content-Encoding`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Coding::Gzip => "gzip",
+            Coding::Deflate => "deflate",
+        }
+    }
+
+    fn encode(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Coding::Gzip => deflate::gzip(data),
+            Coding::Deflate => deflate::zlib(data),
+        }
+    }
+}
+
+/// Parses a quality value (RFC 9110, section 12.4.2) into thousandths.
+fn parse_qvalue(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = frac
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |q, digit| q * 10 + u16::from(digit - b'0'));
+    match int {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// Parses an element of `Accept-Encoding`, such as `gzip;q=0.5`, into the
+/// coding and its quality in thousandths.
+fn parse_element(element: &str) -> Option<(&str, u16)> {
+    let mut parts = element.split(';');
+    let name = parts.next().unwrap().trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut quality = 1000;
+    for param in parts {
+        let (key, value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("q") {
+            quality = parse_qvalue(value.trim())?;
+        }
+    }
+    Some((name, quality))
+}
+
+/// Returns the encodings the client accepts given its `Accept-Encoding`
+/// header, best first, with `None` standing for `identity`, that is no
+/// coding at all. An empty list means that nothing is acceptable.
+///
+/// Encodings of the same quality are ordered gzip, deflate, identity.
+/// Elements with a malformed quality are ignored. Without the header, only
+/// `identity` is used, although any coding would be acceptable.
+pub fn negotiate(accept_encoding: Option<&str>) -> Vec<Option<Coding>> {
+    let Some(header) = accept_encoding else {
+        return vec![None];
+    };
+    let elements: Vec<(&str, u16)> = header.split(',').filter_map(parse_element).collect();
+    let quality_of = |name: &str| {
+        elements
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, q)| q)
+    };
+    let any = quality_of("*");
+    let mut accepted: Vec<(Option<Coding>, u16)> =
+        [Some(Coding::Gzip), Some(Coding::Deflate), None]
+            .into_iter()
+            .map(|encoding| {
+                let quality = match encoding {
+                    Some(coding) => quality_of(coding.name()).or(any).unwrap_or(0),
+                    // `identity` is acceptable unless refused, either by name or
+                    // by `*` (RFC 9110, section 12.5.3).
+                    None => quality_of("identity").or(any).unwrap_or(1000),
+                };
+                (encoding, quality)
+            })
+            .filter(|&(_, quality)| quality > 0)
+            .collect();
+    // The sort is stable, so ties keep the order of the server.
+    accepted.sort_by(|(_, a), (_, b)| b.cmp(a));
+    accepted.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+/// Returns whether bodies of the given media type are worth compressing.
+fn is_compressible(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap().trim();
+    media_type.starts_with("text/")
+        || matches!(
+            media_type,
+            "application/javascript" | "application/json" | "application/wasm" | "image/svg+xml"
+        )
+}
+
+/// Compresses `content` with every supported coding and keeps the results,
+/// so that responses carrying `etag` are not compressed again per request.
+///
+/// The results replace those of earlier calls once they are complete.
+pub fn precompress(content: &[u8], etag: &str) {
+    let mut cache = Vec::new();
+    if content.len() >= MIN_COMPRESS_LEN {
+        for coding in [Coding::Gzip, Coding::Deflate] {
+            cache.push(Precompressed {
+                etag: String::from(etag),
+                coding,
+                body: Arc::new(coding.encode(content)),
+            });
+        }
+    }
+    *lock(&PRECOMPRESSED) = cache;
+}
+
+/// Returns the value of the header `name` of `response`.
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Compresses the complete body of `response` with `coding`.
+fn compress(mut response: Response, coding: Coding) -> Response {
+    let Some(body) = response.body.bytes() else {
+        return response;
+    };
+    let cached = header(&response, "ETag").and_then(|etag| {
+        lock(&PRECOMPRESSED)
+            .iter()
+            .find(|entry| entry.etag == etag && entry.coding == coding)
+            .map(|entry| entry.body.clone())
+    });
+    response.body = match cached {
+        Some(data) => {
+            let len = data.len();
+            Body::Shared(data, 0..len)
+        }
+        None => Body::Full(coding.encode(body)),
+    };
+    response.with_header("Content-Encoding", coding.name())
+}
+
+/// Sends the body of `response` in the encoding the client prefers.
+///
+/// Only complete `200 OK` bodies held in memory are compressed; partial
+/// content refers to offsets in the uncompressed file, and bodies generated
+/// while they are sent, such as those of large files, are not at hand.
+/// Bodies too short or of types that do not compress well are sent as they
+/// are, unless the client refuses `identity`. `200 OK` responses that cannot
+/// be sent in any encoding the client accepts are replaced by
+/// `406 Not Acceptable`.
+pub fn apply(request: &Request, response: Response) -> Response {
+    let encodable = response.status == Status::Ok
+        && response.body.bytes().is_some()
+        && header(&response, "Content-Encoding").is_none();
+    let worthwhile = encodable
+        && response
+            .body
+            .bytes()
+            .is_some_and(|body| body.len() >= MIN_COMPRESS_LEN)
+        && header(&response, "Content-Type").is_some_and(is_compressible);
+
+    let accepted = negotiate(request.header("Accept-Encoding"));
+    let usable = |encoding: &&Option<Coding>| match encoding {
+        Some(_) => worthwhile || (encodable && !accepted.contains(&None)),
+        None => true,
+    };
+    let response = match accepted.iter().find(usable) {
+        Some(&Some(coding)) => compress(response, coding),
+        Some(None) => response,
+        None if response.status == Status::Ok => Response::error(Status::NotAcceptable),
+        None => response,
+    };
+    // Caches must not send the response to clients accepting other
+    // encodings.
+    if encodable || response.status == Status::NotAcceptable {
+        response.with_header("Vary", "Accept-Encoding")
+    } else {
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    #[test]
+    fn test_negotiate() {
+        const GZIP: Option<Coding> = Some(Coding::Gzip);
+        const DEFLATE: Option<Coding> = Some(Coding::Deflate);
+        const IDENTITY: Option<Coding> = None;
+        assert_eq!(negotiate(None), [IDENTITY]);
+        assert_eq!(negotiate(Some("")), [IDENTITY]);
+        assert_eq!(negotiate(Some("identity")), [IDENTITY]);
+        assert_eq!(negotiate(Some("deflate")), [DEFLATE, IDENTITY]);
+        assert_eq!(negotiate(Some("br, deflate")), [DEFLATE, IDENTITY]);
+        assert_eq!(
+            negotiate(Some("deflate, GZIP;q=0.8")),
+            [DEFLATE, IDENTITY, GZIP]
+        );
+        assert_eq!(
+            negotiate(Some("gzip;q=0.5, identity;q=1.0")),
+            [IDENTITY, GZIP]
+        );
+        assert_eq!(negotiate(Some("*")), [GZIP, DEFLATE, IDENTITY]);
+        assert_eq!(negotiate(Some("*;q=0.2, gzip;q=0")), [DEFLATE, IDENTITY]);
+        // Refusing `identity`.
+        assert_eq!(negotiate(Some("gzip, identity;q=0")), [GZIP]);
+        assert_eq!(negotiate(Some("*;q=0")), []);
+        assert_eq!(negotiate(Some("deflate, *;q=0")), [DEFLATE]);
+        assert_eq!(
+            negotiate(Some("deflate, *;q=0, identity;q=0.1")),
+            [DEFLATE, IDENTITY]
+        );
+    }
+
+    #[test]
+    fn test_ties() {
+        const GZIP: Option<Coding> = Some(Coding::Gzip);
+        const DEFLATE: Option<Coding> = Some(Coding::Deflate);
+        assert_eq!(negotiate(Some("deflate, gzip")), [GZIP, DEFLATE, None]);
+        assert_eq!(
+            negotiate(Some("identity;q=0.5, deflate;q=0.500, gzip;q=0.5")),
+            [GZIP, DEFLATE, None]
+        );
+        assert_eq!(
+            negotiate(Some("gzip;q=0.501, deflate;q=0.5")),
+            [None, GZIP, DEFLATE]
+        );
+    }
+
+    #[test]
+    fn test_malformed_quality() {
+        assert_eq!(parse_qvalue("0"), Some(0));
+        assert_eq!(parse_qvalue("0.25"), Some(250));
+        assert_eq!(parse_qvalue("1."), Some(1000));
+        assert_eq!(parse_qvalue("1.000"), Some(1000));
+        for value in ["", ".5", "0.1234", "1.001", "2", "-0", "0.x", "high", "0,5"] {
+            assert_eq!(parse_qvalue(value), None, "{:?}", value);
+        }
+        // Malformed elements are ignored, so these do not refuse anything.
+        assert_eq!(negotiate(Some("gzip;q=1.5")), [None]);
+        assert_eq!(
+            negotiate(Some("identity;q=abc, deflate")),
+            [Some(Coding::Deflate), None]
+        );
+        assert_eq!(
+            negotiate(Some("*;q, gzip;q=0.5")),
+            [None, Some(Coding::Gzip)]
+        );
+        assert_eq!(
+            negotiate(Some(" ; q=1, gzip ; Q = 0.9 ")),
+            [None, Some(Coding::Gzip)]
+        );
+    }
+
+    fn request(accept_encoding: &str) -> Request {
+        let raw = format!(
+            "GET / HTTP/1.1\r\nHost: test\r\nAccept-Encoding: {}\r\n\r\n",
+            accept_encoding
+        );
+        RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap()
+    }
+
+    fn html(len: usize) -> Response {
+        Response::new(Status::Ok, "text/html", vec![b'a'; len])
+    }
+
+    #[test]
+    fn test_apply() {
+        let response = apply(&request("gzip;q=0.5, identity"), html(1000));
+        assert_eq!(header(&response, "Content-Encoding"), None);
+        assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+        let response = apply(&request("gzip;q=0.5, deflate"), html(1000));
+        assert_eq!(header(&response, "Content-Encoding"), Some("deflate"));
+        assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+
+        // Short bodies are only compressed when `identity` is refused.
+        let response = apply(&request("gzip"), html(10));
+        assert_eq!(header(&response, "Content-Encoding"), None);
+        let response = apply(&request("gzip, identity;q=0"), html(10));
+        assert_eq!(header(&response, "Content-Encoding"), Some("gzip"));
+
+        let response = apply(&request("br, identity;q=0"), html(1000));
+        assert_eq!(response.status, Status::NotAcceptable);
+        assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+        // Errors are sent as they are.
+        let response = apply(&request("*;q=0"), Response::error(Status::NotFound));
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(header(&response, "Vary"), None);
+    }
+
+    #[test]
+    fn test_compressible() {
+        assert!(is_compressible("text/html; charset=utf-8"));
+        assert!(is_compressible("application/json"));
+        assert!(!is_compressible("image/png"));
+        assert!(!is_compressible("application/octet-stream"));
+    }
+}
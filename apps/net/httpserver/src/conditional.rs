@@ -0,0 +1,110 @@
+//! Entity tags and conditional requests.
+
+use std::string::String;
+
+use crate::httpdate;
+
+/// Computes a weak entity tag from the length and a hash of `content`.
+pub fn etag_for(content: &[u8]) -> String {
+    // 64-bit FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in content {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("W/\"{:x}-{:016x}\"", content.len(), hash)
+}
+
+/// Computes a weak entity tag from the length of a file and when it was last
+/// modified, for files whose contents are not read up front.
+pub fn etag_for_file(len: u64, modified: u64) -> String {
+    format!("W/\"{:x}-t{:x}\"", len, modified)
+}
+
+/// Returns whether the `If-None-Match` header value `header` matches `etag`,
+/// using the weak comparison function.
+pub fn none_match(header: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| {
+        let tag = tag.trim();
+        String::from(tag.strip_prefix("W/").unwrap_or(tag))
+    };
+    let etag = opaque(etag);
+    header.trim() == "*" || header.split(',').any(|tag| opaque(tag) == etag)
+}
+
+/// Returns whether a resource last modified at `modified` (in seconds since
+/// the Unix epoch) is unchanged since the `If-Modified-Since` header value
+/// `header`. Dates that cannot be parsed are ignored.
+pub fn not_modified_since(header: &str, modified: u64) -> bool {
+    httpdate::parse(header).is_some_and(|since| modified <= since)
+}
+
+/// Returns whether the `If-Range` header value `header` still names the
+/// representation with the entity tag `etag`, last modified at `modified` if
+/// known, so that the `Range` header may be honored (RFC 9110, section
+/// 13.1.5).
+///
+/// Entity tags are compared with the strong comparison function, which weak
+/// tags never pass, and dates must equal the modification time.
+pub fn range_applies(header: &str, etag: &str, modified: Option<u64>) -> bool {
+    let header = header.trim();
+    if header.starts_with('"') || header.starts_with("W/") {
+        return !etag.starts_with("W/") && header == etag;
+    }
+    match (httpdate::parse(header), modified) {
+        (Some(date), Some(modified)) => date == modified,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag() {
+        let a = etag_for(b"hello");
+        assert!(a.starts_with("W/\"5-"));
+        assert_eq!(a, etag_for(b"hello"));
+        assert_ne!(a, etag_for(b"hellp"));
+
+        let file = etag_for_file(5, 784_111_777);
+        assert_eq!(file, "W/\"5-t2ebc98a1\"");
+        assert_ne!(file, etag_for_file(5, 784_111_778));
+    }
+
+    #[test]
+    fn test_none_match() {
+        let etag = etag_for(b"hello");
+        assert!(none_match(&etag, &etag));
+        assert!(none_match("*", &etag));
+        assert!(none_match(&format!("\"x\", {}", etag), &etag));
+        // weak comparison ignores the W/ prefix
+        assert!(none_match(etag.strip_prefix("W/").unwrap(), &etag));
+        assert!(!none_match("\"x\"", &etag));
+        assert!(!none_match("", &etag));
+    }
+
+    #[test]
+    fn test_not_modified_since() {
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert!(not_modified_since(date, 784_111_777));
+        assert!(not_modified_since(date, 784_111_000));
+        assert!(!not_modified_since(date, 784_111_778));
+        assert!(!not_modified_since("yesterday", 0));
+    }
+
+    #[test]
+    fn test_range_applies() {
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert!(range_applies(date, "\"x\"", Some(784_111_777)));
+        assert!(!range_applies(date, "\"x\"", Some(784_111_778)));
+        assert!(!range_applies(date, "\"x\"", None));
+        assert!(range_applies("\"x\"", "\"x\"", None));
+        assert!(!range_applies("\"y\"", "\"x\"", None));
+        // Weak tags never match.
+        let etag = etag_for(b"hello");
+        assert!(!range_applies(&etag, &etag, None));
+        assert!(!range_applies("yesterday", "\"x\"", Some(0)));
+    }
+}
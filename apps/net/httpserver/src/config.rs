@@ -0,0 +1,239 @@
+//! Settings read at startup, so that the server can be tuned without being
+//! rebuilt.
+//!
+//! Settings are `key=value` pairs, read from [`CONFIG_PATH`] if it exists,
+//! one per line with `#` starting a comment, and then from the command line
+//! when running on a host. The keys are:
+//!
+//! - `addr`: the listening address, as `ADDR:PORT` or a bare `PORT`.
+//! - `workers`: the number of threads handling connections.
+//! - `queue_depth`: the number of accepted connections that may wait for a
+//!   worker.
+//! - `doc_root`: the directory files are served from.
+//! - `upload_dir`: the directory files uploaded with `POST /upload` are saved
+//!   in.
+//! - `download_dir`: the directory files are downloaded from with
+//!   `GET /download/<name>`.
+//! - `writable`: `true` to let `PUT` and `DELETE` requests change the files
+//!   in the document roots, which anyone who can reach the server may then
+//!   do. `false` by default.
+//!
+//! Bad values are reported and leave the setting at its default, and unknown
+//! keys are reported and ignored.
+
+use std::boxed::Box;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::string::String;
+
+/// The file settings are read from. It is on the disk image, unlike `/sys`,
+/// which is replaced by the kernel's own filesystem at boot.
+pub const CONFIG_PATH: &str = "/etc/httpd.conf";
+
+/// The settings in effect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub addr: SocketAddr,
+    pub workers: usize,
+    pub queue_depth: usize,
+    pub doc_root: &'static str,
+    pub upload_dir: &'static str,
+    pub download_dir: &'static str,
+    pub writable: bool,
+}
+
+/// Parses a listening address given as `ADDR:PORT` or as a bare `PORT`.
+pub fn parse_addr(s: &str) -> Option<SocketAddr> {
+    let s = s.trim();
+    if let Ok(port) = s.parse::<u16>() {
+        return Some(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port));
+    }
+    s.parse().ok()
+}
+
+/// Parses a count that must be at least 1.
+fn parse_count(value: &str) -> Result<usize, &'static str> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1"),
+        Ok(n) => Ok(n),
+        Err(_) => Err("not a number"),
+    }
+}
+
+/// Parses a directory, which is kept for as long as the server runs.
+fn parse_dir(value: &str) -> Result<&'static str, &'static str> {
+    let dir = value.trim_end_matches('/');
+    if dir.is_empty() {
+        return Err("must not be empty or /");
+    }
+    Ok(Box::leak(String::from(dir).into_boxed_str()))
+}
+
+/// Parses `true` or `false`.
+fn parse_bool(value: &str) -> Result<bool, &'static str> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err("expected true or false"),
+    }
+}
+
+impl Config {
+    /// Changes the setting `key` to `value`, returning why not if the key is
+    /// unknown or the value is bad.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "addr" => match parse_addr(value) {
+                Some(addr) => self.addr = addr,
+                None => return Err(String::from("not an address")),
+            },
+            "workers" => self.workers = parse_count(value)?,
+            "queue_depth" => self.queue_depth = parse_count(value)?,
+            "doc_root" => self.doc_root = parse_dir(value)?,
+            "upload_dir" => self.upload_dir = parse_dir(value)?,
+            "download_dir" => self.download_dir = parse_dir(value)?,
+            "writable" => self.writable = parse_bool(value)?,
+            _ => return Err(String::from("unknown setting")),
+        }
+        Ok(())
+    }
+
+    /// Applies the `key=value` pair `setting`, printing a warning naming
+    /// `source` if it cannot be applied.
+    fn apply(&mut self, setting: &str, source: fmt::Arguments) {
+        let result = match setting.split_once('=') {
+            Some((key, value)) => self.set(key.trim(), value.trim()),
+            None => Err(String::from("expected key=value")),
+        };
+        if let Err(reason) = result {
+            println!("{}: ignoring {:?}: {}", source, setting, reason);
+        }
+    }
+
+    /// Applies the settings in the content of a configuration file, read from
+    /// `path`.
+    pub fn apply_file(&mut self, content: &str, path: &str) {
+        for (i, line) in content.lines().enumerate() {
+            let setting = line.split('#').next().unwrap_or("").trim();
+            if !setting.is_empty() {
+                self.apply(setting, format_args!("{}:{}", path, i + 1));
+            }
+        }
+    }
+
+    /// Applies the settings given as command-line arguments. For
+    /// compatibility, an argument without `=` is the listening address.
+    pub fn apply_args<I: Iterator<Item = String>>(&mut self, args: I) {
+        for arg in args {
+            if arg.contains('=') {
+                self.apply(&arg, format_args!("command line"));
+            } else {
+                self.apply(&format!("addr={}", arg), format_args!("command line"));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "addr={} workers={} queue_depth={} doc_root={} upload_dir={} download_dir={} \
+             writable={}",
+            self.addr,
+            self.workers,
+            self.queue_depth,
+            self.doc_root,
+            self.upload_dir,
+            self.download_dir,
+            self.writable
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Config {
+        Config {
+            addr: "0.0.0.0:5555".parse().unwrap(),
+            workers: 1,
+            queue_depth: 64,
+            doc_root: "/html",
+            upload_dir: "/uploads",
+            download_dir: "/downloads",
+            writable: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(parse_addr("8080"), Some("0.0.0.0:8080".parse().unwrap()));
+        assert_eq!(
+            parse_addr("10.0.2.15:80"),
+            Some("10.0.2.15:80".parse().unwrap())
+        );
+        assert_eq!(parse_addr("[::1]:0"), Some("[::1]:0".parse().unwrap()));
+        assert_eq!(parse_addr("localhost"), None);
+        assert_eq!(parse_addr("10.0.2.15:99999"), None);
+    }
+
+    #[test]
+    fn test_apply_file() {
+        let mut config = defaults();
+        config.apply_file(
+            "# httpd settings\n\
+             \n\
+             workers = 8\n\
+             queue_depth=128  # deeper\n\
+             doc_root=/srv/www/\n\
+             upload_dir=/srv/incoming\n\
+             download_dir=/srv/outgoing\n\
+             writable=true\n\
+             addr=10.0.2.15:80\n",
+            "httpd.conf",
+        );
+        assert_eq!(
+            config,
+            Config {
+                addr: "10.0.2.15:80".parse().unwrap(),
+                workers: 8,
+                queue_depth: 128,
+                doc_root: "/srv/www",
+                upload_dir: "/srv/incoming",
+                download_dir: "/srv/outgoing",
+                writable: true,
+            }
+        );
+        assert_eq!(
+            format!("{}", config),
+            "addr=10.0.2.15:80 workers=8 queue_depth=128 doc_root=/srv/www \
+             upload_dir=/srv/incoming download_dir=/srv/outgoing writable=true"
+        );
+    }
+
+    #[test]
+    fn test_bad_settings() {
+        let mut config = defaults();
+        config.apply_file(
+            "workers=0\nqueue_depth=many\naddr=localhost\ndoc_root=/\nwritable=yes\ncolour=blue\nworkers\n",
+            "httpd.conf",
+        );
+        assert_eq!(config, defaults());
+        // Good settings still apply around bad ones.
+        config.apply_file("workers=-1\nworkers=2\n", "httpd.conf");
+        assert_eq!(config.workers, 2);
+    }
+
+    #[test]
+    fn test_apply_args() {
+        let mut config = defaults();
+        let args = ["8080", "workers=3", "nonsense"]
+            .into_iter()
+            .map(String::from);
+        config.apply_args(args);
+        assert_eq!(config.addr, "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(config.workers, 3);
+    }
+}
@@ -0,0 +1,100 @@
+//! Parsing `Cookie` headers and building `Set-Cookie` headers (RFC 6265).
+
+use std::string::String;
+
+/// Parses the value of a `Cookie` header into name/value pairs, in order and
+/// including repeated names.
+///
+/// Whitespace around names and values is ignored, as are double quotes around
+/// a value. Pairs without `=` or with an empty name are skipped.
+pub fn parse(header: &str) -> impl Iterator<Item = (&str, &str)> {
+    header.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+        let value = value.trim();
+        let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(unquoted) => unquoted,
+            None => value,
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some((name, value))
+    })
+}
+
+/// A cookie to set with a `Set-Cookie` header.
+///
+/// The name and value are sent as they are, so they must only contain the
+/// characters allowed by RFC 6265, section 4.1.1: no whitespace, `"`, `,`,
+/// `;` or `\`.
+pub struct SetCookie<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    /// The paths the cookie is sent back for.
+    pub path: Option<&'a str>,
+    /// How many seconds the cookie is kept; without it the cookie lasts until
+    /// the browser is closed.
+    pub max_age: Option<u64>,
+    /// Whether scripts in the page are denied access to the cookie.
+    pub http_only: bool,
+}
+
+impl SetCookie<'_> {
+    /// Returns the value of the `Set-Cookie` header.
+    pub fn header_value(&self) -> String {
+        let mut value = format!("{}={}", self.name, self.value);
+        if let Some(path) = self.path {
+            value += &format!("; Path={}", path);
+        }
+        if let Some(max_age) = self.max_age {
+            value += &format!("; Max-Age={}", max_age);
+        }
+        if self.http_only {
+            value += "; HttpOnly";
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn cookies(header: &str) -> Vec<(&str, &str)> {
+        parse(header).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(cookies("a=1"), [("a", "1")]);
+        assert_eq!(cookies("a=1; b=2"), [("a", "1"), ("b", "2")]);
+        assert_eq!(cookies("  a = 1 ;b=2;  "), [("a", "1"), ("b", "2")]);
+        assert_eq!(cookies("a=\"x y\"; b=\""), [("a", "x y"), ("b", "\"")]);
+        assert_eq!(cookies("a=; b=\"\""), [("a", ""), ("b", "")]);
+        assert_eq!(cookies("a=1; a=2"), [("a", "1"), ("a", "2")]);
+        assert_eq!(cookies("a=b=c"), [("a", "b=c")]);
+        assert_eq!(cookies("flag; =1; ;b=2"), [("b", "2")]);
+        assert_eq!(cookies(""), []);
+    }
+
+    #[test]
+    fn test_set_cookie() {
+        let mut cookie = SetCookie {
+            name: "id",
+            value: "42",
+            path: None,
+            max_age: None,
+            http_only: false,
+        };
+        assert_eq!(cookie.header_value(), "id=42");
+        cookie.path = Some("/");
+        cookie.max_age = Some(3600);
+        cookie.http_only = true;
+        assert_eq!(
+            cookie.header_value(),
+            "id=42; Path=/; Max-Age=3600; HttpOnly"
+        );
+    }
+}
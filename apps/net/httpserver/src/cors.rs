@@ -0,0 +1,136 @@
+//! Cross-origin resource sharing (CORS), letting pages from other origins
+//! call the server from a browser.
+
+use crate::request::Request;
+use crate::response::{Response, Status};
+
+/// How long browsers may cache the answer to a preflight request, in seconds.
+const PREFLIGHT_MAX_AGE: u32 = 600;
+
+/// The origins allowed to read responses.
+pub struct Cors {
+    /// The value of `Access-Control-Allow-Origin`: an origin such as
+    /// `http://example.com`, or `*` for any origin.
+    pub allow_origin: &'static str,
+}
+
+impl Cors {
+    /// Answers `request` if it is a preflight request, which a browser sends
+    /// with `OPTIONS` to ask whether it may make a cross-origin request.
+    ///
+    /// The method and headers the browser asks for are allowed as they are;
+    /// the handlers still answer requests they do not support with an error.
+    pub fn preflight(&self, request: &Request) -> Option<Response> {
+        if request.method != "OPTIONS" || request.header("Origin").is_none() {
+            return None;
+        }
+        let method = request.header("Access-Control-Request-Method")?;
+        let mut response = Response::empty(Status::NoContent)
+            .with_header("Access-Control-Allow-Methods", method)
+            .with_header("Access-Control-Max-Age", &format!("{}", PREFLIGHT_MAX_AGE));
+        if let Some(headers) = request.header("Access-Control-Request-Headers") {
+            response = response.with_header("Access-Control-Allow-Headers", headers);
+        }
+        Some(response)
+    }
+
+    /// Adds `Access-Control-Allow-Origin` to the response to a cross-origin
+    /// request, that is one with an `Origin` header.
+    pub fn apply(&self, request: &Request, response: Response) -> Response {
+        if request.header("Origin").is_none() {
+            return response;
+        }
+        let response = response.with_header("Access-Control-Allow-Origin", self.allow_origin);
+        if self.allow_origin == "*" {
+            response
+        } else {
+            // Caches must not reuse the response for requests from other
+            // origins, or without an origin.
+            response.with_header("Vary", "Origin")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+    use std::string::String;
+
+    fn request(method: &str, headers: &str) -> Request {
+        let raw = format!("{} /api HTTP/1.1\r\nHost: test\r\n{}\r\n", method, headers);
+        RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        let (_, value) = response.headers.iter().find(|(n, _)| n == name)?;
+        Some(value.as_str())
+    }
+
+    const CORS: Cors = Cors {
+        allow_origin: "http://example.com",
+    };
+
+    #[test]
+    fn test_preflight() {
+        let response = CORS
+            .preflight(&request(
+                "OPTIONS",
+                "Origin: http://example.com\r\n\
+                 Access-Control-Request-Method: PUT\r\n\
+                 Access-Control-Request-Headers: content-type, x-token\r\n",
+            ))
+            .unwrap();
+        assert_eq!(response.status, Status::NoContent);
+        assert_eq!(
+            header(&response, "Access-Control-Allow-Methods"),
+            Some("PUT")
+        );
+        assert_eq!(
+            header(&response, "Access-Control-Allow-Headers"),
+            Some("content-type, x-token")
+        );
+
+        let response = CORS
+            .preflight(&request(
+                "OPTIONS",
+                "Origin: http://example.com\r\nAccess-Control-Request-Method: GET\r\n",
+            ))
+            .unwrap();
+        assert_eq!(header(&response, "Access-Control-Allow-Headers"), None);
+
+        // Plain `OPTIONS` requests are left to the router.
+        assert!(CORS.preflight(&request("OPTIONS", "")).is_none());
+        let no_method = request("OPTIONS", "Origin: http://example.com\r\n");
+        assert!(CORS.preflight(&no_method).is_none());
+        let get = request(
+            "GET",
+            "Origin: http://example.com\r\nAccess-Control-Request-Method: GET\r\n",
+        );
+        assert!(CORS.preflight(&get).is_none());
+    }
+
+    #[test]
+    fn test_apply() {
+        let ok = || Response::new(Status::Ok, "text/plain", b"ok".to_vec());
+        let response = CORS.apply(&request("GET", "Origin: http://other.org\r\n"), ok());
+        assert_eq!(
+            header(&response, "Access-Control-Allow-Origin"),
+            Some("http://example.com")
+        );
+        assert_eq!(header(&response, "Vary"), Some("Origin"));
+
+        let any = Cors { allow_origin: "*" };
+        let response = any.apply(&request("POST", "Origin: http://other.org\r\n"), ok());
+        assert_eq!(header(&response, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(header(&response, "Vary"), None);
+
+        let response = CORS.apply(&request("GET", ""), ok());
+        assert_eq!(
+            response.headers,
+            [(String::from("Content-Type"), String::from("text/plain"))]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! A small DEFLATE compressor (RFC 1951) with gzip and zlib framing.
+//!
+//! Matches are found with a hash chain over 3-byte prefixes and encoded with
+//! the fixed Huffman codes, which keeps the encoder short while still giving
+//! good ratios on HTML and other text.
+
+use std::vec::Vec;
+
+/// The size of the sliding window.
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// The number of candidates visited when looking for a match.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 14;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes bits to a byte vector, least significant bit first.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    nbits: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, n: u32) {
+        self.acc |= value << self.nbits;
+        self.nbits += n;
+        while self.nbits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.nbits -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is packed starting from its most
+    /// significant bit.
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Writes a literal/length symbol using the fixed Huffman code.
+fn write_symbol(w: &mut BitWriter, sym: u32) {
+    match sym {
+        0..=143 => w.write_code(0x30 + sym, 8),
+        144..=255 => w.write_code(0x190 + sym - 144, 9),
+        256..=279 => w.write_code(sym - 256, 7),
+        _ => w.write_code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let li = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= len)
+        .unwrap();
+    write_symbol(w, 257 + li as u32);
+    w.write_bits(
+        (len - LENGTH_BASE[li] as usize) as u32,
+        LENGTH_EXTRA[li] as u32,
+    );
+
+    let di = DIST_BASE.iter().rposition(|&b| b as usize <= dist).unwrap();
+    w.write_code(di as u32, 5);
+    w.write_bits(
+        (dist - DIST_BASE[di] as usize) as u32,
+        DIST_EXTRA[di] as u32,
+    );
+}
+
+/// Links every position of the input to the previous one starting with the
+/// same three bytes.
+struct HashChain {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl HashChain {
+    const NONE: usize = usize::MAX;
+
+    fn new(len: usize) -> Self {
+        Self {
+            head: vec![Self::NONE; 1 << HASH_BITS],
+            prev: vec![Self::NONE; len],
+        }
+    }
+
+    fn hash(data: &[u8]) -> usize {
+        let v = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let h = Self::hash(&data[pos..]);
+            self.prev[pos] = self.head[h];
+            self.head[h] = pos;
+        }
+    }
+}
+
+/// Compresses `data` into a raw DEFLATE stream appended to `out`.
+fn deflate_into(data: &[u8], out: Vec<u8>) -> Vec<u8> {
+    let mut w = BitWriter::new(out);
+    // A single final block with fixed Huffman codes.
+    w.write_bits(1, 1);
+    w.write_bits(1, 2);
+
+    let mut chain = HashChain::new(data.len());
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut cand = chain.head[HashChain::hash(&data[pos..])];
+            let mut visited = 0;
+            while cand != HashChain::NONE && pos - cand <= WINDOW_SIZE && visited < MAX_CHAIN {
+                let len = data[cand..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - cand;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                cand = chain.prev[cand];
+                visited += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut w, best_len, best_dist);
+            for p in pos..pos + best_len {
+                chain.insert(data, p);
+            }
+            pos += best_len;
+        } else {
+            write_symbol(&mut w, data[pos] as u32);
+            chain.insert(data, pos);
+            pos += 1;
+        }
+    }
+
+    write_symbol(&mut w, 256);
+    w.finish()
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Computes the CRC-32 used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Computes the Adler-32 checksum used by zlib.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Compresses `data` in the gzip format (RFC 1952).
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // magic, CM = deflate, no flags, no mtime, no extra flags, unknown OS
+    let header = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    let mut out = deflate_into(data, header);
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Compresses `data` in the zlib format (RFC 1950), which is what the
+/// `deflate` content coding means in HTTP.
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // CM = deflate with a 32K window, no dictionary, fastest compression
+    let header = vec![0x78, 0x01];
+    let mut out = deflate_into(data, header);
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_empty() {
+        // the fixed-Huffman encoding of an empty input is `03 00`
+        assert_eq!(zlib(b""), [0x78, 0x01, 0x03, 0x00, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_gzip_framing() {
+        let data = b"<html><body>hello hello hello hello hello</body></html>".repeat(20);
+        let out = gzip(&data);
+        assert_eq!(out[..3], [0x1f, 0x8b, 8]);
+        assert!(out.len() < data.len() / 4);
+        let trailer = &out[out.len() - 8..];
+        assert_eq!(trailer[..4], crc32(&data).to_le_bytes());
+        assert_eq!(trailer[4..], (data.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_literals() {
+        // "a" is literal 0x61 (code 0x91, 8 bits), then end of block
+        assert_eq!(zlib(b"a")[2..5], [0x4b, 0x04, 0x00]);
+    }
+}
@@ -0,0 +1,152 @@
+//! `GET /download/<name>`, serving the files of the download directory as
+//! attachments, so that browsers save them rather than show them.
+//!
+//! Downloads may be resumed: `Range` and `If-Range` are honored as for other
+//! files.
+
+use std::string::String;
+
+use crate::files;
+use crate::request::Request;
+use crate::response::{Response, Status};
+use crate::url;
+
+/// The directory files are downloaded from, unless another one is
+/// configured.
+pub const DOWNLOAD_DIR: &str = "/downloads";
+
+/// Returns whether `b` may appear unescaped in an extended parameter value
+/// (RFC 8187, section 3.2.1).
+fn is_attr_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || matches!(
+            b,
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~'
+        )
+}
+
+/// Returns the `Content-Disposition` value for saving a file as `filename`.
+///
+/// Names with characters other than those allowed in an extended parameter
+/// value, such as quotes, spaces or non-ASCII characters, are sent
+/// percent-encoded in `filename*`, along with a plain `filename` in which
+/// they are replaced by `_` for clients that do not understand it (RFC 6266,
+/// appendix D).
+pub fn content_disposition(filename: &str) -> String {
+    if filename.bytes().all(is_attr_char) {
+        return format!("attachment; filename=\"{}\"", filename);
+    }
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' && c != '%' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::with_capacity(filename.len() * 3);
+    for b in filename.bytes() {
+        if is_attr_char(b) {
+            encoded.push(b as char);
+        } else {
+            encoded += &format!("%{:02X}", b);
+        }
+    }
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+/// Answers `GET /download<name>`, where `name` is the rest of the path, with
+/// the file `name` of `dir`.
+pub fn handle(request: &Request, name: &str, dir: &str) -> Response {
+    let path = match url::decode_path(name) {
+        Some(path) if !path.ends_with('/') => path,
+        _ => return Response::error(Status::NotFound),
+    };
+    let filename = path.rsplit('/').next().unwrap_or_default();
+    let disposition = content_disposition(filename);
+    let response = files::serve_file(request, &format!("{}{}", dir, path));
+    match response.status {
+        Status::Ok | Status::PartialContent | Status::NotModified => {
+            response.with_header("Content-Disposition", &disposition)
+        }
+        _ => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("my \"best\" file.txt"),
+            "attachment; filename=\"my _best_ file.txt\"; \
+             filename*=UTF-8''my%20%22best%22%20file.txt"
+        );
+        assert_eq!(
+            content_disposition("résumé 100%.txt"),
+            "attachment; filename=\"r_sum_ 100_.txt\"; \
+             filename*=UTF-8''r%C3%A9sum%C3%A9%20100%25.txt"
+        );
+    }
+
+    #[test]
+    fn test_handle() {
+        let dir = std::env::temp_dir().join(format!("httpd-download-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("a b.bin"), b"0123456789").unwrap();
+        let dir_str = dir.to_str().unwrap();
+        let download = |name: &str, headers: &str| {
+            let raw = format!(
+                "GET /download{} HTTP/1.1\r\nHost: test\r\n{}\r\n",
+                name, headers
+            );
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            handle(&request, name, dir_str)
+        };
+        let header = |response: &Response, name: &str| {
+            let (_, value) = response.headers.iter().find(|(n, _)| *n == name)?;
+            Some(value.clone())
+        };
+
+        let response = download("/sub/a%20b.bin", "");
+        assert_eq!(response.status, Status::Ok);
+        assert_eq!(
+            header(&response, "Content-Disposition").as_deref(),
+            Some("attachment; filename=\"a b.bin\"; filename*=UTF-8''a%20b.bin")
+        );
+        let last_modified = header(&response, "Last-Modified").unwrap();
+
+        // Resuming the download.
+        let response = download(
+            "/sub/a%20b.bin",
+            &format!("Range: bytes=4-\r\nIf-Range: {}\r\n", last_modified),
+        );
+        assert_eq!(response.status, Status::PartialContent);
+        assert!(header(&response, "Content-Disposition").is_some());
+        assert_eq!(response.body.bytes(), Some(&b"456789"[..]));
+        // The file changed since the first part was received.
+        let response = download(
+            "/sub/a%20b.bin",
+            "Range: bytes=4-\r\nIf-Range: Thu, 01 Jan 1970 00:00:00 GMT\r\n",
+        );
+        assert_eq!(response.status, Status::Ok);
+
+        for name in ["/sub", "/sub/", "", "/missing", "/../etc/passwd"] {
+            let response = download(name, "");
+            assert_eq!(response.status, Status::NotFound, "{}", name);
+            assert_eq!(header(&response, "Content-Disposition"), None);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
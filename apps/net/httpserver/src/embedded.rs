@@ -0,0 +1,54 @@
+//! A small site built into the server, served when the document root is
+//! missing, such as on images built without a disk.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::fs::{self, Kind};
+
+/// The built-in files, by path.
+const ASSETS: &[(&str, &[u8])] = &[
+    ("/index.html", include_bytes!("../assets/index.html")),
+    ("/404.html", include_bytes!("../assets/404.html")),
+];
+
+/// Whether the built-in site is served.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Checks that the document root `doc_root` is a directory, and serves the
+/// built-in site instead if it is not.
+pub fn check_root(doc_root: &str) {
+    let missing = !matches!(fs::stat(doc_root), Some(Kind::Dir));
+    if missing && !active() {
+        println!(
+            "document root {} not found, falling back to the built-in site",
+            doc_root
+        );
+    }
+    ACTIVE.store(missing, Ordering::Relaxed);
+}
+
+/// Returns whether the built-in site is served instead of the document root.
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Returns the built-in file at `path`, such as `/index.html`.
+pub fn get(path: &str) -> Option<&'static [u8]> {
+    ASSETS
+        .iter()
+        .find(|(p, _)| *p == path)
+        .map(|(_, content)| *content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        assert!(get("/index.html").unwrap().starts_with(b"<html>"));
+        assert!(get("/404.html").is_some());
+        assert!(get("/index").is_none());
+        assert!(get("index.html").is_none());
+    }
+}
@@ -0,0 +1,81 @@
+//! Error pages loaded from the document root.
+//!
+//! The pages are read once at startup. While the document root is missing,
+//! the pages built into the server are used instead. Statuses without a page,
+//! or whose page is missing, get the built-in body of [`Response::error`].
+//!
+//! [`Response::error`]: crate::response::Response::error
+
+use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::embedded;
+use crate::response::Status;
+use crate::sync::lock;
+
+/// The page used for each error status, as a file name in the document root.
+const PAGES: &[(Status, &str)] = &[
+    (Status::NotFound, "404.html"),
+    (Status::InternalServerError, "50x.html"),
+    (Status::ServiceUnavailable, "50x.html"),
+];
+
+/// The contents of the pages found by the last [`load`].
+static LOADED: Mutex<Vec<(Status, Arc<Vec<u8>>)>> = Mutex::new(Vec::new());
+
+/// Reads the pages listed in [`PAGES`] from `root`, skipping missing ones.
+fn read_pages(root: &str) -> Vec<(Status, Arc<Vec<u8>>)> {
+    let mut pages = Vec::new();
+    for &(status, name) in PAGES {
+        let file = format!("{}/{}", root, name);
+        let mut content = Vec::new();
+        match std::fs::File::open(&file).and_then(|mut f| f.read_to_end(&mut content)) {
+            Ok(_) => pages.push((status, Arc::new(content))),
+            Err(_) => match embedded::get(&format!("/{}", name)) {
+                Some(page) if embedded::active() => pages.push((status, Arc::new(page.to_vec()))),
+                _ => info!("no error page {} for {}", file, status.code()),
+            },
+        }
+    }
+    pages
+}
+
+/// Loads the error pages from the document root `root`, replacing the ones
+/// loaded before once all of them have been read.
+pub fn load(root: &str) {
+    let pages = read_pages(root);
+    *lock(&LOADED) = pages;
+}
+
+/// Returns the loaded page for `status`, if any.
+pub fn page(status: Status) -> Option<Vec<u8>> {
+    let page = lock(&LOADED)
+        .iter()
+        .find(|(s, _)| *s == status)
+        .map(|(_, page)| page.clone())?;
+    Some(page.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_pages() {
+        let root = std::env::temp_dir().join(format!("httpd-error-pages-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("50x.html"), "<h1>oops</h1>").unwrap();
+
+        let pages = read_pages(root.to_str().unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+        let pages: Vec<_> = pages.iter().map(|(s, p)| (*s, p.as_slice())).collect();
+        assert_eq!(
+            pages,
+            [
+                (Status::InternalServerError, &b"<h1>oops</h1>"[..]),
+                (Status::ServiceUnavailable, &b"<h1>oops</h1>"[..]),
+            ]
+        );
+    }
+}
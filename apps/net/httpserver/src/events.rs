@@ -0,0 +1,123 @@
+//! `GET /events`, a stream of server-sent events (SSE) carrying live
+//! statistics, which keeps its connection open until the client leaves.
+//!
+//! The stream holds a worker for as long as it lasts. It ends when a write
+//! fails, which is how a client that went away or stopped reading is noticed,
+//! and when the server shuts down.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::json::JsonObject;
+use crate::response::{Response, Status};
+use crate::shutdown;
+use crate::stats::STATS;
+use crate::sysinfo;
+
+/// How often the statistics are checked and sent if they changed.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the stream may go without a write before a comment is sent, so
+/// that a client that went away is noticed even when nothing changes.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// How long browsers wait before reconnecting after the stream ends, in
+/// milliseconds.
+const RECONNECT_DELAY_MS: u64 = 3000;
+
+/// Renders the statistics sent with each event as a JSON object.
+fn event_data() -> String {
+    JsonObject::new()
+        .number("requests", Some(STATS.requests.get() as u64))
+        .number("connections", Some(STATS.connections.get() as u64))
+        .number("active_connections", Some(STATS.active.get() as u64))
+        .number("uptime_secs", sysinfo::uptime())
+        .finish()
+}
+
+/// Writes events to `w` every `interval`, with a comment if nothing was
+/// written for `heartbeat`, until a write fails or the server shuts down.
+///
+/// Each event or comment is written with a single call, so that it is sent
+/// as one chunk, and flushed at once.
+fn stream(w: &mut dyn Write, interval: Duration, heartbeat: Duration) -> io::Result<()> {
+    w.write_all(format!("retry: {}\n\n", RECONNECT_DELAY_MS).as_bytes())?;
+    w.flush()?;
+    let mut last_data = String::new();
+    let mut last_write = Instant::now();
+    while !shutdown::requested() {
+        let data = event_data();
+        if data != last_data {
+            w.write_all(format!("data: {}\n\n", data).as_bytes())?;
+            last_data = data;
+        } else if last_write.elapsed() >= heartbeat {
+            w.write_all(b": heartbeat\n\n")?;
+        } else {
+            thread::sleep(interval);
+            continue;
+        }
+        w.flush()?;
+        last_write = Instant::now();
+        thread::sleep(interval);
+    }
+    Ok(())
+}
+
+/// Answers `GET /events`.
+pub fn handle() -> Response {
+    Response::chunked(Status::Ok, "text/event-stream", |w| {
+        stream(w, EVENT_INTERVAL, HEARTBEAT_INTERVAL)
+    })
+    .with_header("Cache-Control", "no-cache")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    /// Keeps what is written until `writes` calls have been made, then fails
+    /// like a connection the client closed.
+    struct Closing {
+        out: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for Closing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.writes == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.writes -= 1;
+            self.out.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut w = Closing {
+            out: Vec::new(),
+            writes: 3,
+        };
+        let start = Instant::now();
+        let interval = Duration::from_millis(1);
+        let err = stream(&mut w, interval, 5 * interval).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let out = String::from_utf8(w.out).unwrap();
+        let mut events = out.split_terminator("\n\n");
+        assert_eq!(events.next(), Some("retry: 3000"));
+        let data = events.next().unwrap().strip_prefix("data: ").unwrap();
+        assert!(data.contains("\"requests\":"));
+        assert!(data.contains("\"uptime_secs\":"));
+        let next = events.next().unwrap();
+        assert!(next.starts_with("data: {") || next == ": heartbeat");
+        assert_eq!(events.next(), None);
+    }
+}
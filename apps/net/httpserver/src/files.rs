@@ -0,0 +1,692 @@
+//! Serving files from the document root.
+
+use std::boxed::Box;
+use std::io::{prelude::*, SeekFrom};
+use std::ops::Range;
+use std::string::String;
+use std::sync::Arc;
+use std::vec::Vec;
+
+use crate::cache::{Entry, FileCache};
+use crate::compress;
+use crate::conditional;
+use crate::embedded;
+use crate::fs::{self, Kind};
+use crate::httpdate;
+use crate::listing;
+use crate::mime;
+use crate::range::{self, ByteRange};
+use crate::redirect;
+use crate::request::Request;
+use crate::response::{Body, Response, Status};
+use crate::stats::STATS;
+use crate::template;
+use crate::url;
+
+/// The directory that request paths are resolved against, unless a virtual
+/// host has its own or another one is configured.
+pub const DOC_ROOT: &str = "/html";
+/// The files served for requests of a directory, in order of preference.
+const INDEX_FILES: &[&str] = &["index.html", "index"];
+/// Whether to generate listings for directories without an index file, rather
+/// than answering `403 Forbidden`.
+const DIRECTORY_LISTING: bool = true;
+/// The maximum total size of the files kept in memory.
+const CACHE_BUDGET: usize = 4 * 1024 * 1024;
+/// The maximum size of a file kept in memory; larger files are read while
+/// they are sent, for every request.
+const MAX_CACHED_FILE_LEN: usize = 512 * 1024;
+
+static CACHE: FileCache = FileCache::new(CACHE_BUDGET, MAX_CACHED_FILE_LEN);
+
+/// Drops the files kept in memory, so that they are read again.
+pub fn clear_cache() {
+    CACHE.clear();
+}
+
+/// Returns the cache entry `key` for the file named `name`, which is `len`
+/// bytes long and was last modified at `modified`, reading its contents with
+/// `read` if it is not cached.
+fn cached<F>(key: &str, name: &str, len: u64, modified: Option<u64>, read: F) -> Option<Entry>
+where
+    F: FnOnce() -> Option<Vec<u8>>,
+{
+    if let Some(entry) = CACHE.get(key, len, modified) {
+        STATS.cache_hits.add(1);
+        return Some(entry);
+    }
+    STATS.cache_misses.add(1);
+    Some(insert(key, name, read()?, modified))
+}
+
+/// Caches `content` as the entry `key` for the file named `name`, last
+/// modified at `modified`.
+fn insert(key: &str, name: &str, content: Vec<u8>, modified: Option<u64>) -> Entry {
+    let entry = Entry {
+        etag: conditional::etag_for(&content),
+        content: Arc::new(content),
+        content_type: mime::content_type_for(name),
+        modified,
+    };
+    CACHE.insert(key, entry.clone());
+    entry
+}
+
+/// Returns the contents of `file`, which is `len` bytes long and small
+/// enough to be cached, from the cache or from the filesystem.
+///
+/// Without modification times, a file changed in place may keep its length,
+/// so it is read again and the cached entry is only used, keeping its entity
+/// tag, if the contents are the same.
+fn load_file(file: &str, len: u64) -> Option<Entry> {
+    let read = || {
+        let mut content = Vec::new();
+        match std::fs::File::open(file).and_then(|mut f| f.read_to_end(&mut content)) {
+            Ok(_) => Some(content),
+            Err(e) => {
+                info!("failed to read {}: {:?}", file, e);
+                None
+            }
+        }
+    };
+    if let Some(modified) = fs::modified(file) {
+        return cached(file, file, len, Some(modified), read);
+    }
+    let content = read()?;
+    match CACHE.get(file, len, None) {
+        Some(entry) if *entry.content == content => {
+            STATS.cache_hits.add(1);
+            Some(entry)
+        }
+        _ => {
+            STATS.cache_misses.add(1);
+            Some(insert(file, file, content, None))
+        }
+    }
+}
+
+/// Returns the built-in file at `path`, if any. It goes through the cache
+/// like other files, under a key that cannot be a file path.
+fn load_embedded(path: &str) -> Option<Entry> {
+    let content = embedded::get(path)?;
+    let key = format!("embedded:{}", path);
+    // Built-in files never change.
+    cached(&key, path, content.len() as u64, None, || {
+        Some(content.to_vec())
+    })
+}
+
+/// Answers the request with the contents of `file`, which is `len` bytes
+/// long, or the part of it selected by the `Range` header unless `If-Range`
+/// names another version.
+///
+/// Responds with `304 Not Modified` if the client already has the current
+/// version, as told by `If-None-Match`, or by `If-Modified-Since` without
+/// it (RFC 9110, section 13.1.3). `Last-Modified` is only sent if the
+/// filesystem records modification times.
+///
+/// HTML pages with placeholders are sent with the placeholders filled in.
+/// Files too large to be cached are read while they are sent instead, as
+/// described for [`stream_file`].
+fn read_file(request: &Request, file: &str, len: u64) -> Response {
+    if len > MAX_CACHED_FILE_LEN as u64 {
+        return stream_file(request, file, len);
+    }
+    match load_file(file, len) {
+        Some(entry) => send_entry(request, entry),
+        None => Response::error(Status::InternalServerError),
+    }
+}
+
+/// Answers the request with the file `entry`, as described for
+/// [`read_file`].
+fn send_entry(request: &Request, entry: Entry) -> Response {
+    let Entry {
+        content,
+        content_type,
+        etag,
+        modified,
+    } = entry;
+    if let Some(page) = template::render_page(request, content_type, &content) {
+        // The page changes with the values filled in, so it has no
+        // validators and cannot be requested in parts.
+        return Response::new(Status::Ok, content_type, page.into_bytes())
+            .with_header("Cache-Control", "no-cache");
+    }
+    let len = content.len() as u64;
+    send_parts(request, content_type, Some(&etag), modified, len, |part| {
+        Body::Shared(content, part.start as usize..part.end as usize)
+    })
+}
+
+/// Answers the request with `file`, which is `len` bytes long and too large
+/// to be kept in memory, reading it in chunks while the response is sent.
+///
+/// Its entity tag is made of its length and modification time, since its
+/// contents are not read up front; without a modification time, it has no
+/// validators. Placeholders are not filled in.
+fn stream_file(request: &Request, file: &str, len: u64) -> Response {
+    let mut f = match std::fs::File::open(file) {
+        Ok(f) => f,
+        Err(e) => {
+            info!("failed to open {}: {:?}", file, e);
+            return Response::error(Status::InternalServerError);
+        }
+    };
+    let modified = fs::modified(file);
+    let etag = modified.map(|modified| conditional::etag_for_file(len, modified));
+    let content_type = mime::content_type_for(file);
+    let body = |part: Range<u64>| {
+        let part_len = part.end - part.start;
+        let writer = move |out: &mut dyn Write| {
+            f.seek(SeekFrom::Start(part.start))?;
+            // fails if the file was cut short since
+            http1::copy(&mut f, out, Some(part_len)).map(|_| ())
+        };
+        Body::Sized(part_len, Box::new(writer))
+    };
+    send_parts(request, content_type, etag.as_deref(), modified, len, body)
+}
+
+/// Answers the request with a file of `content_type` that is `len` bytes
+/// long, or the part of it selected by the `Range` header, with the
+/// validators `etag` and `modified` that are known, as described for
+/// [`read_file`]. `body` returns the body sending a range of the file.
+fn send_parts<F>(
+    request: &Request,
+    content_type: &str,
+    etag: Option<&str>,
+    modified: Option<u64>,
+    len: u64,
+    body: F,
+) -> Response
+where
+    F: FnOnce(Range<u64>) -> Body,
+{
+    let with_validators = |response: Response| {
+        let response = match etag {
+            Some(etag) => response.with_header("ETag", etag),
+            None => response,
+        };
+        match modified {
+            Some(modified) => response.with_header("Last-Modified", &httpdate::format(modified)),
+            None => response,
+        }
+    };
+
+    let not_modified = match (request.header("If-None-Match"), modified) {
+        (Some(tags), _) => etag.is_some_and(|etag| conditional::none_match(tags, etag)),
+        (None, Some(modified)) => request
+            .header("If-Modified-Since")
+            .is_some_and(|since| conditional::not_modified_since(since, modified)),
+        (None, None) => false,
+    };
+    if not_modified {
+        return with_validators(Response::empty(Status::NotModified));
+    }
+
+    let range = match request.header("If-Range") {
+        // The client's part is of another version: send the whole file.
+        Some(if_range)
+            if !conditional::range_applies(if_range, etag.unwrap_or_default(), modified) =>
+        {
+            None
+        }
+        _ => request.header("Range"),
+    };
+    let response = match range::resolve(range, len) {
+        ByteRange::Full => Response::with_body(Status::Ok, content_type, body(0..len)),
+        ByteRange::Partial { start, end } => {
+            Response::with_body(Status::PartialContent, content_type, body(start..end + 1))
+                .with_header("Content-Range", &format!("bytes {}-{}/{}", start, end, len))
+        }
+        ByteRange::Unsatisfiable => Response::error(Status::RangeNotSatisfiable)
+            .with_header("Content-Range", &format!("bytes */{}", len)),
+    };
+    with_validators(response.with_header("Accept-Ranges", "bytes"))
+}
+
+fn list_dir(dir: &str, url_path: &str) -> Response {
+    let names = match fs::list_dir(dir) {
+        Ok(names) => names,
+        Err(e) => {
+            info!("failed to list {}: {:?}", dir, e);
+            return Response::error(Status::InternalServerError);
+        }
+    };
+    let entries: Vec<_> = names
+        .into_iter()
+        .filter_map(|name| {
+            let kind = fs::stat(&format!("{}/{}", dir, name))?;
+            Some((name, kind))
+        })
+        .collect();
+    let url_path = String::from(url_path);
+    Response::chunked(Status::Ok, "text/html; charset=utf-8", move |w| {
+        listing::write_listing(w, &url_path, &entries)
+    })
+}
+
+/// Returns the path and length of the index file of the directory `dir`.
+fn find_index(dir: &str) -> Option<(String, u64)> {
+    INDEX_FILES.iter().find_map(|name| {
+        let index = format!("{}/{}", dir, name);
+        match fs::stat(&index) {
+            Some(Kind::File(len)) => Some((index, len)),
+            _ => None,
+        }
+    })
+}
+
+/// Compresses the index of the document root `doc_root` ahead of time,
+/// since it is what most clients ask for.
+pub fn precompress_index(doc_root: &str) {
+    let index = match find_index(doc_root) {
+        Some((index, _)) => index,
+        None => {
+            info!("no index file in {}", doc_root);
+            return;
+        }
+    };
+    let mut content = Vec::new();
+    match std::fs::File::open(&index).and_then(|mut f| f.read_to_end(&mut content)) {
+        Ok(_) => compress::precompress(&content, &conditional::etag_for(&content)),
+        Err(e) => info!("failed to read {}: {:?}", index, e),
+    }
+}
+
+/// Serves the directory `dir`, named by `url_path`, with its index file or
+/// a listing if `listing` is set.
+fn serve_dir(request: &Request, dir: &str, url_path: &str, listing: bool) -> Response {
+    match find_index(dir) {
+        Some((index, len)) => read_file(request, &index, len),
+        None if listing => list_dir(dir, url_path),
+        None => Response::error(Status::Forbidden),
+    }
+}
+
+/// Serves the file or directory named by the request path below `root`.
+pub fn serve(request: &Request, root: &str) -> Response {
+    let url_path = match url::decode_path(&request.path) {
+        Some(url_path) => url_path,
+        None => return Response::error(Status::BadRequest),
+    };
+    let path = format!("{}{}", root, url_path.trim_end_matches('/'));
+    let url_path = url_path.as_str();
+
+    match fs::stat(&path) {
+        Some(Kind::File(len)) => read_file(request, &path, len),
+        Some(Kind::Dir) if !url_path.ends_with('/') => {
+            // Relative links in the directory only work with a trailing slash.
+            redirect::moved(&format!("{}/", url_path), request.query.as_deref())
+        }
+        Some(Kind::Dir) => serve_dir(request, &path, url_path, DIRECTORY_LISTING),
+        None if embedded::active() => serve_embedded(request, url_path),
+        None => {
+            info!("not found: {}", path);
+            Response::error(Status::NotFound)
+        }
+    }
+}
+
+/// Serves the file `file`, answering `404 Not Found` if it is missing or a
+/// directory.
+pub fn serve_file(request: &Request, file: &str) -> Response {
+    match fs::stat(file) {
+        Some(Kind::File(len)) => read_file(request, file, len),
+        _ => {
+            info!("not found: {}", file);
+            Response::error(Status::NotFound)
+        }
+    }
+}
+
+/// Serves the built-in file named by `url_path`, while the document root is
+/// missing.
+fn serve_embedded(request: &Request, url_path: &str) -> Response {
+    let path = match url_path.strip_suffix('/') {
+        Some(dir) => format!("{}/index.html", dir),
+        None => String::from(url_path),
+    };
+    match load_embedded(&path) {
+        Some(entry) => send_entry(request, entry),
+        None => Response::error(Status::NotFound),
+    }
+}
+
+/// Returns the file named by the request path below `root`, for a request
+/// changing it, or the response refusing the request.
+fn write_target(request: &Request, root: &str) -> Result<String, Response> {
+    // Paths that cannot be decoded are refused along with those escaping the
+    // root, which `decode_path` does not tell apart.
+    let url_path =
+        url::decode_path(&request.path).ok_or_else(|| Response::error(Status::Forbidden))?;
+    if url_path.ends_with('/') {
+        return Err(Response::error(Status::Conflict));
+    }
+    Ok(format!("{}{}", root, url_path))
+}
+
+/// Handles `PUT`, writing the request body to the file named by the request
+/// path below `root` and creating the directories leading to it.
+///
+/// Answers `201 Created` for a new file and `204 No Content` for a replaced
+/// one, and `409 Conflict` if the path names a directory or goes through a
+/// file.
+pub fn put(request: &Request, root: &str) -> Response {
+    let file = match write_target(request, root) {
+        Ok(file) => file,
+        Err(response) => return response,
+    };
+    if request.header("Content-Range").is_some() {
+        // A partial write must not be taken for the whole file (RFC 9110,
+        // section 9.3.4).
+        return Response::error(Status::BadRequest);
+    }
+    let existed = match fs::stat(&file) {
+        Some(Kind::Dir) => return Response::error(Status::Conflict),
+        Some(Kind::File(_)) => true,
+        None => false,
+    };
+    if let Some((parent, _)) = file.rsplit_once('/') {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            info!("failed to create {}: {:?}", parent, e);
+            return Response::error(Status::Conflict);
+        }
+    }
+    let written = std::fs::write(file.as_str(), &request.body);
+    // Dropped even if the write failed part way.
+    CACHE.remove(&file);
+    match written {
+        Ok(()) if existed => Response::empty(Status::NoContent),
+        Ok(()) => Response::empty(Status::Created),
+        Err(e) => {
+            info!("failed to write {}: {:?}", file, e);
+            Response::error(Status::InternalServerError)
+        }
+    }
+}
+
+/// Handles `DELETE`, removing the file named by the request path below
+/// `root`. Directories are not removed.
+pub fn delete(request: &Request, root: &str) -> Response {
+    let file = match write_target(request, root) {
+        Ok(file) => file,
+        Err(response) => return response,
+    };
+    match fs::stat(&file) {
+        Some(Kind::File(_)) => {}
+        Some(Kind::Dir) => return Response::error(Status::Conflict),
+        None => return Response::error(Status::NotFound),
+    }
+    let removed = std::fs::remove_file(file.as_str());
+    CACHE.remove(&file);
+    match removed {
+        Ok(()) => Response::empty(Status::NoContent),
+        Err(e) => {
+            info!("failed to remove {}: {:?}", file, e);
+            Response::error(Status::InternalServerError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+    use crate::response::Body;
+
+    fn get(path: &str) -> Request {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path);
+        RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap()
+    }
+
+    fn body(response: Response) -> Vec<u8> {
+        match response.body.bytes() {
+            Some(body) => body.to_vec(),
+            None => panic!("unexpected streamed body"),
+        }
+    }
+
+    #[test]
+    fn test_index_files() {
+        let root = std::env::temp_dir().join(format!("httpd-index-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("a/b/c")).unwrap();
+        std::fs::create_dir_all(root.join("my docs")).unwrap();
+        std::fs::write(root.join("index"), "root index").unwrap();
+        std::fs::write(root.join("a/index"), "plain index").unwrap();
+        std::fs::write(root.join("a/index.html"), "html index").unwrap();
+        std::fs::write(root.join("a/b/index"), "nested index").unwrap();
+        let root_str = root.to_str().unwrap();
+
+        let serve = |path: &str| serve(&get(path), root_str);
+        assert_eq!(body(serve("/")), b"root index");
+        assert_eq!(body(serve("/a/")), b"html index");
+        assert_eq!(body(serve("/a/b/")), b"nested index");
+        assert!(matches!(serve("/a/b/c/").body, Body::Chunked(_)));
+
+        let location = |path: &str| {
+            let response = serve(path);
+            assert_eq!(response.status, Status::MovedPermanently);
+            let (_, location) = response
+                .headers
+                .iter()
+                .find(|(n, _)| *n == "Location")
+                .unwrap();
+            location.clone()
+        };
+        assert_eq!(location("/a/b"), "/a/b/");
+        assert_eq!(location("/my%20docs?sort=name"), "/my%20docs/?sort=name");
+
+        let dir = format!("{}/a/b/c", root_str);
+        let response = serve_dir(&get("/a/b/c/"), &dir, "/a/b/c/", false);
+        assert_eq!(response.status, Status::Forbidden);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_binary_file() {
+        let root = std::env::temp_dir().join(format!("httpd-files-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let content: Vec<u8> = (0..=255).collect();
+        std::fs::write(root.join("bytes.bin"), &content).unwrap();
+
+        let request = get("/bytes.bin");
+        let response = compress::apply(&request, serve(&request, root.to_str().unwrap()));
+        let mut out = Vec::new();
+        response.write_to(&mut out, false).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let head_len = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = std::str::from_utf8(&out[..head_len]).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("\r\nContent-Length: 256\r\n"));
+        assert_eq!(&out[head_len..], &content[..]);
+    }
+
+    #[test]
+    fn test_large_file() {
+        let root = std::env::temp_dir().join(format!("httpd-large-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let content: Vec<u8> = (0..MAX_CACHED_FILE_LEN + 10).map(|i| i as u8).collect();
+        std::fs::write(root.join("large.bin"), &content).unwrap();
+        let root_str = root.to_str().unwrap();
+        let send = |response: Response| {
+            let mut out = Vec::new();
+            response.write_to(&mut out, false).unwrap();
+            let head_len = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+            let body = out.split_off(head_len);
+            (String::from_utf8(out).unwrap(), body)
+        };
+
+        let response = serve(&get("/large.bin"), root_str);
+        assert!(matches!(response.body, Body::Sized(..)));
+        let (head, body) = send(response);
+        assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", content.len())));
+        assert!(head.contains("\r\nETag: W/\""));
+        assert_eq!(body, content);
+
+        let raw = "GET /large.bin HTTP/1.1\r\nHost: test\r\nRange: bytes=-4\r\n\r\n";
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        let (head, body) = send(serve(&request, root_str));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(head.contains("\r\nContent-Length: 4\r\n"));
+        assert_eq!(body, &content[content.len() - 4..]);
+    }
+
+    #[test]
+    fn test_changed_file() {
+        let root = std::env::temp_dir().join(format!("httpd-changed-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("same.txt");
+        std::fs::write(&file, "old").unwrap();
+        let root_str = root.to_str().unwrap();
+        assert_eq!(body(serve(&get("/same.txt"), root_str)), b"old");
+
+        // Edited in place to the same length: only the modification time
+        // tells the cached contents are stale.
+        std::fs::write(&file, "new").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        let f = std::fs::File::options().write(true).open(&file).unwrap();
+        f.set_modified(later).unwrap();
+        assert_eq!(body(serve(&get("/same.txt"), root_str)), b"new");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let root = std::env::temp_dir().join(format!("httpd-modified-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("page.txt"), "page").unwrap();
+        let root_str = root.to_str().unwrap();
+        let header = |response: &Response, name: &str| {
+            let (_, value) = response.headers.iter().find(|(n, _)| *n == name)?;
+            Some(value.clone())
+        };
+        let conditional_get = |headers: &str| {
+            let raw = format!("GET /page.txt HTTP/1.1\r\nHost: test\r\n{}\r\n", headers);
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            serve(&request, root_str)
+        };
+
+        let response = serve(&get("/page.txt"), root_str);
+        let last_modified = header(&response, "Last-Modified").unwrap();
+        let etag = header(&response, "ETag").unwrap();
+
+        let response = conditional_get(&format!("If-Modified-Since: {}\r\n", last_modified));
+        assert_eq!(response.status, Status::NotModified);
+        assert_eq!(
+            header(&response, "Last-Modified"),
+            Some(last_modified.clone())
+        );
+        let response = conditional_get("If-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n");
+        assert_eq!(response.status, Status::Ok);
+        let response = conditional_get("If-Modified-Since: not a date\r\n");
+        assert_eq!(response.status, Status::Ok);
+
+        // `If-None-Match` takes precedence.
+        let response = conditional_get(&format!(
+            "If-None-Match: \"other\"\r\nIf-Modified-Since: {}\r\n",
+            last_modified
+        ));
+        assert_eq!(response.status, Status::Ok);
+        let response = conditional_get(&format!(
+            "If-None-Match: {}\r\nIf-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n",
+            etag
+        ));
+        assert_eq!(response.status, Status::NotModified);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_embedded() {
+        let response = serve_embedded(&get("/"), "/");
+        assert_eq!(response.status, Status::Ok);
+        let etag = {
+            let (_, etag) = response.headers.iter().find(|(n, _)| n == "ETag").unwrap();
+            etag.clone()
+        };
+        assert!(response
+            .headers
+            .iter()
+            .any(|(n, v)| n == "Content-Type" && v.starts_with("text/html")));
+        assert_eq!(body(response), embedded::get("/index.html").unwrap());
+
+        let raw = format!(
+            "GET / HTTP/1.1\r\nHost: test\r\nIf-None-Match: {}\r\n\r\n",
+            etag
+        );
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        let response = serve_embedded(&request, "/");
+        assert_eq!(response.status, Status::NotModified);
+        let response = serve_embedded(&get("/missing.png"), "/missing.png");
+        assert_eq!(response.status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_put_delete() {
+        let root = std::env::temp_dir().join(format!("httpd-put-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root_str = root.to_str().unwrap();
+        let request = |method: &str, path: &str, body: &str| {
+            let raw = format!(
+                "{} {} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                body.len(),
+                body
+            );
+            RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap()
+        };
+        let put = |path: &str, body: &str| put(&request("PUT", path, body), root_str).status;
+        let delete = |path: &str| delete(&request("DELETE", path, ""), root_str).status;
+
+        assert_eq!(put("/new/dir/a.txt", "one"), Status::Created);
+        assert_eq!(body(serve(&get("/new/dir/a.txt"), root_str)), b"one");
+        // Same length, so only the write tells the cache that it changed.
+        assert_eq!(put("/new/dir/a.txt", "two"), Status::NoContent);
+        assert_eq!(body(serve(&get("/new/dir/a.txt"), root_str)), b"two");
+
+        assert_eq!(put("/new/dir", "x"), Status::Conflict);
+        assert_eq!(put("/new/dir/", "x"), Status::Conflict);
+        assert_eq!(put("/new/dir/a.txt/b", "x"), Status::Conflict);
+        assert_eq!(put("/../escape.txt", "x"), Status::Forbidden);
+        assert_eq!(put("/new/%2e%2e/%2e%2e/escape.txt", "x"), Status::Forbidden);
+
+        assert_eq!(delete("/new/dir"), Status::Conflict);
+        assert_eq!(delete("/new/dir/a.txt"), Status::NoContent);
+        assert_eq!(delete("/new/dir/a.txt"), Status::NotFound);
+        assert_eq!(
+            serve(&get("/new/dir/a.txt"), root_str).status,
+            Status::NotFound
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_template() {
+        let root = std::env::temp_dir().join(format!("httpd-template-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("page.html"), "<p>{{hostname}} {{{{x}}</p>").unwrap();
+
+        let response = serve(&get("/page.html"), root.to_str().unwrap());
+        assert!(!response.headers.iter().any(|(n, _)| n == "ETag"));
+        let mut out = Vec::new();
+        response.write_to(&mut out, false).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\r\nContent-Length: 17\r\n"));
+        assert!(out.ends_with("\r\n\r\n<p>test {{x}}</p>"));
+    }
+}
@@ -0,0 +1,68 @@
+//! Filesystem helpers that work the same with `std` and `axstd`.
+
+use std::io;
+use std::string::String;
+use std::vec::Vec;
+
+/// The kind of a filesystem entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A regular file of the given size.
+    File(u64),
+    Dir,
+}
+
+/// Returns the kind of the entry at `path`, or `None` if it does not exist.
+#[cfg(feature = "axstd")]
+pub fn stat(path: &str) -> Option<Kind> {
+    // `axstd` opens the entry to read its metadata, which fails for
+    // directories.
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => Some(Kind::Dir),
+        Ok(meta) => Some(Kind::File(meta.len())),
+        Err(io::Error::IsADirectory) => Some(Kind::Dir),
+        Err(_) => None,
+    }
+}
+
+/// Returns the kind of the entry at `path`, or `None` if it does not exist.
+#[cfg(not(feature = "axstd"))]
+pub fn stat(path: &str) -> Option<Kind> {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => Some(Kind::Dir),
+        Ok(meta) => Some(Kind::File(meta.len())),
+        Err(_) => None,
+    }
+}
+
+/// Returns when the file at `path` was last modified, in seconds since the
+/// Unix epoch.
+///
+/// The `axstd` filesystems keep no modification times, so this is always
+/// `None` there.
+#[cfg(feature = "axstd")]
+pub fn modified(_path: &str) -> Option<u64> {
+    None
+}
+
+/// Returns when the file at `path` was last modified, in seconds since the
+/// Unix epoch, or `None` if the filesystem does not record it.
+#[cfg(not(feature = "axstd"))]
+pub fn modified(path: &str) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_secs())
+}
+
+/// Returns the sorted names of the entries in the directory at `path`.
+pub fn list_dir(path: &str) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        #[cfg(feature = "axstd")]
+        names.push(entry?.file_name());
+        #[cfg(not(feature = "axstd"))]
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
@@ -0,0 +1,144 @@
+//! HTTP dates (RFC 9110, section 5.6.7), converted from and to Unix time.
+
+use std::string::String;
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Returns the year, month (1 to 12) and day (1 to 31) of the day `days`
+/// after 1970-01-01, in the proleptic Gregorian calendar.
+///
+/// This is the `civil_from_days` algorithm by Howard Hinnant, which counts in
+/// 400-year eras starting on March 1st, so that leap days end the year.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+/// Returns the number of days from 1970-01-01 to the given date, which must
+/// not be earlier. This is the inverse of [`civil_from_days`].
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Formats `secs` seconds since the Unix epoch as an IMF-fixdate, such as
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn format(secs: u64) -> String {
+    let days = secs / SECS_PER_DAY;
+    let time = secs % SECS_PER_DAY;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parses an IMF-fixdate into seconds since the Unix epoch.
+///
+/// The obsolete RFC 850 and asctime formats, which senders must no longer
+/// generate, are not accepted; neither are dates before the epoch.
+pub fn parse(s: &str) -> Option<u64> {
+    let (day_name, rest) = s.trim().split_once(", ")?;
+    if !DAYS.contains(&day_name) {
+        return None;
+    }
+    let mut parts = rest.split(' ');
+    let (day, month, year, time, zone) = (
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+    );
+    if parts.next().is_some() || zone != "GMT" || day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+    let number = |s: &str| -> Option<u64> {
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    };
+    let day = number(day)?;
+    let month = MONTHS.iter().position(|&m| m == month)? as u64 + 1;
+    let year = number(year)?;
+    let mut hms = time.split(':');
+    let (h, m, s) = (hms.next()?, hms.next()?, hms.next()?);
+    if hms.next().is_some() || h.len() != 2 || m.len() != 2 || s.len() != 2 {
+        return None;
+    }
+    let (h, m, s) = (number(h)?, number(m)?, number(s)?);
+    // Leap seconds are allowed, as 60.
+    if year < 1970 || !(1..=31).contains(&day) || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if civil_from_days(days) != (year, month, day) {
+        // A day past the end of the month.
+        return None;
+    }
+    Some(days * SECS_PER_DAY + h * 3600 + m * 60 + s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format(951_782_400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(format(4_133_980_799), "Fri, 31 Dec 2100 23:59:59 GMT");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse(" Thu, 01 Jan 1970 00:00:00 GMT "), Some(0));
+        for secs in (0..5_000_000_000).step_by(86_399 * 37) {
+            assert_eq!(parse(&format(secs)), Some(secs));
+        }
+
+        for s in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sun, 06 Nvm 1994 08:49:37 GMT",
+            "Sun, 31 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Sun, +6 Nov 1994 08:49:37 GMT",
+            "",
+        ] {
+            assert_eq!(parse(s), None, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! A minimal JSON writer.
+
+use std::string::String;
+
+/// Appends `s` to `out` as a JSON string literal.
+fn push_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Builds a JSON object one member at a time.
+pub struct JsonObject {
+    out: String,
+}
+
+impl JsonObject {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("{"),
+        }
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.out.len() > 1 {
+            self.out.push(',');
+        }
+        push_string(&mut self.out, key);
+        self.out.push(':');
+    }
+
+    /// Adds a string member.
+    pub fn string(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        push_string(&mut self.out, value);
+        self
+    }
+
+    /// Adds a number member, or `null` if `value` is `None`.
+    pub fn number(mut self, key: &str, value: Option<u64>) -> Self {
+        self.key(key);
+        match value {
+            Some(value) => self.out += &format!("{}", value),
+            None => self.out += "null",
+        }
+        self
+    }
+
+    /// Adds a nested object, or `null` if `value` is `None`.
+    pub fn object(mut self, key: &str, value: Option<JsonObject>) -> Self {
+        self.key(key);
+        match value {
+            Some(value) => self.out += &value.finish(),
+            None => self.out += "null",
+        }
+        self
+    }
+
+    /// Returns the text of the object.
+    pub fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+impl Default for JsonObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A parsed JSON value.
+    #[derive(Debug, PartialEq)]
+    pub enum Value {
+        Null,
+        Bool(bool),
+        Number(f64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+
+    impl Value {
+        /// Returns the member named `key` of an object.
+        pub fn get(&self, key: &str) -> Option<&Value> {
+            match self {
+                Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+    }
+
+    struct Parser<'a> {
+        s: &'a [u8],
+        pos: usize,
+    }
+
+    impl Parser<'_> {
+        fn skip_whitespace(&mut self) {
+            while matches!(self.s.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+                self.pos += 1;
+            }
+        }
+
+        fn eat(&mut self, token: &str) -> Option<()> {
+            self.skip_whitespace();
+            if self.s[self.pos..].starts_with(token.as_bytes()) {
+                self.pos += token.len();
+                Some(())
+            } else {
+                None
+            }
+        }
+
+        fn string(&mut self) -> Option<String> {
+            self.eat("\"")?;
+            let mut out = String::new();
+            loop {
+                let c = std::str::from_utf8(&self.s[self.pos..])
+                    .ok()?
+                    .chars()
+                    .next()?;
+                self.pos += c.len_utf8();
+                match c {
+                    '"' => return Some(out),
+                    '\\' => {
+                        let escape = *self.s.get(self.pos)?;
+                        self.pos += 1;
+                        out.push(match escape {
+                            b'"' => '"',
+                            b'\\' => '\\',
+                            b'/' => '/',
+                            b'n' => '\n',
+                            b'r' => '\r',
+                            b't' => '\t',
+                            b'b' => '\u{8}',
+                            b'f' => '\u{c}',
+                            b'u' => {
+                                let hex = std::str::from_utf8(self.s.get(self.pos..self.pos + 4)?);
+                                self.pos += 4;
+                                char::from_u32(u32::from_str_radix(hex.ok()?, 16).ok()?)?
+                            }
+                            _ => return None,
+                        });
+                    }
+                    c if (c as u32) < 0x20 => return None,
+                    c => out.push(c),
+                }
+            }
+        }
+
+        fn value(&mut self) -> Option<Value> {
+            self.skip_whitespace();
+            match *self.s.get(self.pos)? {
+                b'n' => self.eat("null").map(|_| Value::Null),
+                b't' => self.eat("true").map(|_| Value::Bool(true)),
+                b'f' => self.eat("false").map(|_| Value::Bool(false)),
+                b'"' => self.string().map(Value::String),
+                b'[' => {
+                    self.eat("[")?;
+                    let mut items = Vec::new();
+                    if self.eat("]").is_some() {
+                        return Some(Value::Array(items));
+                    }
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat("]").is_some() {
+                            return Some(Value::Array(items));
+                        }
+                        self.eat(",")?;
+                    }
+                }
+                b'{' => {
+                    self.eat("{")?;
+                    let mut members = Vec::new();
+                    if self.eat("}").is_some() {
+                        return Some(Value::Object(members));
+                    }
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.eat(":")?;
+                        members.push((key, self.value()?));
+                        if self.eat("}").is_some() {
+                            return Some(Value::Object(members));
+                        }
+                        self.eat(",")?;
+                    }
+                }
+                _ => {
+                    let start = self.pos;
+                    while matches!(
+                        self.s.get(self.pos),
+                        Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+                    ) {
+                        self.pos += 1;
+                    }
+                    let number = std::str::from_utf8(&self.s[start..self.pos]).ok()?;
+                    number.parse().ok().map(Value::Number)
+                }
+            }
+        }
+    }
+
+    /// Parses a complete JSON document, returning `None` if it is invalid.
+    pub fn parse(s: &str) -> Option<Value> {
+        let mut parser = Parser {
+            s: s.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        (parser.pos == s.len()).then_some(value)
+    }
+
+    #[test]
+    fn test_object() {
+        let inner = JsonObject::new().number("n", Some(7));
+        let json = JsonObject::new()
+            .string("name", "a \"quoted\"\\path\n\u{1}é")
+            .number("count", Some(42))
+            .number("missing", None)
+            .object("inner", Some(inner))
+            .object("none", None)
+            .finish();
+        assert_eq!(
+            parse(&json),
+            Some(Value::Object(vec![
+                (
+                    String::from("name"),
+                    Value::String(String::from("a \"quoted\"\\path\n\u{1}é"))
+                ),
+                (String::from("count"), Value::Number(42.0)),
+                (String::from("missing"), Value::Null),
+                (
+                    String::from("inner"),
+                    Value::Object(vec![(String::from("n"), Value::Number(7.0))])
+                ),
+                (String::from("none"), Value::Null),
+            ]))
+        );
+        assert_eq!(JsonObject::new().finish(), "{}");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse("{\"a\":1,}"), None);
+        assert_eq!(parse("{\"a\" 1}"), None);
+        assert_eq!(parse("\"\u{1}\""), None);
+        assert_eq!(parse("[1] x"), None);
+        assert!(parse("[true, false, null, \"\\u00e9\"]").is_some());
+    }
+}
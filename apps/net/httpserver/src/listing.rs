@@ -0,0 +1,117 @@
+//! Generated HTML listings of directories.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+
+use crate::fs::Kind;
+
+/// Escapes `s` for use in HTML text and attribute values.
+pub fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Percent-encodes `s` for use as a path in a URL, keeping `/` as is.
+pub fn percent_encode_path(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            b => out += &format!("%{:02X}", b),
+        }
+    }
+    out
+}
+
+/// Writes an HTML listing of a directory.
+///
+/// `url_path` is the path of the directory as requested, ending with `/`.
+/// `entries` holds the names and kinds of the entries in the directory.
+pub fn write_listing(
+    w: &mut dyn Write,
+    url_path: &str,
+    entries: &[(String, Kind)],
+) -> io::Result<()> {
+    let title = html_escape(url_path);
+    write!(
+        w,
+        "<html><head><title>Index of {0}</title></head>\n\
+         <body><h1>Index of {0}</h1><hr><pre>\n",
+        title
+    )?;
+    if url_path != "/" {
+        w.write_all(b"<a href=\"../\">../</a>\n")?;
+    }
+    for (name, kind) in entries {
+        let (slash, size) = match kind {
+            Kind::Dir => ("/", String::from("-")),
+            Kind::File(len) => ("", format!("{}", len)),
+        };
+        let shown = format!("{}{}", name, slash);
+        let pad = 50usize.saturating_sub(shown.chars().count());
+        writeln!(
+            w,
+            "<a href=\"{}{}\">{}</a>{:pad$} {:>12}",
+            html_escape(&percent_encode_path(name)),
+            slash,
+            html_escape(&shown),
+            "",
+            size,
+            pad = pad
+        )?;
+    }
+    w.write_all(b"</pre><hr></body></html>\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(html_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&#39;");
+        assert_eq!(html_escape("plain.txt"), "plain.txt");
+    }
+
+    #[test]
+    fn test_percent_encode_path() {
+        assert_eq!(
+            percent_encode_path("my file#1?.txt"),
+            "my%20file%231%3F.txt"
+        );
+        assert_eq!(percent_encode_path("a/b-c_d.~"), "a/b-c_d.~");
+        assert_eq!(percent_encode_path("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn test_listing() {
+        let entries = vec![
+            (String::from("pics"), Kind::Dir),
+            (String::from("<b>.txt"), Kind::File(42)),
+        ];
+        let mut out = Vec::new();
+        write_listing(&mut out, "/docs/", &entries).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("<title>Index of /docs/</title>"));
+        assert!(out.contains("<a href=\"../\">../</a>"));
+        assert!(out.contains("<a href=\"pics/\">pics/</a>"));
+        assert!(out.contains("<a href=\"%3Cb%3E.txt\">&lt;b&gt;.txt</a>"));
+        assert!(out.contains("           42\n"));
+
+        let mut out = Vec::new();
+        write_listing(&mut out, "/", &[]).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("../"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Writing the access log to a file.
+//!
+//! Handlers hand their lines to a queue, and a logger thread appends them to
+//! the file, so that requests never wait for the disk. Lines are buffered and
+//! written every [`FLUSH_LINES`] lines or [`FLUSH_INTERVAL`], whichever comes
+//! first. When the file grows over [`MAX_LOG_SIZE`], it is renamed with a
+//! `.1` suffix, replacing the previous one, and a new file is started.
+//!
+//! If the file cannot be opened or written, lines go to the console instead.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::access_log::error_kind;
+use crate::sync::lock;
+
+/// The size over which the log file is rotated.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// The number of buffered lines that are written at once.
+const FLUSH_LINES: usize = 64;
+/// How long lines may stay in the buffer.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the logger thread checks for new lines when there are none.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The number of lines that may wait for the logger thread. Lines sent
+/// while the queue is full are dropped rather than making handlers wait.
+const QUEUE_CAPACITY: usize = 4096;
+
+static QUEUE: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+/// Whether the logger thread is writing lines to the file.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The number of lines dropped because the queue was full.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// A log file with a write buffer, rotated when it grows too large.
+struct LogFile {
+    path: String,
+    file: File,
+    /// The size of the file, including the buffered lines.
+    size: u64,
+    max_size: u64,
+    buf: Vec<u8>,
+    buffered_lines: usize,
+    last_flush: Instant,
+}
+
+fn open_append(path: &str) -> io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+impl LogFile {
+    /// Opens the log file at `path` for appending, creating it and its
+    /// directory if needed.
+    fn open(path: &str, max_size: u64) -> io::Result<Self> {
+        if let Some((dir, _)) = path.rsplit_once('/') {
+            if !dir.is_empty() {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+        let file = open_append(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: String::from(path),
+            file,
+            size,
+            max_size,
+            buf: Vec::new(),
+            buffered_lines: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Adds `line` to the buffer, rotating the file first if the line would
+    /// make it too large.
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        self.buf.extend_from_slice(line.as_bytes());
+        self.buf.push(b'\n');
+        self.size += len;
+        self.buffered_lines += 1;
+        if self.buffered_lines >= FLUSH_LINES {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered lines to the file.
+    fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        // Cleared even if the write fails, so that a failing disk does not
+        // make the buffer grow without bound.
+        let written = self
+            .file
+            .write_all(&self.buf)
+            .and_then(|_| self.file.flush());
+        self.buf.clear();
+        self.buffered_lines = 0;
+        written
+    }
+
+    /// Returns whether the buffered lines are due to be written.
+    fn flush_due(&self) -> bool {
+        !self.buf.is_empty() && self.last_flush.elapsed() >= FLUSH_INTERVAL
+    }
+
+    /// Moves the current file aside to `<path>.1` and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.flush()?;
+        let old = format!("{}.1", self.path);
+        // `rename` does not replace an existing file everywhere.
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&self.path, &old)?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Sends `line` to the log file, returning it back if file logging is not
+/// running.
+pub fn send(line: String) -> Result<(), String> {
+    if !ENABLED.load(Ordering::Acquire) {
+        return Err(line);
+    }
+    let mut queue = lock(&QUEUE);
+    if queue.len() < QUEUE_CAPACITY {
+        queue.push_back(line);
+    } else {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Opens the log file at `path` and starts the logger thread.
+///
+/// If the file cannot be opened, the access log stays on the console.
+pub fn start(path: &str) {
+    match LogFile::open(path, MAX_LOG_SIZE) {
+        Ok(file) => {
+            println!("access log: {}", path);
+            ENABLED.store(true, Ordering::Release);
+            thread::spawn(move || logger_loop(file));
+        }
+        Err(e) => println!(
+            "cannot open {} ({:?}), logging requests to the console",
+            path,
+            error_kind(&e)
+        ),
+    }
+}
+
+/// Writes queued lines to `file` until writing fails, then goes back to
+/// console logging.
+fn logger_loop(mut file: LogFile) {
+    let mut reported_dropped = 0;
+    let error = loop {
+        let lines = core::mem::take(&mut *lock(&QUEUE));
+        if lines.is_empty() {
+            if file.flush_due() {
+                if let Err(e) = file.flush() {
+                    break e;
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        if let Err(e) = lines.iter().try_for_each(|line| file.write_line(line)) {
+            break e;
+        }
+        let dropped = DROPPED.load(Ordering::Relaxed);
+        if dropped != reported_dropped {
+            info!(
+                "access log queue full, dropped {} lines",
+                dropped - reported_dropped
+            );
+            reported_dropped = dropped;
+        }
+    };
+    ENABLED.store(false, Ordering::Release);
+    println!(
+        "writing {} failed ({:?}), logging requests to the console",
+        file.path,
+        error_kind(&error)
+    );
+    // Lines queued before file logging was turned off.
+    for line in core::mem::take(&mut *lock(&QUEUE)) {
+        info!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("httpd-log-{}", std::process::id()));
+        format!("{}/{}/access.log", dir.display(), name)
+    }
+
+    fn read(path: &str) -> String {
+        std::fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_buffered_writes() {
+        let path = temp_path("buffered");
+        let mut log = LogFile::open(&path, 1024 * 1024).unwrap();
+        log.write_line("first").unwrap();
+        assert_eq!(read(&path), "");
+        for i in 1..FLUSH_LINES {
+            log.write_line(&format!("line {}", i)).unwrap();
+        }
+        // The buffer is written once it holds `FLUSH_LINES` lines.
+        let content = read(&path);
+        assert!(content.starts_with("first\nline 1\n"));
+        assert_eq!(content.lines().count(), FLUSH_LINES);
+
+        log.write_line("last").unwrap();
+        assert!(!log.flush_due());
+        log.flush().unwrap();
+        assert!(read(&path).ends_with("\nlast\n"));
+
+        // Reopening appends.
+        let mut log = LogFile::open(&path, 1024 * 1024).unwrap();
+        log.write_line("again").unwrap();
+        log.flush().unwrap();
+        assert!(read(&path).ends_with("\nlast\nagain\n"));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = temp_path("rotation");
+        let rotated = format!("{}.1", path);
+        let mut log = LogFile::open(&path, 20).unwrap();
+        for line in ["aaaaaaaa", "bbbbbbbb", "cccccccc", "dddddddd", "eeeeeeee"] {
+            log.write_line(line).unwrap();
+        }
+        log.flush().unwrap();
+        assert_eq!(read(&rotated), "cccccccc\ndddddddd\n");
+        assert_eq!(read(&path), "eeeeeeee\n");
+    }
+}
@@ -5,6 +5,59 @@
 //! ```
 //! ab -n 5000 -c 20 http://X.X.X.X:5555/
 //! ```
+//!
+//! The listening address defaults to `0.0.0.0:5555`. It can be changed with
+//! the first command-line argument when running on a host, or with the
+//! `HTTPD_ADDR` environment variable when building for ArceOS, e.g.
+//! `make A=apps/net/httpserver NET=y BLK=y HTTPD_ADDR=10.0.2.15:8080 run`.
+//! Both `ADDR:PORT` and a bare `PORT` are accepted. When listening on all
+//! IPv4 addresses, the server also listens on `[::]` with the same port if the
+//! network stack supports IPv6.
+//!
+//! The listening address, the number of workers, the depth of the connection
+//! queue and the document root can also be set at startup in
+//! `/etc/httpd.conf`, or with `key=value` arguments on a host; see the
+//! [`config`] module. The settings in effect are printed at startup. If the
+//! document root does not exist, such as when running without a disk, a small
+//! site built into the server is served instead.
+//!
+//! Files chosen in the form at `/upload` are saved in the upload directory,
+//! `/uploads` unless configured otherwise, as they arrive; see the [`upload`]
+//! module. With
+//! `writable=true` in the configuration, `PUT` and `DELETE` requests write and
+//! remove files in the document root. Files in the download directory,
+//! `/downloads` unless configured otherwise, are served as attachments under
+//! `/download/<name>`, so that browsers save them; see the [`download`]
+//! module.
+//!
+//! Requests are logged to `/var/log/httpd.log`, which is rotated to
+//! `/var/log/httpd.log.1` when it grows large, or to the console with `LOG=info`
+//! if the file cannot be written.
+//!
+//! Building with `HTTPD_UPSTREAM=ADDR:PORT` forwards requests for paths under
+//! `/upstream` to that server, with the prefix removed.
+//!
+//! Building with `HTTPD_ADMIN_USER=<user> HTTPD_ADMIN_PASSWORD=<password>`
+//! enables the administrative endpoints, which need these credentials with
+//! Basic authentication, e.g.
+//! `curl -u <user>:<password> http://X.X.X.X:5555/shutdown`:
+//!
+//! - `GET /shutdown` stops the server once in-flight connections are done.
+//! - `GET /admin/reload` re-reads the error pages and the index page, which are
+//!   kept in memory, after they have been edited.
+//!
+//! The credentials then also protect `GET /status`, which is public otherwise.
+//! Without them, both administrative endpoints answer `404 Not Found`: edited
+//! error pages and index page are only read again when the server restarts.
+//! Other files are read again whenever they change, either way.
+//!
+//! `GET /events` streams live statistics as server-sent events, which a page
+//! can read with `new EventSource("/events")`.
+//!
+//! Building with `HTTPD_CORS_ORIGIN=<origin>` lets pages from `<origin>`, such
+//! as `http://example.com`, or from any origin with `*`, call the server from
+//! a browser: preflight requests are answered, and responses to cross-origin
+//! requests carry `Access-Control-Allow-Origin`.
 
 #![cfg_attr(feature = "axstd", no_std)]
 #![cfg_attr(feature = "axstd", no_main)]
@@ -13,116 +66,997 @@
 #[cfg(feature = "axstd")]
 extern crate axstd as std;
 
+macro_rules! info {
+    ($($arg:tt)*) => {
+        match option_env!("LOG") {
+            Some("info") | Some("debug") | Some("trace") => {
+                print!("[INFO] {}\n", format_args!($($arg)*));
+            }
+            _ => {}
+        }
+    };
+}
+
+mod access_log;
+mod admin;
+mod auth;
+mod base64;
+mod cache;
+mod compress;
+mod conditional;
+mod config;
+mod cookie;
+mod cors;
+mod deflate;
+mod download;
+mod embedded;
+mod error_pages;
+mod events;
+mod files;
+mod fs;
+mod httpdate;
+mod json;
+mod listing;
+mod log_file;
+mod mime;
+mod multipart;
+mod poll;
+mod pool;
+mod proxy;
+mod range;
+mod ratelimit;
+mod redirect;
+mod request;
+mod response;
+mod router;
+mod sha1;
+mod shutdown;
+mod stats;
+mod sync;
+mod sysinfo;
+mod template;
+mod timeout;
+mod upload;
+mod url;
+mod vhost;
+mod websocket;
+
+use std::io::{self, Read};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream};
 use std::string::String;
-use std::vec::Vec;
-use std::io::{self, prelude::*};
-use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use self::access_log::CountingWriter;
+use self::config::{Config, CONFIG_PATH};
+use self::cookie::SetCookie;
+use self::cors::Cors;
+use self::poll::{Poller, Ready};
+use self::pool::{QueueFullPolicy, ThreadPool};
+use self::ratelimit::{Decision, RateLimit, RateLimiter};
+use self::redirect::Redirect;
+use self::request::{HeadLimits, MalformedHead, ParseError, Request, RequestReader};
+use self::response::{Body, Response, Status};
+use self::router::Router;
+use self::stats::STATS;
+use self::timeout::{DeadlineReader, DeadlineWriter, IDLE_TIMEOUT, REQUEST_TIMEOUT, WRITE_TIMEOUT};
+use self::vhost::VirtualHost;
+
+const DEFAULT_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 5555));
+
+/// Sites served from their own document roots, chosen by the `Host` header.
+///
+/// Requests for other hosts are served from the configured document root.
+/// For example:
+///
+/// ```ignore
+/// const VIRTUAL_HOSTS: &[VirtualHost] = &[
+///     VirtualHost { name: "site-a", root: "/html/site-a" },
+///     VirtualHost { name: "site-b", root: "/html/site-b" },
+/// ];
+/// ```
+const VIRTUAL_HOSTS: &[VirtualHost] = &[];
+
+/// Limits on request heads; larger heads are answered with `431` and the
+/// connection is closed. For example, to allow longer cookies:
+///
+/// ```ignore
+/// const HEAD_LIMITS: HeadLimits = HeadLimits {
+///     max_line_len: 16 * 1024,
+///     max_head_len: 32 * 1024,
+///     ..HeadLimits::DEFAULT
+/// };
+/// ```
+const HEAD_LIMITS: HeadLimits = HeadLimits::DEFAULT;
+
+/// Paths that have moved, answered with `301 Moved Permanently`. For example:
+///
+/// ```ignore
+/// const REDIRECTS: &[Redirect] = &[Redirect { from: "/old", to: "/new" }];
+/// ```
+const REDIRECTS: &[Redirect] = &[];
+
+/// How many requests each client IP address may make, or `None` to allow any
+/// number. For example, to allow 20 requests per second in the long run and
+/// bursts of 100, and to drop the connections of clients over by much more:
+///
+/// ```ignore
+/// const RATE_LIMIT: Option<RateLimit> = Some(RateLimit {
+///     rate: 20,
+///     burst: 100,
+///     drop_after_bursts: 10,
+/// });
+/// ```
+///
+/// Benchmarks from a single host need it to be disabled or generous.
+const RATE_LIMIT: Option<RateLimit> = None;
+/// The number of client addresses the rate limiter keeps track of.
+const RATE_LIMITED_CLIENTS: usize = 1024;
+/// The extra requests a request with wrong credentials counts as.
+const AUTH_FAILURE_PENALTY: u32 = 10;
+
+static RATE_LIMITER: Option<RateLimiter> = match RATE_LIMIT {
+    Some(limit) => Some(RateLimiter::new(limit, RATE_LIMITED_CLIENTS)),
+    None => None,
+};
+
+/// The origins allowed to make cross-origin requests from a browser, or
+/// `None` to answer such requests like any other.
+static CORS: Option<Cors> = match option_env!("HTTPD_CORS_ORIGIN") {
+    Some(allow_origin) if !allow_origin.is_empty() => Some(Cors { allow_origin }),
+    _ => None,
+};
+
+/// The file the access log is appended to, or `None` to log requests to the
+/// console. The console is also used if the file cannot be opened.
+const ACCESS_LOG_PATH: Option<&str> = Some("/var/log/httpd.log");
+
+/// The number of requests served on one connection before it is closed. The
+/// last response says `Connection: close`.
+const MAX_KEEPALIVE_REQUESTS: usize = 100;
+/// The number of accepted connections that may wait for a worker, unless
+/// configured otherwise.
+const QUEUE_DEPTH: usize = 64;
+/// What to do with new connections when all workers are busy and the queue
+/// is full.
+const QUEUE_FULL_POLICY: QueueFullPolicy = QueueFullPolicy::Block;
+/// How often the listeners are checked for new connections and for a
+/// shutdown request while there are none.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// How long to wait for in-flight connections when shutting down.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many times binding the listening address is tried when it is still in
+/// use, such as by the sockets of a server that just stopped.
+const BIND_ATTEMPTS: u32 = 8;
+/// How long to wait before trying to bind again, doubled after each attempt
+/// up to [`MAX_BIND_RETRY_DELAY`].
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_BIND_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// How long to back off after a failed `accept`.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// How often the statistics line is logged.
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+/// The path of the debug echo, which malformed requests reach too.
+const DEBUG_ECHO_PATH: &str = "/debug/echo";
+/// How many seconds the `/visits` counter is kept by browsers.
+const VISITS_COOKIE_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// Sends the request body back to the client.
+fn echo(request: &Request) -> Response {
+    let content_type = request
+        .header("Content-Type")
+        .unwrap_or("application/octet-stream");
+    Response::new(Status::Ok, content_type, request.body.clone())
+}
+
+/// Returns the request line and the headers of `request` as the server
+/// parsed them, with the names of the headers as received.
+fn describe_head(request: &Request) -> String {
+    let mut text = format!("{} {}", request.method, request.path);
+    if let Some(query) = &request.query {
+        text += &format!("?{}", query);
+    }
+    text += &format!(" {}\n", request.version);
+    for (name, value) in request.headers.iter() {
+        text += &format!("{}: {}\n", name, value);
+    }
+    text
+}
+
+/// Describes the request as the server parsed it, for debugging clients: the
+/// request line, the headers with their names as received, and the length
+/// of the body.
+///
+/// Requests with a malformed header line are described by
+/// [`debug_echo_malformed`] instead; other requests the parser cannot read
+/// are answered with an error before reaching a handler.
+fn debug_echo(request: &Request) -> Response {
+    let mut body = describe_head(request);
+    let framing = if request.header("Transfer-Encoding").is_some() {
+        "chunked"
+    } else {
+        "Content-Length"
+    };
+    body += &format!("\nbody: {} bytes ({})\n", request.body.len(), framing);
+    Response::new(Status::Ok, "text/plain; charset=utf-8", body.into_bytes())
+}
+
+/// Answers a request for the debug echo with a malformed header line with
+/// `400 Bad Request`, describing the request up to that line and where
+/// parsing stopped. The body is left unread.
+fn debug_echo_malformed(head: &MalformedHead) -> Response {
+    let mut body = describe_head(&head.request);
+    body += &format!(
+        "\nparsing stopped at line {} (byte {}): {:?}\n",
+        head.line, head.offset, head.text
+    );
+    Response::new(
+        Status::BadRequest,
+        "text/plain; charset=utf-8",
+        body.into_bytes(),
+    )
+}
+
+/// Greets whoever is named by the `name` query parameter.
+fn greet(request: &Request) -> Response {
+    let name = request
+        .query_param("name")
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("world"));
+    let body = format!("Hello, {}!\n", name);
+    Response::new(Status::Ok, "text/plain; charset=utf-8", body.into_bytes())
+}
 
-const LOCAL_IP: &str = "0.0.0.0";
-const LOCAL_PORT: u16 = 5555;
-
-macro_rules! header {
-    () => {
-        "\
-HTTP/1.1 200 OK\r\n\
-Content-Type: text/html\r\n\
-Content-Length: {}\r\n\
-Connection: close\r\n\
-\r\n\
-{}"
+/// Counts the visits of a client with a cookie.
+fn visits(request: &Request) -> Response {
+    let previous: u64 = request
+        .cookie("visits")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let count = previous.saturating_add(1);
+    let body = match count {
+        1 => String::from("This is your first visit.\n"),
+        n => format!("You have visited {} times.\n", n),
     };
+    let value = format!("{}", count);
+    Response::new(Status::Ok, "text/plain; charset=utf-8", body.into_bytes()).set_cookie(
+        &SetCookie {
+            name: "visits",
+            value: &value,
+            path: Some("/visits"),
+            max_age: Some(VISITS_COOKIE_MAX_AGE),
+            http_only: true,
+        },
+    )
 }
 
-macro_rules! image_header {
-    () => {
-        "\
-HTTP/1.1 200 OK\r\n\
-Content-Type: image/png\r\n\
-Content-Length: {}\r\n\
-Connection: close\r\n\
-\r\n\
-"
+/// Demonstrates a body of unknown length, sent in several chunks.
+fn count() -> Response {
+    Response::chunked(Status::Ok, "text/plain; charset=utf-8", |w| {
+        for i in 1..=10 {
+            writeln!(w, "chunk {}", i)?;
+        }
+        Ok(())
+    })
+}
+
+/// A connection waiting to be served, with the router handling its requests.
+type Client = (TcpStream, SocketAddr, Arc<Router>);
+
+/// Builds the router with the built-in endpoints, serving files from the
+/// document roots of `hosts`, or else from the configured one, for all other
+/// paths.
+fn build_router(hosts: &'static [VirtualHost], config: &Config) -> Router {
+    let (doc_root, upload_dir, workers) = (config.doc_root, config.upload_dir, config.workers);
+    let download_dir = config.download_dir;
+    let writable = config.writable;
+    let methods = if writable {
+        "GET, HEAD, PUT, DELETE"
+    } else {
+        "GET, HEAD"
     };
+    Router::new(move |request, _| {
+        let root = vhost::resolve(hosts, request.header("Host"), doc_root);
+        match request.method.as_str() {
+            "GET" | "HEAD" => files::serve(request, root),
+            "PUT" if writable => files::put(request, root),
+            "DELETE" if writable => files::delete(request, root),
+            // Refused rather than unknown, since they would be allowed if
+            // writing were enabled.
+            "PUT" | "DELETE" => Response::error(Status::Forbidden),
+            "OPTIONS" => Response::empty(Status::NoContent)
+                .with_header("Allow", &format!("{}, OPTIONS", methods)),
+            _ => Response::error(Status::MethodNotAllowed).with_header("Allow", methods),
+        }
+    })
+    .route("GET", "/chunked", |_, _| count())
+    .route("GET", "/greet", |request, _| greet(request))
+    .route("GET", "/visits", |request, _| visits(request))
+    .route_with_auth("GET", "/status", admin::CREDENTIALS.as_ref(), |_, _| {
+        let json = STATS.to_json();
+        Response::new(Status::Ok, "application/json", json.into_bytes())
+    })
+    .route("GET", "/events", |_, _| events::handle())
+    .route("GET", "/api/sysinfo", move |_, _| {
+        sysinfo::handle(num_cpus(), workers)
+    })
+    .route_with_auth(
+        "GET",
+        "/shutdown",
+        admin::CREDENTIALS.as_ref(),
+        |request, _| shutdown::handle(request),
+    )
+    .route_with_auth(
+        "GET",
+        "/admin/reload",
+        admin::CREDENTIALS.as_ref(),
+        move |request, _| admin::reload(request, doc_root),
+    )
+    .route("POST", "/echo", |request, _| echo(request))
+    .route("GET", "/upload", |_, _| upload::form())
+    .route_body("POST", "/upload", move |request, _, body| {
+        upload::handle(request, body, upload_dir)
+    })
+    .route("GET", "/download", move |request, name| {
+        download::handle(request, name, download_dir)
+    })
+    .route("*", DEBUG_ECHO_PATH, |request, _| debug_echo(request))
+    .route("GET", "/ws", |request, _| websocket::handshake(request))
+    .route("*", "/upstream", proxy::handle)
 }
 
-macro_rules! info {
-    ($($arg:tt)*) => {
-        match option_env!("LOG") {
-            Some("info") | Some("debug") | Some("trace") => {
-                print!("[INFO] {}\n", format_args!($($arg)*));
+/// Answers `request`, whose body is read from `body` by handlers that read it
+/// themselves.
+fn handle_request(request: &Request, router: &Router, body: &mut dyn Read) -> Response {
+    match CORS.as_ref() {
+        Some(cors) => cors.apply(request, route_request(request, router, body)),
+        None => route_request(request, router, body),
+    }
+}
+
+fn route_request(request: &Request, router: &Router, body: &mut dyn Read) -> Response {
+    if request.version == "HTTP/1.1" && request.header("Host").is_none() {
+        // Required by RFC 9112, section 3.2.
+        return Response::error(Status::BadRequest);
+    }
+    if let Some(preflight) = CORS.as_ref().and_then(|cors| cors.preflight(request)) {
+        return preflight;
+    }
+    let moved = url::decode_path(&request.path).and_then(|path| redirect::find(REDIRECTS, &path));
+    if let Some(to) = moved {
+        return redirect::moved(to, request.query.as_deref());
+    }
+    router.dispatch(request, body)
+}
+
+/// Answers a request that could not be read with an error and closes the
+/// connection.
+fn reject(stream: &mut TcpStream, status: Status) -> io::Result<()> {
+    reject_with(stream, Response::error(status))
+}
+
+/// Answers a request that could not be read with `response`, an error, and
+/// closes the connection.
+fn reject_with(stream: &mut TcpStream, response: Response) -> io::Result<()> {
+    STATS.requests.add(1);
+    let status = response.status;
+    let mut writer = DeadlineWriter::new(stream, WRITE_TIMEOUT);
+    let mut out = CountingWriter::new(&mut writer);
+    let written = response.write_to(&mut out, false);
+    STATS.record_response(status, out.count());
+    written
+}
+
+fn http_server(mut stream: TcpStream, peer: SocketAddr, router: &Router) -> io::Result<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut reader = RequestReader::with_limits(HEAD_LIMITS);
+    for served in 1..=MAX_KEEPALIVE_REQUESTS {
+        let mut timed = if served == 1 {
+            DeadlineReader::new(&mut stream, REQUEST_TIMEOUT)
+        } else {
+            DeadlineReader::after_idle(&mut stream, IDLE_TIMEOUT, REQUEST_TIMEOUT)
+        };
+        // The bodies of requests whose handler reads them as they arrive are
+        // left unread, unless chunked.
+        let head = reader.read_head(&mut timed);
+        let streamed_len = head
+            .as_ref()
+            .ok()
+            .filter(|head| router.reads_body(head) && head.header("Transfer-Encoding").is_none())
+            .and_then(|head| head.content_length().ok());
+        let read = head.and_then(|mut request| {
+            if streamed_len.is_none() {
+                reader.read_body(&mut timed, &mut request)?;
             }
-            _ => {}
+            Ok(request)
+        });
+        let request = match read {
+            Ok(request) => request,
+            Err(ParseError::Closed) => return Ok(()),
+            Err(ParseError::Io(_)) if timed.idle_expired() => {
+                STATS.idle_closes.add(1);
+                return Ok(());
+            }
+            Err(ParseError::Malformed) => {
+                return match reader.take_malformed() {
+                    Some(head) if head.request.path == DEBUG_ECHO_PATH => {
+                        reject_with(&mut stream, debug_echo_malformed(&head))
+                    }
+                    _ => reject(&mut stream, Status::BadRequest),
+                };
+            }
+            Err(ParseError::HeadTooLarge) => {
+                return reject(&mut stream, Status::RequestHeaderFieldsTooLarge);
+            }
+            Err(ParseError::TooLarge) => return reject(&mut stream, Status::PayloadTooLarge),
+            Err(ParseError::UnsupportedVersion) => {
+                return reject(&mut stream, Status::HttpVersionNotSupported);
+            }
+            Err(ParseError::Io(e)) => return Err(e),
+        };
+        STATS.requests.add(1);
+        let start = Instant::now();
+        let decision = RATE_LIMITER
+            .as_ref()
+            .map_or(Decision::Allow, |limiter| limiter.check(peer.ip(), start));
+        let mut unread = streamed_len.unwrap_or(0);
+        let mut response = match decision {
+            Decision::Allow => {
+                let response = match streamed_len {
+                    Some(len) => {
+                        let mut body = reader.body_reader(&mut timed, len);
+                        let response = handle_request(&request, router, &mut body);
+                        unread = body.remaining();
+                        response
+                    }
+                    None => handle_request(&request, router, &mut request.body.as_slice()),
+                };
+                compress::apply(&request, response)
+            }
+            Decision::Limit(retry_after) => Response::error(Status::TooManyRequests)
+                .with_header("Retry-After", &format!("{}", retry_after)),
+            Decision::Drop => {
+                info!("client {} is far over the rate limit, dropping it", peer);
+                return Ok(());
+            }
+        };
+        if request.is_http10() {
+            response = response.for_http10();
+        }
+        // Checked after handling, so that the response to `/shutdown` itself
+        // closes the connection, as does a body the handler did not read all
+        // of, whose rest would be taken for the next request.
+        let keep_alive = request.keep_alive()
+            && unread == 0
+            && served < MAX_KEEPALIVE_REQUESTS
+            && !shutdown::requested()
+            && !response.needs_close();
+        let status = response.status;
+        if status == Status::Unauthorized && request.header("Authorization").is_some() {
+            if let Some(limiter) = RATE_LIMITER.as_ref() {
+                limiter.penalize(peer.ip(), start, AUTH_FAILURE_PENALTY);
+            }
+        }
+
+        let mut writer = DeadlineWriter::new(&mut stream, WRITE_TIMEOUT);
+        let mut out = CountingWriter::new(&mut writer);
+        if let Body::Upgrade(_) = response.body {
+            let written = response.write_head(&mut out, false);
+            STATS.record_response(status, out.count());
+            access_log::log_request(peer, &request, status, out.count(), start.elapsed());
+            written?;
+            if let Body::Upgrade(handler) = response.body {
+                // The handler relies on the socket timeout alone.
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                handler(&mut stream, reader.take_buffered())?;
+            }
+            break;
+        }
+        let written = if request.method == "HEAD" {
+            response.write_head(&mut out, keep_alive)
+        } else {
+            response.write_to(&mut out, keep_alive)
+        };
+        STATS.record_response(status, out.count());
+        access_log::log_request(peer, &request, status, out.count(), start.elapsed());
+        written?;
+        if !keep_alive {
+            break;
         }
+    }
+    Ok(())
+}
+
+/// Returns the address to listen on unless configured otherwise.
+fn default_addr() -> SocketAddr {
+    #[cfg(feature = "axstd")]
+    if let Some(addr) = option_env!("HTTPD_ADDR") {
+        match config::parse_addr(addr) {
+            Some(addr) => return addr,
+            None => println!(
+                "invalid listening address {:?}, using {}",
+                addr, DEFAULT_ADDR
+            ),
+        }
+    }
+    DEFAULT_ADDR
+}
+
+/// Returns the number of CPUs.
+fn num_cpus() -> usize {
+    #[cfg(feature = "axstd")]
+    let cpus = option_env!("AX_SMP").and_then(|n| n.parse().ok());
+    #[cfg(not(feature = "axstd"))]
+    let cpus = std::thread::available_parallelism().ok().map(|n| n.get());
+    cpus.unwrap_or(1)
+}
+
+/// Returns the settings, from [`CONFIG_PATH`] and the command line, with
+/// defaults for those that are not set.
+fn load_config() -> Config {
+    let mut config = Config {
+        addr: default_addr(),
+        // One worker per CPU.
+        workers: num_cpus(),
+        queue_depth: QUEUE_DEPTH,
+        doc_root: files::DOC_ROOT,
+        upload_dir: upload::UPLOAD_DIR,
+        download_dir: download::DOWNLOAD_DIR,
+        writable: false,
     };
+    // The file is optional.
+    if let Ok(content) = std::fs::read_to_string(CONFIG_PATH) {
+        config.apply_file(&content, CONFIG_PATH);
+    }
+    #[cfg(not(feature = "axstd"))]
+    config.apply_args(std::env::args().skip(1));
+    config
 }
 
-fn http_server(mut stream: TcpStream) -> io::Result<()> {
-    let mut buf = [0u8; 4096];
-    let _len = stream.read(&mut buf)?;
+fn serve_client((stream, addr, router): Client) {
+    STATS.active.add(1);
+    let result = http_server(stream, addr, &router);
+    STATS.active.sub(1);
+    match result {
+        Err(e) if timeout::is_timeout(&e) => {
+            STATS.timeouts.add(1);
+            info!("client {} timed out", addr);
+        }
+        Err(e) => info!(
+            "client {} connection error: {:?}",
+            addr,
+            access_log::error_kind(&e)
+        ),
+        Ok(()) => info!("client {} closed successfully", addr),
+    }
+}
+
+/// Returns whether an `accept` error means the listener is unusable, rather
+/// than a transient condition such as an aborted connection or low memory.
+#[cfg(feature = "axstd")]
+fn is_fatal_accept_error(e: &io::Error) -> bool {
+    matches!(
+        e,
+        io::Error::InvalidInput | io::Error::BadState | io::Error::NotConnected
+    )
+}
 
-    let request_head = buf.split(|&x| x == b'\n').next().unwrap();
-    let mut iter = request_head.split(|&x| x == b' ');
+/// Returns whether an `accept` error means the listener is unusable, rather
+/// than a transient condition such as an aborted connection or low memory.
+#[cfg(not(feature = "axstd"))]
+fn is_fatal_accept_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidInput | io::ErrorKind::NotConnected
+    ) || e.raw_os_error() == Some(9) // EBADF
+}
 
-    let method = iter.next().unwrap();
-    let path = iter.next().unwrap();
-    let version = iter.next().unwrap();
+/// Returns whether an I/O error means that the address is already in use.
+#[cfg(feature = "axstd")]
+fn is_addr_in_use(e: &io::Error) -> bool {
+    matches!(e, io::Error::AddrInUse)
+}
 
-    let mut path = std::str::from_utf8(path).unwrap().trim();
+/// Returns whether an I/O error means that the address is already in use.
+#[cfg(not(feature = "axstd"))]
+fn is_addr_in_use(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::AddrInUse
+}
 
-    if path == "/" {
-        path = "/index.html";
+/// Returns whether an I/O error only means that the operation would block.
+#[cfg(feature = "axstd")]
+fn is_would_block(e: &io::Error) -> bool {
+    matches!(e, io::Error::WouldBlock)
+}
+
+/// Returns whether an I/O error only means that the operation would block.
+#[cfg(not(feature = "axstd"))]
+fn is_would_block(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock
+}
+
+/// Logs a statistics line every [`STATS_INTERVAL`].
+fn stats_loop(pool: ThreadPool<Client>) {
+    loop {
+        thread::sleep(STATS_INTERVAL);
+        info!(
+            "stats: accepted {}, accept errors {}, queued {}, active {}",
+            STATS.connections.get(),
+            STATS.accept_errors.get(),
+            pool.queued(),
+            pool.active()
+        );
     }
+}
 
-    match path.split(".").last().unwrap() {
-        "html" => {
-            let content = std::fs::read(format!("/html{}", path).as_str()).unwrap();
-            let response = format!(
-                header!(),
-                content.len(),
-                std::str::from_utf8(&content).unwrap()
-            );
+/// Binds a listening socket to `addr`, retrying with a growing delay while
+/// the address is in use, up to [`BIND_ATTEMPTS`] times.
+fn bind_with_retry(addr: SocketAddr) -> io::Result<TcpListener> {
+    let mut delay = BIND_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match TcpListener::bind(addr) {
+            Err(e) if is_addr_in_use(&e) && attempt < BIND_ATTEMPTS => {
+                println!(
+                    "{} is in use (attempt {} of {}), retrying in {:?}",
+                    addr, attempt, BIND_ATTEMPTS, delay
+                );
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_BIND_RETRY_DELAY);
+                attempt += 1;
+            }
+            Err(e) if is_addr_in_use(&e) => {
+                println!(
+                    "{} is still in use after {} attempts, giving up",
+                    addr, attempt
+                );
+                return Err(e);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Binds the listening sockets for `addr`.
+///
+/// If `addr` is the IPv4 wildcard address, the IPv6 wildcard address with the
+/// same port is bound as well, unless IPv6 is unavailable.
+fn bind_listeners(addr: SocketAddr) -> io::Result<Vec<TcpListener>> {
+    let listener = bind_with_retry(addr)?;
+    let port = listener.local_addr()?.port();
+    let mut listeners = vec![listener];
 
-            stream.write_all(response.as_bytes())?;
+    if addr.ip() == Ipv4Addr::UNSPECIFIED {
+        let addr_v6 = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port);
+        match TcpListener::bind(addr_v6) {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => println!("not listening on {}: {:?}, serving IPv4 only", addr_v6, e),
         }
-        "png" | "jpg" => {
-            let content = std::fs::read(format!("/png{}", path).as_str()).unwrap();
-            let response_header = format!(
-                image_header!(),
-                content.len(),
-            );
-            stream.write_all(response_header.as_bytes())?;
-            stream.write_all(&content)?;
+    }
+    Ok(listeners)
+}
+
+fn accept_loop(config: &Config, router: Arc<Router>) -> io::Result<()> {
+    let listeners = bind_listeners(config.addr)?;
+    for listener in listeners.iter() {
+        println!("listen on: http://{}/", listener.local_addr()?);
+        // Accept without blocking, so that a listener reported ready by the
+        // poller but with nothing to accept does not hold up the others.
+        listener.set_nonblocking(true)?;
+    }
+
+    let pool = ThreadPool::new(
+        config.workers,
+        config.queue_depth,
+        QUEUE_FULL_POLICY,
+        serve_client,
+    );
+    let stats_pool = pool.clone();
+    thread::spawn(move || stats_loop(stats_pool));
+
+    let mut poller = Poller::new(&listeners, &shutdown::SHUTDOWN, ACCEPT_POLL_INTERVAL);
+    while let Ready::Accept(i) = poller.wait()? {
+        let (stream, addr) = match listeners[i].accept() {
+            Ok(client) => client,
+            Err(e) if is_would_block(&e) => {
+                poller.spurious();
+                continue;
+            }
+            Err(e) if is_fatal_accept_error(&e) => return Err(e),
+            Err(e) => {
+                STATS.accept_errors.add(1);
+                let errors = STATS.accept_errors.get();
+                info!("accept failed ({} so far): {:?}", errors, e);
+                thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+        };
+        stream.set_nonblocking(false)?;
+        STATS.connections.add(1);
+        info!(
+            "new client {} (queued: {}, active: {})",
+            addr,
+            pool.queued(),
+            pool.active()
+        );
+        if let Err((mut stream, addr, _)) = pool.execute((stream, addr, router.clone())) {
+            info!("queue full, rejecting client {}", addr);
+            let response = Response::error(Status::ServiceUnavailable);
+            if let Err(e) = response.write_to(&mut stream, false) {
+                info!(
+                    "failed to reject client {}: {:?}",
+                    addr,
+                    access_log::error_kind(&e)
+                );
+            }
         }
-        _ => {}
     }
-    stream.flush()?;
 
+    drop(listeners);
+    drain(&pool);
     Ok(())
 }
 
-fn accept_loop() -> io::Result<()> {
-    let listener = TcpListener::bind((LOCAL_IP, LOCAL_PORT))?;
-    println!("listen on: http://{}/", listener.local_addr().unwrap());
-
-    let mut i = 0;
-    loop {
-        match listener.accept() {
-            Ok((stream, addr)) => {
-                info!("new client {}: {}", i, addr);
-                thread::spawn(move || match http_server(stream) {
-                    Err(e) => info!("client connection error: {:?}", e),
-                    Ok(()) => info!("client {} closed successfully", i),
-                });
-            }
-            Err(e) => return Err(e),
+/// Waits up to [`DRAIN_TIMEOUT`] for the workers to finish the connections
+/// they have.
+fn drain(pool: &ThreadPool<Client>) {
+    let start = Instant::now();
+    println!(
+        "shutting down, waiting for {} connections",
+        pool.queued() + pool.active()
+    );
+    while pool.queued() + pool.active() > 0 {
+        if start.elapsed() >= DRAIN_TIMEOUT {
+            println!(
+                "gave up waiting for {} connections",
+                pool.queued() + pool.active()
+            );
+            return;
         }
-        i += 1;
+        thread::sleep(ACCEPT_POLL_INTERVAL);
     }
+    println!("all connections closed");
 }
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
     println!("Hello, ArceOS HTTP server!");
-    accept_loop().expect("test HTTP server failed");
+    let config = load_config();
+    println!("config: {}", config);
+    admin::load_content(config.doc_root);
+    if let Some(path) = ACCESS_LOG_PATH {
+        log_file::start(path);
+    }
+    let router = Arc::new(build_router(VIRTUAL_HOSTS, &config));
+    accept_loop(&config, router).expect("test HTTP server failed");
+    println!("HTTP server stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn router(workers: usize) -> Router {
+        let config = Config {
+            addr: DEFAULT_ADDR,
+            workers,
+            queue_depth: QUEUE_DEPTH,
+            doc_root: files::DOC_ROOT,
+            upload_dir: upload::UPLOAD_DIR,
+            download_dir: download::DOWNLOAD_DIR,
+            writable: false,
+        };
+        build_router(&[], &config)
+    }
+
+    fn get(target: &str) -> Vec<u8> {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", target);
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        match handle_request(&request, &router(1), &mut io::empty()).body {
+            response::Body::Full(body) => body,
+            _ => panic!("unexpected streamed body"),
+        }
+    }
+
+    #[test]
+    fn test_visits() {
+        let visit = |cookie: Option<&str>| {
+            let mut raw = String::from("GET /visits HTTP/1.1\r\nHost: test\r\n");
+            if let Some(cookie) = cookie {
+                raw += &format!("Cookie: {}\r\n", cookie);
+            }
+            raw += "\r\n";
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            let response = handle_request(&request, &router(1), &mut io::empty());
+            let (_, set_cookie) = response
+                .headers
+                .iter()
+                .find(|(n, _)| *n == "Set-Cookie")
+                .unwrap();
+            let set_cookie = set_cookie.clone();
+            match response.body {
+                response::Body::Full(body) => (String::from_utf8(body).unwrap(), set_cookie),
+                _ => panic!("unexpected streamed body"),
+            }
+        };
+
+        let (body, set_cookie) = visit(None);
+        assert_eq!(body, "This is your first visit.\n");
+        assert!(set_cookie.starts_with("visits=1; Path=/visits; Max-Age="));
+        assert!(set_cookie.ends_with("; HttpOnly"));
+        let (body, set_cookie) = visit(Some("theme=dark; visits=41"));
+        assert_eq!(body, "You have visited 42 times.\n");
+        assert!(set_cookie.starts_with("visits=42;"));
+        assert_eq!(visit(Some("visits=junk")).0, "This is your first visit.\n");
+    }
+
+    #[test]
+    fn test_greet() {
+        assert_eq!(get("/greet"), b"Hello, world!\n");
+        assert_eq!(get("/greet?name=foo"), b"Hello, foo!\n");
+        assert_eq!(
+            get("/greet?x=1&name=Ada+Lovelace%21"),
+            b"Hello, Ada Lovelace!!\n"
+        );
+        assert_eq!(get("/greet?name="), b"Hello, world!\n");
+    }
+
+    #[test]
+    fn test_debug_echo() {
+        let echo = |raw: &str| {
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            match handle_request(&request, &router(1), &mut io::empty()).body {
+                response::Body::Full(body) => String::from_utf8(body).unwrap(),
+                _ => panic!("unexpected streamed body"),
+            }
+        };
+        assert_eq!(
+            echo(
+                "GET /debug/echo?a=%20b HTTP/1.1\r\nHost: test\r\n\
+                 X-MiXeD-case:  Some Value \r\n\r\n"
+            ),
+            "GET /debug/echo?a=%20b HTTP/1.1\n\
+             Host: test\n\
+             X-MiXeD-case: Some Value\n\
+             \n\
+             body: 0 bytes (Content-Length)\n"
+        );
+        assert_eq!(
+            echo(
+                "PUT /debug/echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n\
+                 5\r\nhello\r\n0\r\n\r\n"
+            ),
+            "PUT /debug/echo HTTP/1.1\n\
+             Host: test\n\
+             Transfer-Encoding: chunked\n\
+             \n\
+             body: 5 bytes (chunked)\n"
+        );
+
+        let mut reader = RequestReader::default();
+        let raw = "GET /debug/echo HTTP/1.1\r\nHost: test\r\nX-Bad : x\r\n\r\n";
+        assert!(reader.read_head(&mut raw.as_bytes()).is_err());
+        let response = debug_echo_malformed(&reader.take_malformed().unwrap());
+        assert_eq!(response.status, Status::BadRequest);
+        match response.body {
+            response::Body::Full(body) => assert_eq!(
+                String::from_utf8(body).unwrap(),
+                "GET /debug/echo HTTP/1.1\n\
+                 Host: test\n\
+                 \n\
+                 parsing stopped at line 3 (byte 38): \"X-Bad : x\"\n"
+            ),
+            _ => panic!("unexpected streamed body"),
+        }
+    }
+
+    #[test]
+    fn test_sysinfo() {
+        let raw = "GET /api/sysinfo HTTP/1.1\r\nHost: test\r\n\r\n";
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        let response = handle_request(&request, &router(3), &mut io::empty());
+        let content_type = response.headers.iter().find(|(n, _)| *n == "Content-Type");
+        assert_eq!(content_type.unwrap().1, "application/json");
+        let response::Body::Full(body) = response.body else {
+            panic!("unexpected streamed body");
+        };
+        let json = json::tests::parse(std::str::from_utf8(&body).unwrap()).expect("invalid JSON");
+        assert_eq!(
+            json.get("handler_threads"),
+            Some(&json::tests::Value::Number(3.0))
+        );
+    }
+
+    #[test]
+    fn test_missing_host() {
+        let status = |raw: &str| {
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            handle_request(&request, &router(1), &mut io::empty()).status
+        };
+        assert_eq!(status("GET /greet HTTP/1.1\r\n\r\n"), Status::BadRequest);
+        assert_eq!(status("GET /greet HTTP/1.1\r\nHost: a\r\n\r\n"), Status::Ok);
+        assert_eq!(status("GET /greet HTTP/1.0\r\n\r\n"), Status::Ok);
+    }
+
+    #[test]
+    fn test_http10_streamed_body() {
+        let raw = "GET /chunked HTTP/1.0\r\n\r\n";
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        let router = router(1);
+        let response = handle_request(&request, &router, &mut io::empty()).for_http10();
+        assert!(response.needs_close());
+        let mut out = Vec::new();
+        response.write_to(&mut out, false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!out.contains("Transfer-Encoding"));
+        assert!(out.ends_with("\r\n\r\nchunk 1\nchunk 2\nchunk 3\nchunk 4\nchunk 5\nchunk 6\nchunk 7\nchunk 8\nchunk 9\nchunk 10\n"));
+    }
+
+    #[test]
+    fn test_streamed_request_body() {
+        let router = Router::new(|_, _| Response::error(Status::NotFound))
+            .route_body("POST", "/count", |_, _, body| {
+                let len = http1::copy(body, &mut io::sink(), None).unwrap();
+                Response::new(Status::Ok, "text/plain", format!("{}", len).into_bytes())
+            })
+            .route_body("POST", "/ignore", |_, _, _| {
+                Response::empty(Status::NoContent)
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, peer) = listener.accept().unwrap();
+            http_server(stream, peer, &router)
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        // More than the bodies read into memory may hold.
+        let len = request::MAX_BODY_LEN + 1;
+        let head = format!(
+            "POST /count HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n",
+            len
+        );
+        client.write_all(head.as_bytes()).unwrap();
+        client.write_all(&vec![b'x'; len]).unwrap();
+        let ignored = "POST /ignore HTTP/1.1\r\nHost: test\r\nContent-Length: 3\r\n\r\nabc";
+        client.write_all(ignored.as_bytes()).unwrap();
+        let mut out = String::new();
+        client.read_to_string(&mut out).unwrap();
+        server.join().unwrap().unwrap();
+
+        let (counted, ignored) = out.split_at(out.find("HTTP/1.1 204").unwrap());
+        assert!(counted.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(counted.ends_with(&format!("\r\n\r\n{}", len)));
+        // The body left unread ends the connection.
+        assert!(ignored.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_bind_with_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let holder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(250));
+            drop(listener);
+        });
+        let listener = bind_with_retry(addr).unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+        holder.join().unwrap();
+    }
 }
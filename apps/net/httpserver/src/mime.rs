@@ -0,0 +1,93 @@
+//! Content type detection based on file extensions.
+
+/// The content type used when the extension is unknown.
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Known file extensions (in lower case) and their content types.
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("wasm", "application/wasm"),
+];
+
+/// Returns the content type for the file at `path`, judged by its extension.
+///
+/// The extension is matched case-insensitively. Files without a known
+/// extension are served as `application/octet-stream`.
+pub fn content_type_for(path: &str) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let ext = match name.rsplit_once('.') {
+        Some((_, ext)) => ext,
+        None => return DEFAULT_CONTENT_TYPE,
+    };
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        .map(|&(_, ty)| ty)
+        .unwrap_or(DEFAULT_CONTENT_TYPE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::content_type_for;
+
+    #[test]
+    fn test_known_extensions() {
+        assert_eq!(
+            content_type_for("/html/index.html"),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            content_type_for("/html/style.css"),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(
+            content_type_for("/html/app.js"),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type_for("/html/data.json"), "application/json");
+        assert_eq!(content_type_for("/html/logo.png"), "image/png");
+        assert_eq!(content_type_for("/html/photo.jpg"), "image/jpeg");
+        assert_eq!(content_type_for("/html/favicon.ico"), "image/x-icon");
+        assert_eq!(
+            content_type_for("/html/readme.txt"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(content_type_for("/html/main.wasm"), "application/wasm");
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert_eq!(
+            content_type_for("/html/INDEX.HTML"),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(content_type_for("/html/Logo.Png"), "image/png");
+    }
+
+    #[test]
+    fn test_unknown_extensions() {
+        assert_eq!(
+            content_type_for("/html/archive.tar"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type_for("/html/Makefile"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type_for("/html.d/noext"),
+            "application/octet-stream"
+        );
+    }
+}
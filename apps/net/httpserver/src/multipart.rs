@@ -0,0 +1,398 @@
+//! Parsing `multipart/form-data` bodies (RFC 7578), which browsers send for
+//! forms that upload files.
+//!
+//! The body is read through a buffer of [`BUF_LEN`] bytes, and the data of
+//! each part is passed on as it is found, so that memory use does not grow
+//! with the size of the parts.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::vec::Vec;
+
+/// The size of the buffer the body is read through. Part heads must fit in
+/// it.
+const BUF_LEN: usize = 8 * 1024;
+/// The longest boundary allowed (RFC 2046, section 5.1.1).
+const MAX_BOUNDARY_LEN: usize = 70;
+
+/// Errors that can happen when reading a multipart body.
+#[derive(Debug)]
+pub enum Error {
+    /// The underlying stream returned an error.
+    Io(io::Error),
+    /// The body is not valid multipart data, such as when a boundary is
+    /// missing or a part has no name.
+    Malformed,
+    /// The data of a part is longer than the caller allows.
+    TooLarge,
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Returns the boundary of a `multipart/form-data` content type, or `None`
+/// for other content types and for boundaries that are missing, given twice
+/// or invalid.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    let mut boundary = None;
+    for param in params {
+        match param.split_once('=') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case("boundary") => {
+                if boundary.is_some() {
+                    return None;
+                }
+                let value = value.trim();
+                let unquoted = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'));
+                boundary = Some(unquoted.unwrap_or(value));
+            }
+            _ => {}
+        }
+    }
+    boundary.filter(|b| {
+        (1..=MAX_BOUNDARY_LEN).contains(&b.len())
+            && !b.ends_with(' ')
+            && b.bytes()
+                .all(|c| c.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&c))
+    })
+}
+
+/// The head of a part.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Part {
+    /// The name of the form field the part is for.
+    pub name: String,
+    /// The name of the file the part holds, as given by the client.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+/// Parses the value of the `Content-Disposition` header of a part, such as
+/// `form-data; name="field"; filename="a.txt"`, into the field name and the
+/// file name.
+///
+/// Browsers percent-encode quotes in names rather than escaping them, so a
+/// quoted value ends at the next quote and backslashes are kept, as in the
+/// Windows paths some browsers send as file names.
+fn parse_disposition(value: &str) -> Option<(String, Option<String>)> {
+    let (kind, mut rest) = value.split_once(';').unwrap_or((value, ""));
+    if !kind.trim().eq_ignore_ascii_case("form-data") {
+        return None;
+    }
+    let (mut name, mut filename) = (None, None);
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            break;
+        }
+        let (key, after) = rest.split_once('=')?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim(), &after[end..])
+            }
+        };
+        match key.trim() {
+            k if k.eq_ignore_ascii_case("name") => name = Some(String::from(value)),
+            k if k.eq_ignore_ascii_case("filename") => filename = Some(String::from(value)),
+            _ => {}
+        }
+        rest = after;
+    }
+    Some((name?, filename))
+}
+
+/// Parses the header lines of a part.
+fn parse_head<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<Part, Error> {
+    let (mut disposition, mut content_type) = (None, None);
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or(Error::Malformed)?;
+        if name.eq_ignore_ascii_case("Content-Disposition") {
+            disposition = Some(parse_disposition(value.trim()).ok_or(Error::Malformed)?);
+        } else if name.eq_ignore_ascii_case("Content-Type") {
+            content_type = Some(String::from(value.trim()));
+        }
+    }
+    let (name, filename) = disposition.ok_or(Error::Malformed)?;
+    Ok(Part {
+        name,
+        filename,
+        content_type,
+    })
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Where the reader is in the body.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// In the preamble before the first part, or in the data of a part.
+    Data,
+    /// Right after a delimiter.
+    Delimiter,
+    /// After the closing delimiter.
+    Done,
+}
+
+/// Reads the parts of a `multipart/form-data` body.
+///
+/// [`MultipartReader::next_part`] returns the head of each part in turn, and
+/// [`MultipartReader::read_data`] its data. Data that is not read is
+/// skipped.
+pub struct MultipartReader<R: Read> {
+    stream: R,
+    /// `CRLF--` followed by the boundary, which precedes every part and the
+    /// end of the body.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    eof: bool,
+    state: State,
+}
+
+impl<R: Read> MultipartReader<R> {
+    /// Creates a reader for a body with the given boundary.
+    pub fn new(stream: R, boundary: &str) -> Self {
+        let mut delimiter = Vec::from(&b"\r\n--"[..]);
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            stream,
+            delimiter,
+            // The first delimiter may start the body, without a line break
+            // before it.
+            buf: Vec::from(&b"\r\n"[..]),
+            eof: false,
+            state: State::Data,
+        }
+    }
+
+    /// Reads more of the body into the buffer, which must not be full.
+    fn fill(&mut self) -> io::Result<()> {
+        let len = self.buf.len();
+        self.buf.resize(BUF_LEN, 0);
+        let read = self.stream.read(&mut self.buf[len..]);
+        let n = *read.as_ref().unwrap_or(&0);
+        self.buf.truncate(len + n);
+        self.eof = n == 0;
+        read.map(|_| ())
+    }
+
+    /// Passes the first `n` bytes of the buffer on to `out`, counting them in
+    /// `copied`.
+    fn emit<W: Write + ?Sized>(
+        &mut self,
+        out: &mut W,
+        n: usize,
+        copied: &mut u64,
+        limit: u64,
+    ) -> Result<(), Error> {
+        *copied += n as u64;
+        if *copied > limit {
+            return Err(Error::TooLarge);
+        }
+        out.write_all(&self.buf[..n])?;
+        self.buf.drain(..n);
+        Ok(())
+    }
+
+    /// Writes the data of the current part to `out`, returning its length,
+    /// or fails with [`Error::TooLarge`] once more than `limit` bytes have
+    /// been written.
+    pub fn read_data<W: Write + ?Sized>(&mut self, out: &mut W, limit: u64) -> Result<u64, Error> {
+        let mut copied = 0;
+        if self.state != State::Data {
+            return Ok(copied);
+        }
+        loop {
+            if let Some(i) = find(&self.buf, &self.delimiter) {
+                self.emit(out, i, &mut copied, limit)?;
+                self.buf.drain(..self.delimiter.len());
+                self.state = State::Delimiter;
+                return Ok(copied);
+            }
+            if self.eof {
+                // The closing delimiter is missing.
+                return Err(Error::Malformed);
+            }
+            // Everything but what may be the start of a delimiter is data.
+            let keep = self.buf.len().min(self.delimiter.len() - 1);
+            self.emit(out, self.buf.len() - keep, &mut copied, limit)?;
+            self.fill()?;
+        }
+    }
+
+    /// Returns the head of the next part, or `None` after the last one.
+    pub fn next_part(&mut self) -> Result<Option<Part>, Error> {
+        if self.state == State::Data {
+            self.read_data(&mut io::sink(), u64::MAX)?;
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+        while self.buf.len() < 2 && !self.eof {
+            self.fill()?;
+        }
+        if self.buf.starts_with(b"--") {
+            // What follows the closing delimiter is to be ignored.
+            self.state = State::Done;
+            return Ok(None);
+        }
+
+        // The rest of the delimiter line, then the header lines.
+        let end = loop {
+            if let Some(i) = find(&self.buf, b"\r\n\r\n") {
+                break i;
+            }
+            if self.eof || self.buf.len() == BUF_LEN {
+                return Err(Error::Malformed);
+            }
+            self.fill()?;
+        };
+        let head = std::str::from_utf8(&self.buf[..end]).map_err(|_| Error::Malformed)?;
+        let mut lines = head.split("\r\n");
+        let padding = lines.next().unwrap_or("");
+        if !padding.bytes().all(|b| b == b' ' || b == b'\t') {
+            return Err(Error::Malformed);
+        }
+        let part = parse_head(lines)?;
+        self.buf.drain(..end + 4);
+        self.state = State::Data;
+        Ok(Some(part))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits the data it reads into reads of at most 3 bytes, to exercise
+    /// delimiters spanning reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        My file\r\n\
+        --XyZ  \r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a;b.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line 1\r\n--Xy\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    fn parts<R: Read>(reader: &mut MultipartReader<R>) -> Result<Vec<(Part, Vec<u8>)>, Error> {
+        let mut parts = Vec::new();
+        while let Some(part) = reader.next_part()? {
+            let mut data = Vec::new();
+            reader.read_data(&mut data, 1024)?;
+            parts.push((part, data));
+        }
+        Ok(parts)
+    }
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(boundary("multipart/form-data; boundary=XyZ"), Some("XyZ"));
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b:c\""),
+            Some("a b:c")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/mixed; boundary=XyZ"), None);
+        assert_eq!(
+            boundary("multipart/form-data; boundary=a; boundary=b"),
+            None
+        );
+        assert_eq!(boundary("multipart/form-data; boundary="), None);
+        assert_eq!(boundary("multipart/form-data; boundary=\"a\"b\""), None);
+        let long = format!("multipart/form-data; boundary={}", "x".repeat(71));
+        assert_eq!(boundary(&long), None);
+    }
+
+    #[test]
+    fn test_parts() {
+        for trickle in [false, true] {
+            let mut reader = if trickle {
+                MultipartReader::new(Box::new(Trickle(BODY.as_bytes())) as Box<dyn Read>, "XyZ")
+            } else {
+                MultipartReader::new(Box::new(BODY.as_bytes()) as Box<dyn Read>, "XyZ")
+            };
+            let parts = parts(&mut reader).unwrap();
+            assert_eq!(parts.len(), 2);
+            assert_eq!(
+                parts[0],
+                (
+                    Part {
+                        name: String::from("title"),
+                        filename: None,
+                        content_type: None,
+                    },
+                    b"My file".to_vec()
+                )
+            );
+            assert_eq!(parts[1].0.filename.as_deref(), Some("a;b.txt"));
+            assert_eq!(parts[1].0.content_type.as_deref(), Some("text/plain"));
+            assert_eq!(parts[1].1, b"line 1\r\n--Xy");
+        }
+
+        // Parts may be skipped, and the body may start with the boundary.
+        let body = "--b\r\nContent-Disposition: form-data; name=a\r\n\r\n1\r\n\
+                    --b\r\nContent-Disposition: form-data; name=b\r\n\r\n2\r\n--b--";
+        let mut reader = MultipartReader::new(body.as_bytes(), "b");
+        assert_eq!(reader.next_part().unwrap().unwrap().name, "a");
+        assert_eq!(reader.next_part().unwrap().unwrap().name, "b");
+        assert!(reader.next_part().unwrap().is_none());
+        assert!(reader.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bad_bodies() {
+        let malformed = |body: &str| {
+            let mut reader = MultipartReader::new(body.as_bytes(), "b");
+            matches!(parts(&mut reader), Err(Error::Malformed))
+        };
+        let field = "Content-Disposition: form-data; name=a\r\n\r\n";
+        assert!(malformed("no boundary at all"));
+        assert!(malformed(&format!("--b\r\n{}data without end", field)));
+        assert!(malformed(&format!("--b\r\n--b\r\n{}x\r\n--b--", field)));
+        assert!(malformed(
+            "--b\r\nContent-Type: text/plain\r\n\r\nx\r\n--b--"
+        ));
+        assert!(malformed(&format!("--bx\r\n{}x\r\n--b--", field)));
+        assert!(malformed(&format!(
+            "--b\r\nX: {}\r\n\r\n",
+            "x".repeat(BUF_LEN)
+        )));
+
+        let body = format!("--b\r\n{}{}\r\n--b--", field, "x".repeat(2000));
+        let mut reader = MultipartReader::new(body.as_bytes(), "b");
+        assert!(matches!(parts(&mut reader), Err(Error::TooLarge)));
+    }
+}
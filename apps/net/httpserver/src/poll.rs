@@ -0,0 +1,165 @@
+//! Waiting until one of several listeners has a connection to accept, or a
+//! shutdown is requested, from a single thread.
+
+use std::io;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use std::vec::Vec;
+
+/// A flag that is set once, such as to stop the server, and that a
+/// [`Poller`] watches alongside its listeners.
+pub struct Event(AtomicBool);
+
+impl Event {
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    /// Sets the event, waking the pollers watching it.
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Returns whether the event has been set.
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// What a [`Poller`] woke up for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ready {
+    /// The event was set.
+    Event,
+    /// The listener with this index may have a connection to accept.
+    Accept(usize),
+}
+
+/// Returns whether `listener` has a connection to accept.
+#[cfg(feature = "axstd")]
+fn accept_ready(listener: &TcpListener) -> io::Result<bool> {
+    listener.accept_ready()
+}
+
+/// Returns whether `listener` may have a connection to accept. The standard
+/// library cannot tell, so this is always true and `accept` finds out.
+#[cfg(not(feature = "axstd"))]
+fn accept_ready(_listener: &TcpListener) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Watches nonblocking listeners and an event.
+///
+/// A listener may be reported ready and still have nothing to accept, when
+/// readiness is not known or another thread accepted first. The caller then
+/// calls [`Poller::spurious`], and the listener is not reported again before
+/// the next poll interval, so that such wakeups cannot make the caller spin.
+pub struct Poller<'a> {
+    listeners: &'a [TcpListener],
+    event: &'a Event,
+    interval: Duration,
+    /// The listeners with nothing to accept since the last interval.
+    idle: Vec<bool>,
+    /// The listener checked first, taking turns so that a busy listener does
+    /// not starve the others.
+    next: usize,
+    last: Option<usize>,
+}
+
+impl<'a> Poller<'a> {
+    /// Watches `listeners` and `event`, checking them every `interval` while
+    /// none is ready.
+    pub fn new(listeners: &'a [TcpListener], event: &'a Event, interval: Duration) -> Self {
+        Self {
+            listeners,
+            event,
+            interval,
+            idle: vec![false; listeners.len()],
+            next: 0,
+            last: None,
+        }
+    }
+
+    /// Waits until the event is set or a listener is ready. The event takes
+    /// precedence.
+    pub fn wait(&mut self) -> io::Result<Ready> {
+        loop {
+            if self.event.is_set() {
+                return Ok(Ready::Event);
+            }
+            let count = self.listeners.len();
+            for i in (0..count).map(|k| (self.next + k) % count) {
+                if !self.idle[i] && accept_ready(&self.listeners[i])? {
+                    self.next = (i + 1) % count;
+                    self.last = Some(i);
+                    return Ok(Ready::Accept(i));
+                }
+            }
+            self.idle.iter_mut().for_each(|idle| *idle = false);
+            thread::sleep(self.interval);
+        }
+    }
+
+    /// Notes that the listener reported by the last [`Poller::wait`] had
+    /// nothing to accept.
+    pub fn spurious(&mut self) {
+        if let Some(i) = self.last.take() {
+            self.idle[i] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+    use std::time::Instant;
+
+    fn listener() -> TcpListener {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        listener
+    }
+
+    #[test]
+    fn test_accept() {
+        let listeners = [listener(), listener()];
+        let event = Event::new();
+        let mut poller = Poller::new(&listeners, &event, Duration::from_millis(5));
+        let addr = listeners[1].local_addr().unwrap();
+        let _client = TcpStream::connect(addr).unwrap();
+
+        let accepted = loop {
+            let Ready::Accept(i) = poller.wait().unwrap() else {
+                panic!("unexpected event");
+            };
+            match listeners[i].accept() {
+                Ok(_) => break i,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => poller.spurious(),
+                Err(e) => panic!("accept failed: {:?}", e),
+            }
+        };
+        assert_eq!(accepted, 1);
+    }
+
+    #[test]
+    fn test_event() {
+        let listeners = [listener()];
+        let event = Event::new();
+        let mut poller = Poller::new(&listeners, &event, Duration::from_millis(20));
+
+        // Nothing to accept: spurious wakeups are spaced by the interval.
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(poller.wait().unwrap(), Ready::Accept(0));
+            poller.spurious();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        event.set();
+        assert_eq!(poller.wait().unwrap(), Ready::Event);
+        assert_eq!(poller.wait().unwrap(), Ready::Event);
+    }
+}
@@ -0,0 +1,153 @@
+//! A fixed-size pool of worker threads fed by a bounded queue.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::sync::{lock, wait_while};
+
+/// What to do with a new job when the queue is full.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum QueueFullPolicy {
+    /// Wait until a worker takes a job from the queue.
+    Block,
+    /// Hand the job back to the caller.
+    Reject,
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    /// Notified when a job is queued, for idle workers.
+    queued: Condvar,
+    /// Notified when a worker takes a job, for callers waiting for space.
+    taken: Condvar,
+    capacity: usize,
+    active: AtomicUsize,
+    handler: fn(T),
+}
+
+/// A pool of worker threads, each running `handler` on queued jobs.
+///
+/// Clones refer to the same pool.
+pub struct ThreadPool<T> {
+    shared: Arc<Shared<T>>,
+    policy: QueueFullPolicy,
+}
+
+impl<T> Clone for ThreadPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            policy: self.policy,
+        }
+    }
+}
+
+impl<T: Send + 'static> ThreadPool<T> {
+    /// Starts `workers` threads that take jobs from a queue holding at most
+    /// `capacity` jobs.
+    pub fn new(workers: usize, capacity: usize, policy: QueueFullPolicy, handler: fn(T)) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            queued: Condvar::new(),
+            taken: Condvar::new(),
+            capacity,
+            active: AtomicUsize::new(0),
+            handler,
+        });
+        for _ in 0..workers {
+            let shared = shared.clone();
+            thread::spawn(move || worker(&shared));
+        }
+        Self { shared, policy }
+    }
+
+    /// Queues a job for the workers.
+    ///
+    /// If the queue is full, this either waits for space or returns the job
+    /// back, depending on the [`QueueFullPolicy`].
+    pub fn execute(&self, job: T) -> Result<(), T> {
+        let capacity = self.shared.capacity;
+        let mut queue = lock(&self.shared.queue);
+        if queue.len() >= capacity {
+            queue = match self.policy {
+                QueueFullPolicy::Block => {
+                    wait_while(&self.shared.taken, queue, |queue| queue.len() >= capacity)
+                }
+                QueueFullPolicy::Reject => return Err(job),
+            };
+        }
+        queue.push_back(job);
+        drop(queue);
+        self.shared.queued.notify_one();
+        Ok(())
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        lock(&self.shared.queue).len()
+    }
+
+    /// Returns the number of jobs currently being handled.
+    pub fn active(&self) -> usize {
+        self.shared.active.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs the handler on queued jobs, sleeping while the queue is empty.
+fn worker<T>(shared: &Shared<T>) {
+    loop {
+        let queue = lock(&shared.queue);
+        let job = wait_while(&shared.queued, queue, |queue| queue.is_empty()).pop_front();
+        let Some(job) = job else {
+            continue;
+        };
+        shared.taken.notify_one();
+        shared.active.fetch_add(1, Ordering::Relaxed);
+        (shared.handler)(job);
+        shared.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    static DONE: AtomicUsize = AtomicUsize::new(0);
+
+    fn count(n: usize) {
+        DONE.fetch_add(n, Ordering::SeqCst);
+    }
+
+    fn park(_: ()) {
+        thread::sleep(Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_runs_all_jobs() {
+        let pool = ThreadPool::new(4, 2, QueueFullPolicy::Block, count);
+        for _ in 0..100 {
+            pool.execute(1).unwrap();
+        }
+        while DONE.load(Ordering::SeqCst) < 100 {
+            thread::yield_now();
+        }
+        assert_eq!(DONE.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_reject_when_full() {
+        let pool = ThreadPool::new(1, 2, QueueFullPolicy::Reject, park);
+        pool.execute(()).unwrap();
+        while pool.active() == 0 {
+            thread::yield_now();
+        }
+        pool.execute(()).unwrap();
+        pool.execute(()).unwrap();
+        assert_eq!(pool.queued(), 2);
+        assert!(pool.execute(()).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! Forwarding requests to an upstream server.
+
+use std::boxed::Box;
+use std::io::{self, prelude::*, BufReader};
+use std::net::{SocketAddr, TcpStream};
+use std::string::String;
+use std::time::Duration;
+
+use http1::{invalid_data, ChunkedReader, COPY_CHUNK};
+
+use crate::access_log::error_kind;
+use crate::request::{has_token, Request};
+use crate::response::{Body, Response, Status};
+
+/// The upstream server, as `ADDR:PORT`, set when building the server.
+const UPSTREAM: Option<&str> = option_env!("HTTPD_UPSTREAM");
+
+/// How long the upstream server may make no progress.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Headers that only describe a single connection, and are not forwarded
+/// (RFC 9110, section 7.6.1).
+const HOP_BY_HOP: &[&str] = &[
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Connection",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+/// How the end of the upstream response body is found.
+enum Framing {
+    Chunked,
+    Length(u64),
+    UntilClose,
+}
+
+/// Returns whether the header `name` is hop-by-hop, given the `Connection`
+/// header of the message, which may name more of them.
+fn is_hop_by_hop(name: &str, connection: Option<&str>) -> bool {
+    HOP_BY_HOP.iter().any(|h| h.eq_ignore_ascii_case(name))
+        || connection.is_some_and(|c| has_token(c, name))
+}
+
+/// Returns the configured upstream server.
+fn upstream() -> Option<SocketAddr> {
+    UPSTREAM.and_then(|addr| addr.trim().parse().ok())
+}
+
+/// Answers a request under the proxied prefix by forwarding it upstream, with
+/// `path` (the rest of the path after the prefix) as its path.
+///
+/// Without an upstream server, the prefix does not exist. If the upstream
+/// server cannot be reached or sends an invalid response head, the answer is
+/// `502 Bad Gateway`. A request body sent with a transfer coding other than
+/// `chunked` cannot be forwarded, and is answered with `501 Not Implemented`.
+pub fn handle(request: &Request, path: &str) -> Response {
+    let Some(upstream) = upstream() else {
+        return Response::error(Status::NotFound);
+    };
+    if !is_only_chunked(request.header("Transfer-Encoding")) {
+        return Response::error(Status::NotImplemented);
+    }
+    match forward(request, upstream, path) {
+        Ok(response) => response,
+        Err(e) => {
+            info!("proxy to {} failed: {:?}", upstream, error_kind(&e));
+            Response::error(Status::BadGateway)
+        }
+    }
+}
+
+/// Returns whether the `Transfer-Encoding` header of a request, if any, only
+/// names `chunked`, the one coding the request reader removes.
+fn is_only_chunked(transfer_encoding: Option<&str>) -> bool {
+    transfer_encoding.is_none_or(|codings| {
+        codings
+            .split(',')
+            .all(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    })
+}
+
+/// Builds the head of the request sent upstream.
+///
+/// The body has already been read and any chunked coding removed, so the
+/// client's framing headers are dropped and the body is sent with a
+/// `Content-Length` of its own; forwarding them could make the upstream
+/// server split the request differently than this server did.
+fn request_head(request: &Request, upstream: SocketAddr, path: &str) -> String {
+    let path = if path.is_empty() { "/" } else { path };
+    let mut head = format!("{} {}", request.method, path);
+    if let Some(query) = &request.query {
+        head += &format!("?{}", query);
+    }
+    head += " HTTP/1.1\r\n";
+    let connection = request.header("Connection");
+    for (name, value) in request.headers.iter() {
+        if !is_hop_by_hop(name, connection)
+            && !name.eq_ignore_ascii_case("Host")
+            && !name.eq_ignore_ascii_case("Content-Length")
+        {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+    }
+    let framed =
+        request.header("Content-Length").is_some() || request.header("Transfer-Encoding").is_some();
+    if framed || !request.body.is_empty() {
+        head += &format!("Content-Length: {}\r\n", request.body.len());
+    }
+    head += &format!("Host: {}\r\nConnection: close\r\n\r\n", upstream);
+    head
+}
+
+/// Sends `request` upstream and reads the head of the response; its body is
+/// relayed while the response is being sent.
+///
+/// The request body has already been read by the request reader, which
+/// limits its size; it is sent upstream in chunks of [`COPY_CHUNK`] bytes.
+fn forward(request: &Request, upstream: SocketAddr, path: &str) -> io::Result<Response> {
+    let mut stream = TcpStream::connect(upstream)?;
+    stream.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+    stream.set_write_timeout(Some(UPSTREAM_TIMEOUT))?;
+    stream.write_all(request_head(request, upstream, path).as_bytes())?;
+    for chunk in request.body.chunks(COPY_CHUNK) {
+        stream.write_all(chunk)?;
+    }
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let head = http1::read_response_head(&mut reader)?;
+    let status = Status::from_code(head.status);
+    let mut response = Response::empty(status);
+    let connection = head.header("Connection").map(String::from);
+    let mut framing = Framing::UntilClose;
+    for (name, value) in head.headers {
+        if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            framing = Framing::Chunked;
+        } else if name.eq_ignore_ascii_case("Content-Length") {
+            if !matches!(framing, Framing::Chunked) {
+                let len = value.parse().map_err(|_| invalid_data())?;
+                framing = Framing::Length(len);
+            }
+        } else if !is_hop_by_hop(&name, connection.as_deref()) {
+            response.headers.push((name, value));
+        }
+    }
+
+    // These responses never have a body (RFC 9112, section 6.3).
+    if matches!(status.code(), 204 | 304) {
+        return Ok(response);
+    }
+    // The body is re-framed as chunks, so that it is relayed without being
+    // buffered whatever its length. The head of the response to a `HEAD`
+    // request says the same, though no body follows.
+    let is_head = request.method == "HEAD";
+    response.body = Body::Chunked(Box::new(move |out: &mut dyn Write| {
+        let copied = match framing {
+            _ if is_head => return Ok(()),
+            Framing::Chunked => http1::copy(&mut ChunkedReader::new(&mut reader), out, None),
+            Framing::Length(len) => http1::copy(&mut reader, out, Some(len)),
+            Framing::UntilClose => http1::copy(&mut reader, out, None),
+        };
+        copied.map(|_| ())
+    }));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    fn request(raw: &str) -> Request {
+        RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_request_head() {
+        let upstream = "127.0.0.1:8000".parse().unwrap();
+        let post = request(
+            "POST /upstream/a/b?x=1 HTTP/1.1\r\nHost: front\r\nConnection: keep-alive, X-Hop\r\n\
+             X-Hop: 1\r\nKeep-Alive: timeout=5\r\nContent-Length: 2\r\nAccept: */*\r\n\r\nhi",
+        );
+        assert_eq!(
+            request_head(&post, upstream, "/a/b"),
+            "POST /a/b?x=1 HTTP/1.1\r\nAccept: */*\r\nContent-Length: 2\r\n\
+             Host: 127.0.0.1:8000\r\nConnection: close\r\n\r\n"
+        );
+        let get = request("GET /upstream HTTP/1.0\r\n\r\n");
+        assert_eq!(
+            request_head(&get, upstream, ""),
+            "GET / HTTP/1.1\r\nHost: 127.0.0.1:8000\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_request_head_framing() {
+        let upstream = "127.0.0.1:8000".parse().unwrap();
+        // Both framing headers: the chunked body wins, and only its decoded
+        // length is sent upstream.
+        let smuggled = request(
+            "POST /upstream HTTP/1.1\r\nContent-Length: 3\r\n\
+             Transfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        );
+        assert_eq!(smuggled.body, b"hello");
+        assert_eq!(
+            request_head(&smuggled, upstream, "/"),
+            "POST / HTTP/1.1\r\nContent-Length: 5\r\n\
+             Host: 127.0.0.1:8000\r\nConnection: close\r\n\r\n"
+        );
+        let empty = request("POST /upstream HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        assert!(request_head(&empty, upstream, "/").contains("\r\nContent-Length: 0\r\n"));
+    }
+
+    #[test]
+    fn test_transfer_coding() {
+        assert!(is_only_chunked(None));
+        assert!(is_only_chunked(Some("chunked")));
+        assert!(is_only_chunked(Some("Chunked")));
+        assert!(!is_only_chunked(Some("gzip, chunked")));
+        assert!(!is_only_chunked(Some("identity")));
+    }
+
+    #[test]
+    fn test_hop_by_hop() {
+        assert!(is_hop_by_hop("connection", None));
+        assert!(is_hop_by_hop("Transfer-Encoding", None));
+        assert!(is_hop_by_hop("X-Hop", Some("close, x-hop")));
+        assert!(!is_hop_by_hop("Content-Type", Some("close")));
+    }
+}
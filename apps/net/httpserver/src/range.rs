@@ -0,0 +1,136 @@
+//! `Range` request header handling.
+
+/// How much of a resource to send in response to a `Range` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The whole resource, because no (usable) range was requested.
+    Full,
+    /// The bytes from `start` to `end`, both inclusive.
+    Partial { start: u64, end: u64 },
+    /// The requested range lies outside the resource.
+    Unsatisfiable,
+}
+
+/// Resolves the `Range` header `header` against a resource of `len` bytes.
+///
+/// Only a single range in `bytes` units is supported. Malformed headers are
+/// ignored as permitted by RFC 7233, and requests for several ranges are
+/// answered with the whole resource.
+pub fn resolve(header: Option<&str>, len: u64) -> ByteRange {
+    let spec = match header.and_then(|h| h.trim().strip_prefix("bytes=")) {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (first, last) = match spec.split_once('-') {
+        Some(bounds) => bounds,
+        None => return ByteRange::Full,
+    };
+    let parse = |s: &str| -> Option<u64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            None
+        } else {
+            s.parse().ok()
+        }
+    };
+
+    match (parse(first), parse(last)) {
+        // bytes=start-end
+        (Some(start), Some(end)) if start <= end => {
+            if start >= len {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial {
+                    start,
+                    end: end.min(len - 1),
+                }
+            }
+        }
+        // bytes=start-
+        (Some(start), None) if last.is_empty() => {
+            if start >= len {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial {
+                    start,
+                    end: len - 1,
+                }
+            }
+        }
+        // bytes=-suffix
+        (None, Some(suffix)) if first.is_empty() => {
+            if suffix == 0 || len == 0 {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial {
+                    start: len.saturating_sub(suffix),
+                    end: len - 1,
+                }
+            }
+        }
+        _ => ByteRange::Full,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closed_range() {
+        assert_eq!(
+            resolve(Some("bytes=0-99"), 1000),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            resolve(Some("bytes=900-2000"), 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            resolve(Some("bytes=1000-1001"), 1000),
+            ByteRange::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn test_open_ended_range() {
+        assert_eq!(
+            resolve(Some("bytes=100-"), 1000),
+            ByteRange::Partial {
+                start: 100,
+                end: 999
+            }
+        );
+        assert_eq!(resolve(Some("bytes=1000-"), 1000), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn test_suffix_range() {
+        assert_eq!(
+            resolve(Some("bytes=-500"), 1000),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        assert_eq!(
+            resolve(Some("bytes=-5000"), 1000),
+            ByteRange::Partial { start: 0, end: 999 }
+        );
+        assert_eq!(resolve(Some("bytes=-0"), 1000), ByteRange::Unsatisfiable);
+        assert_eq!(resolve(Some("bytes=-10"), 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn test_ignored() {
+        assert_eq!(resolve(None, 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("bytes=0-1,5-6"), 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("items=0-1"), 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("bytes=5-1"), 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("bytes=a-b"), 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("bytes=-"), 1000), ByteRange::Full);
+        assert_eq!(resolve(Some("bytes=+1-2"), 1000), ByteRange::Full);
+    }
+}
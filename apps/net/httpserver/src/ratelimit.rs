@@ -0,0 +1,225 @@
+//! Per-client rate limiting with token buckets.
+
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::sync::lock;
+
+/// Tokens are counted in thousandths, so that buckets refill smoothly.
+const TOKEN: u64 = 1000;
+
+/// How many requests a client may make.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// Requests per second allowed in the long run.
+    pub rate: u32,
+    /// Requests allowed in a burst, after the client has been idle.
+    pub burst: u32,
+    /// Clients that keep sending requests after being limited run up a debt
+    /// of tokens. Once the debt exceeds this many bursts, their connections
+    /// are dropped without a response.
+    pub drop_after_bursts: u32,
+}
+
+/// What to do with a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    /// Answer with `429 Too Many Requests`; the client may retry after the
+    /// given number of seconds.
+    Limit(u64),
+    /// Close the connection without answering.
+    Drop,
+}
+
+struct Bucket {
+    ip: IpAddr,
+    /// Thousandths of tokens, negative when in debt.
+    tokens: i64,
+    last: Instant,
+}
+
+/// Token buckets for a bounded number of clients, keyed by IP address.
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<Vec<Bucket>>,
+    max_clients: usize,
+}
+
+impl RateLimiter {
+    /// Creates a limiter tracking at most `max_clients` addresses.
+    pub const fn new(limit: RateLimit, max_clients: usize) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(Vec::new()),
+            max_clients,
+        }
+    }
+
+    fn capacity(&self) -> i64 {
+        (self.limit.burst as u64 * TOKEN) as i64
+    }
+
+    /// Returns the tokens in `bucket` after refilling it up to `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> i64 {
+        let elapsed = now.duration_since(bucket.last).as_millis() as u64;
+        // `rate` tokens per second are `rate` thousandths per millisecond.
+        let refill = elapsed.saturating_mul(self.limit.rate as u64);
+        let tokens = bucket
+            .tokens
+            .saturating_add(refill.min(i64::MAX as u64) as i64);
+        tokens.min(self.capacity())
+    }
+
+    /// Makes room for a new client, first by forgetting clients whose bucket
+    /// has refilled completely, then by forgetting the least recently seen.
+    fn evict(&self, buckets: &mut Vec<Bucket>, now: Instant) {
+        let capacity = self.capacity();
+        buckets.retain(|b| self.refilled(b, now) < capacity);
+        if buckets.len() >= self.max_clients {
+            if let Some(oldest) = (0..buckets.len()).min_by_key(|&i| buckets[i].last) {
+                buckets.swap_remove(oldest);
+            }
+        }
+    }
+
+    /// Takes a token for a request from `ip` arriving at `now`.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Decision {
+        let mut buckets = lock(&self.buckets);
+        let pos = match buckets.iter().position(|b| b.ip == ip) {
+            Some(pos) => pos,
+            None => {
+                if buckets.len() >= self.max_clients {
+                    self.evict(&mut buckets, now);
+                }
+                buckets.push(Bucket {
+                    ip,
+                    tokens: self.capacity(),
+                    last: now,
+                });
+                buckets.len() - 1
+            }
+        };
+
+        let tokens = self.refilled(&buckets[pos], now);
+        let floor = -self.capacity() * self.limit.drop_after_bursts as i64;
+        let bucket = &mut buckets[pos];
+        bucket.last = now;
+        bucket.tokens = (tokens - TOKEN as i64).max(floor);
+        if tokens >= TOKEN as i64 {
+            Decision::Allow
+        } else if tokens - (TOKEN as i64) < floor {
+            Decision::Drop
+        } else {
+            // Wait until the bucket holds a whole token again.
+            let missing = (TOKEN as i64 - bucket.tokens) as u64;
+            let rate = (self.limit.rate as u64).max(1);
+            let millis = missing.div_ceil(rate);
+            Decision::Limit(Duration::from_millis(millis).as_secs().max(1))
+        }
+    }
+
+    /// Takes `tokens` more from `ip` for a request that turned out to be
+    /// suspicious, such as one with wrong credentials, so that clients
+    /// guessing them are limited sooner.
+    pub fn penalize(&self, ip: IpAddr, now: Instant, tokens: u32) {
+        let mut buckets = lock(&self.buckets);
+        let Some(pos) = buckets.iter().position(|b| b.ip == ip) else {
+            return;
+        };
+        let refilled = self.refilled(&buckets[pos], now);
+        let floor = -self.capacity() * self.limit.drop_after_bursts as i64;
+        let bucket = &mut buckets[pos];
+        bucket.last = now;
+        bucket.tokens = (refilled - (tokens as u64 * TOKEN) as i64).max(floor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        rate: 2,
+        burst: 3,
+        drop_after_bursts: 2,
+    };
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([10, 0, 2, last])
+    }
+
+    #[test]
+    fn test_burst_and_refill() {
+        let limiter = RateLimiter::new(LIMIT, 16);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check(ip(1), start), Decision::Allow);
+        }
+        assert_eq!(limiter.check(ip(1), start), Decision::Limit(1));
+        // Other clients have their own bucket.
+        assert_eq!(limiter.check(ip(2), start), Decision::Allow);
+
+        // The rejected request was charged, so one token takes a second.
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.check(ip(1), later), Decision::Limit(1));
+        let later = start + Duration::from_secs(3);
+        assert_eq!(limiter.check(ip(1), later), Decision::Allow);
+    }
+
+    #[test]
+    fn test_drop() {
+        let limiter = RateLimiter::new(LIMIT, 16);
+        let now = Instant::now();
+        let decisions: Vec<_> = (0..12).map(|_| limiter.check(ip(1), now)).collect();
+        assert_eq!(decisions[..3], [Decision::Allow; 3]);
+        assert!(decisions[3..9]
+            .iter()
+            .all(|d| matches!(d, Decision::Limit(_))));
+        assert_eq!(decisions[9..], [Decision::Drop; 3]);
+
+        // The debt is bounded, so the client recovers eventually.
+        let later = now + Duration::from_secs(5);
+        assert_eq!(limiter.check(ip(1), later), Decision::Allow);
+    }
+
+    #[test]
+    fn test_penalize() {
+        let limiter = RateLimiter::new(LIMIT, 16);
+        let now = Instant::now();
+        // Unknown clients are not tracked.
+        limiter.penalize(ip(1), now, 2);
+        assert_eq!(limiter.check(ip(1), now), Decision::Allow);
+        limiter.penalize(ip(1), now, 2);
+        assert_eq!(limiter.check(ip(1), now), Decision::Limit(1));
+
+        // The debt is bounded like that of limited requests.
+        limiter.penalize(ip(1), now, 100);
+        assert_eq!(limiter.check(ip(1), now), Decision::Drop);
+        let later = now + Duration::from_secs(5);
+        assert_eq!(limiter.check(ip(1), later), Decision::Allow);
+    }
+
+    #[test]
+    fn test_bounded_table() {
+        let limiter = RateLimiter::new(LIMIT, 4);
+        let start = Instant::now();
+        for i in 0..4 {
+            limiter.check(ip(i), start + Duration::from_secs(i as u64));
+        }
+        // `ip(3)` is still refilling, the others are idle and forgotten.
+        let now = start + Duration::from_millis(3100);
+        limiter.check(ip(10), now);
+        assert_eq!(lock(&limiter.buckets).len(), 2);
+
+        // Without idle clients, the least recently seen one is forgotten.
+        for i in 20..30 {
+            limiter.check(ip(i), now + Duration::from_millis(i as u64));
+        }
+        let buckets = lock(&limiter.buckets);
+        assert_eq!(buckets.len(), 4);
+        assert!(buckets.iter().all(|b| b.ip >= ip(26)));
+    }
+}
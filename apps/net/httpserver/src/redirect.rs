@@ -0,0 +1,66 @@
+//! Redirects to other paths on the server.
+
+use crate::listing::percent_encode_path;
+use crate::response::{Response, Status};
+
+/// A path that has permanently moved elsewhere.
+pub struct Redirect {
+    /// The old path, compared with the decoded and normalized request path.
+    pub from: &'static str,
+    /// The new path, not percent-encoded.
+    pub to: &'static str,
+}
+
+/// Returns the new path for `path` in `table`, if it has moved.
+pub fn find<'a>(table: &'a [Redirect], path: &str) -> Option<&'a str> {
+    table.iter().find(|r| r.from == path).map(|r| r.to)
+}
+
+/// Creates a `301 Moved Permanently` response pointing to the decoded path
+/// `path`, with the original `query` if any.
+pub fn moved(path: &str, query: Option<&str>) -> Response {
+    let mut location = percent_encode_path(path);
+    if let Some(query) = query {
+        location.push('?');
+        location += query;
+    }
+    Response::redirect(Status::MovedPermanently, &location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Body;
+
+    const TABLE: &[Redirect] = &[
+        Redirect {
+            from: "/old",
+            to: "/new",
+        },
+        Redirect {
+            from: "/old/page.html",
+            to: "/new page.html",
+        },
+    ];
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find(TABLE, "/old"), Some("/new"));
+        assert_eq!(find(TABLE, "/old/page.html"), Some("/new page.html"));
+        assert_eq!(find(TABLE, "/old/"), None);
+        assert_eq!(find(TABLE, "/other"), None);
+    }
+
+    #[test]
+    fn test_moved() {
+        let response = moved("/new page.html", Some("a=1&b=<2>"));
+        assert_eq!(response.status, Status::MovedPermanently);
+        let location = response.headers.iter().find(|(n, _)| *n == "Location");
+        assert_eq!(location.unwrap().1, "/new%20page.html?a=1&b=<2>");
+        let body = match response.body {
+            Body::Full(body) => String::from_utf8(body).unwrap(),
+            _ => panic!("unexpected streamed body"),
+        };
+        assert!(body.contains("<a href=\"/new%20page.html?a=1&amp;b=&lt;2&gt;\">"));
+    }
+}
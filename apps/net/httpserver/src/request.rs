@@ -0,0 +1,942 @@
+//! HTTP request parsing.
+
+use std::boxed::Box;
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::vec::Vec;
+
+use crate::cookie;
+use crate::url;
+
+/// Limits on the size of request heads, which protect the server from
+/// clients sending endless header lines.
+#[derive(Clone, Copy, Debug)]
+pub struct HeadLimits {
+    /// The maximum number of header fields.
+    pub max_headers: usize,
+    /// The maximum length of a single line, including the request line.
+    pub max_line_len: usize,
+    /// The maximum size of the whole head (request line and headers).
+    ///
+    /// The buffer grows as needed up to this size, so long cookies are fine
+    /// as long as they fit in a line.
+    pub max_head_len: usize,
+}
+
+impl HeadLimits {
+    /// The limits used unless configured otherwise.
+    pub const DEFAULT: Self = Self {
+        max_headers: 64,
+        max_line_len: 8 * 1024,
+        max_head_len: 16 * 1024,
+    };
+}
+/// The maximum size of a request body.
+pub const MAX_BODY_LEN: usize = 1024 * 1024;
+
+/// A parsed HTTP request head.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    /// The path of the request target, still percent-encoded.
+    pub path: String,
+    /// The query of the request target, without the `?`.
+    pub query: Option<String>,
+    pub version: String,
+    /// Header fields in the order they were received.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Errors that can happen when reading a request.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The underlying stream returned an error.
+    Io(io::Error),
+    /// The peer closed the connection before sending a complete request.
+    Closed,
+    /// The request head is not valid HTTP.
+    Malformed,
+    /// The request head exceeds one of the [`HeadLimits`].
+    HeadTooLarge,
+    /// The request body is larger than [`MAX_BODY_LEN`].
+    TooLarge,
+    /// The request is for an HTTP version other than 1.0 and 1.1.
+    UnsupportedVersion,
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+/// A request head with a malformed header line, parsed up to that line.
+#[derive(Debug)]
+pub struct MalformedHead {
+    /// The request line and the headers before the malformed line.
+    pub request: Request,
+    /// The number of the malformed line, the request line being line 1.
+    pub line: usize,
+    /// Where the malformed line starts in the head, in bytes.
+    pub offset: usize,
+    /// The malformed line, without its line ending.
+    pub text: String,
+}
+
+/// Why a request head failed to parse.
+enum HeadError {
+    Invalid(ParseError),
+    /// A header line is malformed, after a valid request line.
+    BadHeader(Box<MalformedHead>),
+}
+
+impl From<ParseError> for HeadError {
+    fn from(e: ParseError) -> Self {
+        HeadError::Invalid(e)
+    }
+}
+
+impl Request {
+    /// Returns the value of the first header named `name`, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the parameters in the query string as decoded key/value pairs,
+    /// in order and including repeated keys.
+    ///
+    /// `key=value` yields `(key, Some(value))` and a bare `flag` yields
+    /// `(flag, None)`. `+` stands for a space.
+    pub fn query_params(&self) -> impl Iterator<Item = (String, Option<String>)> + '_ {
+        self.query
+            .as_deref()
+            .unwrap_or("")
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| match param.split_once('=') {
+                Some((key, value)) => (url::decode_query(key), Some(url::decode_query(value))),
+                None => (url::decode_query(param), None),
+            })
+    }
+
+    /// Returns the value of the first query parameter named `key`.
+    pub fn query_param(&self, key: &str) -> Option<String> {
+        self.query_params()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.unwrap_or_default())
+    }
+
+    /// Returns the cookies sent with the request as name/value pairs, in
+    /// order and including repeated names, from all `Cookie` headers.
+    pub fn cookies(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("Cookie"))
+            .flat_map(|(_, v)| cookie::parse(v))
+    }
+
+    /// Returns the value of the first cookie named `name`. Browsers send the
+    /// cookie with the most specific path first.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// Returns whether the request was made with HTTP/1.0, whose clients do
+    /// not understand chunked responses.
+    pub fn is_http10(&self) -> bool {
+        self.version == "HTTP/1.0"
+    }
+
+    /// Returns whether the client wants the connection to stay open after
+    /// this request.
+    ///
+    /// An explicit `Connection` header decides; otherwise HTTP/1.1 connections
+    /// are persistent by default.
+    pub fn keep_alive(&self) -> bool {
+        match self.header("Connection") {
+            Some(v) if has_token(v, "close") => false,
+            Some(v) if has_token(v, "keep-alive") => true,
+            _ => self.version == "HTTP/1.1",
+        }
+    }
+
+    /// Returns the length of the body given by the `Content-Length` header,
+    /// or 0 without one. `Transfer-Encoding` overrides it if present.
+    ///
+    /// The value must be digits only; `str::parse` alone would take a sign.
+    pub fn content_length(&self) -> Result<u64, ParseError> {
+        match self.header("Content-Length") {
+            Some(len) if !len.is_empty() && len.bytes().all(|b| b.is_ascii_digit()) => {
+                len.parse().map_err(|_| ParseError::Malformed)
+            }
+            Some(_) => Err(ParseError::Malformed),
+            None => Ok(0),
+        }
+    }
+
+    /// Parses a complete request head, without the terminating empty line.
+    fn parse(head: &[u8], limits: &HeadLimits) -> Result<Self, HeadError> {
+        let head = std::str::from_utf8(head).map_err(|_| ParseError::Malformed)?;
+        if head.split('\n').any(|l| l.len() > limits.max_line_len) {
+            return Err(ParseError::HeadTooLarge.into());
+        }
+        let mut lines = head.split('\n');
+
+        let request_line = lines.next().ok_or(ParseError::Malformed)?;
+        let mut offset = request_line.len() + 1;
+        let request_line = request_line.strip_suffix('\r').unwrap_or(request_line);
+        let mut parts = request_line.split(' ');
+        let (method, target, version) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(m), Some(t), Some(v), None) if !m.is_empty() && !t.is_empty() => (m, t, v),
+                _ => return Err(ParseError::Malformed.into()),
+            };
+        match version {
+            "HTTP/1.0" | "HTTP/1.1" => {}
+            v if is_version_token(v) => return Err(ParseError::UnsupportedVersion.into()),
+            _ => return Err(ParseError::Malformed.into()),
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+
+        let mut request = Request {
+            method: String::from(method),
+            path: String::from(path),
+            query: query.map(String::from),
+            version: String::from(version),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+        for (i, raw) in lines.enumerate() {
+            if request.headers.len() == limits.max_headers {
+                return Err(ParseError::HeadTooLarge.into());
+            }
+            let line = raw.strip_suffix('\r').unwrap_or(raw);
+            let header = line.split_once(':').filter(|(name, _)| {
+                !name.is_empty() && !name.contains(|c: char| c.is_ascii_whitespace())
+            });
+            let Some((name, value)) = header else {
+                return Err(HeadError::BadHeader(Box::new(MalformedHead {
+                    request,
+                    line: i + 2,
+                    offset,
+                    text: String::from(line),
+                })));
+            };
+            request
+                .headers
+                .push((String::from(name), String::from(value.trim())));
+            offset += raw.len() + 1;
+        }
+        Ok(request)
+    }
+}
+
+/// Reads requests from a stream, keeping bytes that arrive after the head.
+pub struct RequestReader {
+    buf: Vec<u8>,
+    limits: HeadLimits,
+    /// The last head with a malformed header line, until taken.
+    malformed: Option<MalformedHead>,
+}
+
+impl Default for RequestReader {
+    fn default() -> Self {
+        Self::with_limits(HeadLimits::DEFAULT)
+    }
+}
+
+impl RequestReader {
+    /// Creates a reader enforcing the given limits on request heads.
+    pub fn with_limits(limits: HeadLimits) -> Self {
+        Self {
+            buf: Vec::new(),
+            limits,
+            malformed: None,
+        }
+    }
+
+    /// Reads from `stream` until a complete request is available and parses
+    /// it, as [`RequestReader::read_head`] and [`RequestReader::read_body`]
+    /// do. The server calls them itself, to leave some bodies unread.
+    #[cfg(test)]
+    pub fn read_request<R: Read>(&mut self, stream: &mut R) -> Result<Request, ParseError> {
+        let mut request = self.read_head(stream)?;
+        self.read_body(stream, &mut request)?;
+        Ok(request)
+    }
+
+    /// Reads the body of `request`, whose head was just read from `stream`.
+    ///
+    /// The body is read according to the `Transfer-Encoding` header, which
+    /// must end with `chunked` if present, or else the `Content-Length`
+    /// header. Bodies larger than [`MAX_BODY_LEN`] are rejected before being
+    /// read, or as soon as a chunk makes them too large.
+    pub fn read_body<R: Read>(
+        &mut self,
+        stream: &mut R,
+        request: &mut Request,
+    ) -> Result<(), ParseError> {
+        // `Transfer-Encoding` overrides `Content-Length` (RFC 9112, section
+        // 6.3). Without `chunked` last, the end of the body is unknown.
+        if let Some(codings) = request.header("Transfer-Encoding") {
+            let last = codings.rsplit(',').next().unwrap_or("").trim();
+            if !last.eq_ignore_ascii_case("chunked") {
+                return Err(ParseError::Malformed);
+            }
+            request.body = self.read_chunked_body(stream)?;
+            return Ok(());
+        }
+
+        let body_len = request.content_length()?;
+        if body_len > MAX_BODY_LEN as u64 {
+            return Err(ParseError::TooLarge);
+        }
+        let body_len = body_len as usize;
+        while self.buf.len() < body_len {
+            self.fill(stream)?;
+        }
+        request.body = self.buf.drain(..body_len).collect();
+        Ok(())
+    }
+
+    /// Returns a reader of the `len` bytes of the body of the request whose
+    /// head was just read from `stream`, for handlers that read the body as it
+    /// arrives rather than once it has been read into memory.
+    ///
+    /// The body is not limited to [`MAX_BODY_LEN`]; the reader of the head of
+    /// the next request must only be used once it has all been read.
+    pub fn body_reader<'a, R: Read>(
+        &'a mut self,
+        stream: &'a mut R,
+        len: u64,
+    ) -> BodyReader<'a, R> {
+        BodyReader {
+            reader: self,
+            stream,
+            remaining: len,
+        }
+    }
+
+    /// Reads and decodes a chunked body. Chunk extensions and trailer fields
+    /// are skipped.
+    fn read_chunked_body<R: Read>(&mut self, stream: &mut R) -> Result<Vec<u8>, ParseError> {
+        let mut body = Vec::new();
+        loop {
+            let line = self.read_line(stream)?;
+            let size = line.split(|&b| b == b';').next().unwrap_or(&[]);
+            let size = std::str::from_utf8(size)
+                .ok()
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|s| usize::from_str_radix(s, 16).ok())
+                .ok_or(ParseError::Malformed)?;
+            if size == 0 {
+                break;
+            }
+            if size > MAX_BODY_LEN - body.len() {
+                return Err(ParseError::TooLarge);
+            }
+            while self.buf.len() < size {
+                self.fill(stream)?;
+            }
+            body.extend(self.buf.drain(..size));
+            if !self.read_line(stream)?.is_empty() {
+                return Err(ParseError::Malformed);
+            }
+        }
+        let mut trailers = 0;
+        while !self.read_line(stream)?.is_empty() {
+            trailers += 1;
+            if trailers > self.limits.max_headers {
+                return Err(ParseError::HeadTooLarge);
+            }
+        }
+        Ok(body)
+    }
+
+    /// Reads a line of a chunked body and returns it without the line
+    /// ending. Lines longer than the line limit are malformed.
+    fn read_line<R: Read>(&mut self, stream: &mut R) -> Result<Vec<u8>, ParseError> {
+        let mut scanned = 0;
+        loop {
+            if let Some(pos) = self.buf[scanned..].iter().position(|&b| b == b'\n') {
+                let mut line: Vec<u8> = self.buf.drain(..=scanned + pos).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                if line.len() > self.limits.max_line_len {
+                    return Err(ParseError::Malformed);
+                }
+                return Ok(line);
+            }
+            // Leaves room for the `\r` before the `\n`.
+            if self.buf.len() > self.limits.max_line_len + 1 {
+                return Err(ParseError::Malformed);
+            }
+            scanned = self.buf.len();
+            self.fill(stream)?;
+        }
+    }
+
+    /// Reads from `stream` until the head of a request is available and
+    /// parses it, leaving its body to be read.
+    pub fn read_head<R: Read>(&mut self, stream: &mut R) -> Result<Request, ParseError> {
+        let mut scanned = 0;
+        loop {
+            if let Some((head_len, end)) = find_head_end(&self.buf, scanned) {
+                let request = Request::parse(&self.buf[..head_len], &self.limits);
+                self.buf.drain(..end);
+                return request.map_err(|e| match e {
+                    HeadError::Invalid(e) => e,
+                    HeadError::BadHeader(head) => {
+                        self.malformed = Some(*head);
+                        ParseError::Malformed
+                    }
+                });
+            }
+            if self.buf.len() >= self.limits.max_head_len {
+                return Err(ParseError::HeadTooLarge);
+            }
+            // The terminator may straddle the previous read.
+            scanned = self.buf.len().saturating_sub(3);
+            self.fill(stream)?;
+        }
+    }
+
+    /// Returns the head of the last request rejected as
+    /// [`Malformed`](ParseError::Malformed) for one of its header lines, as
+    /// far as it was parsed.
+    pub fn take_malformed(&mut self) -> Option<MalformedHead> {
+        self.malformed.take()
+    }
+
+    /// Returns the bytes received after the last request, which belong to the
+    /// protocol the connection switches to after an upgrade.
+    pub fn take_buffered(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+
+    /// Appends the next chunk of data from `stream` to the buffer.
+    fn fill<R: Read>(&mut self, stream: &mut R) -> Result<(), ParseError> {
+        let mut chunk = [0u8; 1024];
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(ParseError::Closed);
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+}
+
+/// Reads a request body of known length from the bytes received after the
+/// head, then from the stream.
+pub struct BodyReader<'a, R: Read> {
+    reader: &'a mut RequestReader,
+    stream: &'a mut R,
+    remaining: u64,
+}
+
+impl<R: Read> BodyReader<'_, R> {
+    /// Returns the number of bytes of the body not read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl<R: Read> Read for BodyReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        if max == 0 {
+            return Ok(0);
+        }
+        let buffered = &mut self.reader.buf;
+        let n = if buffered.is_empty() {
+            self.stream.read(&mut buf[..max])?
+        } else {
+            let n = max.min(buffered.len());
+            buf[..n].copy_from_slice(&buffered[..n]);
+            buffered.drain(..n);
+            n
+        };
+        if n == 0 {
+            // The client closed the connection before sending the whole body.
+            return Err(http1::invalid_data());
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Returns whether `version` is a syntactically valid version token, such as
+/// `HTTP/1.1` or `HTTP/2`.
+fn is_version_token(version: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match version.strip_prefix("HTTP/") {
+        Some(number) => match number.split_once('.') {
+            Some((major, minor)) => is_number(major) && is_number(minor),
+            None => is_number(number),
+        },
+        None => false,
+    }
+}
+
+/// Returns whether the comma-separated header value contains `token`.
+pub fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+/// Searches for the empty line ending a request head, starting at `from`.
+///
+/// Returns the length of the head and the offset just after the empty line.
+fn find_head_end(buf: &[u8], from: usize) -> Option<(usize, usize)> {
+    for i in from..buf.len() {
+        if buf[i..].starts_with(b"\r\n\r\n") {
+            return Some((i, i + 4));
+        }
+        if buf[i..].starts_with(b"\n\n") {
+            return Some((i, i + 2));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader that returns the given chunks one `read` call at a time,
+    /// splitting chunks that do not fit in the caller's buffer.
+    struct ChunkedReader<'a> {
+        chunks: Vec<&'a [u8]>,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            let chunk = self.chunks[0];
+            let n = chunk.len().min(buf.len());
+            buf[..n].copy_from_slice(&chunk[..n]);
+            if n == chunk.len() {
+                self.chunks.remove(0);
+            } else {
+                self.chunks[0] = &chunk[n..];
+            }
+            Ok(n)
+        }
+    }
+
+    fn parse(chunks: &[&[u8]]) -> Result<Request, ParseError> {
+        let mut stream = ChunkedReader {
+            chunks: chunks.to_vec(),
+        };
+        RequestReader::default().read_request(&mut stream)
+    }
+
+    #[test]
+    fn test_simple_request() {
+        let req =
+            parse(&[b"GET /index.html HTTP/1.1\r\nHost: example\r\nAccept: */*\r\n\r\n"]).unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/index.html");
+        assert_eq!(req.version, "HTTP/1.1");
+        assert_eq!(req.headers.len(), 2);
+        assert_eq!(req.header("host"), Some("example"));
+        assert_eq!(req.header("ACCEPT"), Some("*/*"));
+        assert_eq!(req.header("Cookie"), None);
+    }
+
+    #[test]
+    fn test_query_params() {
+        let req = parse(&[b"GET /s?q=a+b%21&flag&q=&e=%zz&&x=1=2 HTTP/1.1\r\n\r\n"]).unwrap();
+        assert_eq!(req.path, "/s");
+        let params: Vec<_> = req.query_params().collect();
+        let param = |k: &str, v: Option<&str>| (String::from(k), v.map(String::from));
+        assert_eq!(
+            params,
+            [
+                param("q", Some("a b!")),
+                param("flag", None),
+                param("q", Some("")),
+                param("e", Some("%zz")),
+                param("x", Some("1=2")),
+            ]
+        );
+        assert_eq!(req.query_param("q").as_deref(), Some("a b!"));
+        assert_eq!(req.query_param("flag").as_deref(), Some(""));
+        assert_eq!(req.query_param("missing"), None);
+
+        let req = parse(&[b"GET /s HTTP/1.1\r\n\r\n"]).unwrap();
+        assert_eq!(req.query, None);
+        assert_eq!(req.query_params().count(), 0);
+    }
+
+    #[test]
+    fn test_cookies() {
+        let req = parse(&[
+            b"GET / HTTP/1.1\r\nCookie: a=1;  b = \"two\" ;empty=\r\n",
+            b"cookie: a=3\r\n\r\n",
+        ])
+        .unwrap();
+        let cookies: Vec<_> = req.cookies().collect();
+        assert_eq!(
+            cookies,
+            [("a", "1"), ("b", "two"), ("empty", ""), ("a", "3")]
+        );
+        assert_eq!(req.cookie("a"), Some("1"));
+        assert_eq!(req.cookie("empty"), Some(""));
+        assert_eq!(req.cookie("missing"), None);
+    }
+
+    #[test]
+    fn test_split_across_reads() {
+        let req = parse(&[b"GET / HT", b"TP/1.1\r\nHost: a", b"b\r\n\r", b"\n"]).unwrap();
+        assert_eq!(req.path, "/");
+        assert_eq!(req.header("Host"), Some("ab"));
+    }
+
+    #[test]
+    fn test_leftover_bytes() {
+        let mut stream = ChunkedReader {
+            chunks: vec![b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n"],
+        };
+        let mut reader = RequestReader::default();
+        assert_eq!(reader.read_request(&mut stream).unwrap().path, "/a");
+        assert_eq!(reader.read_request(&mut stream).unwrap().path, "/b");
+        assert!(matches!(
+            reader.read_request(&mut stream),
+            Err(ParseError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let req = parse(&[b"GET / HTTP/1.1\r\n\r\n"]).unwrap();
+        assert!(req.keep_alive());
+        let req = parse(&[b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n"]).unwrap();
+        assert!(!req.keep_alive());
+        let req = parse(&[b"GET / HTTP/1.0\r\n\r\n"]).unwrap();
+        assert!(!req.keep_alive());
+        let req = parse(&[b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"]).unwrap();
+        assert!(req.keep_alive());
+    }
+
+    #[test]
+    fn test_body() {
+        let req = parse(&[
+            b"POST /echo HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello",
+            b" world",
+        ])
+        .unwrap();
+        assert_eq!(req.body, b"hello world");
+
+        let req = parse(&[b"GET / HTTP/1.1\r\n\r\nextra"]).unwrap();
+        assert!(req.body.is_empty());
+    }
+
+    #[test]
+    fn test_bad_body() {
+        assert!(matches!(
+            parse(&[b"POST / HTTP/1.1\r\nContent-Length: 9999999999\r\n\r\n"]),
+            Err(ParseError::TooLarge)
+        ));
+        for len in ["-1", "+3", "", "0x3", "3 3"] {
+            let raw = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\nabc", len);
+            assert!(
+                matches!(parse(&[raw.as_bytes()]), Err(ParseError::Malformed)),
+                "{:?}",
+                len
+            );
+        }
+        assert!(matches!(
+            parse(&[b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"]),
+            Err(ParseError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let req = parse(&[
+            b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            b"lo\r\n6;name=value\r\n world\r",
+            b"\n0\r\nX-Checksum: 1\r\n\r\n",
+        ])
+        .unwrap();
+        assert_eq!(req.body, b"hello world");
+
+        // `Content-Length` is ignored, and the next request follows the body.
+        let mut stream = ChunkedReader {
+            chunks: vec![
+                b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\
+                           Transfer-Encoding: gzip, Chunked\r\n\r\n\
+                           A\r\n0123456789\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n",
+            ],
+        };
+        let mut reader = RequestReader::default();
+        assert_eq!(
+            reader.read_request(&mut stream).unwrap().body,
+            b"0123456789"
+        );
+        assert_eq!(reader.read_request(&mut stream).unwrap().path, "/b");
+    }
+
+    #[test]
+    fn test_bad_chunked_body() {
+        let chunked = |body: &[u8]| {
+            let head = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+            parse(&[head, body])
+        };
+        for body in [
+            &b"z\r\n"[..],
+            b"\r\n",
+            b"+5\r\nhello\r\n0\r\n\r\n",
+            b"5\r\nhelloXX\r\n0\r\n\r\n",
+            b"fffffffffffffffffffff\r\n",
+        ] {
+            assert!(matches!(chunked(body), Err(ParseError::Malformed)));
+        }
+        let long_line = vec![b'0'; HeadLimits::DEFAULT.max_line_len + 2];
+        assert!(matches!(chunked(&long_line), Err(ParseError::Malformed)));
+        assert!(matches!(chunked(b"5\r\nhel"), Err(ParseError::Closed)));
+
+        let big = format!("{:x}\r\n", MAX_BODY_LEN + 1);
+        assert!(matches!(chunked(big.as_bytes()), Err(ParseError::TooLarge)));
+        // The limit applies to the whole body, not to each chunk.
+        let mut body = Vec::new();
+        let chunk = vec![b'x'; MAX_BODY_LEN / 2];
+        for _ in 0..3 {
+            body.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            body.extend_from_slice(&chunk);
+            body.extend_from_slice(b"\r\n");
+        }
+        assert!(matches!(chunked(&body), Err(ParseError::TooLarge)));
+
+        assert!(matches!(
+            parse(&[b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(matches!(
+            parse(&[b"GET /\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse(&[b"GET / HTTP/1.1 extra\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse(&[b"GET / FTP/1.0\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse(&[b"GET / HTTP/1.1\r\nno colon\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse(&[b"GET / HTTP/1.1\r\nBad Name: x\r\n\r\n"]),
+            Err(ParseError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_malformed_head() {
+        let mut reader = RequestReader::default();
+        let raw = b"GET /a?b HTTP/1.1\r\nHost: x\r\nno colon\r\nAccept: */*\r\n\r\n";
+        assert!(matches!(
+            reader.read_head(&mut &raw[..]),
+            Err(ParseError::Malformed)
+        ));
+        let head = reader.take_malformed().unwrap();
+        assert_eq!(head.request.path, "/a");
+        assert_eq!(
+            head.request.headers,
+            [(String::from("Host"), String::from("x"))]
+        );
+        assert_eq!((head.line, head.offset), (3, 28));
+        assert_eq!(head.text, "no colon");
+        assert!(reader.take_malformed().is_none());
+
+        // Only a bad header line leaves a head behind.
+        assert!(reader.read_head(&mut &b"GET /\r\n\r\n"[..]).is_err());
+        assert!(reader.take_malformed().is_none());
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(
+            parse(&[b"GET / HTTP/1.0\r\n\r\n"]).unwrap().version,
+            "HTTP/1.0"
+        );
+        assert!(parse(&[b"GET / HTTP/1.0\r\n\r\n"]).unwrap().is_http10());
+        assert!(!parse(&[b"GET / HTTP/1.1\r\n\r\n"]).unwrap().is_http10());
+        for version in ["HTTP/0.9", "HTTP/1.2", "HTTP/2.0", "HTTP/2", "HTTP/10.10"] {
+            let head = format!("GET / {}\r\n\r\n", version);
+            assert!(
+                matches!(
+                    parse(&[head.as_bytes()]),
+                    Err(ParseError::UnsupportedVersion)
+                ),
+                "{}",
+                version
+            );
+        }
+        for version in [
+            "HTTP/",
+            "HTTP/1.",
+            "HTTP/.1",
+            "HTTP/1.1.1",
+            "HTTP/x.y",
+            "http/1.1",
+        ] {
+            let head = format!("GET / {}\r\n\r\n", version);
+            assert!(
+                matches!(parse(&[head.as_bytes()]), Err(ParseError::Malformed)),
+                "{}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_large_head() {
+        let cookie = "x".repeat(7 * 1024);
+        let head = format!("GET / HTTP/1.1\r\nCookie: {}\r\nHost: a\r\n\r\n", cookie);
+        let req = parse(&[head.as_bytes()]).unwrap();
+        assert_eq!(req.header("Cookie"), Some(cookie.as_str()));
+        assert_eq!(req.header("Host"), Some("a"));
+
+        // A single line over the line limit.
+        let cookie = "x".repeat(HeadLimits::DEFAULT.max_line_len);
+        let head = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", cookie);
+        assert!(matches!(
+            parse(&[head.as_bytes()]),
+            Err(ParseError::HeadTooLarge)
+        ));
+
+        // Lines within the line limit, but over the total limit.
+        let cookie = "x".repeat(7 * 1024);
+        let head = format!(
+            "GET / HTTP/1.1\r\nA: {0}\r\nB: {0}\r\nC: {0}\r\n\r\n",
+            cookie
+        );
+        assert!(matches!(
+            parse(&[head.as_bytes()]),
+            Err(ParseError::HeadTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_header_count() {
+        let head = |count: usize| {
+            let mut head = String::from("GET / HTTP/1.1\r\n");
+            for i in 0..count {
+                head += &format!("X-Header-{}: {}\r\n", i, i);
+            }
+            head + "\r\n"
+        };
+        let max = HeadLimits::DEFAULT.max_headers;
+        assert_eq!(parse(&[head(max).as_bytes()]).unwrap().headers.len(), max);
+        assert!(matches!(
+            parse(&[head(max + 1).as_bytes()]),
+            Err(ParseError::HeadTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_custom_limits() {
+        let limits = HeadLimits {
+            max_headers: 2,
+            max_line_len: 32 * 1024,
+            max_head_len: 64 * 1024,
+        };
+        let cookie = "x".repeat(20 * 1024);
+        let head = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", cookie);
+        let mut stream = ChunkedReader {
+            chunks: vec![head.as_bytes()],
+        };
+        let req = RequestReader::with_limits(limits)
+            .read_request(&mut stream)
+            .unwrap();
+        assert_eq!(req.header("Cookie"), Some(cookie.as_str()));
+
+        let mut stream = ChunkedReader {
+            chunks: vec![b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n"],
+        };
+        assert!(matches!(
+            RequestReader::with_limits(limits).read_request(&mut stream),
+            Err(ParseError::HeadTooLarge)
+        ));
+    }
+
+    /// Feeds random soups of line breaks, separators and letters to the
+    /// reader, split at random points. Any result is fine, as long as the
+    /// reader neither panics nor loops forever.
+    #[test]
+    fn test_fuzz() {
+        const ALPHABET: &[u8] = b"\r\n\r\n\r\n: /GET HTTP/1.1\0\xff";
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move |bound: usize| {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let limits = HeadLimits {
+            max_headers: 4,
+            max_line_len: 16,
+            max_head_len: 64,
+        };
+
+        for round in 0..5000 {
+            let len = next(256);
+            let soup: Vec<u8> = (0..len).map(|_| ALPHABET[next(ALPHABET.len())]).collect();
+            let mut chunks = Vec::new();
+            let mut rest = &soup[..];
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(1 + next(rest.len()));
+                chunks.push(chunk);
+                rest = tail;
+            }
+
+            let mut stream = ChunkedReader { chunks };
+            let mut reader = if round % 2 == 0 {
+                RequestReader::default()
+            } else {
+                RequestReader::with_limits(limits)
+            };
+            // At most one request per byte can be read before the stream ends.
+            for _ in 0..=len {
+                if reader.read_request(&mut stream).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_incomplete() {
+        assert!(matches!(
+            parse(&[b"GET / HTTP/1.1\r\n"]),
+            Err(ParseError::Closed)
+        ));
+        assert!(matches!(parse(&[]), Err(ParseError::Closed)));
+    }
+}
@@ -0,0 +1,531 @@
+//! HTTP response generation.
+
+use std::boxed::Box;
+use std::io::{self, prelude::*};
+use std::net::TcpStream;
+use std::ops::Range;
+use std::string::String;
+use std::sync::Arc;
+use std::vec::Vec;
+
+use http1::ChunkedWriter;
+
+use crate::cookie::SetCookie;
+use crate::error_pages;
+use crate::listing::html_escape;
+
+/// HTTP status codes sent by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    SwitchingProtocols,
+    Ok,
+    Created,
+    NoContent,
+    PartialContent,
+    MovedPermanently,
+    NotModified,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    Conflict,
+    PayloadTooLarge,
+    RangeNotSatisfiable,
+    UpgradeRequired,
+    TooManyRequests,
+    RequestHeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    HttpVersionNotSupported,
+    /// Any other status, such as one relayed from an upstream server.
+    Other(u16),
+}
+
+impl Status {
+    /// Returns the numeric status code.
+    pub const fn code(self) -> u16 {
+        match self {
+            Status::SwitchingProtocols => 101,
+            Status::Ok => 200,
+            Status::Created => 201,
+            Status::NoContent => 204,
+            Status::PartialContent => 206,
+            Status::MovedPermanently => 301,
+            Status::NotModified => 304,
+            Status::BadRequest => 400,
+            Status::Unauthorized => 401,
+            Status::Forbidden => 403,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed => 405,
+            Status::NotAcceptable => 406,
+            Status::Conflict => 409,
+            Status::PayloadTooLarge => 413,
+            Status::RangeNotSatisfiable => 416,
+            Status::UpgradeRequired => 426,
+            Status::TooManyRequests => 429,
+            Status::RequestHeaderFieldsTooLarge => 431,
+            Status::InternalServerError => 500,
+            Status::NotImplemented => 501,
+            Status::BadGateway => 502,
+            Status::ServiceUnavailable => 503,
+            Status::HttpVersionNotSupported => 505,
+            Status::Other(code) => code,
+        }
+    }
+
+    /// Returns the status with the given numeric code.
+    pub fn from_code(code: u16) -> Self {
+        const KNOWN: &[Status] = &[
+            Status::SwitchingProtocols,
+            Status::Ok,
+            Status::Created,
+            Status::NoContent,
+            Status::PartialContent,
+            Status::MovedPermanently,
+            Status::NotModified,
+            Status::BadRequest,
+            Status::Unauthorized,
+            Status::Forbidden,
+            Status::NotFound,
+            Status::MethodNotAllowed,
+            Status::NotAcceptable,
+            Status::Conflict,
+            Status::PayloadTooLarge,
+            Status::RangeNotSatisfiable,
+            Status::UpgradeRequired,
+            Status::TooManyRequests,
+            Status::RequestHeaderFieldsTooLarge,
+            Status::InternalServerError,
+            Status::NotImplemented,
+            Status::BadGateway,
+            Status::ServiceUnavailable,
+            Status::HttpVersionNotSupported,
+        ];
+        let known = KNOWN.iter().find(|s| s.code() == code);
+        known.copied().unwrap_or(Status::Other(code))
+    }
+
+    /// Returns the reason phrase sent after the status code.
+    ///
+    /// The phrase of [`Status::Other`] is empty, which is allowed (RFC 9112,
+    /// section 4) since clients must not depend on it.
+    pub const fn reason(self) -> &'static str {
+        match self {
+            Status::SwitchingProtocols => "Switching Protocols",
+            Status::Ok => "OK",
+            Status::Created => "Created",
+            Status::NoContent => "No Content",
+            Status::PartialContent => "Partial Content",
+            Status::MovedPermanently => "Moved Permanently",
+            Status::NotModified => "Not Modified",
+            Status::BadRequest => "Bad Request",
+            Status::Unauthorized => "Unauthorized",
+            Status::Forbidden => "Forbidden",
+            Status::NotFound => "Not Found",
+            Status::MethodNotAllowed => "Method Not Allowed",
+            Status::NotAcceptable => "Not Acceptable",
+            Status::Conflict => "Conflict",
+            Status::PayloadTooLarge => "Payload Too Large",
+            Status::RangeNotSatisfiable => "Range Not Satisfiable",
+            Status::UpgradeRequired => "Upgrade Required",
+            Status::TooManyRequests => "Too Many Requests",
+            Status::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Status::InternalServerError => "Internal Server Error",
+            Status::NotImplemented => "Not Implemented",
+            Status::BadGateway => "Bad Gateway",
+            Status::ServiceUnavailable => "Service Unavailable",
+            Status::HttpVersionNotSupported => "HTTP Version Not Supported",
+            Status::Other(_) => "",
+        }
+    }
+}
+
+/// Renders the status line and `Content-Type` header of a common response.
+macro_rules! prefix {
+    ($status:ident, $code:literal, $reason:literal, $content_type:literal) => {
+        (
+            Status::$status,
+            $content_type,
+            concat!(
+                "HTTP/1.1 ",
+                $code,
+                " ",
+                $reason,
+                "\r\nContent-Type: ",
+                $content_type,
+                "\r\n"
+            ),
+        )
+    };
+}
+
+/// The status lines and `Content-Type` headers of the most common responses,
+/// rendered ahead of time.
+const PREFIXES: &[(Status, &str, &str)] = &[
+    prefix!(Ok, 200, "OK", "text/html; charset=utf-8"),
+    prefix!(Ok, 200, "OK", "text/css; charset=utf-8"),
+    prefix!(Ok, 200, "OK", "text/javascript; charset=utf-8"),
+    prefix!(Ok, 200, "OK", "text/plain; charset=utf-8"),
+    prefix!(Ok, 200, "OK", "text/plain"),
+    prefix!(Ok, 200, "OK", "application/json"),
+    prefix!(Ok, 200, "OK", "image/png"),
+    prefix!(NotFound, 404, "Not Found", "text/html; charset=utf-8"),
+];
+
+/// The size of the buffer the head of a response is put together in, enough
+/// for most heads to be sent with a single write.
+const HEAD_BUF_LEN: usize = 512;
+
+/// Puts the head of a response together on the stack, passing it on to the
+/// stream when the buffer is full.
+struct HeadBuf<'a, W: Write> {
+    stream: &'a mut W,
+    buf: [u8; HEAD_BUF_LEN],
+    len: usize,
+}
+
+impl<'a, W: Write> HeadBuf<'a, W> {
+    fn new(stream: &'a mut W) -> Self {
+        Self {
+            stream,
+            buf: [0; HEAD_BUF_LEN],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.len + bytes.len() > HEAD_BUF_LEN {
+            self.flush()?;
+            if bytes.len() > HEAD_BUF_LEN {
+                return self.stream.write_all(bytes);
+            }
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn push_decimal(&mut self, mut n: u64) -> io::Result<()> {
+        let mut digits = [0; 20];
+        let mut start = digits.len();
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push(&digits[start..])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.buf[..self.len])?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Generates a body while it is being sent.
+pub type BodyWriter = Box<dyn FnOnce(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// Takes over a connection after a `101 Switching Protocols` response, given
+/// the bytes the client sent after the request.
+pub type UpgradeHandler = Box<dyn FnOnce(&mut TcpStream, Vec<u8>) -> io::Result<()> + Send>;
+
+/// The body of a response.
+pub enum Body {
+    /// A body whose length is known up front, sent with `Content-Length`.
+    Full(Vec<u8>),
+    /// The bytes `range` of contents shared with the file cache, sent like
+    /// [`Body::Full`] without being copied.
+    Shared(Arc<Vec<u8>>, Range<usize>),
+    /// A body of the given length generated while it is being sent, such as
+    /// a file too large to be kept in memory, sent with `Content-Length`.
+    /// The writer fails rather than write fewer bytes.
+    Sized(u64, BodyWriter),
+    /// A body of unknown length, sent with `Transfer-Encoding: chunked`.
+    Chunked(BodyWriter),
+    /// A body of unknown length for HTTP/1.0 clients, which do not understand
+    /// chunked encoding. It ends when the connection is closed.
+    UntilClose(BodyWriter),
+    /// No body; the connection switches to another protocol, spoken by the
+    /// handler.
+    Upgrade(UpgradeHandler),
+}
+
+impl Body {
+    /// Returns the body, if it is held in memory.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::Full(body) => Some(body),
+            Body::Shared(content, range) => Some(&content[range.clone()]),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the body, if it is known up front.
+    fn content_length(&self) -> Option<u64> {
+        match self {
+            Body::Full(body) => Some(body.len() as u64),
+            Body::Shared(_, range) => Some(range.len() as u64),
+            Body::Sized(len, _) => Some(*len),
+            _ => None,
+        }
+    }
+}
+
+/// An HTTP response waiting to be sent.
+///
+/// Framing headers (`Content-Length` or `Transfer-Encoding`) and `Connection`
+/// are added when the response is written, since they depend on the body and
+/// on the state of the connection.
+pub struct Response {
+    pub status: Status,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    /// Creates a response with the given status, content type and body.
+    pub fn new(status: Status, content_type: &str, body: Vec<u8>) -> Self {
+        Self::with_body(status, content_type, Body::Full(body))
+    }
+
+    /// Creates a response with the given status, content type and body of
+    /// any kind.
+    pub fn with_body(status: Status, content_type: &str, body: Body) -> Self {
+        Self {
+            status,
+            headers: vec![(String::from("Content-Type"), String::from(content_type))],
+            body,
+        }
+    }
+
+    /// Creates a response whose body is produced by `writer` as it is sent,
+    /// using chunked transfer encoding.
+    pub fn chunked<F>(status: Status, content_type: &str, writer: F) -> Self
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+    {
+        Self {
+            status,
+            headers: vec![(String::from("Content-Type"), String::from(content_type))],
+            body: Body::Chunked(Box::new(writer)),
+        }
+    }
+
+    /// Creates a `101 Switching Protocols` response to the protocol named by
+    /// `protocol`, after which `handler` takes over the connection.
+    pub fn upgrade<F>(protocol: &str, handler: F) -> Self
+    where
+        F: FnOnce(&mut TcpStream, Vec<u8>) -> io::Result<()> + Send + 'static,
+    {
+        Self {
+            status: Status::SwitchingProtocols,
+            headers: vec![(String::from("Upgrade"), String::from(protocol))],
+            body: Body::Upgrade(Box::new(handler)),
+        }
+    }
+
+    /// Creates a response without a body, such as `304 Not Modified`.
+    pub fn empty(status: Status) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Full(Vec::new()),
+        }
+    }
+
+    /// Creates a response with the error page for `status`, or a small HTML
+    /// page describing it if there is none.
+    pub fn error(status: Status) -> Self {
+        if let Some(page) = error_pages::page(status) {
+            return Self::new(status, "text/html; charset=utf-8", page);
+        }
+        let body = format!(
+            "<html><head><title>{0} {1}</title></head>\
+             <body><h1>{0} {1}</h1></body></html>\n",
+            status.code(),
+            status.reason()
+        );
+        Self::new(status, "text/html; charset=utf-8", body.into_bytes())
+    }
+
+    /// Prepares the response for an HTTP/1.0 client, which cannot receive a
+    /// chunked body. Such a body is sent unframed instead, and the connection
+    /// has to be closed after it.
+    pub fn for_http10(mut self) -> Self {
+        if let Body::Chunked(writer) = self.body {
+            self.body = Body::UntilClose(writer);
+        }
+        self
+    }
+
+    /// Returns whether the connection must be closed after the response,
+    /// because closing it is what ends the body.
+    pub fn needs_close(&self) -> bool {
+        matches!(self.body, Body::UntilClose(_))
+    }
+
+    /// Creates a redirect to `location`, which must already be escaped for
+    /// use in a header.
+    ///
+    /// The body links to the new location for clients that do not follow
+    /// redirects.
+    pub fn redirect(status: Status, location: &str) -> Self {
+        let body = format!(
+            "<html><head><title>{0} {1}</title></head>\
+             <body><h1>{0} {1}</h1><p>The document has moved \
+             <a href=\"{2}\">here</a>.</p></body></html>\n",
+            status.code(),
+            status.reason(),
+            html_escape(location)
+        );
+        Self::new(status, "text/html; charset=utf-8", body.into_bytes())
+            .with_header("Location", location)
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    /// Adds a `Set-Cookie` header for `cookie` to the response.
+    pub fn set_cookie(self, cookie: &SetCookie) -> Self {
+        self.with_header("Set-Cookie", &cookie.header_value())
+    }
+
+    /// Writes the status line and headers to `stream`.
+    ///
+    /// The status line always says `HTTP/1.1`, the version the server
+    /// implements, whatever the version of the request (RFC 9110, section
+    /// 2.5). Responses to HTTP/1.0 clients only use features they understand.
+    ///
+    /// `Content-Length` always describes the body, even if it is not sent
+    /// afterwards (as for `HEAD` requests).
+    pub fn write_head<W: Write>(&self, stream: &mut W, keep_alive: bool) -> io::Result<()> {
+        let mut head = HeadBuf::new(stream);
+        let mut headers = self.headers.as_slice();
+        let prefix = match headers.first() {
+            Some((name, value)) if name == "Content-Type" => PREFIXES
+                .iter()
+                .find(|&&(status, content_type, _)| status == self.status && content_type == value),
+            _ => None,
+        };
+        match prefix {
+            Some((_, _, prefix)) => {
+                head.push(prefix.as_bytes())?;
+                headers = &headers[1..];
+            }
+            None => {
+                head.push(b"HTTP/1.1 ")?;
+                head.push_decimal(self.status.code() as u64)?;
+                head.push(b" ")?;
+                head.push(self.status.reason().as_bytes())?;
+                head.push(b"\r\n")?;
+            }
+        }
+        for (name, value) in headers {
+            head.push(name.as_bytes())?;
+            head.push(b": ")?;
+            head.push(value.as_bytes())?;
+            head.push(b"\r\n")?;
+        }
+        match &self.body {
+            // Never have a body, so the connection needs no framing.
+            _ if matches!(self.status.code(), 204 | 304) => {}
+            Body::Chunked(_) => head.push(b"Transfer-Encoding: chunked\r\n")?,
+            Body::UntilClose(_) | Body::Upgrade(_) => {}
+            body => {
+                head.push(b"Content-Length: ")?;
+                head.push_decimal(body.content_length().unwrap_or(0))?;
+                head.push(b"\r\n")?;
+            }
+        }
+        let connection: &[u8] = match &self.body {
+            Body::Upgrade(_) => b"Connection: Upgrade\r\n\r\n",
+            _ if keep_alive => b"Connection: keep-alive\r\n\r\n",
+            _ => b"Connection: close\r\n\r\n",
+        };
+        head.push(connection)?;
+        head.flush()?;
+        stream.flush()
+    }
+
+    /// Writes the status line, headers and body to `stream`.
+    pub fn write_to<W: Write>(self, stream: &mut W, keep_alive: bool) -> io::Result<()> {
+        self.write_head(stream, keep_alive)?;
+        match self.body {
+            Body::Full(body) => stream.write_all(&body)?,
+            Body::Shared(content, range) => stream.write_all(&content[range])?,
+            Body::Chunked(writer) => {
+                let mut chunked = ChunkedWriter::new(stream);
+                writer(&mut chunked)?;
+                chunked.finish()?;
+            }
+            Body::Sized(_, writer) | Body::UntilClose(writer) => writer(stream)?,
+            // Run by the caller, which has the connection.
+            Body::Upgrade(_) => {}
+        }
+        stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(response: &Response, keep_alive: bool) -> String {
+        let mut out = Vec::new();
+        response.write_head(&mut out, keep_alive).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_prefixes() {
+        for &(status, content_type, prefix) in PREFIXES {
+            let expected = format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n",
+                status.code(),
+                status.reason(),
+                content_type
+            );
+            assert_eq!(prefix, expected);
+        }
+    }
+
+    #[test]
+    fn test_write_head() {
+        let page = Response::new(Status::Ok, "text/html; charset=utf-8", vec![b'x'; 1234]);
+        assert_eq!(
+            head(&page, true),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: 1234\r\nConnection: keep-alive\r\n\r\n"
+        );
+
+        let other = Response::new(Status::Other(299), "text/x-custom", Vec::new())
+            .with_header("ETag", "\"abc\"");
+        assert_eq!(
+            head(&other, false),
+            "HTTP/1.1 299 \r\nContent-Type: text/x-custom\r\nETag: \"abc\"\r\n\
+             Content-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+
+        // Heads longer than the buffer are written in parts.
+        let long = "v".repeat(2 * HEAD_BUF_LEN);
+        let response = Response::empty(Status::NotModified).with_header("X-Long", &long);
+        assert_eq!(
+            head(&response, true),
+            format!(
+                "HTTP/1.1 304 Not Modified\r\nX-Long: {}\r\nConnection: keep-alive\r\n\r\n",
+                long
+            )
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Dispatching requests to handlers by method and path prefix.
+
+use std::boxed::Box;
+use std::io::Read;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::auth::{self, Credentials};
+use crate::request::Request;
+use crate::response::{Response, Status};
+
+/// Handles a request, given the part of its path after the matched prefix.
+pub type Handler = Box<dyn Fn(&Request, &str) -> Response + Send + Sync>;
+
+/// Handles a request like a [`Handler`], reading its body from the given
+/// reader.
+pub type BodyHandler = Box<dyn Fn(&Request, &str, &mut dyn Read) -> Response + Send + Sync>;
+
+/// How a route handles its requests.
+enum RouteHandler {
+    /// With the body already read into [`Request::body`].
+    Request(Handler),
+    /// Reading the body itself, as it arrives.
+    Body(BodyHandler),
+}
+
+struct Route {
+    method: &'static str,
+    prefix: &'static str,
+    /// The credentials the route requires, if any.
+    auth: Option<&'static Credentials>,
+    handler: RouteHandler,
+}
+
+/// Maps methods and path prefixes to handlers.
+///
+/// The route with the longest prefix matching the path wins. A prefix ending
+/// with `/` matches every path below it, while other prefixes match the path
+/// itself and the paths below it, so `/greet` matches `/greet/x` but not
+/// `/greeting`. `HEAD` requests go to `GET` handlers, and routes for the
+/// method `*` take requests of any method. `OPTIONS` requests without a
+/// route are answered with the methods of the path. Paths without a route go
+/// to the fallback handler. Routes added with [`Router::route_with_auth`]
+/// answer requests without the right credentials with `401 Unauthorized`.
+/// The handlers of routes added with [`Router::route_body`] read the request
+/// body themselves.
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Handler,
+}
+
+/// Returns the value of the `Allow` header listing the methods of `routes`.
+fn allow(routes: &[&Route]) -> String {
+    let mut allow = String::new();
+    for route in routes.iter() {
+        if !allow.is_empty() {
+            allow += ", ";
+        }
+        allow += route.method;
+        if route.method == "GET" {
+            allow += ", HEAD";
+        }
+    }
+    allow
+}
+
+/// Returns the rest of `path` after `prefix`, if `prefix` matches it.
+fn suffix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix)?;
+    if prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+impl Router {
+    /// Creates a router sending every request to `fallback`.
+    pub fn new<F>(fallback: F) -> Self
+    where
+        F: Fn(&Request, &str) -> Response + Send + Sync + 'static,
+    {
+        Self {
+            routes: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Adds a route for `method` requests with paths under `prefix`.
+    pub fn route<F>(self, method: &'static str, prefix: &'static str, handler: F) -> Self
+    where
+        F: Fn(&Request, &str) -> Response + Send + Sync + 'static,
+    {
+        self.route_with_auth(method, prefix, None, handler)
+    }
+
+    /// Adds a route like [`Router::route`], which requires Basic
+    /// authentication with `auth` if given.
+    pub fn route_with_auth<F>(
+        mut self,
+        method: &'static str,
+        prefix: &'static str,
+        auth: Option<&'static Credentials>,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(&Request, &str) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            prefix,
+            auth,
+            handler: RouteHandler::Request(Box::new(handler)),
+        });
+        self
+    }
+
+    /// Adds a route like [`Router::route`] whose handler reads the request
+    /// body itself, so that it need not be held in memory.
+    pub fn route_body<F>(mut self, method: &'static str, prefix: &'static str, handler: F) -> Self
+    where
+        F: Fn(&Request, &str, &mut dyn Read) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            prefix,
+            auth: None,
+            handler: RouteHandler::Body(Box::new(handler)),
+        });
+        self
+    }
+
+    /// Finds the routes with the longest prefix matching `path`, with the
+    /// rest of the path.
+    fn matches<'a>(&'a self, path: &'a str) -> (Vec<&'a Route>, &'a str) {
+        let mut best: Vec<&Route> = Vec::new();
+        let mut rest = path;
+        for route in self.routes.iter() {
+            let Some(suffix) = suffix(path, route.prefix) else {
+                continue;
+            };
+            match best.first() {
+                Some(b) if b.prefix.len() > route.prefix.len() => continue,
+                Some(b) if b.prefix.len() == route.prefix.len() => {}
+                _ => best.clear(),
+            }
+            best.push(route);
+            rest = suffix;
+        }
+        (best, rest)
+    }
+
+    /// Finds the route of `request` among the routes matching its path,
+    /// which are returned with the rest of the path.
+    fn find<'a>(&'a self, request: &'a Request) -> (Option<&'a Route>, Vec<&'a Route>, &'a str) {
+        let (routes, rest) = self.matches(&request.path);
+        let method = match request.method.as_str() {
+            "HEAD" => "GET",
+            method => method,
+        };
+        let route = routes.iter().find(|r| r.method == method);
+        let route = route.or_else(|| routes.iter().find(|r| r.method == "*"));
+        (route.copied(), routes, rest)
+    }
+
+    /// Returns whether the handler of the route of `request` reads its body
+    /// itself, which must then be left unread for [`Router::dispatch`].
+    pub fn reads_body(&self, request: &Request) -> bool {
+        let (route, _, _) = self.find(request);
+        route.is_some_and(|route| matches!(route.handler, RouteHandler::Body(_)))
+    }
+
+    /// Passes `request` to the handler of its route, with `body` to read its
+    /// body from if the handler reads it itself.
+    ///
+    /// Requests whose path has a route, but not for their method, are
+    /// answered with `405 Method Not Allowed`, or with `204 No Content` for
+    /// `OPTIONS`.
+    pub fn dispatch(&self, request: &Request, body: &mut dyn Read) -> Response {
+        let (route, routes, rest) = self.find(request);
+        if routes.is_empty() {
+            return (self.fallback)(request, &request.path);
+        }
+        match route {
+            Some(route) => match (&route.handler, route.auth) {
+                (_, Some(credentials)) if !credentials.verify(request.header("Authorization")) => {
+                    auth::challenge()
+                }
+                (RouteHandler::Request(handler), _) => handler(request, rest),
+                (RouteHandler::Body(handler), _) => handler(request, rest, body),
+            },
+            None if request.method == "OPTIONS" => Response::empty(Status::NoContent)
+                .with_header("Allow", &format!("{}, OPTIONS", allow(&routes))),
+            None => Response::error(Status::MethodNotAllowed).with_header("Allow", &allow(&routes)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+    use std::io;
+
+    fn request(method: &str, path: &str) -> Request {
+        let raw = format!("{} {} HTTP/1.1\r\nHost: test\r\n\r\n", method, path);
+        RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap()
+    }
+
+    fn text(name: &'static str) -> impl Fn(&Request, &str) -> Response + Send + Sync {
+        move |_, rest| {
+            let body = format!("{} {}", name, rest);
+            Response::new(Status::Ok, "text/plain", body.into_bytes())
+        }
+    }
+
+    fn router() -> Router {
+        Router::new(text("fallback"))
+            .route("GET", "/api", text("api"))
+            .route("GET", "/api/v2/", text("v2"))
+            .route("POST", "/api/v2/", text("v2-post"))
+            .route("GET", "/greet", text("greet"))
+            .route("*", "/any", text("any"))
+            .route("GET", "/any/get", text("any-get"))
+    }
+
+    fn body(response: Response) -> String {
+        match response.body {
+            crate::response::Body::Full(body) => String::from_utf8(body).unwrap(),
+            _ => panic!("unexpected streamed body"),
+        }
+    }
+
+    #[test]
+    fn test_suffix() {
+        assert_eq!(suffix("/greet", "/greet"), Some(""));
+        assert_eq!(suffix("/greet/x", "/greet"), Some("/x"));
+        assert_eq!(suffix("/greeting", "/greet"), None);
+        assert_eq!(suffix("/static/a/b.css", "/static/"), Some("a/b.css"));
+        assert_eq!(suffix("/static", "/static/"), None);
+        assert_eq!(suffix("/anything", "/"), Some("anything"));
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let router = router();
+        let get = |path: &str| body(router.dispatch(&request("GET", path), &mut io::empty()));
+        assert_eq!(get("/api"), "api ");
+        assert_eq!(get("/api/users"), "api /users");
+        assert_eq!(get("/api/v2/users/1"), "v2 users/1");
+        assert_eq!(get("/api/v2"), "api /v2");
+        assert_eq!(get("/greet"), "greet ");
+        assert_eq!(get("/greeting"), "fallback /greeting");
+        assert_eq!(get("/"), "fallback /");
+    }
+
+    #[test]
+    fn test_methods() {
+        let router = router();
+        let dispatch =
+            |method: &str, path: &str| router.dispatch(&request(method, path), &mut io::empty());
+        assert_eq!(body(dispatch("POST", "/api/v2/x")), "v2-post x");
+        assert_eq!(body(dispatch("HEAD", "/greet")), "greet ");
+
+        let response = dispatch("DELETE", "/api/v2/x");
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        let allow = response.headers.iter().find(|(n, _)| *n == "Allow");
+        assert_eq!(allow.unwrap().1, "GET, HEAD, POST");
+
+        let response = dispatch("POST", "/api/users");
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(body(dispatch("POST", "/other")), "fallback /other");
+
+        assert_eq!(body(dispatch("DELETE", "/any/x")), "any /x");
+        assert_eq!(body(dispatch("HEAD", "/any")), "any ");
+        assert_eq!(body(dispatch("GET", "/any/get")), "any-get ");
+        assert_eq!(dispatch("PUT", "/any/get").status, Status::MethodNotAllowed);
+    }
+
+    #[test]
+    fn test_options() {
+        let router = router();
+        let options = |path: &str| router.dispatch(&request("OPTIONS", path), &mut io::empty());
+        let response = options("/api/v2/x");
+        assert_eq!(response.status, Status::NoContent);
+        let allow = response.headers.iter().find(|(n, _)| *n == "Allow");
+        assert_eq!(allow.unwrap().1, "GET, HEAD, POST, OPTIONS");
+        // Routes for any method take `OPTIONS` too.
+        assert_eq!(body(options("/any/x")), "any /x");
+        assert_eq!(body(options("/other")), "fallback /other");
+    }
+
+    #[test]
+    fn test_auth() {
+        static ADMIN: Credentials = Credentials {
+            user: "admin",
+            password: "secret",
+        };
+        let router = Router::new(text("fallback"))
+            .route_with_auth("GET", "/private", Some(&ADMIN), text("private"))
+            .route_with_auth("GET", "/open", None, text("open"));
+        let get = |authorization: Option<&str>| {
+            let mut request = request("GET", "/private/x");
+            if let Some(value) = authorization {
+                let header = (String::from("Authorization"), String::from(value));
+                request.headers.push(header);
+            }
+            router.dispatch(&request, &mut io::empty())
+        };
+
+        assert_eq!(get(None).status, Status::Unauthorized);
+        assert_eq!(
+            get(Some("Basic YWRtaW46d3Jvbmc=")).status,
+            Status::Unauthorized
+        );
+        assert_eq!(body(get(Some("Basic YWRtaW46c2VjcmV0"))), "private /x");
+        assert_eq!(
+            body(router.dispatch(&request("GET", "/open"), &mut io::empty())),
+            "open "
+        );
+        // Methods are checked before credentials.
+        let response = router.dispatch(&request("POST", "/private"), &mut io::empty());
+        assert_eq!(response.status, Status::MethodNotAllowed);
+    }
+
+    #[test]
+    fn test_body_route() {
+        let router = Router::new(text("fallback"))
+            .route("GET", "/upload", text("form"))
+            .route_body("POST", "/upload", |_, rest, body| {
+                let mut content = String::new();
+                body.read_to_string(&mut content).unwrap();
+                let body = format!("{} {}", rest, content);
+                Response::new(Status::Ok, "text/plain", body.into_bytes())
+            });
+        assert!(router.reads_body(&request("POST", "/upload/x")));
+        assert!(!router.reads_body(&request("GET", "/upload")));
+        assert!(!router.reads_body(&request("POST", "/other")));
+        let response = router.dispatch(&request("POST", "/upload/x"), &mut &b"data"[..]);
+        assert_eq!(body(response), "/x data");
+    }
+}
@@ -0,0 +1,114 @@
+//! SHA-1, as needed for the WebSocket handshake.
+//!
+//! SHA-1 is broken as a cryptographic hash; it must not be used for anything
+//! that relies on collision resistance.
+
+/// Computes the SHA-1 digest of `data`.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+
+    // Pad with a single 1 bit, zeros, and the length in bits, to a multiple
+    // of 64 bytes.
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    let padded_len = (data.len() + 8) / 64 * 64 + 64;
+    let block_at = |offset: usize, i: usize| -> u8 {
+        let pos = offset + i;
+        if pos < data.len() {
+            data[pos]
+        } else if pos == data.len() {
+            0x80
+        } else if pos >= padded_len - 8 {
+            (bit_len >> ((padded_len - 1 - pos) * 8)) as u8
+        } else {
+            0
+        }
+    };
+
+    for offset in (0..padded_len).step_by(64) {
+        let mut w = [0u32; 80];
+        for (t, word) in w.iter_mut().take(16).enumerate() {
+            *word = u32::from_be_bytes([
+                block_at(offset, t * 4),
+                block_at(offset, t * 4 + 1),
+                block_at(offset, t * 4 + 2),
+                block_at(offset, t * 4 + 3),
+            ]);
+        }
+        for t in 16..80 {
+            w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (t, &word) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(digest: [u8; 20]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        // Lengths around the padding boundary.
+        assert_eq!(
+            hex(sha1(&[b'a'; 55])),
+            "c1c8bbdc22796e28c0e15163d20899b65621d65a"
+        );
+        assert_eq!(
+            hex(sha1(&[b'a'; 64])),
+            "0098ba824b5c16427bd7a1122a5a442a25ec644d"
+        );
+        assert_eq!(
+            hex(sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Stopping the server on request.
+//!
+//! `GET /shutdown` makes the accept loop stop taking new connections. Like
+//! the other administrative endpoints, it needs the admin credentials.
+
+use crate::admin;
+use crate::poll::Event;
+use crate::request::Request;
+use crate::response::{Response, Status};
+
+/// Set when the server is shutting down, waking the accept loop.
+pub static SHUTDOWN: Event = Event::new();
+
+/// Returns whether the server is shutting down.
+pub fn requested() -> bool {
+    SHUTDOWN.is_set()
+}
+
+/// Handles `GET /shutdown`.
+pub fn handle(request: &Request) -> Response {
+    if let Err(response) = admin::check(request) {
+        return response;
+    }
+
+    info!("shutdown requested");
+    SHUTDOWN.set();
+    Response::new(Status::Ok, "text/plain", b"shutting down\n".to_vec())
+}
@@ -0,0 +1,211 @@
+//! Server-wide counters, shown by the `/status` endpoint.
+
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::response::Status;
+
+/// The number of shards each [`Counter`] is split into.
+const SHARDS: usize = 16;
+
+/// One shard of a counter, on a cache line of its own so that threads
+/// updating neighbouring shards do not slow each other down.
+#[repr(align(64))]
+struct Shard(AtomicUsize);
+
+/// A counter split into per-thread shards, which are summed when it is read.
+///
+/// Each thread only updates the shard it is assigned, so that workers
+/// counting requests do not contend for one cache line. Threads share shards
+/// when there are more than [`SHARDS`] of them, which is why the shards are
+/// still atomic. A value read while the shards are being updated may miss
+/// the latest updates.
+pub struct Counter {
+    shards: [Shard; SHARDS],
+}
+
+impl Counter {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const fn new() -> Self {
+        const ZERO: Shard = Shard(AtomicUsize::new(0));
+        Self {
+            shards: [ZERO; SHARDS],
+        }
+    }
+
+    /// Adds `n` to the counter.
+    pub fn add(&self, n: usize) {
+        self.shards[shard_index()].0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Subtracts `n`, which the calling thread has added before, from the
+    /// counter. This makes it a gauge, such as the number of connections
+    /// being served.
+    pub fn sub(&self, n: usize) {
+        self.shards[shard_index()].0.fetch_sub(n, Ordering::Relaxed);
+    }
+
+    /// Returns the sum of the shards.
+    pub fn get(&self) -> usize {
+        self.shards.iter().fold(0, |sum, shard| {
+            // A shard may have gone below zero if its thread subtracted what
+            // another thread sharing it added.
+            sum.wrapping_add(shard.0.load(Ordering::Relaxed))
+        })
+    }
+}
+
+/// Returns the shard of the current thread.
+#[cfg(feature = "axstd")]
+fn shard_index() -> usize {
+    std::thread::current().id().as_u64().get() as usize % SHARDS
+}
+
+/// Returns the shard of the current thread.
+#[cfg(not(feature = "axstd"))]
+fn shard_index() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::thread_local! {
+        static SHARD: usize = NEXT.fetch_add(1, Ordering::Relaxed) % SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+/// Counters updated by the accept loop and the handlers.
+///
+/// All counters are independent, so a snapshot taken while requests are
+/// being served may be slightly inconsistent between fields.
+pub struct Stats {
+    /// Accepted connections.
+    pub connections: Counter,
+    /// Connections currently being served.
+    pub active: Counter,
+    /// Connections aborted because the client was too slow to send a request
+    /// or to receive the response.
+    pub timeouts: Counter,
+    /// Of these, connections aborted because the client stopped receiving the
+    /// response.
+    pub write_timeouts: Counter,
+    /// Connections kept alive that were closed after staying idle between
+    /// requests; these are not timeouts.
+    pub idle_closes: Counter,
+    /// Failed `accept` calls.
+    pub accept_errors: Counter,
+    /// Requests read, including malformed ones.
+    pub requests: Counter,
+    /// Files served from memory.
+    pub cache_hits: Counter,
+    /// Files read from the filesystem.
+    pub cache_misses: Counter,
+    /// Responses sent, by status class (1xx to 5xx).
+    responses: [Counter; 5],
+    /// Response bytes written, including heads.
+    bytes_sent: Counter,
+}
+
+pub static STATS: Stats = Stats::new();
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            connections: Counter::new(),
+            active: Counter::new(),
+            timeouts: Counter::new(),
+            write_timeouts: Counter::new(),
+            idle_closes: Counter::new(),
+            accept_errors: Counter::new(),
+            requests: Counter::new(),
+            cache_hits: Counter::new(),
+            cache_misses: Counter::new(),
+            responses: [
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+            ],
+            bytes_sent: Counter::new(),
+        }
+    }
+
+    /// Records a response that has been written, completely or not.
+    pub fn record_response(&self, status: Status, bytes: usize) {
+        let class = (status.code() / 100) as usize;
+        if let Some(counter) = self.responses.get(class.wrapping_sub(1)) {
+            counter.add(1);
+        }
+        self.bytes_sent.add(bytes);
+    }
+
+    /// Renders the counters as a JSON object.
+    pub fn to_json(&self) -> String {
+        let get = Counter::get;
+        let mut json = format!(
+            "{{\"connections\":{},\"active_connections\":{},\"timeouts\":{},\
+             \"write_timeouts\":{},\"idle_closes\":{},\"accept_errors\":{},\"requests\":{},\
+             \"responses\":{{",
+            get(&self.connections),
+            get(&self.active),
+            get(&self.timeouts),
+            get(&self.write_timeouts),
+            get(&self.idle_closes),
+            get(&self.accept_errors),
+            get(&self.requests),
+        );
+        for (i, counter) in self.responses.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json += &format!("\"{}xx\":{}", i + 1, get(counter));
+        }
+        json += &format!(
+            "}},\"bytes_sent\":{},\"cache\":{{\"hits\":{},\"misses\":{}}}}}\n",
+            get(&self.bytes_sent),
+            get(&self.cache_hits),
+            get(&self.cache_misses),
+        );
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        static COUNTER: Counter = Counter::new();
+        let threads: std::vec::Vec<_> = (0..SHARDS + 4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..1000 {
+                        COUNTER.add(3);
+                        COUNTER.sub(1);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(COUNTER.get(), (SHARDS + 4) * 2000);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let stats = Stats::new();
+        stats.connections.add(2);
+        stats.requests.add(3);
+        stats.record_response(Status::Ok, 100);
+        stats.record_response(Status::NotModified, 20);
+        stats.record_response(Status::NotFound, 30);
+        stats.cache_hits.add(4);
+        stats.cache_misses.add(1);
+        assert_eq!(
+            stats.to_json(),
+            "{\"connections\":2,\"active_connections\":0,\"timeouts\":0,\
+             \"write_timeouts\":0,\"idle_closes\":0,\"accept_errors\":0,\"requests\":3,\"responses\":{\"1xx\":0,\"2xx\":1,\"3xx\":1,\"4xx\":1,\
+             \"5xx\":0},\"bytes_sent\":150,\"cache\":{\"hits\":4,\"misses\":1}}\n"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Locking that works the same with `std` and `axstd`.
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// Locks `mutex`, blocking the current thread until it is available.
+///
+/// `std` mutexes report poisoning while the `axstd` ones do not. A handler
+/// that panicked cannot leave the shared state half-updated in a way that
+/// matters here, so poisoning is ignored.
+#[cfg(feature = "axstd")]
+pub fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock()
+}
+
+/// Locks `mutex`, blocking the current thread until it is available.
+///
+/// `std` mutexes report poisoning while the `axstd` ones do not. A handler
+/// that panicked cannot leave the shared state half-updated in a way that
+/// matters here, so poisoning is ignored.
+#[cfg(not(feature = "axstd"))]
+pub fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Blocks the current thread on `condvar` while `condition` holds for the
+/// value locked by `guard`, ignoring poisoning as [`lock`] does.
+#[cfg(feature = "axstd")]
+pub fn wait_while<'a, T, F>(
+    condvar: &Condvar,
+    guard: MutexGuard<'a, T>,
+    condition: F,
+) -> MutexGuard<'a, T>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    condvar.wait_while(guard, condition)
+}
+
+/// Blocks the current thread on `condvar` while `condition` holds for the
+/// value locked by `guard`, ignoring poisoning as [`lock`] does.
+#[cfg(not(feature = "axstd"))]
+pub fn wait_while<'a, T, F>(
+    condvar: &Condvar,
+    guard: MutexGuard<'a, T>,
+    condition: F,
+) -> MutexGuard<'a, T>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    condvar
+        .wait_while(guard, condition)
+        .unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,83 @@
+//! System information, shown by the `/api/sysinfo` endpoint.
+
+use std::string::String;
+
+use crate::json::JsonObject;
+use crate::response::{Response, Status};
+
+/// The name of the operating system the server runs on.
+#[cfg(feature = "axstd")]
+const OS: &str = "arceos";
+#[cfg(not(feature = "axstd"))]
+const OS: &str = std::env::consts::OS;
+
+/// Returns the time since boot, in seconds.
+#[cfg(feature = "axstd")]
+pub fn uptime() -> Option<u64> {
+    Some(std::os::arceos::api::time::ax_current_time().as_secs())
+}
+
+/// Returns the time since boot, in seconds, if the host tells it.
+#[cfg(not(feature = "axstd"))]
+pub fn uptime() -> Option<u64> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs = uptime.split(['.', ' ']).next()?;
+    secs.parse().ok()
+}
+
+/// Returns the bytes in use and still available in the heap.
+#[cfg(feature = "axstd")]
+fn memory() -> Option<(usize, usize)> {
+    Some(std::os::arceos::api::mem::ax_memory_usage())
+}
+
+/// The host does not tell the heap usage of a process.
+#[cfg(not(feature = "axstd"))]
+fn memory() -> Option<(usize, usize)> {
+    None
+}
+
+/// Renders the system information as a JSON object, given the number of
+/// CPUs and of threads handling connections.
+pub fn to_json(cpus: usize, workers: usize) -> String {
+    let memory = memory().map(|(used, free)| {
+        JsonObject::new()
+            .number("total", Some((used + free) as u64))
+            .number("free", Some(free as u64))
+    });
+    JsonObject::new()
+        .string("os", OS)
+        .number("uptime_secs", uptime())
+        .number("cpus", Some(cpus as u64))
+        .object("memory", memory)
+        .number("handler_threads", Some(workers as u64))
+        .finish()
+}
+
+/// Answers `GET /api/sysinfo`.
+pub fn handle(cpus: usize, workers: usize) -> Response {
+    let json = to_json(cpus, workers);
+    Response::new(Status::Ok, "application/json", json.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::tests::{parse, Value};
+
+    #[test]
+    fn test_to_json() {
+        let json = parse(&to_json(4, 2)).expect("invalid JSON");
+        assert_eq!(json.get("os"), Some(&Value::String(String::from(OS))));
+        assert_eq!(json.get("cpus"), Some(&Value::Number(4.0)));
+        assert_eq!(json.get("handler_threads"), Some(&Value::Number(2.0)));
+        assert!(matches!(
+            json.get("uptime_secs"),
+            Some(Value::Number(_) | Value::Null)
+        ));
+        assert!(matches!(
+            json.get("memory"),
+            Some(Value::Object(_) | Value::Null)
+        ));
+    }
+}
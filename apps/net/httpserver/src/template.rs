@@ -0,0 +1,136 @@
+//! Filling placeholders such as `{{uptime}}` in HTML pages with live values.
+//!
+//! A placeholder is a name between `{{` and `}}`, without spaces. Unknown
+//! names are left as they are, so pages using the same syntax for scripts
+//! are not disturbed, and `{{{{` stands for a literal `{{`.
+
+use std::string::String;
+use std::vec::Vec;
+
+use crate::listing::html_escape;
+use crate::request::Request;
+use crate::stats::STATS;
+use crate::sysinfo;
+
+/// Replaces the placeholders in `template` with the values of `values`,
+/// which are inserted as they are.
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{{{{") {
+            out.push_str("{{");
+            rest = after;
+            continue;
+        }
+        let value = rest[2..].find("}}").and_then(|end| {
+            let name = &rest[2..2 + end];
+            let (_, value) = values.iter().find(|(n, _)| *n == name)?;
+            Some((value, end + 4))
+        });
+        match value {
+            Some((value, len)) => {
+                out.push_str(value);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Returns the values available to pages served for `request`, escaped for
+/// HTML.
+fn page_values(request: &Request) -> Vec<(&'static str, String)> {
+    let mut values = vec![("requests", format!("{}", STATS.requests.get()))];
+    if let Some(uptime) = sysinfo::uptime() {
+        values.push(("uptime", format!("{}", uptime)));
+    }
+    if let Some(host) = request.header("Host") {
+        // Without the port, which may follow an IPv6 address in brackets.
+        let hostname = match host.rsplit_once(':') {
+            Some((name, port)) if !port.contains(']') => name,
+            _ => host,
+        };
+        values.push(("hostname", html_escape(hostname)));
+    }
+    values
+}
+
+/// Fills the placeholders of an HTML page served for `request`, returning
+/// `None` if the content is not an HTML page or has nothing to fill.
+pub fn render_page(request: &Request, content_type: &str, content: &[u8]) -> Option<String> {
+    if !content_type.starts_with("text/html") || !content.windows(2).any(|w| w == b"{{") {
+        return None;
+    }
+    let template = std::str::from_utf8(content).ok()?;
+    let page = render(template, &page_values(request));
+    if page == template {
+        return None;
+    }
+    Some(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    fn values() -> Vec<(&'static str, String)> {
+        vec![
+            ("uptime", String::from("42")),
+            ("requests", String::from("7")),
+        ]
+    }
+
+    #[test]
+    fn test_render() {
+        let render = |template: &str| render(template, &values());
+        assert_eq!(
+            render("up {{uptime}}s, {{requests}} requests"),
+            "up 42s, 7 requests"
+        );
+        assert_eq!(render("{{uptime}}{{uptime}}"), "4242");
+        assert_eq!(render("no placeholders"), "no placeholders");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn test_render_untouched() {
+        let render = |template: &str| render(template, &values());
+        assert_eq!(render("{{unknown}} {{uptime}}"), "{{unknown}} 42");
+        assert_eq!(render("{{ uptime }}"), "{{ uptime }}");
+        assert_eq!(render("{{uptime"), "{{uptime");
+        assert_eq!(render("a {{ b"), "a {{ b");
+        assert_eq!(render("}}{{}}"), "}}{{}}");
+        assert_eq!(render("{{{uptime}}"), "{{{uptime}}");
+    }
+
+    #[test]
+    fn test_render_escape() {
+        let render = |template: &str| render(template, &values());
+        assert_eq!(render("{{{{uptime}}"), "{{uptime}}");
+        assert_eq!(render("{{{{{{uptime}}"), "{{42");
+        assert_eq!(render("{{{{"), "{{");
+    }
+
+    #[test]
+    fn test_render_page() {
+        let raw = "GET / HTTP/1.1\r\nHost: example.com:8080\r\n\r\n";
+        let request = RequestReader::default()
+            .read_request(&mut raw.as_bytes())
+            .unwrap();
+        let html = "text/html; charset=utf-8";
+        let page = render_page(&request, html, b"<p>{{hostname}}</p>");
+        assert_eq!(page.as_deref(), Some("<p>example.com</p>"));
+        assert!(render_page(&request, "text/plain", b"{{hostname}}").is_none());
+        assert!(render_page(&request, html, b"<p>{{ vue }}</p>").is_none());
+        assert!(render_page(&request, html, b"<p>plain</p>").is_none());
+    }
+}
@@ -0,0 +1,274 @@
+//! Deadlines for reading requests and writing responses, so that slow or
+//! silent clients cannot hold on to a worker forever.
+
+use std::io::{self, prelude::*};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::shutdown;
+use crate::stats::STATS;
+
+/// How long a client may take to send a complete request, counted from when
+/// the server starts waiting for it, or from its first bytes on a connection
+/// kept alive.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a connection kept alive may stay silent between requests before
+/// it is closed.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+/// How often an idle connection checks whether the server is shutting down.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the client may take to receive [`WRITE_CHUNK`] bytes of the
+/// response before the connection is aborted.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The most bytes of the response written with one deadline.
+const WRITE_CHUNK: usize = 16 * 1024;
+
+/// Reads from a stream, failing with a timeout error once the deadline has
+/// passed.
+///
+/// On a connection kept alive, the deadline only starts with the first bytes
+/// of the request. Until then the client may stay idle for the idle timeout,
+/// and the wait is cut short if the server is shutting down.
+pub struct DeadlineReader<'a> {
+    stream: &'a mut TcpStream,
+    /// When the request must be complete, or `None` while the connection is
+    /// idle.
+    deadline: Option<Instant>,
+    timeout: Duration,
+    idle_deadline: Instant,
+    idle_expired: bool,
+}
+
+impl<'a> DeadlineReader<'a> {
+    /// Creates a reader that times out `timeout` from now.
+    pub fn new(stream: &'a mut TcpStream, timeout: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            stream,
+            deadline: Some(now + timeout),
+            timeout,
+            idle_deadline: now,
+            idle_expired: false,
+        }
+    }
+
+    /// Creates a reader that waits up to `idle_timeout` for the first bytes,
+    /// then times out `timeout` after them.
+    pub fn after_idle(
+        stream: &'a mut TcpStream,
+        idle_timeout: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            stream,
+            deadline: None,
+            timeout,
+            idle_deadline: Instant::now() + idle_timeout,
+            idle_expired: false,
+        }
+    }
+
+    /// Returns whether reading failed because the connection stayed idle, or
+    /// was idle when the server started shutting down.
+    pub fn idle_expired(&self) -> bool {
+        self.idle_expired
+    }
+
+    /// Waits for the first bytes, checking for a shutdown now and then.
+    fn read_idle(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let remaining = self.idle_deadline.duration_since(Instant::now());
+            if remaining.is_zero() || shutdown::requested() {
+                self.idle_expired = true;
+                return Err(timed_out());
+            }
+            self.stream
+                .set_read_timeout(Some(remaining.min(IDLE_POLL_INTERVAL)))?;
+            match self.stream.read(buf) {
+                Err(e) if is_timeout(&e) => continue,
+                Ok(n) if n > 0 => {
+                    self.deadline = Some(Instant::now() + self.timeout);
+                    return Ok(n);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(deadline) = self.deadline else {
+            return self.read_idle(buf);
+        };
+        let remaining = deadline.duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(timed_out());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Writes to a stream in chunks of at most [`WRITE_CHUNK`] bytes, each of
+/// which must be sent within a timeout.
+///
+/// The socket timeout alone only limits each `write` call, so a client that
+/// reads a few bytes now and then could keep a worker busy forever. Missing a
+/// deadline counts as a write timeout in the statistics.
+pub struct DeadlineWriter<'a> {
+    stream: &'a mut TcpStream,
+    timeout: Duration,
+}
+
+impl<'a> DeadlineWriter<'a> {
+    /// Creates a writer giving each chunk `timeout` to be sent.
+    pub fn new(stream: &'a mut TcpStream, timeout: Duration) -> Self {
+        Self { stream, timeout }
+    }
+}
+
+impl Write for DeadlineWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk = &buf[..buf.len().min(WRITE_CHUNK)];
+        let deadline = Instant::now() + self.timeout;
+        let mut written = 0;
+        while written < chunk.len() {
+            let remaining = deadline.duration_since(Instant::now());
+            let result = if remaining.is_zero() {
+                Err(timed_out())
+            } else {
+                self.stream.set_write_timeout(Some(remaining))?;
+                self.stream.write(&chunk[written..])
+            };
+            match result {
+                Ok(0) => break,
+                Ok(n) => written += n,
+                Err(e) if is_timeout(&e) => {
+                    STATS.write_timeouts.add(1);
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(feature = "axstd")]
+fn timed_out() -> io::Error {
+    io::Error::TimedOut
+}
+
+#[cfg(not(feature = "axstd"))]
+fn timed_out() -> io::Error {
+    io::Error::from(io::ErrorKind::TimedOut)
+}
+
+/// Returns whether an I/O error means that a read or write timed out.
+#[cfg(feature = "axstd")]
+pub fn is_timeout(e: &io::Error) -> bool {
+    matches!(e, io::Error::TimedOut)
+}
+
+/// Returns whether an I/O error means that a read or write timed out.
+///
+/// Depending on the platform, an expired socket timeout is reported as
+/// `WouldBlock` or `TimedOut`.
+#[cfg(not(feature = "axstd"))]
+pub fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_stalled_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Reads 1 KiB, then stops reading while keeping the connection open.
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut buf = [0; 1024];
+            stream.read_exact(&mut buf).unwrap();
+            thread::sleep(Duration::from_secs(3));
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let before = STATS.write_timeouts.get();
+        let start = Instant::now();
+        let mut writer = DeadlineWriter::new(&mut stream, Duration::from_millis(200));
+        // Far more than the socket buffers can hold.
+        let body = vec![b'x'; 64 * 1024 * 1024];
+        let err = writer.write_all(&body).unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert!(STATS.write_timeouts.get() > before);
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_chunked_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut received = Vec::new();
+            TcpStream::connect(addr)
+                .unwrap()
+                .read_to_end(&mut received)
+                .unwrap();
+            received
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let body: Vec<u8> = (0..WRITE_CHUNK * 3 + 7).map(|i| i as u8).collect();
+        let mut writer = DeadlineWriter::new(&mut stream, WRITE_TIMEOUT);
+        assert_eq!(writer.write(&body).unwrap(), WRITE_CHUNK);
+        writer.write_all(&body[WRITE_CHUNK..]).unwrap();
+        drop(stream);
+        assert_eq!(client.join().unwrap(), body);
+    }
+
+    #[test]
+    fn test_idle_read() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Sends a byte after a pause, then another much later.
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            thread::sleep(Duration::from_millis(300));
+            stream.write_all(b"G").unwrap();
+            thread::sleep(Duration::from_millis(1500));
+            let _ = stream.write_all(b"E");
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let idle = Duration::from_secs(1);
+        let timeout = Duration::from_millis(300);
+        let mut reader = DeadlineReader::after_idle(&mut stream, idle, timeout);
+        let mut buf = [0; 16];
+        // The pause is within the idle timeout, although longer than the
+        // request timeout.
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(!reader.idle_expired());
+
+        let mut reader = DeadlineReader::after_idle(&mut stream, timeout, timeout);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(reader.idle_expired());
+        client.join().unwrap();
+    }
+}
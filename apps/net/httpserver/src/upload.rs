@@ -0,0 +1,292 @@
+//! Receiving files uploaded from a browser form with `POST /upload`.
+//!
+//! `GET /upload` serves a form for choosing a file. The body of an upload is
+//! read as it arrives, and its files are written to temporary files in the
+//! upload directory while the other form fields are collected. Once the whole
+//! upload has been received, the files are renamed to their own names, made
+//! safe first, replacing files of the same name. If the upload fails, only its
+//! temporary files are removed.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::vec::Vec;
+
+use crate::multipart::{self, MultipartReader};
+use crate::request::Request;
+use crate::response::{Response, Status};
+
+/// The directory uploaded files are saved in, unless another one is
+/// configured.
+pub const UPLOAD_DIR: &str = "/uploads";
+/// The maximum total size of the files of one upload. They are written to
+/// files as they arrive, so this can be more than other request bodies.
+const MAX_UPLOAD_LEN: u64 = 16 * 1024 * 1024;
+/// The maximum size of a form field other than a file.
+const MAX_FIELD_LEN: u64 = 4 * 1024;
+/// The maximum length of the name of a saved file, in bytes.
+const MAX_NAME_LEN: usize = 100;
+
+/// The form served by `GET /upload`.
+const FORM: &str = "<html><head><title>Upload</title></head><body>\
+    <form method=\"post\" action=\"/upload\" enctype=\"multipart/form-data\">\
+    <p><input type=\"file\" name=\"file\" multiple></p>\
+    <p><input type=\"submit\" value=\"Upload\"></p>\
+    </form></body></html>\n";
+
+/// Numbers the temporary files of uploads.
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// A file of an upload, written to a temporary file until the upload has
+/// been received.
+struct Received {
+    /// The path of the temporary file, whose name starts with a dot so that
+    /// no sanitized name is the same.
+    temp: String,
+    /// The name to save the file under.
+    name: String,
+    len: u64,
+}
+
+/// What an upload contained.
+#[derive(Debug, Default)]
+pub struct Upload {
+    /// The names the files were saved under, and their sizes.
+    pub files: Vec<(String, u64)>,
+    /// The other form fields, by name. Of fields given more than once, the
+    /// last value is kept.
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Turns a file name given by a client into one that is safe to save in the
+/// upload directory, or `None` if nothing usable is left.
+///
+/// Only the last component of a path is kept, characters other than ASCII
+/// letters, digits, `.`, `-` and `_` become `_`, and leading dots are
+/// dropped so that the file is not hidden.
+pub fn sanitize(filename: &str) -> Option<String> {
+    let base = filename.rsplit(['/', '\\']).next()?;
+    let safe: String = base
+        .trim_start_matches('.')
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    if safe.is_empty() {
+        None
+    } else {
+        Some(safe)
+    }
+}
+
+/// Writes the files of a `multipart/form-data` body with the given boundary
+/// to temporary files in `dir`, adding them to `received` as they are
+/// created, and adds the other form fields to `fields`.
+fn receive_parts(
+    body: &mut dyn Read,
+    boundary: &str,
+    dir: &str,
+    fields: &mut BTreeMap<String, String>,
+    received: &mut Vec<Received>,
+) -> Result<(), Status> {
+    let mut reader = MultipartReader::new(body, boundary);
+    let mut total = 0;
+    while let Some(part) = reader.next_part().map_err(status_for)? {
+        let filename = match part.filename {
+            // No file chosen for this input.
+            Some(filename) if filename.is_empty() => continue,
+            Some(filename) => sanitize(&filename).ok_or(Status::BadRequest)?,
+            None => {
+                let mut value = Vec::new();
+                reader
+                    .read_data(&mut value, MAX_FIELD_LEN)
+                    .map_err(status_for)?;
+                let value = String::from_utf8_lossy(&value).into_owned();
+                fields.insert(part.name, value);
+                continue;
+            }
+        };
+        let temp = format!(
+            "{}/.upload-{}",
+            dir,
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut file = File::create(&temp).map_err(|e| {
+            info!("failed to create {}: {:?}", temp, e);
+            Status::InternalServerError
+        })?;
+        // Added before the data is written, so that a partial file is
+        // removed too.
+        received.push(Received {
+            temp,
+            name: filename,
+            len: 0,
+        });
+        let len = reader
+            .read_data(&mut file, MAX_UPLOAD_LEN - total)
+            .map_err(status_for)?;
+        total += len;
+        if let Some(last) = received.last_mut() {
+            last.len = len;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the status answering an upload that failed with `e`.
+fn status_for(e: multipart::Error) -> Status {
+    match e {
+        multipart::Error::Malformed => Status::BadRequest,
+        multipart::Error::TooLarge => Status::PayloadTooLarge,
+        multipart::Error::Io(e) => {
+            info!("failed to save upload: {:?}", e);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Receives the files sent with `request`, whose body is read from `body`,
+/// into `dir`, returning the status to answer with if the upload is rejected
+/// or fails.
+pub fn receive(request: &Request, body: &mut dyn Read, dir: &str) -> Result<Upload, Status> {
+    let boundary = request
+        .header("Content-Type")
+        .and_then(multipart::boundary)
+        .ok_or(Status::BadRequest)?;
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        info!("failed to create {}: {:?}", dir, e);
+        return Err(Status::InternalServerError);
+    }
+    let mut upload = Upload::default();
+    let mut received = Vec::new();
+    let result = receive_parts(body, boundary, dir, &mut upload.fields, &mut received);
+    let mut received = received.into_iter();
+    let saved = result.and_then(|()| {
+        for file in received.by_ref() {
+            let path = format!("{}/{}", dir, file.name);
+            if let Err(e) = std::fs::rename(file.temp.as_str(), path.as_str()) {
+                info!("failed to save {}: {:?}", path, e);
+                let _ = std::fs::remove_file(file.temp.as_str());
+                return Err(Status::InternalServerError);
+            }
+            upload.files.push((file.name, file.len));
+        }
+        Ok(())
+    });
+    if let Err(status) = saved {
+        // The files the upload would have replaced are left as they were.
+        for file in received {
+            let _ = std::fs::remove_file(file.temp.as_str());
+        }
+        return Err(status);
+    }
+    Ok(upload)
+}
+
+/// Handles `GET /upload`.
+pub fn form() -> Response {
+    Response::new(Status::Ok, "text/html; charset=utf-8", FORM.into())
+}
+
+/// Handles `POST /upload`, whose body is read from `body`, saving files in
+/// `dir`.
+pub fn handle(request: &Request, body: &mut dyn Read, dir: &str) -> Response {
+    let upload = match receive(request, body, dir) {
+        Ok(upload) => upload,
+        Err(status) => return Response::error(status),
+    };
+    let mut body = String::new();
+    for (filename, len) in upload.files.iter() {
+        info!("saved upload {}/{} ({} bytes)", dir, filename, len);
+        body += &format!("saved {} ({} bytes)\n", filename, len);
+    }
+    for (name, value) in upload.fields.iter() {
+        body += &format!("{}: {}\n", name, value);
+    }
+    Response::new(Status::Ok, "text/plain; charset=utf-8", body.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    /// Receives the upload with the given body into `dir`.
+    fn post(body: &str, dir: &str) -> Result<Upload, Status> {
+        let raw = format!(
+            "POST /upload HTTP/1.1\r\nHost: test\r\n\
+             Content-Type: multipart/form-data; boundary=b\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = raw.as_bytes();
+        let mut reader = RequestReader::default();
+        let request = reader.read_head(&mut stream).unwrap();
+        let len = request.content_length().unwrap();
+        receive(&request, &mut reader.body_reader(&mut stream, len), dir)
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("report.pdf").as_deref(), Some("report.pdf"));
+        assert_eq!(sanitize("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(
+            sanitize("C:\\Users\\me\\a b.txt").as_deref(),
+            Some("a_b.txt")
+        );
+        assert_eq!(sanitize(".profile").as_deref(), Some("profile"));
+        assert_eq!(sanitize("résumé.txt").as_deref(), Some("r_sum_.txt"));
+        assert_eq!(sanitize("dir/"), None);
+        assert_eq!(sanitize(".."), None);
+        assert_eq!(
+            sanitize(&"x".repeat(200)).map(|s| s.len()),
+            Some(MAX_NAME_LEN)
+        );
+    }
+
+    #[test]
+    fn test_receive() {
+        let dir = std::env::temp_dir().join(format!("httpd-upload-{}", std::process::id()));
+        let dir_str = dir.to_str().unwrap();
+
+        let upload = post(
+            "--b\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhello\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"../a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nfile data\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"other\"; filename=\"\"\r\n\r\n\r\n\
+             --b--\r\n",
+            dir_str,
+        )
+        .unwrap();
+        assert_eq!(upload.files, [(String::from("a.txt"), 9)]);
+        assert_eq!(upload.fields.get("note").map(String::as_str), Some("hello"));
+        assert_eq!(std::fs::read(dir.join("a.txt")).unwrap(), b"file data");
+
+        // A failed upload leaves no files behind, and the files it would
+        // have replaced as they were.
+        let result = post(
+            "--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\r\n\
+             new data\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"g\"; filename=\"b.txt\"\r\n\r\n\
+             cut short",
+            dir_str,
+        );
+        assert_eq!(result.unwrap_err(), Status::BadRequest);
+        assert_eq!(std::fs::read(dir.join("a.txt")).unwrap(), b"file data");
+        assert!(!dir.join("b.txt").exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let field = format!(
+            "--b\r\nContent-Disposition: form-data; name=\"big\"\r\n\r\n{}\r\n--b--",
+            "x".repeat(MAX_FIELD_LEN as usize + 1)
+        );
+        assert_eq!(post(&field, dir_str).unwrap_err(), Status::PayloadTooLarge);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Decoding and normalizing request targets.
+
+use std::string::String;
+use std::vec::Vec;
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes in `s`.
+///
+/// Returns `None` if an escape is truncated or not hexadecimal.
+pub fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hi = hex_value(*bytes.get(i + 1)?)?;
+            let lo = hex_value(*bytes.get(i + 2)?)?;
+            out.push(hi << 4 | lo);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
+
+/// Decodes a key or value of a query string, where `+` stands for a space.
+///
+/// Invalid escapes are kept as they are and invalid UTF-8 is replaced, since
+/// query parameters are only ever shown or compared.
+pub fn decode_query(s: &str) -> String {
+    let s = s.replace('+', " ");
+    match percent_decode(&s) {
+        Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        None => s,
+    }
+}
+
+/// Resolves `.` and `..` segments and collapses repeated slashes in an
+/// absolute path, keeping a trailing slash.
+///
+/// Returns `None` if the path is not absolute or `..` would climb above the
+/// root.
+pub fn normalize_path(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for seg in path[1..].split('/') {
+        trailing_slash = matches!(seg, "" | "." | "..");
+        match seg {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            seg => segments.push(seg),
+        }
+    }
+
+    let mut out = String::with_capacity(path.len());
+    for seg in segments.iter() {
+        out.push('/');
+        out.push_str(seg);
+    }
+    if trailing_slash || out.is_empty() {
+        out.push('/');
+    }
+    Some(out)
+}
+
+/// Decodes the path of a request target and normalizes it.
+///
+/// Decoding happens first, so encoded separators and dots such as `%2e%2e%2f`
+/// cannot be used to escape the root. Returns `None` for invalid escapes,
+/// paths that are not UTF-8 or contain NUL bytes, and paths escaping the root.
+pub fn decode_path(raw: &str) -> Option<String> {
+    let decoded = String::from_utf8(percent_decode(raw)?).ok()?;
+    if decoded.contains('\0') {
+        return None;
+    }
+    normalize_path(&decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/my%20page.html").unwrap(), b"/my page.html");
+        assert_eq!(percent_decode("%C3%a9").unwrap(), "é".as_bytes());
+        assert_eq!(percent_decode("plain").unwrap(), b"plain");
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("abc%"), None);
+    }
+
+    #[test]
+    fn test_decode_query() {
+        assert_eq!(decode_query("a+b%2Bc"), "a b+c");
+        assert_eq!(decode_query("%E4%BD%A0%E5%A5%BD"), "你好");
+        assert_eq!(decode_query("100%"), "100%");
+        assert_eq!(decode_query("%ff"), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/").as_deref(), Some("/"));
+        assert_eq!(normalize_path("/a/../b").as_deref(), Some("/b"));
+        assert_eq!(normalize_path("/a/./b/").as_deref(), Some("/a/b/"));
+        assert_eq!(normalize_path("//a///b").as_deref(), Some("/a/b"));
+        assert_eq!(normalize_path("/a/b/..").as_deref(), Some("/a/"));
+        assert_eq!(normalize_path("/a/..").as_deref(), Some("/"));
+        assert_eq!(normalize_path("/.."), None);
+        assert_eq!(normalize_path("/a/../../b"), None);
+        assert_eq!(normalize_path("a/b"), None);
+    }
+
+    #[test]
+    fn test_encoded_traversal() {
+        assert_eq!(decode_path("/%2e%2e%2fetc/passwd"), None);
+        assert_eq!(decode_path("/%2E%2E/%2E%2E/etc/passwd"), None);
+        assert_eq!(decode_path("/a/%2e%2e%2f%2e%2e%2fb"), None);
+        assert_eq!(decode_path("/a%2f..%2f..%2fb"), None);
+        assert_eq!(decode_path("/a/%2e%2e/b").as_deref(), Some("/b"));
+        assert_eq!(decode_path("/a%00.html"), None);
+        assert_eq!(decode_path("/%ff"), None);
+        assert_eq!(
+            decode_path("/my%20page.html").as_deref(),
+            Some("/my page.html")
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Name-based virtual hosts.
+
+/// A site served for requests whose `Host` header names it.
+pub struct VirtualHost {
+    /// The host name, compared case-insensitively and without the port.
+    pub name: &'static str,
+    /// The document root of the site.
+    pub root: &'static str,
+}
+
+/// Returns the host name in a `Host` header value, without the port.
+fn host_name(host: &str) -> &str {
+    let host = host.trim();
+    if host.starts_with('[') {
+        // An IPv6 literal, such as `[::1]:8080`.
+        return match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// Returns the document root for a request with the given `Host` header,
+/// or `default` for unknown or missing hosts.
+pub fn resolve<'a>(hosts: &'a [VirtualHost], host: Option<&str>, default: &'a str) -> &'a str {
+    let name = match host {
+        Some(host) => host_name(host),
+        None => return default,
+    };
+    hosts
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map_or(default, |h| h.root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTS: &[VirtualHost] = &[
+        VirtualHost {
+            name: "site-a",
+            root: "/html/a",
+        },
+        VirtualHost {
+            name: "site-b.example",
+            root: "/html/b",
+        },
+    ];
+
+    #[test]
+    fn test_host_name() {
+        assert_eq!(host_name("example.com"), "example.com");
+        assert_eq!(host_name("example.com:8080"), "example.com");
+        assert_eq!(host_name("10.0.2.15:5555"), "10.0.2.15");
+        assert_eq!(host_name("[::1]:5555"), "[::1]");
+        assert_eq!(host_name("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(HOSTS, Some("site-a"), "/html"), "/html/a");
+        assert_eq!(resolve(HOSTS, Some("SITE-A:5555"), "/html"), "/html/a");
+        assert_eq!(resolve(HOSTS, Some("site-b.example"), "/html"), "/html/b");
+        assert_eq!(resolve(HOSTS, Some("site-c"), "/html"), "/html");
+        assert_eq!(resolve(HOSTS, None, "/html"), "/html");
+        assert_eq!(resolve(&[], Some("site-a"), "/html"), "/html");
+    }
+}
@@ -0,0 +1,432 @@
+//! A WebSocket (RFC 6455) endpoint echoing every message back.
+//!
+//! No extensions or subprotocols are supported. Messages are echoed once
+//! they are complete, as a single frame.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::base64;
+use crate::request::{has_token, Request};
+use crate::response::{Response, Status};
+use crate::sha1::sha1;
+
+/// Appended to the client's key to compute `Sec-WebSocket-Accept`.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The maximum size of a message, after reassembling its fragments.
+const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+/// How long a session may stay without receiving anything before it is
+/// closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Computes the `Sec-WebSocket-Accept` value for a `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    let mut input = String::from(key.trim());
+    input += GUID;
+    base64::encode(&sha1(input.as_bytes()))
+}
+
+/// Handles the opening handshake on `GET /ws`, switching the connection to
+/// an echo session if the request is valid.
+pub fn handshake(request: &Request) -> Response {
+    if request.method != "GET" {
+        return Response::error(Status::MethodNotAllowed).with_header("Allow", "GET");
+    }
+    let upgrade = request
+        .header("Upgrade")
+        .is_some_and(|v| has_token(v, "websocket"));
+    let connection = request
+        .header("Connection")
+        .is_some_and(|v| has_token(v, "upgrade"));
+    if request.version != "HTTP/1.1" || !upgrade || !connection {
+        return Response::error(Status::UpgradeRequired)
+            .with_header("Upgrade", "websocket")
+            .with_header("Connection", "Upgrade");
+    }
+    if request.header("Sec-WebSocket-Version") != Some("13") {
+        return Response::error(Status::UpgradeRequired).with_header("Sec-WebSocket-Version", "13");
+    }
+    let key = match request.header("Sec-WebSocket-Key") {
+        Some(key) if base64::decode(key).is_some_and(|k| k.len() == 16) => key,
+        _ => return Response::error(Status::BadRequest),
+    };
+
+    Response::upgrade("websocket", |stream, pending| {
+        stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        Session::new(stream, pending).run()
+    })
+    .with_header("Sec-WebSocket-Accept", &accept_key(key))
+}
+
+/// A frame received from the client, already unmasked.
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// The server side of a WebSocket connection.
+struct Session<'a, S: Read + Write> {
+    stream: &'a mut S,
+    /// Bytes received before the session started, read before the stream.
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl<'a, S: Read + Write> Session<'a, S> {
+    fn new(stream: &'a mut S, pending: Vec<u8>) -> Self {
+        Self {
+            stream,
+            pending,
+            pending_pos: 0,
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let n = buf.len().min(self.pending.len() - self.pending_pos);
+        buf[..n].copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + n]);
+        self.pending_pos += n;
+        self.stream.read_exact(&mut buf[n..])
+    }
+
+    /// Reads the next frame, or returns the close code for a frame that
+    /// breaks the protocol.
+    fn read_frame(&mut self) -> io::Result<Result<Frame, u16>> {
+        let mut head = [0u8; 2];
+        self.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        // Reserved bits are only used by extensions, and none were agreed.
+        if head[0] & 0x70 != 0 {
+            return Ok(Err(CLOSE_PROTOCOL_ERROR));
+        }
+        // Clients must mask every frame (section 5.1).
+        if head[1] & 0x80 == 0 {
+            return Ok(Err(CLOSE_PROTOCOL_ERROR));
+        }
+
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                self.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                self.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        if opcode >= OP_CLOSE && (!fin || len > 125) {
+            // Control frames cannot be fragmented or long (section 5.5).
+            return Ok(Err(CLOSE_PROTOCOL_ERROR));
+        }
+        if len > MAX_MESSAGE_LEN as u64 {
+            return Ok(Err(CLOSE_TOO_BIG));
+        }
+
+        let mut mask = [0u8; 4];
+        self.read_exact(&mut mask)?;
+        let mut payload = vec![0u8; len as usize];
+        self.read_exact(&mut payload)?;
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+        Ok(Ok(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+
+    /// Sends an unfragmented, unmasked frame.
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut head = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => head.push(len as u8),
+            len if len <= 0xffff => {
+                head.push(126);
+                head.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                head.push(127);
+                head.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        self.stream.write_all(&head)?;
+        self.stream.write_all(payload)?;
+        self.stream.flush()
+    }
+
+    /// Sends a close frame with `code`, ending the session.
+    fn close(&mut self, code: u16) -> io::Result<()> {
+        info!("closing WebSocket with {}", code);
+        self.write_frame(OP_CLOSE, &code.to_be_bytes())
+    }
+
+    /// Echoes messages until the client closes the session.
+    fn run(mut self) -> io::Result<()> {
+        // The opcode and data of a fragmented message being received.
+        let mut message: Option<(u8, Vec<u8>)> = None;
+        loop {
+            let frame = match self.read_frame()? {
+                Ok(frame) => frame,
+                Err(code) => return self.close(code),
+            };
+            match (frame.opcode, &mut message) {
+                (OP_PING, _) => self.write_frame(OP_PONG, &frame.payload)?,
+                (OP_PONG, _) => {}
+                (OP_CLOSE, _) => {
+                    // Echo the status code, if any, to complete the closing
+                    // handshake.
+                    return match frame.payload.len() {
+                        0 => self.write_frame(OP_CLOSE, &[]),
+                        1 => self.close(CLOSE_PROTOCOL_ERROR),
+                        _ => self.write_frame(OP_CLOSE, &frame.payload[..2]),
+                    };
+                }
+                (OP_TEXT | OP_BINARY, None) => message = Some((frame.opcode, frame.payload)),
+                (OP_CONTINUATION, Some((_, data))) => {
+                    if data.len() + frame.payload.len() > MAX_MESSAGE_LEN {
+                        return self.close(CLOSE_TOO_BIG);
+                    }
+                    data.extend_from_slice(&frame.payload);
+                }
+                // Unknown opcodes, continuations of nothing, and new
+                // messages interleaved with a fragmented one.
+                _ => return self.close(CLOSE_PROTOCOL_ERROR),
+            }
+
+            if frame.fin && frame.opcode < OP_CLOSE {
+                let (opcode, data) = message.take().unwrap();
+                if opcode == OP_TEXT && std::str::from_utf8(&data).is_err() {
+                    return self.close(CLOSE_INVALID_DATA);
+                }
+                self.write_frame(opcode, &data)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestReader;
+
+    /// A client connection with scripted input, recording what the server
+    /// sends.
+    struct Client {
+        input: Vec<u8>,
+        pos: usize,
+        output: Vec<u8>,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.input.len() - self.pos);
+            buf[..n].copy_from_slice(&self.input[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Builds a client frame, masked unless `masked` is false.
+    fn frame(fin: bool, opcode: u8, payload: &[u8], masked: bool) -> Vec<u8> {
+        let mut out = vec![if fin { 0x80 } else { 0 } | opcode];
+        let mask_bit = if masked { 0x80 } else { 0 };
+        if payload.len() < 126 {
+            out.push(mask_bit | payload.len() as u8);
+        } else {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        if masked {
+            let mask = [0x37, 0xfa, 0x21, 0x3d];
+            out.extend_from_slice(&mask);
+            out.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        } else {
+            out.extend_from_slice(payload);
+        }
+        out
+    }
+
+    /// Runs a session on the concatenated frames, with the first `pending`
+    /// bytes given as already received, and returns the frames sent back.
+    fn run(frames: &[Vec<u8>], pending: usize) -> Vec<(u8, Vec<u8>)> {
+        let input = frames.concat();
+        let mut client = Client {
+            input: input[pending..].to_vec(),
+            pos: 0,
+            output: Vec::new(),
+        };
+        Session::new(&mut client, input[..pending].to_vec())
+            .run()
+            .unwrap();
+
+        let mut replies = Vec::new();
+        let out = client.output;
+        let mut i = 0;
+        while i < out.len() {
+            assert_eq!(out[i] & 0xf0, 0x80, "server frames are final");
+            let (len, start) = match out[i + 1] {
+                126 => (u16::from_be_bytes([out[i + 2], out[i + 3]]) as usize, i + 4),
+                len => {
+                    assert!(len < 126, "server frames are unmasked");
+                    (len as usize, i + 2)
+                }
+            };
+            replies.push((out[i] & 0x0f, out[start..start + len].to_vec()));
+            i = start + len;
+        }
+        replies
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example from RFC 6455, section 1.3.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_handshake() {
+        let status = |headers: &str| {
+            let raw = format!("GET /ws HTTP/1.1\r\nHost: test\r\n{}\r\n", headers);
+            let request = RequestReader::default()
+                .read_request(&mut raw.as_bytes())
+                .unwrap();
+            let response = handshake(&request);
+            let accept = response
+                .headers
+                .iter()
+                .find(|(n, _)| *n == "Sec-WebSocket-Accept")
+                .map(|(_, v)| v.clone());
+            (response.status, accept)
+        };
+        let valid = "Upgrade: websocket\r\nConnection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+        assert_eq!(
+            status(valid),
+            (
+                Status::SwitchingProtocols,
+                Some(String::from("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="))
+            )
+        );
+        assert_eq!(status("").0, Status::UpgradeRequired);
+        assert_eq!(
+            status(&valid.replace("Version: 13", "Version: 8")).0,
+            Status::UpgradeRequired
+        );
+        assert_eq!(
+            status(&valid.replace("dGhlIHNhbXBsZSBub25jZQ==", "c2hvcnQ=")).0,
+            Status::BadRequest
+        );
+    }
+
+    #[test]
+    fn test_echo() {
+        let long = vec![b'x'; 300];
+        let replies = run(
+            &[
+                frame(true, OP_TEXT, b"hello", true),
+                frame(true, OP_BINARY, &[0, 1, 2, 255], true),
+                frame(true, OP_BINARY, &long, true),
+                frame(true, OP_CLOSE, &1000u16.to_be_bytes(), true),
+            ],
+            3,
+        );
+        assert_eq!(
+            replies,
+            [
+                (OP_TEXT, b"hello".to_vec()),
+                (OP_BINARY, vec![0, 1, 2, 255]),
+                (OP_BINARY, long),
+                (OP_CLOSE, 1000u16.to_be_bytes().to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fragmented() {
+        let replies = run(
+            &[
+                frame(false, OP_TEXT, b"Hel", true),
+                frame(true, OP_PING, b"are you there", true),
+                frame(false, OP_CONTINUATION, b"lo, ", true),
+                frame(true, OP_CONTINUATION, "wörld".as_bytes(), true),
+                frame(true, OP_PONG, b"", true),
+                frame(true, OP_CLOSE, b"", true),
+            ],
+            0,
+        );
+        assert_eq!(
+            replies,
+            [
+                (OP_PONG, b"are you there".to_vec()),
+                (OP_TEXT, "Hello, wörld".as_bytes().to_vec()),
+                (OP_CLOSE, Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let closed_with = |frames: &[Vec<u8>]| {
+            let replies = run(frames, 0);
+            let (opcode, payload) = replies.last().unwrap().clone();
+            assert_eq!(opcode, OP_CLOSE);
+            u16::from_be_bytes([payload[0], payload[1]])
+        };
+        // Unmasked client frame.
+        assert_eq!(closed_with(&[frame(true, OP_TEXT, b"hi", false)]), 1002);
+        // Continuation without a message.
+        assert_eq!(
+            closed_with(&[frame(true, OP_CONTINUATION, b"hi", true)]),
+            1002
+        );
+        // New message while a fragmented one is unfinished.
+        assert_eq!(
+            closed_with(&[
+                frame(false, OP_TEXT, b"a", true),
+                frame(true, OP_TEXT, b"b", true),
+            ]),
+            1002
+        );
+        // Fragmented control frame.
+        assert_eq!(closed_with(&[frame(false, OP_PING, b"", true)]), 1002);
+        // Unknown opcode.
+        assert_eq!(closed_with(&[frame(true, 0x3, b"", true)]), 1002);
+        // Invalid UTF-8 in a text message.
+        assert_eq!(
+            closed_with(&[frame(true, OP_TEXT, &[0xc3, 0x28], true)]),
+            1007
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Key-value store over HTTP, kept in memory, which gives many clients
+//! something to read and write at once, e.g. with `httpbench`.
+//!
+//! - `PUT /kv/<key>` stores the request body under the key, answering
+//!   `201 Created` for a new key and `204 No Content` for a replaced value.
+//! - `GET /kv/<key>` returns the value of the key, or `404 Not Found`.
+//! - `DELETE /kv/<key>` removes the key.
+//! - `GET /kv` lists the keys, as `{"keys":[...]}`.
+//!
+//! Keys are the percent-decoded rest of the path. The limits on keys and
+//! values are those of the [`store`] module. Each connection is served by a
+//! thread of its own; readers do not wait for each other, only for writers.
+//! The store is lost when the app stops.
+//!
+//! The server listens on port 5555, e.g. with
+//! `make A=apps/net/kvstore NET=y run`, and
+//! `curl -T file http://127.0.0.1:5555/kv/file` stores a file.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+mod rwlock;
+mod store;
+
+use std::io::{self, prelude::*, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::string::String;
+use std::thread;
+use std::vec::Vec;
+
+use http1::{invalid_data, read_line, ChunkedReader, MAX_HEADERS, MAX_LINE_LEN};
+
+use crate::store::{PutError, Store, MAX_KEY_LEN, MAX_VALUE_LEN};
+
+const LOCAL_IP: &str = "0.0.0.0";
+const LOCAL_PORT: u16 = 5555;
+
+/// The store shared by all connections.
+static STORE: Store = Store::new();
+
+/// The head of a request, with what is needed of its headers.
+struct Request {
+    method: String,
+    target: String,
+    content_length: u64,
+    chunked: bool,
+    /// Whether the client closes the connection after the response.
+    close: bool,
+}
+
+struct Response {
+    status: u16,
+    content_type: Option<&'static str>,
+    /// The methods allowed, for `405 Method Not Allowed`.
+    allow: Option<&'static str>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type: Some(content_type),
+            allow: None,
+            body,
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        Self {
+            status,
+            content_type: None,
+            allow: None,
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns a response with the reason of `status` as its body.
+    fn error(status: u16) -> Self {
+        let body = format!("{}\n", reason(status));
+        Self::new(status, "text/plain", body.into_bytes())
+    }
+
+    fn not_allowed(allow: &'static str) -> Self {
+        Self {
+            allow: Some(allow),
+            ..Self::error(405)
+        }
+    }
+
+    fn write_to<W: Write + ?Sized>(&self, out: &mut W, close: bool) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            self.status,
+            reason(self.status),
+            self.body.len()
+        );
+        if let Some(content_type) = self.content_type {
+            head.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        if let Some(allow) = self.allow {
+            head.push_str(&format!("Allow: {}\r\n", allow));
+        }
+        if close {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str("\r\n");
+        // written at once, so that the body does not wait for the head to
+        // be acknowledged
+        let mut response = head.into_bytes();
+        response.extend_from_slice(&self.body);
+        out.write_all(&response)
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        507 => "Insufficient Storage",
+        _ => "",
+    }
+}
+
+/// Reads the head of a request, or returns `None` if the connection is closed
+/// before one starts.
+fn read_request<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<Option<Request>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let line = read_line(reader, MAX_LINE_LEN)?;
+    let mut parts = line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version))
+            if !method.is_empty() && version.starts_with("HTTP/1.") && parts.next().is_none() =>
+        {
+            (method, target, version)
+        }
+        _ => return Err(invalid_data()),
+    };
+    let mut request = Request {
+        method: String::from(method),
+        target: String::from(target),
+        content_length: 0,
+        chunked: false,
+        close: version == "HTTP/1.0",
+    };
+    for _ in 0..=MAX_HEADERS {
+        let line = read_line(reader, MAX_LINE_LEN)?;
+        if line.is_empty() {
+            return Ok(Some(request));
+        }
+        let (name, value) = line.split_once(':').ok_or_else(invalid_data)?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid_data());
+            }
+            request.content_length = value.parse().map_err(|_| invalid_data())?;
+        } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            if !value.eq_ignore_ascii_case("chunked") {
+                return Err(invalid_data());
+            }
+            request.chunked = true;
+        } else if name.eq_ignore_ascii_case("Connection") {
+            if value.eq_ignore_ascii_case("close") {
+                request.close = true;
+            } else if value.eq_ignore_ascii_case("keep-alive") {
+                request.close = false;
+            }
+        }
+    }
+    Err(invalid_data())
+}
+
+/// Reads the body of `request`, or returns `None`, leaving it unread, if it
+/// is longer than [`MAX_VALUE_LEN`].
+fn read_body<R: BufRead + ?Sized>(
+    reader: &mut R,
+    request: &Request,
+) -> io::Result<Option<Vec<u8>>> {
+    let limit = MAX_VALUE_LEN as u64 + 1;
+    let mut body = Vec::new();
+    if request.chunked {
+        ChunkedReader::new(reader)
+            .take(limit)
+            .read_to_end(&mut body)?;
+    } else if request.content_length < limit {
+        reader.take(request.content_length).read_to_end(&mut body)?;
+        if (body.len() as u64) < request.content_length {
+            return Err(invalid_data());
+        }
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(body).filter(|body| body.len() <= MAX_VALUE_LEN))
+}
+
+/// Returns the key named by `rest`, the path after `/kv`, `Ok(None)` for
+/// `/kv` itself, or the response refusing the request.
+fn key(rest: &str) -> Result<Option<String>, Response> {
+    let encoded = match rest.strip_prefix('/') {
+        None | Some("") => return Ok(None),
+        Some(encoded) => encoded,
+    };
+    let key = percent_decode(encoded)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| Response::error(400))?;
+    if key.len() > MAX_KEY_LEN {
+        return Err(Response::error(400));
+    }
+    Ok(Some(key))
+}
+
+/// Decodes the `%XX` escapes of `s`, or returns `None` if one is invalid.
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = core::str::from_utf8(&hex).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    Some(out)
+}
+
+/// Returns the JSON object listing `keys`.
+fn keys_json(keys: &[String]) -> String {
+    let mut out = String::from("{\"keys\":[");
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('"');
+        for c in key.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+    out.push_str("]}");
+    out
+}
+
+/// Answers `request`, with `body` read already.
+fn handle(request: &Request, body: Vec<u8>) -> Response {
+    let path = request.target.split('?').next().unwrap_or("");
+    let rest = match path.strip_prefix("/kv") {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return Response::error(404),
+    };
+    let key = match key(rest) {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    match (request.method.as_str(), key) {
+        ("GET", None) => {
+            let json = keys_json(&STORE.keys());
+            Response::new(200, "application/json", json.into_bytes())
+        }
+        ("GET", Some(key)) => match STORE.get(&key) {
+            Some(value) => Response::new(200, "application/octet-stream", value.to_vec()),
+            None => Response::error(404),
+        },
+        ("PUT", Some(key)) => match STORE.put(key, body) {
+            Ok(true) => Response::empty(201),
+            Ok(false) => Response::empty(204),
+            Err(PutError::TooLarge) => Response::error(413),
+            Err(PutError::Full) => Response::error(507),
+        },
+        ("DELETE", Some(key)) if STORE.delete(&key) => Response::empty(204),
+        ("DELETE", Some(_)) => Response::error(404),
+        (_, None) => Response::not_allowed("GET"),
+        (_, Some(_)) => Response::not_allowed("GET, PUT, DELETE"),
+    }
+}
+
+fn kv_server(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                Response::error(400).write_to(reader.get_mut(), true)?;
+                return Err(e);
+            }
+        };
+        match read_body(&mut reader, &request)? {
+            Some(body) => {
+                let response = handle(&request, body);
+                response.write_to(reader.get_mut(), request.close)?;
+                if request.close {
+                    return Ok(());
+                }
+            }
+            None => return Response::error(413).write_to(reader.get_mut(), true),
+        }
+    }
+}
+
+fn accept_loop() -> io::Result<()> {
+    let listener = TcpListener::bind((LOCAL_IP, LOCAL_PORT))?;
+    println!("listen on: {}", listener.local_addr().unwrap());
+
+    loop {
+        let (stream, addr) = listener.accept()?;
+        thread::spawn(move || {
+            if let Err(e) = kv_server(stream) {
+                println!("client {} connection error: {:?}", addr, e);
+            }
+        });
+    }
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, key-value store!");
+    accept_loop().expect("test key-value store failed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves the requests in `raw` as a connection would, returning the
+    /// status lines of the responses.
+    fn statuses(raw: &str) -> Vec<String> {
+        let mut reader = raw.as_bytes();
+        let mut statuses = Vec::new();
+        while let Some(request) = read_request(&mut reader).unwrap() {
+            let body = read_body(&mut reader, &request).unwrap().unwrap();
+            let mut out = Vec::new();
+            handle(&request, body).write_to(&mut out, false).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            statuses.push(String::from(out.lines().next().unwrap()));
+        }
+        statuses
+    }
+
+    #[test]
+    fn test_requests() {
+        // Keys of their own, since the store is shared with other tests.
+        let raw = "PUT /kv/test%20a HTTP/1.1\r\nContent-Length: 3\r\n\r\none\
+                   PUT /kv/test%20a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   3\r\ntwo\r\n0\r\n\r\n\
+                   GET /kv/test%20a HTTP/1.1\r\n\r\n\
+                   DELETE /kv/test%20a HTTP/1.1\r\n\r\n\
+                   GET /kv/test%20a HTTP/1.1\r\n\r\n\
+                   POST /kv HTTP/1.1\r\n\r\n\
+                   GET /kv/%zz HTTP/1.1\r\n\r\n\
+                   GET /other HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            statuses(raw),
+            [
+                "HTTP/1.1 201 Created",
+                "HTTP/1.1 204 No Content",
+                "HTTP/1.1 200 OK",
+                "HTTP/1.1 204 No Content",
+                "HTTP/1.1 404 Not Found",
+                "HTTP/1.1 405 Method Not Allowed",
+                "HTTP/1.1 400 Bad Request",
+                "HTTP/1.1 404 Not Found",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        for raw in [
+            "GET /kv\r\n\r\n",
+            "GET /kv HTTP/1.1\r\nContent-Length: +3\r\n\r\nabc",
+            "GET /kv HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+            "GET /kv HTTP/1.1\r\nno colon\r\n\r\n",
+        ] {
+            assert!(read_request(&mut raw.as_bytes()).is_err(), "{:?}", raw);
+        }
+        let raw = format!(
+            "PUT /kv/big HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_VALUE_LEN + 1
+        );
+        let mut reader = raw.as_bytes();
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert!(read_body(&mut reader, &request).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_keys_json() {
+        let keys = [String::from("a"), String::from("b\"\\\n")];
+        assert_eq!(keys_json(&keys), "{\"keys\":[\"a\",\"b\\\"\\\\\\u000a\"]}");
+        assert_eq!(keys_json(&[]), "{\"keys\":[]}");
+    }
+}
@@ -0,0 +1,152 @@
+//! A reader-writer lock that works the same with `std` and `axstd`, which has
+//! none.
+
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// A lock held by any number of readers at once, or by one writer.
+///
+/// Threads waiting for the lock yield rather than block, which suits the
+/// short critical sections it is used for. Readers do not take the lock
+/// while a writer waits, so that a steady stream of readers cannot keep
+/// writers out.
+pub struct RwLock<T> {
+    /// The number of readers holding the lock, with [`WRITER`] if a writer
+    /// holds it and [`WRITER_WAITING`] if one waits for it.
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+const WRITER: usize = 1 << (usize::BITS - 1);
+const WRITER_WAITING: usize = 1 << (usize::BITS - 2);
+
+// Like `std::sync::RwLock`, readers on several threads share `&T`.
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Locks for reading, waiting while a writer holds the lock or waits for
+    /// it.
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & (WRITER | WRITER_WAITING) == 0
+                && self
+                    .state
+                    .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return ReadGuard { lock: self };
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// Locks for writing, waiting until no reader or other writer holds the
+    /// lock.
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & !WRITER_WAITING == 0 {
+                // The other writers waiting mark themselves again.
+                if self
+                    .state
+                    .compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return WriteGuard { lock: self };
+                }
+            } else if state & WRITER_WAITING == 0 {
+                self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+/// Shared access to the value of a [`RwLock`], until dropped.
+pub struct ReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Writers wait until the readers are gone.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Exclusive access to the value of a [`RwLock`], until dropped.
+pub struct WriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // No one else holds the lock.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // No one else holds the lock.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Clears the mark of waiting writers too, which they set again.
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_rwlock() {
+        let lock = Arc::new(RwLock::new(0u64));
+        {
+            let a = lock.read();
+            let b = lock.read();
+            assert_eq!(*a + *b, 0);
+        }
+        let writers: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *lock.write() += 1;
+                        assert!(*lock.read() > 0);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(*lock.read(), 4000);
+    }
+}
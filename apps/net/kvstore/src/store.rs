@@ -0,0 +1,105 @@
+//! The values, kept in memory and shared by all connections.
+
+use std::collections::BTreeMap;
+use std::string::String;
+use std::sync::Arc;
+use std::vec::Vec;
+
+use crate::rwlock::RwLock;
+
+/// The maximum size of a value.
+pub const MAX_VALUE_LEN: usize = 64 * 1024;
+/// The maximum length of a key, in bytes.
+pub const MAX_KEY_LEN: usize = 256;
+/// The maximum number of keys.
+pub const MAX_KEYS: usize = 1024;
+
+/// A map of keys to values, read by any number of connections at once.
+///
+/// The values are shared with the responses sending them, so that no copy is
+/// made under the lock.
+pub struct Store {
+    map: RwLock<BTreeMap<String, Arc<Vec<u8>>>>,
+}
+
+/// Why a value was not stored.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PutError {
+    /// The value is longer than [`MAX_VALUE_LEN`].
+    TooLarge,
+    /// [`MAX_KEYS`] keys are stored already.
+    Full,
+}
+
+impl Store {
+    pub const fn new() -> Self {
+        Self {
+            map: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &str) -> Option<Arc<Vec<u8>>> {
+        self.map.read().get(key).cloned()
+    }
+
+    /// Returns the keys, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.map.read().keys().cloned().collect()
+    }
+
+    /// Stores `value` under `key`, returning whether the key is new.
+    pub fn put(&self, key: String, value: Vec<u8>) -> Result<bool, PutError> {
+        if value.len() > MAX_VALUE_LEN {
+            return Err(PutError::TooLarge);
+        }
+        let value = Arc::new(value);
+        let mut map = self.map.write();
+        if map.len() >= MAX_KEYS && !map.contains_key(&key) {
+            return Err(PutError::Full);
+        }
+        Ok(map.insert(key, value).is_none())
+    }
+
+    /// Removes `key`, returning whether it was there.
+    pub fn delete(&self, key: &str) -> bool {
+        self.map.write().remove(key).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    #[test]
+    fn test_store() {
+        let store = Store::new();
+        assert_eq!(store.put(String::from("a"), b"one".to_vec()), Ok(true));
+        assert_eq!(store.put(String::from("a"), b"two".to_vec()), Ok(false));
+        assert_eq!(store.put(String::from("b"), Vec::new()), Ok(true));
+        assert_eq!(store.get("a").unwrap().as_slice(), b"two");
+        assert_eq!(store.get("b").unwrap().as_slice(), b"");
+        assert_eq!(store.keys(), ["a", "b"]);
+
+        assert!(store.delete("b"));
+        assert!(!store.delete("b"));
+        assert_eq!(store.get("b"), None);
+    }
+
+    #[test]
+    fn test_limits() {
+        let store = Store::new();
+        let big = vec![0; MAX_VALUE_LEN + 1];
+        assert_eq!(store.put(String::from("big"), big), Err(PutError::TooLarge));
+        for i in 0..MAX_KEYS {
+            store.put(format!("{}", i), Vec::new()).unwrap();
+        }
+        assert_eq!(
+            store.put(String::from("new"), Vec::new()),
+            Err(PutError::Full)
+        );
+        // replacing a value still works when full
+        assert_eq!(store.put(String::from("0"), b"x".to_vec()), Ok(false));
+    }
+}
@@ -0,0 +1,258 @@
+//! The commands of the shell.
+//!
+//! A command is a line of the table [`COMMANDS`], which gives its name, a
+//! short description for `help` and the function running it.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+/// What the session does after a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Exit,
+}
+
+/// Runs a command with its arguments, writing its output to `out`.
+type Handler = fn(&str, &mut dyn Write) -> io::Result<Flow>;
+
+const COMMANDS: &[(&str, &str, Handler)] = &[
+    ("cat", "<file>...  print files", do_cat),
+    ("exit", "           close the connection", do_exit),
+    ("help", "           list the commands", do_help),
+    ("ls", "[dir]...   list directories", do_ls),
+    ("mem", "           show the heap usage", do_mem),
+    ("uptime", "           show the time since boot", do_uptime),
+];
+
+/// Returns the time since boot.
+#[cfg(feature = "axstd")]
+fn uptime() -> Option<Duration> {
+    Some(std::os::arceos::api::time::ax_current_time())
+}
+
+/// Returns the time since boot, if the host tells it.
+#[cfg(not(feature = "axstd"))]
+fn uptime() -> Option<Duration> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split(' ').next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Returns the bytes in use and still available in the heap.
+#[cfg(feature = "axstd")]
+fn memory() -> Option<(usize, usize)> {
+    Some(std::os::arceos::api::mem::ax_memory_usage())
+}
+
+/// The host does not tell the heap usage of a process.
+#[cfg(not(feature = "axstd"))]
+fn memory() -> Option<(usize, usize)> {
+    None
+}
+
+/// Returns whether `path` is a directory.
+#[cfg(all(feature = "axstd", feature = "fs"))]
+fn is_dir(path: &str) -> bool {
+    // `axstd` opens the entry to read its metadata, which fails for
+    // directories.
+    match std::fs::metadata(path) {
+        Ok(meta) => meta.is_dir(),
+        Err(e) => matches!(e, io::Error::IsADirectory),
+    }
+}
+
+#[cfg(not(feature = "axstd"))]
+fn is_dir(path: &str) -> bool {
+    std::fs::metadata(path).is_ok_and(|meta| meta.is_dir())
+}
+
+/// Returns the sorted names of the entries in the directory at `path`, with
+/// a `/` after those of directories.
+#[cfg(any(not(feature = "axstd"), feature = "fs"))]
+fn list_dir(path: &str) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        #[cfg(feature = "axstd")]
+        let name = entry?.file_name();
+        #[cfg(not(feature = "axstd"))]
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        let full = format!("{}/{}", path.trim_end_matches('/'), name);
+        names.push(if is_dir(&full) {
+            format!("{}/", name)
+        } else {
+            name
+        });
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Listing directories needs a file system.
+#[cfg(all(feature = "axstd", not(feature = "fs")))]
+fn list_dir(_path: &str) -> io::Result<Vec<String>> {
+    Err(io::Error::Unsupported)
+}
+
+/// Copies the file at `path` to `out`.
+#[cfg(any(not(feature = "axstd"), feature = "fs"))]
+fn copy_file(path: &str, out: &mut dyn Write) -> io::Result<()> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = [0; 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        out.write_all(&buf[..n])?;
+    }
+}
+
+/// Reading files needs a file system.
+#[cfg(all(feature = "axstd", not(feature = "fs")))]
+fn copy_file(_path: &str, _out: &mut dyn Write) -> io::Result<()> {
+    Err(io::Error::Unsupported)
+}
+
+fn do_cat(args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    if args.is_empty() {
+        writeln!(out, "cat: no file given")?;
+    }
+    for path in args.split_whitespace() {
+        // Errors writing to the connection end the session, others are
+        // reported.
+        if let Err(e) = copy_file(path, out) {
+            writeln!(out, "cat: {}: {}", path, e)?;
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+fn do_exit(_args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    writeln!(out, "Bye~")?;
+    Ok(Flow::Exit)
+}
+
+fn do_help(_args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    writeln!(out, "Available commands:")?;
+    for (name, help, _) in COMMANDS {
+        writeln!(out, "  {:<6} {}", name, help)?;
+    }
+    Ok(Flow::Continue)
+}
+
+fn do_ls(args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    let dirs: Vec<&str> = match args {
+        "" => vec!["/"],
+        args => args.split_whitespace().collect(),
+    };
+    for dir in dirs.iter() {
+        if dirs.len() > 1 {
+            writeln!(out, "{}:", dir)?;
+        }
+        match list_dir(dir) {
+            Ok(names) => {
+                for name in names {
+                    writeln!(out, "{}", name)?;
+                }
+            }
+            Err(e) => writeln!(out, "ls: {}: {}", dir, e)?,
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+fn do_mem(_args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    match memory() {
+        Some((used, free)) => writeln!(
+            out,
+            "used {} KiB, free {} KiB, total {} KiB",
+            used / 1024,
+            free / 1024,
+            (used + free) / 1024
+        )?,
+        None => writeln!(out, "mem: the heap usage is not known")?,
+    }
+    Ok(Flow::Continue)
+}
+
+fn do_uptime(_args: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    match uptime() {
+        Some(uptime) => {
+            let secs = uptime.as_secs();
+            writeln!(
+                out,
+                "up {}:{:02}:{:02}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )?
+        }
+        None => writeln!(out, "uptime: the time since boot is not known")?,
+    }
+    Ok(Flow::Continue)
+}
+
+/// Runs the command `line`, writing its output to `out`.
+pub fn run(line: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(Flow::Continue);
+    }
+    let (name, args) = match line.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+    match COMMANDS.iter().find(|(n, _, _)| *n == name) {
+        Some((_, _, handler)) => handler(args, out),
+        None => {
+            writeln!(out, "{}: command not found", name)?;
+            Ok(Flow::Continue)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(line: &str) -> (Flow, String) {
+        let mut out = Vec::new();
+        let flow = run(line, &mut out).unwrap();
+        (flow, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let (flow, help) = run_str("help");
+        assert_eq!(flow, Flow::Continue);
+        for (name, _, _) in COMMANDS {
+            assert!(help.contains(&format!("  {} ", name)), "{}", help);
+        }
+        assert_eq!(run_str("  "), (Flow::Continue, String::new()));
+        assert_eq!(
+            run_str("reboot now"),
+            (Flow::Continue, String::from("reboot: command not found\n"))
+        );
+        assert_eq!(run_str("exit"), (Flow::Exit, String::from("Bye~\n")));
+        assert!(run_str("uptime").1.starts_with("up"));
+        assert!(run_str("mem").1.starts_with("mem: "));
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("netshell-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "hello\n").unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        let (_, out) = run_str(&format!("ls {}", dir_str));
+        assert_eq!(out, "a.txt\nsub/\n");
+        let (_, out) = run_str(&format!("cat {0}/a.txt {0}/missing", dir_str));
+        assert!(out.starts_with("hello\ncat: "), "{}", out);
+        assert_eq!(run_str("cat").1, "cat: no file given\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Reading command lines from a connection, with the little editing a
+//! terminal sends in character mode.
+
+use std::io::{self, prelude::*};
+use std::string::String;
+use std::vec::Vec;
+
+/// The maximum length of a line, in bytes. Longer lines are cut.
+const MAX_LINE_LEN: usize = 256;
+
+const BS: u8 = b'\x08';
+const DEL: u8 = b'\x7f';
+/// Starts a Telnet command (RFC 854).
+const IAC: u8 = 255;
+/// Starts a Telnet subnegotiation, which ends with `IAC SE`.
+const SB: u8 = 250;
+const SE: u8 = 240;
+/// `WILL`, `WONT`, `DO` and `DONT`, which take an option.
+const WILL: u8 = 251;
+const DONT: u8 = 254;
+
+/// Reads lines from `inner`.
+///
+/// Lines may end with CR, LF, CR LF or CR NUL. Backspace and delete remove
+/// the last character, other control characters are dropped, and Telnet
+/// commands are skipped.
+pub struct LineReader<S> {
+    inner: S,
+    buf: [u8; 512],
+    pos: usize,
+    len: usize,
+    /// Whether the last line ended with CR, so that an LF or NUL right after
+    /// it belongs to it.
+    after_cr: bool,
+}
+
+impl<S: Read> LineReader<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            buf: [0; 512],
+            pos: 0,
+            len: 0,
+            after_cr: false,
+        }
+    }
+
+    /// Returns the connection, to write to it.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.pos == self.len {
+            self.len = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+            if self.len == 0 {
+                return Ok(None);
+            }
+        }
+        self.pos += 1;
+        Ok(Some(self.buf[self.pos - 1]))
+    }
+
+    /// Skips the rest of a Telnet command after `IAC`, returning the data
+    /// byte 255 if it was escaped as `IAC IAC`.
+    fn skip_command(&mut self) -> io::Result<Option<u8>> {
+        match self.next_byte()? {
+            Some(IAC) => return Ok(Some(IAC)),
+            Some(WILL..=DONT) => {
+                self.next_byte()?;
+            }
+            Some(SB) => {
+                let mut last = 0;
+                while let Some(b) = self.next_byte()? {
+                    if last == IAC && b == SE {
+                        break;
+                    }
+                    last = b;
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Reads the next line, without its ending. Returns `None` once the
+    /// connection is closed.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line: Vec<u8> = Vec::new();
+        loop {
+            let Some(mut b) = self.next_byte()? else {
+                return Ok(None);
+            };
+            if core::mem::take(&mut self.after_cr) && matches!(b, b'\n' | 0) {
+                continue;
+            }
+            if b == IAC {
+                match self.skip_command()? {
+                    Some(escaped) => b = escaped,
+                    None => continue,
+                }
+            }
+            match b {
+                b'\r' | b'\n' => {
+                    self.after_cr = b == b'\r';
+                    return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                }
+                BS | DEL => {
+                    // Removes a whole UTF-8 character.
+                    while let Some(last) = line.pop() {
+                        if last & 0xc0 != 0x80 {
+                            break;
+                        }
+                    }
+                }
+                b if b < b' ' && b != b'\t' => {}
+                b if line.len() < MAX_LINE_LEN => line.push(b),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(input: &[u8]) -> Vec<String> {
+        let mut reader = LineReader::new(input);
+        let mut lines = Vec::new();
+        while let Some(line) = reader.read_line().unwrap() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn test_endings() {
+        assert_eq!(
+            lines(b"a\nb\r\nc\rd\r\0e\n\n"),
+            ["a", "b", "c", "d", "e", ""]
+        );
+        // A line cut by the end of the connection is dropped.
+        assert_eq!(lines(b"a\nunfinished"), ["a"]);
+    }
+
+    #[test]
+    fn test_editing() {
+        assert_eq!(lines(b"lx\x08s\n\x7f\x7fok\n"), ["ls", "ok"]);
+        assert_eq!(lines("é\x7fe\n".as_bytes()), ["e"]);
+        assert_eq!(lines(b"a\x1bb\tc\n"), ["ab\tc"]);
+        let long = [b'x'; MAX_LINE_LEN + 10];
+        assert_eq!(lines(&[&long[..], b"\n"].concat())[0].len(), MAX_LINE_LEN);
+    }
+
+    #[test]
+    fn test_telnet() {
+        let input = [
+            &[IAC, 253, 1, IAC, SB, 24, 1, IAC, SE][..],
+            b"help",
+            &[IAC, 241, IAC, IAC],
+            b"\r\0",
+        ]
+        .concat();
+        assert_eq!(lines(&input), ["help\u{fffd}"]);
+    }
+}
@@ -0,0 +1,88 @@
+//! A remote shell, reached with `telnet` or `nc`, for poking at an ArceOS
+//! instance that has nothing but its network up.
+//!
+//! The shell listens on port 2323, or the port given as the first
+//! command-line argument when running on a host, or with `NETSHELL_PORT` when
+//! building for ArceOS, e.g.
+//! `make A=apps/net/netshell NET=y NETSHELL_PORT=23 run`. Each connection gets
+//! a thread of its own, and runs the commands listed by `help` one line at a
+//! time; see the [`cmd`] module.
+//!
+//! `ls` and `cat` need the `fs` feature and `BLK=y` on ArceOS. There is no
+//! authentication: anyone who can reach the port can read the files.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+mod cmd;
+mod line;
+
+use std::io::{self, prelude::*};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+
+use crate::cmd::Flow;
+use crate::line::LineReader;
+
+/// The port the shell listens on, unless another one is given.
+const DEFAULT_PORT: u16 = 2323;
+const PROMPT: &str = "arceos> ";
+
+/// Returns the port to listen on.
+fn port() -> Option<u16> {
+    #[cfg(not(feature = "axstd"))]
+    let port = std::env::args().nth(1);
+    #[cfg(feature = "axstd")]
+    let port = option_env!("NETSHELL_PORT");
+    match port {
+        Some(port) => port.parse().ok(),
+        None => Some(DEFAULT_PORT),
+    }
+}
+
+/// Runs a shell on `stream` until the client exits or goes away.
+fn session(stream: TcpStream) -> io::Result<()> {
+    let mut reader = LineReader::new(stream);
+    let out = reader.get_mut();
+    writeln!(
+        out,
+        "Welcome to the ArceOS network shell. Type `help` for the commands."
+    )?;
+    out.write_all(PROMPT.as_bytes())?;
+    while let Some(line) = reader.read_line()? {
+        if cmd::run(&line, reader.get_mut())? == Flow::Exit {
+            break;
+        }
+        reader.get_mut().write_all(PROMPT.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn serve(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+    println!("listening on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = listener.accept()?;
+        println!("{} connected", peer);
+        thread::spawn(move || match session(stream) {
+            Ok(()) => println!("{} disconnected", peer),
+            Err(e) => println!("{} failed: {}", peer, e),
+        });
+    }
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, network shell!");
+    let Some(port) = port() else {
+        println!("usage: netshell [port]");
+        return;
+    };
+    if let Err(e) = serve(port) {
+        println!("network shell failed: {}", e);
+    }
+}
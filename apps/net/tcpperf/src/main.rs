@@ -0,0 +1,398 @@
+//! TCP throughput tester, in the manner of `iperf`, measuring what the network
+//! stack achieves without the overhead of a protocol such as HTTP.
+//!
+//! In server mode (`-s`), connections are accepted on port 5001, or the one
+//! given with `-p <port>`, and the data sent on them is read and dropped as
+//! fast as possible, with the rate printed every second.
+//!
+//! In client mode (`-c <host>[:<port>]`), a fixed pattern is written to the
+//! server for 10 seconds, for the number of seconds given with `-t`, or until
+//! the number of bytes given with `-n` is sent, and the rate achieved is
+//! reported. With `-d`, the server also sends to the client over a second
+//! connection at the same time, and both directions are reported.
+//!
+//! `-l <bytes>` sets the size of the buffer written or read at once, 16 KiB
+//! by default. The arguments are given on the command line when running on a
+//! host, e.g. `cargo run --release -- -c 127.0.0.1 -d`, and with
+//! `TCPPERF_ARGS` when building for ArceOS, e.g.
+//! `make A=apps/net/tcpperf NET=y TCPPERF_ARGS="-s" run`. Host names need the
+//! `dns` feature on ArceOS.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+mod meter;
+
+use std::io::{self, prelude::*};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::thread;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::meter::{Meter, Summary};
+
+/// The port the server listens on, and the client connects to, unless given.
+const DEFAULT_PORT: u16 = 5001;
+/// The size of the buffer written or read at once, unless given.
+const DEFAULT_LEN: usize = 16 * 1024;
+/// How long the client sends for, unless given.
+const DEFAULT_SECS: u64 = 10;
+/// The maximum length of the line that starts a connection.
+const MAX_HELLO_LEN: usize = 64;
+
+#[cfg(feature = "axstd")]
+fn invalid_data() -> io::Error {
+    io::Error::InvalidData
+}
+
+#[cfg(not(feature = "axstd"))]
+fn invalid_data() -> io::Error {
+    io::Error::from(io::ErrorKind::InvalidData)
+}
+
+/// When a sender stops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Limit {
+    Secs(u64),
+    Bytes(u64),
+}
+
+impl Limit {
+    /// Returns whether a sender that has sent `sent` bytes for `elapsed`
+    /// should stop.
+    fn reached(self, elapsed: Duration, sent: u64) -> bool {
+        match self {
+            Limit::Secs(secs) => elapsed >= Duration::from_secs(secs),
+            Limit::Bytes(bytes) => sent >= bytes,
+        }
+    }
+}
+
+/// What the client asks the server to do with a connection, in the line
+/// that starts it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hello {
+    /// Read and drop what the client sends: `sink`.
+    Sink,
+    /// Send to the client with buffers of `len` bytes until `limit`:
+    /// `source <len> secs <n>` or `source <len> bytes <n>`.
+    Source { len: usize, limit: Limit },
+}
+
+impl Hello {
+    fn to_line(self) -> String {
+        match self {
+            Hello::Sink => String::from("sink\n"),
+            Hello::Source { len, limit } => match limit {
+                Limit::Secs(secs) => format!("source {} secs {}\n", len, secs),
+                Limit::Bytes(bytes) => format!("source {} bytes {}\n", len, bytes),
+            },
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let hello = match (words.next()?, words.next(), words.next(), words.next()) {
+            ("sink", None, None, None) => Hello::Sink,
+            ("source", Some(len), Some(unit), Some(n)) => {
+                let n = n.parse().ok()?;
+                let limit = match unit {
+                    "secs" => Limit::Secs(n),
+                    "bytes" => Limit::Bytes(n),
+                    _ => return None,
+                };
+                Hello::Source {
+                    len: len.parse().ok().filter(|&len| len > 0)?,
+                    limit,
+                }
+            }
+            _ => return None,
+        };
+        match words.next() {
+            None => Some(hello),
+            Some(_) => None,
+        }
+    }
+
+    /// Reads the line starting a connection from `stream`, a byte at a time
+    /// so that nothing after it is consumed.
+    fn read(stream: &mut TcpStream) -> io::Result<Self> {
+        let mut line = Vec::new();
+        let mut byte = [0];
+        while line.len() < MAX_HELLO_LEN {
+            if stream.read(&mut byte)? == 0 {
+                break;
+            }
+            if byte[0] == b'\n' {
+                let line = core::str::from_utf8(&line).map_err(|_| invalid_data())?;
+                return Self::parse(line).ok_or_else(invalid_data);
+            }
+            line.push(byte[0]);
+        }
+        Err(invalid_data())
+    }
+}
+
+/// What to do, and how.
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    /// The server to connect to, or `None` to be the server.
+    server: Option<String>,
+    port: u16,
+    len: usize,
+    limit: Limit,
+    bidirectional: bool,
+}
+
+/// Parses the arguments, returning why not if they are bad.
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        server: None,
+        port: DEFAULT_PORT,
+        len: DEFAULT_LEN,
+        limit: Limit::Secs(DEFAULT_SECS),
+        bidirectional: false,
+    };
+    let mut server_mode = false;
+    while let Some(arg) = args.next() {
+        let mut value = |what: &str| args.next().ok_or_else(|| format!("{} needs {}", arg, what));
+        let number = |value: String| -> Result<u64, String> {
+            match value.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("{} needs a number of at least 1", arg)),
+            }
+        };
+        match arg.as_str() {
+            "-s" => server_mode = true,
+            "-c" => options.server = Some(value("a server")?),
+            "-p" => match value("a port")?.parse() {
+                Ok(port) => options.port = port,
+                Err(_) => return Err(String::from("-p needs a port")),
+            },
+            "-l" => options.len = number(value("a size")?)? as usize,
+            "-t" => options.limit = Limit::Secs(number(value("a duration")?)?),
+            "-n" => options.limit = Limit::Bytes(number(value("a size")?)?),
+            "-d" => options.bidirectional = true,
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    if server_mode == options.server.is_some() {
+        return Err(String::from("expected either -s or -c"));
+    }
+    Ok(options)
+}
+
+/// Returns the command-line arguments, or the ones the app was built with on
+/// ArceOS.
+fn args() -> Vec<String> {
+    #[cfg(not(feature = "axstd"))]
+    let args = std::env::args().skip(1).collect();
+    #[cfg(feature = "axstd")]
+    let args = option_env!("TCPPERF_ARGS")
+        .unwrap_or("")
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    args
+}
+
+/// Returns a buffer of `len` bytes of the pattern sent.
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| i as u8).collect()
+}
+
+/// Writes the pattern to `stream` with buffers of `len` bytes until `limit`.
+fn send(stream: &mut TcpStream, len: usize, limit: Limit, label: &str) -> io::Result<Summary> {
+    let buf = pattern(len);
+    let mut meter = Meter::new(label);
+    while !limit.reached(meter.elapsed(), meter.total()) {
+        let n = match limit {
+            Limit::Bytes(bytes) => len.min((bytes - meter.total()) as usize),
+            Limit::Secs(_) => len,
+        };
+        stream.write_all(&buf[..n])?;
+        meter.add(n);
+    }
+    Ok(meter.finish())
+}
+
+/// Reads and drops what is sent on `stream` with a buffer of `len` bytes,
+/// until the peer closes it.
+fn receive(stream: &mut TcpStream, len: usize, label: &str) -> io::Result<Summary> {
+    let mut buf = vec![0; len];
+    let mut meter = Meter::new(label);
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(meter.finish());
+        }
+        meter.add(n);
+    }
+}
+
+/// Serves a connection from `peer`, reading with buffers of `len` bytes.
+fn serve(mut stream: TcpStream, peer: SocketAddr, len: usize) -> io::Result<Summary> {
+    match Hello::read(&mut stream)? {
+        Hello::Sink => receive(&mut stream, len, &format!("{} recv", peer)),
+        Hello::Source { len, limit } => send(&mut stream, len, limit, &format!("{} send", peer)),
+    }
+}
+
+fn server(options: &Options) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, options.port))?;
+    println!("listening on {}", listener.local_addr()?);
+    let len = options.len;
+    loop {
+        let (stream, peer) = listener.accept()?;
+        println!("connection from {}", peer);
+        thread::spawn(move || match serve(stream, peer, len) {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => println!("connection from {} failed: {}", peer, e),
+        });
+    }
+}
+
+/// Sends to the server at `addr`, and receives from it at the same time if
+/// `options.bidirectional` is set, returning the summary of each direction.
+fn client(addr: SocketAddr, options: &Options) -> io::Result<(Summary, Option<Summary>)> {
+    let (len, limit) = (options.len, options.limit);
+    let receiver = options.bidirectional.then(|| {
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr)?;
+            stream.write_all(Hello::Source { len, limit }.to_line().as_bytes())?;
+            receive(&mut stream, len, "recv")
+        })
+    });
+    let mut stream = TcpStream::connect(addr)?;
+    stream.write_all(Hello::Sink.to_line().as_bytes())?;
+    let sent = send(&mut stream, len, limit, "send")?;
+    drop(stream);
+    let received = match receiver.map(|receiver| receiver.join()) {
+        None => None,
+        Some(Ok(result)) => Some(result?),
+        Some(Err(_)) => return Err(invalid_data()),
+    };
+    Ok((sent, received))
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let Some(host) = &options.server else {
+        return server(&options);
+    };
+    let addr = match host.parse::<SocketAddr>() {
+        Ok(addr) => Some(addr),
+        Err(_) => (host.as_str(), options.port).to_socket_addrs()?.next(),
+    };
+    let Some(addr) = addr else {
+        println!("cannot resolve {}", host);
+        return Ok(());
+    };
+    println!("connecting to {}", addr);
+    let (sent, received) = client(addr, &options)?;
+    println!("- - - - - - - - - - - - - - - - - - - - - - - - -");
+    println!("{}", sent);
+    if let Some(received) = received {
+        println!("{}", received);
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, TCP throughput tester!");
+    match parse_options(args().into_iter()) {
+        Ok(options) => {
+            if let Err(e) = run(options) {
+                println!("tcpperf failed: {}", e);
+            }
+        }
+        Err(reason) => {
+            println!("{}", reason);
+            println!("usage: tcpperf -s [-p port] [-l len]");
+            println!("       tcpperf -c host[:port] [-t secs | -n bytes] [-l len] [-d]");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        parse_options(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse("-s").unwrap();
+        assert_eq!(options.server, None);
+        assert_eq!((options.port, options.len), (DEFAULT_PORT, DEFAULT_LEN));
+
+        let options = parse("-c 10.0.2.2 -p 6000 -l 1024 -n 1000000 -d").unwrap();
+        assert_eq!(options.server.as_deref(), Some("10.0.2.2"));
+        assert_eq!((options.port, options.len), (6000, 1024));
+        assert_eq!(options.limit, Limit::Bytes(1_000_000));
+        assert!(options.bidirectional);
+        assert_eq!(parse("-c host -t 3").unwrap().limit, Limit::Secs(3));
+
+        for args in ["", "-s -c host", "-c", "-c host -l 0", "-s -p x", "-s -x"] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_hello() {
+        for hello in [
+            Hello::Sink,
+            Hello::Source {
+                len: 100,
+                limit: Limit::Secs(3),
+            },
+            Hello::Source {
+                len: 1,
+                limit: Limit::Bytes(5),
+            },
+        ] {
+            assert_eq!(Hello::parse(&hello.to_line()), Some(hello));
+        }
+        for line in [
+            "",
+            "sink 1",
+            "source 0 secs 1",
+            "source 1 hours 1",
+            "source 1 secs",
+        ] {
+            assert_eq!(Hello::parse(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut summaries = Vec::new();
+            let mut threads = Vec::new();
+            for _ in 0..2 {
+                let (stream, peer) = listener.accept().unwrap();
+                threads.push(thread::spawn(move || serve(stream, peer, 1000)));
+            }
+            for thread in threads {
+                summaries.push(thread.join().unwrap().unwrap());
+            }
+            summaries
+        });
+        let options = parse(&format!("-c {} -n 100000 -l 3000 -d", addr)).unwrap();
+        let (sent, received) = client(addr, &options).unwrap();
+        assert_eq!(sent.bytes, 100_000);
+        assert_eq!(received.unwrap().bytes, 100_000);
+        for summary in server.join().unwrap() {
+            assert_eq!(summary.bytes, 100_000);
+        }
+    }
+}
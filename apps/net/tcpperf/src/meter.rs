@@ -0,0 +1,168 @@
+//! Measuring how fast data goes through a connection, and reporting it like
+//! `iperf`.
+
+use std::fmt;
+use std::string::String;
+use std::time::{Duration, Instant};
+
+/// How often the rate is reported while data is going through.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns `bytes` in the largest binary unit that keeps it above 1, as
+/// `iperf` does.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[(u64, &str)] = &[
+        (1 << 30, "GBytes"),
+        (1 << 20, "MBytes"),
+        (1 << 10, "KBytes"),
+    ];
+    for &(size, unit) in UNITS {
+        if bytes >= size {
+            return format!("{:.2} {}", bytes as f64 / size as f64, unit);
+        }
+    }
+    format!("{} Bytes", bytes)
+}
+
+/// The amount of data that went through a connection in a period of time.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    /// What the connection was used for, and by whom if not obvious.
+    pub label: String,
+    /// The start and the end of the period, since the measurement started.
+    pub from: Duration,
+    pub to: Duration,
+    pub bytes: u64,
+}
+
+impl Summary {
+    /// Returns the rate, in megabits per second.
+    pub fn mbits_per_sec(&self) -> f64 {
+        let secs = (self.to - self.from).as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.bytes as f64 * 8.0 / secs / 1_000_000.0
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {:5.1}-{:5.1} sec  {:>12}  {:8.2} Mbits/sec",
+            self.label,
+            self.from.as_secs_f64(),
+            self.to.as_secs_f64(),
+            format_bytes(self.bytes),
+            self.mbits_per_sec()
+        )
+    }
+}
+
+/// Counts the bytes going through a connection, printing the rate of each
+/// interval as it ends.
+pub struct Meter {
+    label: String,
+    start: Instant,
+    /// The start of the current interval, since `start`.
+    interval_start: Duration,
+    interval_bytes: u64,
+    total: u64,
+}
+
+impl Meter {
+    /// Starts measuring, printing reports with `label`.
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: String::from(label),
+            start: Instant::now(),
+            interval_start: Duration::ZERO,
+            interval_bytes: 0,
+            total: 0,
+        }
+    }
+
+    /// Returns the time since the measurement started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Returns the number of bytes counted so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Counts `n` more bytes, and reports the interval if it has ended.
+    pub fn add(&mut self, n: usize) {
+        self.total += n as u64;
+        self.interval_bytes += n as u64;
+        let now = self.elapsed();
+        if now - self.interval_start >= INTERVAL {
+            println!(
+                "{}",
+                self.summary(self.interval_start, now, self.interval_bytes)
+            );
+            self.interval_start = now;
+            self.interval_bytes = 0;
+        }
+    }
+
+    fn summary(&self, from: Duration, to: Duration, bytes: u64) -> Summary {
+        Summary {
+            label: self.label.clone(),
+            from,
+            to,
+            bytes,
+        }
+    }
+
+    /// Stops measuring, returning the summary of the whole measurement.
+    pub fn finish(self) -> Summary {
+        self.summary(Duration::ZERO, self.elapsed(), self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 Bytes");
+        assert_eq!(format_bytes(1536), "1.50 KBytes");
+        assert_eq!(format_bytes(112 << 20), "112.00 MBytes");
+        assert_eq!(format_bytes(5 << 29), "2.50 GBytes");
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary {
+            label: String::from("send"),
+            from: Duration::from_secs(1),
+            to: Duration::from_secs(3),
+            bytes: 250_000_000,
+        };
+        assert_eq!(summary.mbits_per_sec(), 1000.0);
+        assert_eq!(
+            summary.to_string(),
+            "[send]   1.0-  3.0 sec  238.42 MBytes   1000.00 Mbits/sec"
+        );
+        let empty = Summary {
+            to: Duration::from_secs(1),
+            ..summary
+        };
+        assert_eq!(empty.mbits_per_sec(), 0.0);
+    }
+
+    #[test]
+    fn test_meter() {
+        let mut meter = Meter::new("recv");
+        meter.add(100);
+        meter.add(50);
+        let summary = meter.finish();
+        assert_eq!((summary.label.as_str(), summary.bytes), ("recv", 150));
+        assert_eq!(summary.from, Duration::ZERO);
+    }
+}
@@ -1,3 +1,13 @@
+//! Simple UDP echo server.
+//!
+//! Every datagram received on port 5555 is sent back to its sender as is, so
+//! the UDP path can be checked with e.g. `iperf -u` or a small script. Each
+//! datagram is logged when built with `LOG=info`, and a line with the packet
+//! and byte counts is printed every few seconds while datagrams arrive.
+//!
+//! Datagrams of up to 64 KiB are accepted; those larger than the MTU also need
+//! IP fragmentation in the network stack.
+
 #![cfg_attr(feature = "axstd", no_std)]
 #![cfg_attr(feature = "axstd", no_main)]
 
@@ -5,34 +15,98 @@
 #[cfg(feature = "axstd")]
 extern crate axstd as std;
 
+macro_rules! info {
+    ($($arg:tt)*) => {
+        match option_env!("LOG") {
+            Some("info") | Some("debug") | Some("trace") => {
+                print!("[INFO] {}\n", format_args!($($arg)*));
+            }
+            _ => {}
+        }
+    };
+}
+
 use std::io;
-use std::net::{ToSocketAddrs, UdpSocket};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
 
 const LOCAL_IP: &str = "0.0.0.0";
 const LOCAL_PORT: u16 = 5555;
 
-fn receive_loop() -> io::Result<()> {
-    let addr = (LOCAL_IP, LOCAL_PORT).to_socket_addrs()?.next().unwrap();
-    let socket = UdpSocket::bind(addr)?;
+/// The largest datagram that is received whole.
+const MAX_DATAGRAM_LEN: usize = 64 * 1024;
+/// How often the statistics are printed.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Counts the echoed datagrams.
+struct Stats {
+    packets: u64,
+    bytes: u64,
+    /// The counts when the statistics were last printed.
+    last_packets: u64,
+    last_bytes: u64,
+    last_print: Instant,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            packets: 0,
+            bytes: 0,
+            last_packets: 0,
+            last_bytes: 0,
+            last_print: Instant::now(),
+        }
+    }
+
+    fn record(&mut self, len: usize) {
+        self.packets += 1;
+        self.bytes += len as u64;
+    }
+
+    /// Prints the totals and the rates since the last line, if the interval
+    /// has passed.
+    ///
+    /// This is checked as datagrams arrive, so nothing is printed while the
+    /// server is idle.
+    fn maybe_print(&mut self) {
+        let elapsed = self.last_print.elapsed();
+        if elapsed < STATS_INTERVAL {
+            return;
+        }
+        let secs = elapsed.as_secs_f64();
+        let packets = self.packets - self.last_packets;
+        let bytes = self.bytes - self.last_bytes;
+        println!(
+            "stats: {} packets, {} bytes in total; {:.1} packets/s, {:.1} KiB/s",
+            self.packets,
+            self.bytes,
+            packets as f64 / secs,
+            bytes as f64 / secs / 1024.0,
+        );
+        self.last_packets = self.packets;
+        self.last_bytes = self.bytes;
+        self.last_print = Instant::now();
+    }
+}
+
+fn echo_loop() -> io::Result<()> {
+    let socket = UdpSocket::bind((LOCAL_IP, LOCAL_PORT))?;
     println!("listen on: {}", socket.local_addr().unwrap());
-    let mut buf = [0u8; 1024];
+    let mut buf: Vec<u8> = vec![0; MAX_DATAGRAM_LEN];
+    let mut stats = Stats::new();
     loop {
-        match socket.recv_from(&mut buf) {
-            Ok((size, addr)) => {
-                println!("recv: {}Bytes from {}", size, addr);
-                let mid = core::str::from_utf8(&buf).unwrap();
-                println!("{}", mid);
-                let mid = ["response_", mid].join("");
-                socket.send_to(mid.as_bytes(), addr)?;
-                buf = [0u8; 1024];
-            }
-            Err(e) => return Err(e),
-        };
+        let (len, peer) = socket.recv_from(&mut buf)?;
+        info!("recv {} bytes from {}", len, peer);
+        socket.send_to(&buf[..len], peer)?;
+        stats.record(len);
+        stats.maybe_print();
     }
 }
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
-    println!("Hello, simple udp client!");
-    receive_loop().expect("test udp client failed");
+    println!("Hello, UDP echo server!");
+    echo_loop().expect("test UDP echo server failed");
 }
@@ -0,0 +1,146 @@
+//! Simple HTTP client that fetches a URL.
+//!
+//! The URL is the first command-line argument when running on a host, e.g.
+//! `cargo run -- http://127.0.0.1:5555/`, or the `WGET_URL` environment
+//! variable when building for ArceOS, e.g.
+//! `make A=apps/net/wget NET=y WGET_URL=http://10.0.2.2:5555/ run`.
+//! Only `http://` URLs are supported; host names need the `dns` feature on
+//! ArceOS.
+//!
+//! The body is printed, or saved to the file given as the second argument
+//! (`WGET_OUTPUT` on ArceOS, which needs the `fs` feature and `BLK=y`).
+//! Redirects are followed up to 5 times.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::fmt;
+use std::io::{self, prelude::*, BufReader};
+use std::net::TcpStream;
+use std::string::String;
+
+use http1::{ResponseHead, Url};
+
+/// The maximum number of redirects followed for one fetch.
+const MAX_REDIRECTS: usize = 5;
+
+/// Why a fetch failed.
+enum FetchError {
+    /// The connection or the response failed.
+    Io(io::Error),
+    /// A `Location` header is missing or not a supported URL.
+    BadRedirect(Option<String>),
+    TooManyRedirects,
+}
+
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FetchError::Io(e) => write!(f, "{}", e),
+            FetchError::BadRedirect(Some(location)) => {
+                write!(f, "cannot follow a redirect to {:?}", location)
+            }
+            FetchError::BadRedirect(None) => write!(f, "redirect without a location"),
+            FetchError::TooManyRedirects => write!(f, "more than {} redirects", MAX_REDIRECTS),
+        }
+    }
+}
+
+/// Sends a `GET` request for `url` and copies the body of the response to
+/// `out`, unless the response is a redirect.
+fn get(url: &Url, out: &mut dyn Write) -> io::Result<ResponseHead> {
+    let mut stream = TcpStream::connect((url.connect_host(), url.port))?;
+    stream.write_all(http1::get_request(url, false).as_bytes())?;
+    let mut reader = BufReader::new(stream);
+    let head = http1::read_response_head(&mut reader)?;
+    println!("{} {}", head.status, head.reason);
+    if !head.is_redirect() {
+        http1::copy_body(&head, &mut reader, out)?;
+    }
+    Ok(head)
+}
+
+/// Fetches `url`, following redirects, and copies the final body to `out`.
+fn fetch(mut url: Url, out: &mut dyn Write) -> Result<ResponseHead, FetchError> {
+    for _ in 0..=MAX_REDIRECTS {
+        println!("GET {}", url);
+        let head = get(&url, out)?;
+        if !head.is_redirect() {
+            return Ok(head);
+        }
+        let location = head.header("Location");
+        url = match location.and_then(|location| url.join(location)) {
+            Some(next) => next,
+            None => return Err(FetchError::BadRedirect(location.map(String::from))),
+        };
+    }
+    Err(FetchError::TooManyRedirects)
+}
+
+/// Returns the URL to fetch and the file to save the body to, if any.
+fn args() -> (Option<String>, Option<String>) {
+    #[cfg(not(feature = "axstd"))]
+    let args = (std::env::args().nth(1), std::env::args().nth(2));
+    #[cfg(feature = "axstd")]
+    let args = (
+        option_env!("WGET_URL").map(String::from),
+        option_env!("WGET_OUTPUT").map(String::from),
+    );
+    args
+}
+
+#[cfg(any(not(feature = "axstd"), feature = "fs"))]
+fn create_output(path: &str) -> io::Result<std::fs::File> {
+    std::fs::File::create(path)
+}
+
+/// Saving to a file needs a file system.
+#[cfg(all(feature = "axstd", not(feature = "fs")))]
+fn create_output(_path: &str) -> io::Result<io::Stdout> {
+    Err(io::Error::Unsupported)
+}
+
+fn run(url: Url, output: Option<String>) -> Result<(), FetchError> {
+    match output {
+        Some(path) => {
+            let mut file = create_output(&path)?;
+            let head = fetch(url, &mut file)?;
+            file.flush()?;
+            println!("saved the {} response to {}", head.status, path);
+        }
+        None => {
+            let mut stdout = io::stdout();
+            fetch(url, &mut stdout)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, simple HTTP fetcher!");
+    let (url, output) = args();
+    let Some(url) = url else {
+        println!("usage: wget <http://host[:port]/path> [output file]");
+        return;
+    };
+    match Url::parse(&url) {
+        Some(url) => {
+            if let Err(e) = run(url, output) {
+                println!("fetch failed: {}", e);
+            }
+        }
+        None => println!("unsupported URL {:?}", url),
+    }
+}
@@ -74,6 +74,8 @@ pub enum AxError {
     ResourceBusy,
     /// The underlying storage (typically, a filesystem) is full.
     StorageFull,
+    /// The I/O operation's timeout expired, causing it to be canceled.
+    TimedOut,
     /// An error returned when an operation could not be completed because an
     /// "end of file" was reached prematurely.
     UnexpectedEof,
@@ -206,6 +208,7 @@ impl AxError {
             PermissionDenied => "Permission denied",
             ResourceBusy => "Resource busy",
             StorageFull => "No storage space",
+            TimedOut => "Timed out",
             UnexpectedEof => "Unexpected end of file",
             Unsupported => "Operation not supported",
             WouldBlock => "Operation would block",
@@ -258,6 +261,7 @@ impl From<AxError> for LinuxError {
             PermissionDenied => LinuxError::EACCES,
             ResourceBusy => LinuxError::EBUSY,
             StorageFull => LinuxError::ENOSPC,
+            TimedOut => LinuxError::ETIMEDOUT,
             Unsupported => LinuxError::ENOSYS,
             UnexpectedEof | WriteZero => LinuxError::EIO,
             WouldBlock => LinuxError::EAGAIN,
@@ -283,7 +287,7 @@ mod tests {
     #[test]
     fn test_try_from() {
         let max_code = core::mem::variant_count::<AxError>() as i32;
-        assert_eq!(max_code, 22);
+        assert_eq!(max_code, 23);
         assert_eq!(max_code, AxError::WriteZero.code());
 
         assert_eq!(AxError::AddrInUse.code(), 1);
@@ -20,6 +20,9 @@
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
+//! - `log-buffer`: Keep the console output in a ring buffer to be read later.
+//! - `serial-mirror`: Write the output of the VGA console to the serial port
+//!    as well, on x86 PCs.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -53,16 +56,26 @@ pub mod irq;
 #[cfg(feature = "paging")]
 pub mod paging;
 
+#[cfg(feature = "log-buffer")]
+pub mod log_buffer;
+
 /// Console input and output.
 pub mod console {
     pub use super::platform::console::*;
 
     /// Write a slice of bytes to the console.
+    #[cfg(not(all(target_arch = "x86_64", platform_family = "x86-pc")))]
     pub fn write_bytes(bytes: &[u8]) {
+        #[cfg(feature = "log-buffer")]
+        crate::log_buffer::write_bytes(bytes);
         for c in bytes {
             putchar(*c);
         }
     }
+
+    /// Show the panic message `args`, which is only logged on this console.
+    #[cfg(not(all(target_arch = "x86_64", platform_family = "x86-pc")))]
+    pub fn panic_banner(_args: core::fmt::Arguments) {}
 }
 
 pub mod keyboard {
@@ -0,0 +1,284 @@
+//! A ring buffer of the console output, kept to be read later, as by `dmesg`,
+//! for the messages printed before anyone watched the screen.
+//!
+//! The output is kept as records of a debug level, a length as two bytes in
+//! little endian, and the bytes: a record for each message of `print_debug`,
+//! at its level, and one for each line of other output, at level 0. The
+//! oldest records are overwritten once the buffer is full.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use spinlock::SpinNoIrq;
+
+/// The size of the buffer, headers of the records included.
+const LOG_BUFFER_SIZE: usize = 64 * 1024;
+/// The size of the level and the length before the bytes of a record.
+const HEADER_LEN: usize = 3;
+/// The maximum length of the bytes of a record; those after are dropped.
+const MAX_RECORD_LEN: usize = LOG_BUFFER_SIZE - HEADER_LEN;
+
+static LOG_BUFFER: SpinNoIrq<LogBuffer> = SpinNoIrq::new(LogBuffer::new());
+
+/// A message or a line of output kept in the buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRecord {
+    /// The debug level of the message, or 0 for other output.
+    pub level: u8,
+    pub bytes: Vec<u8>,
+}
+
+struct LogBuffer {
+    data: [u8; LOG_BUFFER_SIZE],
+    /// Where the oldest record starts, counting all the bytes ever written.
+    start: usize,
+    /// Where the next record starts.
+    end: usize,
+    /// Where the newest record starts if it is a line of output not ended
+    /// yet.
+    open: Option<usize>,
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            data: [0; LOG_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            open: None,
+        }
+    }
+
+    fn byte(&self, offset: usize) -> u8 {
+        self.data[offset % LOG_BUFFER_SIZE]
+    }
+
+    fn set_byte(&mut self, offset: usize, byte: u8) {
+        self.data[offset % LOG_BUFFER_SIZE] = byte;
+    }
+
+    /// Returns the length of the bytes of the record at `offset`.
+    fn record_len(&self, offset: usize) -> usize {
+        u16::from_le_bytes([self.byte(offset + 1), self.byte(offset + 2)]) as usize
+    }
+
+    /// Drops the oldest records until `n` more bytes fit.
+    fn make_room(&mut self, n: usize) {
+        while self.end + n - self.start > LOG_BUFFER_SIZE {
+            self.start += HEADER_LEN + self.record_len(self.start);
+        }
+    }
+
+    /// Starts a record of `level`, returning where it starts.
+    fn begin(&mut self, level: u8) -> usize {
+        self.make_room(HEADER_LEN);
+        let record = self.end;
+        for (i, byte) in [level, 0, 0].into_iter().enumerate() {
+            self.set_byte(record + i, byte);
+        }
+        self.end += HEADER_LEN;
+        self.open = None;
+        record
+    }
+
+    /// Adds `bytes` to the record at `record`, the newest, as far as it may
+    /// grow.
+    fn push(&mut self, record: usize, bytes: &[u8]) {
+        let len = self.record_len(record);
+        let n = bytes.len().min(MAX_RECORD_LEN - len);
+        self.make_room(n);
+        for (i, &byte) in bytes[..n].iter().enumerate() {
+            self.set_byte(self.end + i, byte);
+        }
+        self.end += n;
+        let [low, high] = ((len + n) as u16).to_le_bytes();
+        self.set_byte(record + 1, low);
+        self.set_byte(record + 2, high);
+    }
+
+    /// Keeps `bytes` of output, a line per record.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for line in bytes.split_inclusive(|&c| c == b'\n') {
+            let record = match self.open {
+                Some(record) => record,
+                None => self.begin(0),
+            };
+            self.push(record, line);
+            self.open = if line.ends_with(b"\n") {
+                None
+            } else {
+                Some(record)
+            };
+        }
+    }
+
+    /// Returns the number of bytes of the records from `cursor`.
+    fn len_since(&self, cursor: usize) -> usize {
+        self.end - cursor.clamp(self.start, self.end)
+    }
+
+    /// Copies the bytes of the records from `cursor` to `buf`, returning
+    /// where they end.
+    ///
+    /// The records before `cursor` that are still kept are left out, and the
+    /// line of output not ended yet is ended, so that the output after it is
+    /// in a record after `end`.
+    fn copy_since(&mut self, cursor: usize, buf: &mut Vec<u8>) -> usize {
+        let start = cursor.clamp(self.start, self.end);
+        buf.extend((start..self.end).map(|offset| self.byte(offset)));
+        self.open = None;
+        self.end
+    }
+}
+
+/// Keeps a message as a record of its level.
+struct MessageWriter<'a> {
+    buffer: &'a mut LogBuffer,
+    record: usize,
+}
+
+impl fmt::Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.push(self.record, s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Splits the records copied by [`LogBuffer::copy_since`].
+fn parse_records(mut raw: &[u8]) -> Vec<LogRecord> {
+    let mut records = Vec::new();
+    while raw.len() >= HEADER_LEN {
+        let len = u16::from_le_bytes([raw[1], raw[2]]) as usize;
+        let end = (HEADER_LEN + len).min(raw.len());
+        records.push(LogRecord {
+            level: raw[0],
+            bytes: raw[HEADER_LEN..end].to_vec(),
+        });
+        raw = &raw[end..];
+    }
+    records
+}
+
+/// Keep `bytes` of output other than the messages of `print_debug`.
+pub fn write_bytes(bytes: &[u8]) {
+    LOG_BUFFER.lock().write_bytes(bytes);
+}
+
+/// Keep the message `args` of debug level `level`.
+///
+/// The errors are those formatting `args`; the message is kept as far as it
+/// was formatted.
+pub fn write_message(level: u8, args: fmt::Arguments) -> fmt::Result {
+    let mut buffer = LOG_BUFFER.lock();
+    let record = buffer.begin(level);
+    fmt::write(
+        &mut MessageWriter {
+            buffer: &mut buffer,
+            record,
+        },
+        args,
+    )
+}
+
+/// Return the records kept, oldest first, with the cursor to read those
+/// kept after them with [`read_since`].
+pub fn read_all() -> (Vec<LogRecord>, usize) {
+    read_since(0)
+}
+
+/// Return the records kept since `cursor`, as returned by [`read_all`] or
+/// [`read_since`], oldest first, with the cursor to read those kept after
+/// them.
+///
+/// The records overwritten since `cursor` are lost.
+pub fn read_since(cursor: usize) -> (Vec<LogRecord>, usize) {
+    let mut raw = Vec::new();
+    loop {
+        let mut buffer = LOG_BUFFER.lock();
+        let len = buffer.len_since(cursor);
+        if len <= raw.capacity() {
+            // nothing is allocated if nothing was kept since `cursor`
+            let end = buffer.copy_since(cursor, &mut raw);
+            drop(buffer);
+            return (parse_records(&raw), end);
+        }
+        // allocated without the lock, in case the allocator logs
+        drop(buffer);
+        raw.reserve_exact(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    fn read(buffer: &mut LogBuffer, cursor: usize) -> (Vec<(u8, Vec<u8>)>, usize) {
+        let mut raw = Vec::new();
+        let end = buffer.copy_since(cursor, &mut raw);
+        let records = parse_records(&raw)
+            .into_iter()
+            .map(|record| (record.level, record.bytes))
+            .collect();
+        (records, end)
+    }
+
+    #[test]
+    fn test_records() {
+        let mut buffer = Box::new(LogBuffer::new());
+        buffer.write_bytes(b"boot");
+        buffer.write_bytes(b"ing\nok\n$ ");
+        let record = buffer.begin(2);
+        buffer.push(record, b"[DEV] probed");
+        buffer.write_bytes(b"ls\n");
+
+        let (records, cursor) = read(&mut buffer, 0);
+        assert_eq!(
+            records,
+            vec![
+                (0, b"booting\n".to_vec()),
+                (0, b"ok\n".to_vec()),
+                (0, b"$ ".to_vec()),
+                (2, b"[DEV] probed".to_vec()),
+                (0, b"ls\n".to_vec()),
+            ]
+        );
+        assert_eq!(buffer.len_since(cursor), 0);
+        assert_eq!(read(&mut buffer, cursor), (vec![], cursor));
+
+        // a line read before it ended goes on in a record of its own
+        buffer.write_bytes(b"$ ");
+        assert_eq!(buffer.len_since(cursor), HEADER_LEN + 2);
+        let (records, cursor) = read(&mut buffer, cursor);
+        assert_eq!(records, vec![(0, b"$ ".to_vec())]);
+        buffer.write_bytes(b"exit\n");
+        assert_eq!(read(&mut buffer, cursor).0, vec![(0, b"exit\n".to_vec())]);
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut buffer = Box::new(LogBuffer::new());
+        let line = [b'x'; 99];
+        for i in 0..2000u16 {
+            let record = buffer.begin(1);
+            buffer.push(record, &i.to_le_bytes());
+            buffer.push(record, &line);
+        }
+        let (records, end) = read(&mut buffer, 0);
+        // the records are 104 bytes long, 630 of which fit in the buffer
+        assert_eq!(records.len(), LOG_BUFFER_SIZE / 104);
+        assert_eq!(records[0].1[..2], (2000 - 630u16).to_le_bytes());
+        assert_eq!(records[629].1[..2], 1999u16.to_le_bytes());
+        assert_eq!(end, 2000 * 104);
+
+        // too long a record is cut to fill the buffer
+        let record = buffer.begin(3);
+        buffer.push(record, &vec![b'y'; LOG_BUFFER_SIZE]);
+        let (records, _) = read(&mut buffer, end);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].1.len(), MAX_RECORD_LEN);
+    }
+}
@@ -13,7 +13,12 @@ pub mod console {
     }
 
     /// Set the maximum debug level.
-    pub fn set_max_level(level: u8){
+    pub fn set_max_level(level: u8) -> Result<(), u8> {
+        unimplemented!()
+    }
+
+    /// Returns the maximum debug level.
+    pub fn max_level() -> u8 {
         unimplemented!()
     }
 }
@@ -6,6 +6,14 @@ cfg_if::cfg_if! {
     }
 }
 
+// The VGA console of x86 PCs, tested on the host with the other platforms.
+cfg_if::cfg_if! {
+    if #[cfg(any(test, all(target_arch = "x86_64", platform_family = "x86-pc")))] {
+        #[cfg_attr(test, allow(dead_code))]
+        mod vga_text;
+    }
+}
+
 cfg_if::cfg_if! {
     if #[cfg(all(target_arch = "x86_64", platform_family = "x86-pc"))] {
         mod x86_pc;
@@ -0,0 +1,2486 @@
+//! The state machine of the VGA text consoles: the cells of each console,
+//! the cursor, the escape sequences and the scrollback, without the
+//! hardware, so that it is built and tested on the host as well. The lock
+//! of the consoles, the bytes kept while it is held and the levels of the
+//! debug messages are here for the same reason.
+
+extern crate alloc;
+
+#[cfg(any(feature = "alloc", test))]
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use core::fmt::{self, Write};
+use core::ops::{Deref, DerefMut, Range};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axlog::ColorCode as ConsoleColorCode;
+use spinlock::{SpinNoIrq, SpinNoIrqGuard};
+
+/// The most rows of the vga text buffer, in any mode.
+pub(super) const VGA_MAX_HEIGHT: usize = 50;
+/// The width of the vga text buffer, the same in all modes.
+pub(super) const VGA_BUFFER_WIDTH: usize = 80;
+/// The distance between tab stops, in columns.
+const TAB_WIDTH: usize = 8;
+/// The rows at the bottom of the screen cleared for the message of a panic.
+pub(super) const PANIC_BANNER_ROWS: usize = 6;
+/// The colors of the message of a panic.
+pub(super) const PANIC_COLOR: VgaTextColorCode =
+    VgaTextColorCode::new(VgaTextColor::White, VgaTextColor::Red);
+/// The number of lines scrolled off the screen kept to be viewed again.
+#[cfg(any(feature = "alloc", test))]
+const SCROLLBACK_LINES: usize = 500;
+/// The characters shown by the glyphs 0x01 to 0x1f of code page 437, the
+/// character set of the VGA font.
+const CP437_LOW: &str = "☺☻♥♦♣♠•◘○◙♂♀♪♫☼►◄↕‼¶§▬↨↑↓→←∟↔▲▼";
+/// The characters shown by the glyphs 0x80 to 0xff of code page 437.
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+    ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+    αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+/// The glyph shown for characters not in code page 437.
+pub(super) const REPLACEMENT_GLYPH: u8 = b'?';
+/// The number of virtual consoles, each with its own text, cursor and
+/// colors.
+pub const VGA_CONSOLES: usize = 4;
+/// The default tags of the messages of `print_debug` and their colors, by
+/// level from 1: info, dev, debug and trace.
+const DEBUG_LEVELS: [(&str, VgaTextColor); 4] = [
+    ("[INFO]  ", VgaTextColor::LightGreen),
+    ("[DEV]   ", VgaTextColor::LightBlue),
+    ("[DEBUG] ", VgaTextColor::Yellow),
+    ("[TRACE] ", VgaTextColor::DarkGray),
+];
+/// The number of bytes a CPU can write while it holds the lock of the
+/// console.
+const SPILL_SIZE: usize = 256;
+/// The owner of a `CpuLock` while no CPU holds it.
+const NO_OWNER: usize = usize::MAX;
+
+/// The text modes, by their columns and rows.
+///
+/// Both have 80 columns, with characters 8 pixels wide, in 400 scanlines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VgaMode {
+    /// Characters 16 scanlines high, as the BIOS leaves them.
+    Mode80x25,
+    /// Characters 8 scanlines high.
+    Mode80x50,
+}
+
+/// Returns the glyph of code page 437 showing `c`, if there is one.
+pub(super) fn cp437_glyph(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    match CP437_HIGH.chars().position(|glyph| glyph == c) {
+        Some(i) => Some(0x80 + i as u8),
+        None => CP437_LOW
+            .chars()
+            .position(|glyph| glyph == c)
+            .map(|i| 0x01 + i as u8),
+    }
+}
+
+impl VgaMode {
+    /// Returns the number of rows.
+    pub(super) const fn height(self) -> usize {
+        match self {
+            VgaMode::Mode80x25 => 25,
+            VgaMode::Mode80x50 => 50,
+        }
+    }
+
+    /// Returns the height of characters, in scanlines.
+    pub(super) const fn char_height(self) -> u8 {
+        match self {
+            VgaMode::Mode80x25 => 16,
+            VgaMode::Mode80x50 => 8,
+        }
+    }
+}
+
+/// The standard color palette in VGA text mode.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VgaTextColor {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Purple = 5,
+    Brown = 6,
+    Gray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    LightPurple = 13,
+    Yellow = 14,
+    White = 15,
+}
+
+impl VgaTextColor {
+    fn from_console_color(color: ConsoleColorCode) -> VgaTextColor {
+        match color {
+            ConsoleColorCode::Black => VgaTextColor::Black,
+            ConsoleColorCode::Red => VgaTextColor::Red,
+            ConsoleColorCode::Green => VgaTextColor::Green,
+            ConsoleColorCode::Yellow => VgaTextColor::Brown,
+            ConsoleColorCode::Blue => VgaTextColor::Blue,
+            ConsoleColorCode::Magenta => VgaTextColor::Purple,
+            ConsoleColorCode::Cyan => VgaTextColor::Cyan,
+            ConsoleColorCode::White => VgaTextColor::Gray,
+            ConsoleColorCode::BrightBlack => VgaTextColor::Gray,
+            ConsoleColorCode::BrightRed => VgaTextColor::LightRed,
+            ConsoleColorCode::BrightGreen => VgaTextColor::LightGreen,
+            ConsoleColorCode::BrightYellow => VgaTextColor::Yellow,
+            ConsoleColorCode::BrightBlue => VgaTextColor::LightBlue,
+            ConsoleColorCode::BrightMagenta => VgaTextColor::LightPurple,
+            ConsoleColorCode::BrightCyan => VgaTextColor::LightCyan,
+            ConsoleColorCode::BrightWhite => VgaTextColor::White,
+        }
+    }
+
+    /// Returns the color nearest to `rgb`, by the squared distance between
+    /// the RGB values.
+    fn nearest(rgb: [u8; 3]) -> VgaTextColor {
+        let distance = |other: &[u8; 3]| -> u32 {
+            rgb.iter()
+                .zip(other)
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+                .sum()
+        };
+        VGA_PALETTE
+            .iter()
+            .min_by_key(|(_, other)| distance(other))
+            .map_or(DEFAULT_FG, |&(color, _)| color)
+    }
+
+    /// Returns the color nearest to color `index` of the 256-color palette
+    /// of xterm: the 16 console colors, a 6x6x6 color cube, then a ramp of
+    /// 24 grays.
+    fn from_256(index: u8) -> VgaTextColor {
+        match index {
+            0..=15 => {
+                let code = index + if index < 8 { 30 } else { 90 - 8 };
+                ConsoleColorCode::try_from(code).map_or(DEFAULT_FG, Self::from_console_color)
+            }
+            16..=231 => {
+                let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+                let n = index - 16;
+                Self::nearest([level(n / 36), level(n / 6 % 6), level(n % 6)])
+            }
+            _ => {
+                let gray = 8 + (index - 232) * 10;
+                Self::nearest([gray; 3])
+            }
+        }
+    }
+}
+
+/// The usual RGB values of the colors.
+const VGA_PALETTE: [(VgaTextColor, [u8; 3]); 16] = [
+    (VgaTextColor::Black, [0, 0, 0]),
+    (VgaTextColor::Blue, [0, 0, 170]),
+    (VgaTextColor::Green, [0, 170, 0]),
+    (VgaTextColor::Cyan, [0, 170, 170]),
+    (VgaTextColor::Red, [170, 0, 0]),
+    (VgaTextColor::Purple, [170, 0, 170]),
+    (VgaTextColor::Brown, [170, 85, 0]),
+    (VgaTextColor::Gray, [170, 170, 170]),
+    (VgaTextColor::DarkGray, [85, 85, 85]),
+    (VgaTextColor::LightBlue, [85, 85, 255]),
+    (VgaTextColor::LightGreen, [85, 255, 85]),
+    (VgaTextColor::LightCyan, [85, 255, 255]),
+    (VgaTextColor::LightRed, [255, 85, 85]),
+    (VgaTextColor::LightPurple, [255, 85, 255]),
+    (VgaTextColor::Yellow, [255, 255, 85]),
+    (VgaTextColor::White, [255, 255, 255]),
+];
+
+/// The foreground color used unless set otherwise.
+pub(super) const DEFAULT_FG: VgaTextColor = VgaTextColor::White;
+/// The background color used unless set otherwise.
+pub(super) const DEFAULT_BG: VgaTextColor = VgaTextColor::Black;
+/// Text attributes set by SGR parameters, applied on top of the colors:
+/// bold, shown with a bright foreground since there is no bold font, and
+/// reverse video, which swaps the foreground and background.
+const ATTR_BOLD: u8 = 1 << 0;
+const ATTR_REVERSE: u8 = 1 << 1;
+
+/// A combination of a foreground and a background color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct VgaTextColorCode(pub(super) u8);
+
+impl VgaTextColorCode {
+    /// Create a new `VgaTextColorCode` with the given foreground and background colors.
+    pub const fn new(fg: VgaTextColor, bg: VgaTextColor) -> VgaTextColorCode {
+        VgaTextColorCode((bg as u8) << 4 | (fg as u8))
+    }
+
+    /// Returns this color code with the foreground replaced by `fg`.
+    pub(super) const fn with_fg(self, fg: VgaTextColor) -> VgaTextColorCode {
+        VgaTextColorCode(self.0 & 0xf0 | (fg as u8))
+    }
+
+    /// Returns this color code with the bright variant of its foreground.
+    const fn with_bright_fg(self) -> VgaTextColorCode {
+        VgaTextColorCode(self.0 | 0x08)
+    }
+
+    /// Returns this color code with the foreground and background swapped.
+    const fn reversed(self) -> VgaTextColorCode {
+        VgaTextColorCode(self.0.rotate_right(4))
+    }
+
+    /// Returns this color code with the background replaced by `bg`.
+    ///
+    /// Bright backgrounds blink instead if `BLINK` is set.
+    const fn with_bg(self, bg: VgaTextColor) -> VgaTextColorCode {
+        VgaTextColorCode((bg as u8) << 4 | self.0 & 0x0f)
+    }
+
+    /// Returns the SGR parameters setting the foreground and the background
+    /// of this color code.
+    pub(super) const fn sgr_params(self) -> (u8, u8) {
+        // the foreground codes by VGA color, the background ones being 10 more
+        const CODES: [u8; 16] = [
+            30, 34, 32, 36, 31, 35, 33, 37, 90, 94, 92, 96, 91, 95, 93, 97,
+        ];
+        let (fg, bg) = (self.0 & 0x0f, self.0 >> 4);
+        (CODES[fg as usize], CODES[bg as usize] + 10)
+    }
+}
+
+/// Character for the VGA text buffer, including an ASCII character and a `VgaTextColorCode`.
+#[derive(Clone, Copy)]
+pub(super) struct VgaTextChar(pub(super) u8, pub(super) VgaTextColorCode);
+
+/// A line of the VGA text buffer.
+pub(super) type VgaTextRow = [VgaTextChar; VGA_BUFFER_WIDTH];
+
+/// The rows of the VGA text buffer, as many as in any mode; those past the
+/// height of the mode are not shown.
+#[repr(transparent)]
+pub(super) struct VgaTextBuffer {
+    pub(super) chars: [VgaTextRow; VGA_MAX_HEIGHT],
+}
+
+/// The lines scrolled off the top of the screen, and the view into them.
+#[cfg(any(feature = "alloc", test))]
+pub(super) struct VgaTextScrollback {
+    /// Whether lines are kept, once the heap is ready.
+    enabled: bool,
+    /// The lines, oldest first.
+    lines: VecDeque<VgaTextRow>,
+    /// How many lines the view is scrolled back by; 0 shows live output.
+    pub(super) offset: usize,
+    /// The live screen, written to instead of the VGA buffer while the view
+    /// is scrolled back. It is allocated along with the lines when they start
+    /// being kept, as the view is scrolled from the keyboard interrupt.
+    live: Option<Box<VgaTextBuffer>>,
+    /// Whether new output scrolls the view back to live output, rather than
+    /// only being shown once the view is.
+    pub(super) snap: bool,
+}
+
+#[cfg(any(feature = "alloc", test))]
+impl VgaTextScrollback {
+    /// Starts keeping lines, allocating the memory for them and for the live
+    /// screen.
+    pub(super) fn enable(&mut self) {
+        self.lines.reserve_exact(SCROLLBACK_LINES);
+        self.live = Some(Box::new(VgaTextBuffer {
+            chars: [[VgaTextChar(b' ', VgaTextColorCode::new(DEFAULT_FG, DEFAULT_BG));
+                VGA_BUFFER_WIDTH]; VGA_MAX_HEIGHT],
+        }));
+        self.enabled = true;
+    }
+}
+
+/// The text, colors and cursor of a console, saved by
+/// [`save_screen`](super::console::save_screen) to be put back by
+/// [`restore_screen`](super::console::restore_screen).
+#[cfg(any(feature = "alloc", test))]
+pub struct ScreenSnapshot {
+    /// The rows of text, without the status line.
+    rows: Vec<VgaTextRow>,
+    cursor: VgaTextSavedCursor,
+}
+
+/// The most parameters of a control sequence; longer sequences are ignored.
+const MAX_CSI_PARAMS: usize = 16;
+/// The most bytes of an escape sequence kept to be put as they are if it
+/// turns out invalid; longer sequences are given up on and put.
+const MAX_ESCAPE_LEN: usize = 64;
+
+/// An escape sequence being read.
+#[derive(Clone, Copy)]
+enum VgaTextEscape {
+    // \x1b, to Csi, or 7 or 8 to end
+    Start,
+    // [, then numbers separated by ;, to a final byte such as m
+    //
+    // `len` is the index of the number being read, `MAX_CSI_PARAMS` once
+    // there are too many. `private` is set by a ? before the numbers.
+    Csi {
+        values: [u16; MAX_CSI_PARAMS],
+        len: usize,
+        private: bool,
+    },
+}
+
+/// The cursor position and colors saved by `ESC 7` or `CSI s`.
+#[derive(Clone, Copy)]
+struct VgaTextSavedCursor {
+    x: usize,
+    y: usize,
+    color: VgaTextColorCode,
+    attrs: u8,
+}
+
+#[derive(Clone, Copy)]
+enum VgaTextState {
+    PutChar,
+    Escape(VgaTextEscape),
+}
+
+/// One of the virtual consoles: its text, kept whether it is shown or not,
+/// and how it is being written.
+pub(super) struct VgaTextConsole {
+    pub(super) current_x: usize,
+    pub(super) current_y: usize,
+    pub(super) current_color: VgaTextColorCode,
+    /// The colors SGR 39 and 49 go back to, and SGR 0 to both.
+    default_fg: VgaTextColor,
+    default_bg: VgaTextColor,
+    /// The `ATTR_*` bits set.
+    attrs: u8,
+    saved_cursor: Option<VgaTextSavedCursor>,
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) scrollback: VgaTextScrollback,
+    state: VgaTextState,
+    /// The bytes of the escape sequence being read.
+    escape: [u8; MAX_ESCAPE_LEN],
+    escape_len: usize,
+    /// The bytes of the UTF-8 sequence being read, how many there are, and
+    /// how many it takes; 0 if none is.
+    utf8: [u8; 4],
+    utf8_len: usize,
+    utf8_need: usize,
+    /// The cells of the console, written to instead of the VGA buffer and
+    /// copied to it by `flush_to` while the console is shown, since
+    /// writing to device memory is slow.
+    pub(super) shadow: VgaTextBuffer,
+    /// The rows of `shadow` changed since they were last copied, a bit each.
+    pub(super) dirty_rows: u64,
+    /// The number of rows of the mode.
+    pub(super) height: usize,
+    /// The status line kept on the last row, out of the way of the text, if
+    /// there is one.
+    status: Option<VgaTextRow>,
+    /// The first row of the scroll region and the row after its last, set
+    /// by `CSI r`, if it is not all the rows of text.
+    margins: Option<(usize, usize)>,
+    /// Whether the rows of `CSI H` count from the top of the scroll region,
+    /// rather than of the screen, as set by `CSI ? 6 h`.
+    origin_mode: bool,
+    /// The screen saved by `CSI ? 1049 h`, to be put back by `CSI ? 1049 l`.
+    #[cfg(any(feature = "alloc", test))]
+    alt_screen: Option<ScreenSnapshot>,
+    /// Whether the hardware cursor is shown while the console is, as set by
+    /// `CSI ? 25 h` and `CSI ? 25 l`.
+    pub(super) cursor_visible: bool,
+}
+
+impl VgaTextConsole {
+    pub(super) const fn new() -> Self {
+        Self {
+            current_x: 0,
+            current_y: 0,
+            current_color: VgaTextColorCode::new(DEFAULT_FG, DEFAULT_BG),
+            default_fg: DEFAULT_FG,
+            default_bg: DEFAULT_BG,
+            attrs: 0,
+            saved_cursor: None,
+            #[cfg(any(feature = "alloc", test))]
+            scrollback: VgaTextScrollback {
+                enabled: false,
+                lines: VecDeque::new(),
+                offset: 0,
+                live: None,
+                snap: false,
+            },
+            state: VgaTextState::PutChar,
+            escape: [0; MAX_ESCAPE_LEN],
+            escape_len: 0,
+            utf8: [0; 4],
+            utf8_len: 0,
+            utf8_need: 0,
+            shadow: VgaTextBuffer {
+                chars: [[VgaTextChar(b' ', VgaTextColorCode::new(DEFAULT_FG, DEFAULT_BG));
+                    VGA_BUFFER_WIDTH]; VGA_MAX_HEIGHT],
+            },
+            dirty_rows: 0,
+            height: VgaMode::Mode80x25.height(),
+            status: None,
+            margins: None,
+            origin_mode: false,
+            #[cfg(any(feature = "alloc", test))]
+            alt_screen: None,
+            cursor_visible: true,
+        }
+    }
+
+    /// Returns the screen written to: the cells of the console, unless the
+    /// view is scrolled back.
+    ///
+    /// The rows changed are to be marked with `mark_dirty`.
+    fn screen(&mut self) -> &mut VgaTextBuffer {
+        #[cfg(any(feature = "alloc", test))]
+        if let (Some(live), true) = (&mut self.scrollback.live, self.scrollback.offset > 0) {
+            return live;
+        }
+        &mut self.shadow
+    }
+
+    /// Returns the number of rows the text is written to, all but the last
+    /// if it is the status line.
+    fn text_height(&self) -> usize {
+        self.height - self.status.is_some() as usize
+    }
+
+    /// Returns the rows scrolled by line feeds on the last of them: those of
+    /// the scroll region, or all the rows of text.
+    ///
+    /// A region past the rows of text, as the status line took the last, is
+    /// not kept to.
+    fn scroll_region(&self) -> Range<usize> {
+        match self.margins {
+            Some((top, end)) if end <= self.text_height() => top..end,
+            _ => 0..self.text_height(),
+        }
+    }
+
+    /// Marks the rows `rows` of the cells of the console as changed.
+    pub(super) fn mark_dirty(&mut self, rows: Range<usize>) {
+        for y in rows {
+            self.dirty_rows |= 1 << y;
+        }
+    }
+
+    /// Copies the rows changed since they were last copied to `buffer`, the
+    /// rows of the VGA buffer.
+    pub(super) fn flush_to(&mut self, buffer: &mut [VgaTextRow]) {
+        let rows = self.shadow.chars.iter().zip(buffer).take(self.height);
+        for (y, (row, shown)) in rows.enumerate() {
+            if self.dirty_rows & 1 << y != 0 {
+                *shown = *row;
+            }
+        }
+        self.dirty_rows = 0;
+    }
+
+    /// Changes the number of rows to `height`, clearing the screen.
+    pub(super) fn set_height(&mut self, height: usize) {
+        #[cfg(any(feature = "alloc", test))]
+        self.scroll_view_reset();
+        self.height = height;
+        // the saved position and the scroll region may be off the screen
+        self.saved_cursor = None;
+        self.margins = None;
+        self.clear();
+        self.paint_status();
+    }
+
+    /// Shows `text` in `color` on the last row as the status line, or
+    /// gives the row back to the text if `text` is `None`.
+    ///
+    /// The characters are shown by their glyphs in code page 437, and those
+    /// past the end of the row are dropped.
+    pub(super) fn set_status_line(&mut self, text: Option<(&str, VgaTextColorCode)>) {
+        let Some((text, color)) = text else {
+            if self.status.take().is_some() {
+                let last = (self.height - 1) * VGA_BUFFER_WIDTH;
+                self.erase(last, last + VGA_BUFFER_WIDTH);
+                // the view scrolled back shows the row of the live screen
+                #[cfg(any(feature = "alloc", test))]
+                self.render_view();
+            }
+            return;
+        };
+        if self.status.is_none() && self.current_y == self.height - 1 {
+            // keep the line of the cursor, scrolling it above the status line
+            self.scroll_up(0..self.height, 1);
+            self.current_y -= 1;
+        }
+        if let Some(saved) = &mut self.saved_cursor {
+            saved.y = saved.y.min(self.height - 2);
+        }
+        let mut row = [VgaTextChar(b' ', color); VGA_BUFFER_WIDTH];
+        for (cell, c) in row.iter_mut().zip(text.chars()) {
+            cell.0 = cp437_glyph(c).unwrap_or(REPLACEMENT_GLYPH);
+        }
+        self.status = Some(row);
+        self.paint_status();
+    }
+
+    /// Puts the status line on the last row, of the live screen as well as
+    /// of the cells shown while the view is scrolled back.
+    fn paint_status(&mut self) {
+        let Some(status) = self.status else {
+            return;
+        };
+        let y = self.height - 1;
+        self.shadow.chars[y] = status;
+        #[cfg(any(feature = "alloc", test))]
+        if let Some(live) = &mut self.scrollback.live {
+            live.chars[y] = status;
+        }
+        self.mark_dirty(y..y + 1);
+    }
+
+    /// Puts `ch` unless it is part of an escape sequence, decoding UTF-8.
+    ///
+    /// ASCII bytes are taken as they are; other characters are shown by
+    /// their glyphs in code page 437, or `REPLACEMENT_GLYPH`, as are invalid
+    /// sequences.
+    pub(super) fn write_byte(&mut self, ch: u8) {
+        #[cfg(any(feature = "alloc", test))]
+        if self.scrollback.snap && self.scrollback.offset > 0 {
+            self.scroll_view_reset();
+        }
+        if self.utf8_need == 0 && ch.is_ascii() {
+            self.write_ascii(ch);
+        } else {
+            self.decode_utf8(ch);
+        }
+    }
+
+    fn write_ascii(&mut self, ch: u8) {
+        if !self.process_char(ch) {
+            self.putchar(ch);
+        }
+    }
+
+    /// Takes `ch` as part of a UTF-8 sequence, putting the character once
+    /// the sequence is complete.
+    fn decode_utf8(&mut self, ch: u8) {
+        if self.utf8_need > 0 {
+            if ch & 0xc0 == 0x80 {
+                self.utf8[self.utf8_len] = ch;
+                self.utf8_len += 1;
+                if self.utf8_len == self.utf8_need {
+                    // overlong forms and surrogates are invalid too
+                    let c = core::str::from_utf8(&self.utf8[..self.utf8_len])
+                        .ok()
+                        .and_then(|s| s.chars().next());
+                    self.utf8_need = 0;
+                    self.put_char(c);
+                }
+                return;
+            }
+            // cut short, and `ch` starts something else
+            self.utf8_need = 0;
+            self.put_char(None);
+            if ch.is_ascii() {
+                self.write_ascii(ch);
+                return;
+            }
+        }
+        self.utf8_need = match ch {
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => 0,
+        };
+        if self.utf8_need == 0 {
+            self.put_char(None);
+        } else {
+            self.utf8[0] = ch;
+            self.utf8_len = 1;
+        }
+    }
+
+    /// Puts the glyph of the non-ASCII character `c`, or `REPLACEMENT_GLYPH`
+    /// if there is none or it is `None`, which ends any escape sequence.
+    fn put_char(&mut self, c: Option<char>) {
+        if !matches!(self.state, VgaTextState::PutChar) {
+            self.abort_escape();
+        }
+        let glyph = c.and_then(cp437_glyph).unwrap_or(REPLACEMENT_GLYPH);
+        self.put_glyph(glyph);
+    }
+
+    /// Scrolls the rows `rows` up by `line` rows, blanking the rows exposed
+    /// at the bottom of them, and leaves the cursor where it is.
+    ///
+    /// Only the rows scrolled off the top of the screen are kept to be
+    /// scrolled back to.
+    fn scroll_up(&mut self, rows: Range<usize>, line: usize) {
+        if line == 0 || line > rows.len() {
+            return;
+        }
+
+        #[cfg(any(feature = "alloc", test))]
+        if rows.start == 0 {
+            self.keep_scrolled_off(line);
+        }
+
+        self.shift_rows(rows.start, rows.end, -(line as isize));
+    }
+
+    /// Moves the rows from `start` to `end` down by `delta` rows, or up if it
+    /// is negative, dropping those moved past `start` or `end` and blanking
+    /// those left behind.
+    fn shift_rows(&mut self, start: usize, end: usize, delta: isize) {
+        let line = delta.unsigned_abs().min(end - start);
+        let color = self.cell_color();
+        let buffer = &mut self.screen().chars;
+
+        let blank = if delta < 0 {
+            buffer.copy_within(start + line..end, start);
+            end - line..end
+        } else {
+            buffer.copy_within(start..end - line, start + line);
+            start..start + line
+        };
+        for row in &mut buffer[blank] {
+            for c in row.iter_mut() {
+                *c = VgaTextChar(b' ', color);
+            }
+        }
+        self.mark_dirty(start..end);
+    }
+
+    /// Returns a copy of the rows of text, the cursor and the colors.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn save_screen(&mut self) -> ScreenSnapshot {
+        let height = self.text_height();
+        ScreenSnapshot {
+            rows: self.screen().chars[..height].to_vec(),
+            cursor: VgaTextSavedCursor {
+                x: self.current_x,
+                y: self.current_y,
+                color: self.current_color,
+                attrs: self.attrs,
+            },
+        }
+    }
+
+    /// Puts back the rows of text, the cursor and the colors of `snapshot`,
+    /// as far as the rows fit, blanking the rows it does not have.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn restore_screen(&mut self, snapshot: &ScreenSnapshot) {
+        let height = self.text_height();
+        let blank = [VgaTextChar(b' ', self.default_color()); VGA_BUFFER_WIDTH];
+        for (y, row) in self.screen().chars[..height].iter_mut().enumerate() {
+            *row = snapshot.rows.get(y).copied().unwrap_or(blank);
+        }
+        let cursor = snapshot.cursor;
+        self.current_x = cursor.x;
+        self.current_y = cursor.y.min(height - 1);
+        self.current_color = cursor.color;
+        self.attrs = cursor.attrs;
+        self.mark_dirty(0..height);
+    }
+
+    /// Keeps the top `line` rows of the screen, about to be scrolled off.
+    #[cfg(any(feature = "alloc", test))]
+    fn keep_scrolled_off(&mut self, line: usize) {
+        if !self.scrollback.enabled {
+            return;
+        }
+        for y in 0..line {
+            let row = self.screen().chars[y];
+            let lines = &mut self.scrollback.lines;
+            if lines.len() == SCROLLBACK_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(row);
+        }
+        let scrollback = &mut self.scrollback;
+        if scrollback.offset > 0 {
+            // keep the view on the same lines, as far as they are kept
+            scrollback.offset = (scrollback.offset + line).min(scrollback.lines.len());
+            self.render_view();
+        }
+    }
+
+    /// Shows the lines `offset` lines back from the live screen.
+    #[cfg(any(feature = "alloc", test))]
+    fn render_view(&mut self) {
+        let scrollback = &self.scrollback;
+        let (Some(live), true) = (&scrollback.live, scrollback.offset > 0) else {
+            return;
+        };
+        let first = scrollback.lines.len() - scrollback.offset;
+        for y in 0..self.text_height() {
+            let i = first + y;
+            self.shadow.chars[y] = match scrollback.lines.get(i) {
+                Some(row) => *row,
+                None => live.chars[i - scrollback.lines.len()],
+            };
+        }
+        self.mark_dirty(0..self.text_height());
+    }
+
+    /// Scrolls the view `lines` further back, as far as lines are kept.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn scroll_view_up(&mut self, lines: usize) {
+        let scrollback = &mut self.scrollback;
+        let offset = (scrollback.offset + lines).min(scrollback.lines.len());
+        let Some(live) = &mut scrollback.live else {
+            return;
+        };
+        if offset == scrollback.offset {
+            return;
+        }
+        if scrollback.offset == 0 {
+            live.chars = self.shadow.chars;
+        }
+        scrollback.offset = offset;
+        self.render_view();
+    }
+
+    /// Scrolls the view `lines` forward, back to live output at most.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn scroll_view_down(&mut self, lines: usize) {
+        let offset = self.scrollback.offset.saturating_sub(lines);
+        if offset == 0 {
+            self.scroll_view_reset();
+        } else {
+            self.scrollback.offset = offset;
+            self.render_view();
+        }
+    }
+
+    /// Shows live output again.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn scroll_view_reset(&mut self) {
+        if let (Some(live), true) = (&self.scrollback.live, self.scrollback.offset > 0) {
+            self.shadow.chars = live.chars;
+            self.mark_dirty(0..self.height);
+        }
+        self.scrollback.offset = 0;
+    }
+
+    /// Takes `ch` as part of an escape sequence, returning whether it is one.
+    ///
+    /// The bytes of a sequence that turns out invalid are put as they are.
+    fn process_char(&mut self, ch: u8) -> bool {
+        let consumed = match &mut self.state {
+            VgaTextState::PutChar => {
+                if ch != 0x1b {
+                    return false;
+                }
+                self.state = VgaTextState::Escape(VgaTextEscape::Start);
+                true
+            }
+            VgaTextState::Escape(VgaTextEscape::Start) => match ch {
+                b'[' => {
+                    self.state = VgaTextState::Escape(VgaTextEscape::Csi {
+                        values: [0; MAX_CSI_PARAMS],
+                        len: 0,
+                        private: false,
+                    });
+                    true
+                }
+                b'7' | b'8' => {
+                    self.state = VgaTextState::PutChar;
+                    self.escape_len = 0;
+                    if ch == b'7' {
+                        self.save_cursor();
+                    } else {
+                        self.restore_cursor();
+                    }
+                    return true;
+                }
+                _ => false,
+            },
+            VgaTextState::Escape(VgaTextEscape::Csi {
+                values,
+                len,
+                private,
+            }) => match ch {
+                0x40..=0x7e => {
+                    // an empty parameter, as in `\x1b[m`, is 0
+                    let (values, count, private) = (*values, *len + 1, *private);
+                    self.state = VgaTextState::PutChar;
+                    self.escape_len = 0;
+                    if count <= MAX_CSI_PARAMS {
+                        self.run_csi(ch, &values[..count], private);
+                    }
+                    return true;
+                }
+                // only right after the [
+                b'?' if self.escape_len == 2 => {
+                    *private = true;
+                    true
+                }
+                b';' => {
+                    *len = (*len + 1).min(MAX_CSI_PARAMS);
+                    true
+                }
+                ch_val @ b'0'..=b'9' => {
+                    if let Some(v) = values.get_mut(*len) {
+                        let digit = (ch_val - b'0') as u16;
+                        *v = v.saturating_mul(10).saturating_add(digit);
+                    }
+                    true
+                }
+                _ => false,
+            },
+        };
+
+        if !consumed {
+            // not an escape sequence after all, `ch` may start another one
+            self.abort_escape();
+            return self.process_char(ch);
+        }
+        if self.escape_len == MAX_ESCAPE_LEN {
+            self.abort_escape();
+            return false;
+        }
+        self.escape[self.escape_len] = ch;
+        self.escape_len += 1;
+        true
+    }
+
+    /// Runs the control sequence ending with `final_byte`, with a ? before
+    /// its parameters if `private` is set, ignoring those that are not
+    /// supported.
+    fn run_csi(&mut self, final_byte: u8, params: &[u16], private: bool) {
+        // the number of rows or columns to move by, or the row or column to
+        // move to counting from 1, and 1 if 0 or missing
+        let count = |i: usize| match params.get(i) {
+            Some(&0) | None => 1,
+            Some(&n) => n as usize,
+        };
+        if private {
+            if matches!(final_byte, b'h' | b'l') {
+                for &mode in params {
+                    self.set_private_mode(mode, final_byte == b'h');
+                }
+            }
+            return;
+        }
+        let region = self.scroll_region();
+        // moves up and down stop at the margins from inside the region
+        let (top, end) = match region.contains(&self.current_y) {
+            true => (region.start, region.end),
+            false => (0, self.text_height()),
+        };
+        match final_byte {
+            b'm' => {
+                let mut params = params.iter().copied();
+                while let Some(code) = params.next() {
+                    match code {
+                        38 | 48 => {
+                            // The color takes the parameters after it, which
+                            // are all ignored if they are not as expected.
+                            let Some(color) = Self::extended_color(&mut params) else {
+                                break;
+                            };
+                            self.current_color = match code {
+                                38 => self.current_color.with_fg(color),
+                                _ => self.current_color.with_bg(color),
+                            };
+                        }
+                        code => self.apply_sgr(code),
+                    }
+                }
+            }
+            b'A' => self.current_y = self.current_y.saturating_sub(count(0)).max(top),
+            b'B' => self.current_y = (self.current_y + count(0)).min(end - 1),
+            b'C' => self.current_x = (self.current_x + count(0)).min(VGA_BUFFER_WIDTH - 1),
+            b'D' => self.current_x = self.current_x.saturating_sub(count(0)),
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            b'H' | b'f' => {
+                let rows = match self.origin_mode {
+                    true => region,
+                    false => 0..self.text_height(),
+                };
+                self.current_y = (rows.start + count(0) - 1).min(rows.end - 1);
+                self.current_x = (count(1) - 1).min(VGA_BUFFER_WIDTH - 1);
+            }
+            // set the scroll region, from row `top` to row `bottom`
+            b'r' => {
+                let height = self.text_height();
+                let top = count(0) - 1;
+                let end = match params.get(1) {
+                    Some(&0) | None => height,
+                    Some(&n) => n as usize,
+                };
+                // a region of one row or off the screen is ignored
+                if top + 1 < end && end <= height {
+                    self.margins = ((top, end) != (0, height)).then_some((top, end));
+                    self.cursor_home();
+                }
+            }
+            // insert or delete lines at the cursor, moving those below it in
+            // the scroll region
+            b'L' | b'M' if region.contains(&self.current_y) => {
+                let line = count(0) as isize;
+                let delta = if final_byte == b'L' { line } else { -line };
+                self.shift_rows(self.current_y, region.end, delta);
+                self.current_x = 0;
+            }
+            // erase after the cursor (0), up to it (1) or all (2)
+            b'J' | b'K' => {
+                let cursor = self.current_y * VGA_BUFFER_WIDTH + self.current_x;
+                let (start, end) = match final_byte {
+                    b'J' => (0, self.text_height() * VGA_BUFFER_WIDTH),
+                    _ => {
+                        let line = self.current_y * VGA_BUFFER_WIDTH;
+                        (line, line + VGA_BUFFER_WIDTH)
+                    }
+                };
+                match params[0] {
+                    0 => self.erase(cursor, end),
+                    1 => self.erase(start, cursor + 1),
+                    2 if final_byte == b'J' => self.clear(),
+                    2 => self.erase(start, end),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets (`CSI ? n h`) or resets (`CSI ? n l`) the private mode `mode`,
+    /// ignoring those that are not supported.
+    fn set_private_mode(&mut self, mode: u16, set: bool) {
+        match mode {
+            // origin mode
+            6 => {
+                self.origin_mode = set;
+                self.cursor_home();
+            }
+            // the hardware cursor shown
+            25 => self.cursor_visible = set,
+            // the alternate screen of xterm, as the screen saved and blanked,
+            // then put back
+            #[cfg(any(feature = "alloc", test))]
+            1049 => {
+                // the heap is ready once lines are kept
+                if set && self.alt_screen.is_none() && self.scrollback.enabled {
+                    self.alt_screen = Some(self.save_screen());
+                    self.clear();
+                } else if !set {
+                    if let Some(snapshot) = self.alt_screen.take() {
+                        self.restore_screen(&snapshot);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the cursor to the top left of the screen, or of the scroll
+    /// region in origin mode.
+    fn cursor_home(&mut self) {
+        self.current_x = 0;
+        self.current_y = match self.origin_mode {
+            true => self.scroll_region().start,
+            false => 0,
+        };
+    }
+
+    /// Saves the cursor position and colors, replacing those saved before.
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(VgaTextSavedCursor {
+            x: self.current_x,
+            y: self.current_y,
+            color: self.current_color,
+            attrs: self.attrs,
+        });
+    }
+
+    /// Moves the cursor back to where it was saved, with the colors it had,
+    /// if it was.
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.current_x = saved.x;
+            self.current_y = saved.y;
+            self.current_color = saved.color;
+            self.attrs = saved.attrs;
+        }
+    }
+
+    /// Gives up on the escape sequence being read, putting its bytes.
+    fn abort_escape(&mut self) {
+        self.state = VgaTextState::PutChar;
+        let (escape, len) = (self.escape, self.escape_len);
+        self.escape_len = 0;
+        for &ch in &escape[..len] {
+            self.putchar(ch);
+        }
+    }
+
+    /// Returns the default colors.
+    fn default_color(&self) -> VgaTextColorCode {
+        VgaTextColorCode::new(self.default_fg, self.default_bg)
+    }
+
+    /// Sets the default colors to `fg` on `bg`, repainting the blank cells
+    /// in the old ones, and the characters put after if they are in them.
+    pub(super) fn set_default_colors(&mut self, fg: VgaTextColor, bg: VgaTextColor) {
+        let (old, new) = (self.default_color(), VgaTextColorCode::new(fg, bg));
+        self.default_fg = fg;
+        self.default_bg = bg;
+        if self.current_color == old {
+            self.current_color = new;
+        }
+        for row in self.screen().chars.iter_mut() {
+            for c in row.iter_mut().filter(|c| c.0 == b' ' && c.1 == old) {
+                c.1 = new;
+            }
+        }
+        self.mark_dirty(0..self.height);
+    }
+
+    /// Sets the colors of the characters put, or the default ones if `None`,
+    /// and clears the attributes.
+    pub(super) fn set_color(&mut self, color: Option<VgaTextColorCode>) {
+        self.current_color = color.unwrap_or(self.default_color());
+        self.attrs = 0;
+    }
+
+    /// Returns the color of the characters put, with the attributes applied.
+    fn cell_color(&self) -> VgaTextColorCode {
+        let mut color = self.current_color;
+        if self.attrs & ATTR_BOLD != 0 {
+            color = color.with_bright_fg();
+        }
+        if self.attrs & ATTR_REVERSE != 0 {
+            color = color.reversed();
+        }
+        color
+    }
+
+    /// Reads the color given after SGR 38 or 48, either `5;<index>` in the
+    /// 256-color palette or `2;<r>;<g>;<b>`, as the nearest VGA color.
+    fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<VgaTextColor> {
+        let mut next = || u8::try_from(params.next()?).ok();
+        match next()? {
+            5 => Some(VgaTextColor::from_256(next()?)),
+            2 => Some(VgaTextColor::nearest([next()?, next()?, next()?])),
+            _ => None,
+        }
+    }
+
+    /// Applies the SGR (Select Graphic Rendition) parameter `code`, changing
+    /// the foreground or the background color and keeping the other, or an
+    /// attribute.
+    fn apply_sgr(&mut self, code: u16) {
+        let console_color = |code: u16| {
+            let color: ConsoleColorCode = u8::try_from(code).ok()?.try_into().ok()?;
+            Some(VgaTextColor::from_console_color(color))
+        };
+        match code {
+            0 => self.set_color(None),
+            1 => self.attrs |= ATTR_BOLD,
+            7 => self.attrs |= ATTR_REVERSE,
+            22 => self.attrs &= !ATTR_BOLD,
+            27 => self.attrs &= !ATTR_REVERSE,
+            30..=37 | 90..=97 => {
+                if let Some(fg) = console_color(code) {
+                    self.current_color = self.current_color.with_fg(fg);
+                }
+            }
+            39 => self.current_color = self.current_color.with_fg(self.default_fg),
+            // The background codes are the foreground ones plus 10.
+            40..=47 | 100..=107 => {
+                if let Some(bg) = console_color(code - 10) {
+                    self.current_color = self.current_color.with_bg(bg);
+                }
+            }
+            49 => self.current_color = self.current_color.with_bg(self.default_bg),
+            _ => {}
+        }
+    }
+
+    /// Blanks the cells from `start` up to `end`, counted from the top left,
+    /// in the current color and attributes.
+    fn erase(&mut self, start: usize, end: usize) {
+        let color = self.cell_color();
+        for i in start..end {
+            let (y, x) = (i / VGA_BUFFER_WIDTH, i % VGA_BUFFER_WIDTH);
+            self.screen().chars[y][x] = VgaTextChar(b' ', color);
+        }
+        self.mark_dirty(start / VGA_BUFFER_WIDTH..end.div_ceil(VGA_BUFFER_WIDTH));
+    }
+
+    /// Blanks the whole screen but the status line and moves the cursor to
+    /// the top left.
+    pub(super) fn clear(&mut self) {
+        self.erase(0, self.text_height() * VGA_BUFFER_WIDTH);
+        self.current_x = 0;
+        self.current_y = 0;
+    }
+
+    /// Erases the character before the cursor.
+    ///
+    /// At the start of a line, moves back to the last character of the line
+    /// above, which may have wrapped onto this one, and erases it.
+    fn backspace(&mut self) {
+        if self.current_x > 0 {
+            self.current_x -= 1;
+        } else if self.current_y > 0 {
+            self.current_y -= 1;
+            let y = self.current_y;
+            let row = &self.screen().chars[y];
+            match row.iter().rposition(|c| c.0 != b' ') {
+                Some(x) => self.current_x = x,
+                None => return,
+            }
+        } else {
+            return;
+        }
+        let (x, y, color) = (self.current_x, self.current_y, self.cell_color());
+        self.screen().chars[y][x] = VgaTextChar(b' ', color);
+        self.mark_dirty(y..y + 1);
+    }
+
+    /// Puts `ch` in `color` at column `x` of row `y`, if it is on the
+    /// screen, leaving the cursor where it is.
+    pub(super) fn put_cell(&mut self, x: usize, y: usize, ch: u8, color: VgaTextColorCode) {
+        if x < VGA_BUFFER_WIDTH && y < self.height {
+            self.screen().chars[y][x] = VgaTextChar(ch, color);
+            self.mark_dirty(y..y + 1);
+        }
+    }
+
+    /// Fills the `w` by `h` cells from column `x` of row `y` with `ch` in
+    /// `color`, as far as they are on the screen, leaving the cursor where
+    /// it is.
+    pub(super) fn fill_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        ch: u8,
+        color: VgaTextColorCode,
+    ) {
+        let x_end = x.saturating_add(w).min(VGA_BUFFER_WIDTH);
+        let y_end = y.saturating_add(h).min(self.height);
+        for y in y..y_end {
+            for x in x..x_end {
+                self.screen().chars[y][x] = VgaTextChar(ch, color);
+            }
+        }
+        self.mark_dirty(y..y_end);
+    }
+
+    /// Shows the panic message `args` in white on red at the bottom of the
+    /// screen, whatever the console was in the middle of.
+    pub(super) fn panic_banner(&mut self, args: fmt::Arguments) {
+        self.state = VgaTextState::PutChar;
+        self.escape_len = 0;
+        self.utf8_need = 0;
+        #[cfg(any(feature = "alloc", test))]
+        self.scroll_view_reset();
+        self.margins = None;
+        self.origin_mode = false;
+        self.set_color(Some(PANIC_COLOR));
+        let height = self.text_height();
+        let top = height.saturating_sub(PANIC_BANNER_ROWS);
+        self.erase(top * VGA_BUFFER_WIDTH, height * VGA_BUFFER_WIDTH);
+        self.current_x = 0;
+        self.current_y = top;
+        // longer lines wrap, and more lines scroll in the same colors
+        let _ = self.write_fmt(args);
+        self.cursor_visible = true;
+    }
+
+    /// Writes `args` after `prefix` and `tag` in `color`, the tag of the
+    /// level of a debug message.
+    pub(super) fn print_debug(
+        &mut self,
+        prefix: DebugPrefix,
+        tag: &str,
+        color: VgaTextColorCode,
+        args: fmt::Arguments,
+    ) -> fmt::Result {
+        self.set_color(None);
+        write!(self, "{}", prefix)?;
+        self.set_color(Some(color));
+        self.write_str(tag)?;
+        self.set_color(None);
+        self.write_fmt(args)
+    }
+
+    /// Returns the lines of text shown, without trailing spaces.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn dump(&self) -> String {
+        let mut text = String::new();
+        for row in self.shadow.chars[..self.height].iter() {
+            let len = row.iter().rposition(|c| c.0 != b' ').map_or(0, |x| x + 1);
+            text.extend(row[..len].iter().map(|c| char::from(c.0)));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Returns the lines of text shown, with the SGR sequences setting the
+    /// colors of the characters, and without trailing spaces in the default
+    /// colors.
+    #[cfg(any(feature = "alloc", test))]
+    pub(super) fn dump_with_colors(&self) -> String {
+        let default = self.default_color();
+        let mut text = String::new();
+        for row in self.shadow.chars[..self.height].iter() {
+            let len = row
+                .iter()
+                .rposition(|c| c.0 != b' ' || c.1 != default)
+                .map_or(0, |x| x + 1);
+            let mut color = default;
+            for c in row[..len].iter() {
+                if c.1 != color {
+                    color = c.1;
+                    if color == default {
+                        text.push_str("\x1b[0m");
+                    } else {
+                        let (fg, bg) = color.sgr_params();
+                        let _ = write!(text, "\x1b[{};{}m", fg, bg);
+                    }
+                }
+                text.push(char::from(c.0));
+            }
+            if color != default {
+                text.push_str("\x1b[0m");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn putchar(&mut self, ch: u8) {
+        match ch {
+            b'\r' => {
+                self.current_x = 0;
+            }
+            b'\n' | b'\x0b' => {
+                // treat it as \r\n, and so the vertical tab
+                self.current_x = 0;
+                self.line_feed();
+            }
+            b'\t' => {
+                // move to the next tab stop, wrapping at the end of the line
+                self.current_x = (self.current_x / TAB_WIDTH + 1) * TAB_WIDTH;
+            }
+            b'\x0c' => self.clear(),
+            b'\x08' => self.backspace(),
+            _ => self.put_glyph(ch),
+        }
+        self.wrap();
+    }
+
+    /// Puts the glyph `ch` at the cursor, even that of a control character,
+    /// and moves the cursor on.
+    fn put_glyph(&mut self, ch: u8) {
+        let (x, y, color) = (self.current_x, self.current_y, self.cell_color());
+        self.screen().chars[y][x] = VgaTextChar(ch, color);
+        self.mark_dirty(y..y + 1);
+        self.current_x += 1;
+        self.wrap();
+    }
+
+    /// Moves the cursor to the next line if it is past the end of this one.
+    fn wrap(&mut self) {
+        if self.current_x >= VGA_BUFFER_WIDTH {
+            self.current_x = 0;
+            self.line_feed();
+        }
+    }
+
+    /// Moves the cursor down a row, or scrolls the scroll region up if the
+    /// cursor is on its last row.
+    ///
+    /// Below the region, the cursor stops at the last row of text.
+    fn line_feed(&mut self) {
+        let region = self.scroll_region();
+        if self.current_y + 1 == region.end {
+            self.scroll_up(region, 1);
+        } else if self.current_y + 1 < self.text_height() {
+            self.current_y += 1;
+        }
+    }
+}
+
+impl Write for VgaTextConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.as_bytes() {
+            self.write_byte(*c);
+        }
+        Ok(())
+    }
+}
+
+/// The virtual consoles, and which of them are shown and written to.
+pub(super) struct VgaConsoles {
+    consoles: [VgaTextConsole; VGA_CONSOLES],
+    /// The console shown.
+    pub(super) active: usize,
+    /// The consoles written to by `putchar` and the like, and by
+    /// `print_debug`.
+    pub(super) output: usize,
+    pub(super) debug_output: usize,
+}
+
+impl VgaConsoles {
+    pub(super) const fn new() -> Self {
+        const CONSOLE: VgaTextConsole = VgaTextConsole::new();
+        Self {
+            consoles: [CONSOLE; VGA_CONSOLES],
+            active: 0,
+            output: 0,
+            debug_output: 0,
+        }
+    }
+
+    /// Returns the console shown.
+    pub(super) fn shown(&self) -> &VgaTextConsole {
+        &self.consoles[self.active]
+    }
+
+    /// Returns the console shown, to be written to.
+    pub(super) fn active_console(&mut self) -> &mut VgaTextConsole {
+        &mut self.consoles[self.active]
+    }
+
+    /// Returns the console written to by `putchar` and the like.
+    pub(super) fn output_console(&mut self) -> &mut VgaTextConsole {
+        &mut self.consoles[self.output]
+    }
+
+    /// Returns the console written to by `print_debug`.
+    pub(super) fn debug_console(&mut self) -> &mut VgaTextConsole {
+        &mut self.consoles[self.debug_output]
+    }
+
+    pub(super) fn iter_mut(&mut self) -> core::slice::IterMut<'_, VgaTextConsole> {
+        self.consoles.iter_mut()
+    }
+
+    /// Shows console `n`, if there is one.
+    pub(super) fn switch_console(&mut self, n: usize) {
+        if n < VGA_CONSOLES && n != self.active {
+            self.active = n;
+            let console = &mut self.consoles[n];
+            console.mark_dirty(0..console.height);
+        }
+    }
+
+    /// Copies the rows of the console shown changed to `buffer`, the rows of
+    /// the VGA buffer.
+    pub(super) fn flush_to(&mut self, buffer: &mut [VgaTextRow]) {
+        self.consoles[self.active].flush_to(buffer);
+    }
+}
+
+/// The time since boot and the CPU of a message of `print_debug`, written
+/// before its tag as `[   12.345678 cpu0] ` if asked.
+#[derive(Clone, Copy)]
+pub(super) struct DebugPrefix(pub(super) Option<(Duration, usize)>);
+
+impl fmt::Display for DebugPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some((time, cpu)) => write!(
+                f,
+                "[{:5}.{:06} cpu{}] ",
+                time.as_secs(),
+                time.subsec_micros(),
+                cpu
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The levels of the messages of `print_debug`, from 1: the tag of each and
+/// its colors, and the highest level printed.
+pub(super) struct DebugLevels {
+    /// The highest level printed.
+    max: AtomicU8,
+    /// The colors of the tags, as `VgaTextColorCode`s.
+    colors: [AtomicU8; DEBUG_LEVELS.len()],
+    /// The tags, copied out of the lock to be written.
+    tags: SpinNoIrq<[&'static str; DEBUG_LEVELS.len()]>,
+}
+
+impl DebugLevels {
+    /// Returns the levels with their default tags and colors, printed up to
+    /// level 3.
+    pub(super) const fn new() -> Self {
+        let mut colors = [const { AtomicU8::new(0) }; DEBUG_LEVELS.len()];
+        let mut i = 0;
+        while i < DEBUG_LEVELS.len() {
+            let color = VgaTextColorCode::new(DEBUG_LEVELS[i].1, VgaTextColor::Black);
+            colors[i] = AtomicU8::new(color.0);
+            i += 1;
+        }
+        Self {
+            max: AtomicU8::new(3),
+            colors,
+            tags: SpinNoIrq::new([
+                DEBUG_LEVELS[0].0,
+                DEBUG_LEVELS[1].0,
+                DEBUG_LEVELS[2].0,
+                DEBUG_LEVELS[3].0,
+            ]),
+        }
+    }
+
+    /// Sets the highest level printed, one of 0 to 4; another is given back
+    /// as the error, and the level is left as it was.
+    pub(super) fn set_max(&self, level: u8) -> Result<(), u8> {
+        if level as usize > DEBUG_LEVELS.len() {
+            return Err(level);
+        }
+        self.max.store(level, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the highest level printed.
+    pub(super) fn max(&self) -> u8 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Sets the tag of level `level`, one of 1 to 4, and its colors; another
+    /// level is given back as the error.
+    pub(super) fn set_style(
+        &self,
+        level: u8,
+        fg: VgaTextColor,
+        bg: VgaTextColor,
+        tag: &'static str,
+    ) -> Result<(), u8> {
+        if level == 0 || level as usize > DEBUG_LEVELS.len() {
+            return Err(level);
+        }
+        let i = level as usize - 1;
+        self.tags.lock()[i] = tag;
+        self.colors[i].store(VgaTextColorCode::new(fg, bg).0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the tag of level `level`, from 1, and its color.
+    pub(super) fn style(&self, level: u8) -> (&'static str, VgaTextColorCode) {
+        let i = level as usize - 1;
+        let tag = self.tags.lock()[i];
+        let color = VgaTextColorCode(self.colors[i].load(Ordering::Relaxed));
+        (tag, color)
+    }
+}
+
+/// The bytes a CPU wrote while it held the lock of the console, written to
+/// the output console once the lock is taken again.
+pub(super) struct Spill {
+    bytes: [u8; SPILL_SIZE],
+    len: usize,
+}
+
+impl Spill {
+    pub(super) const fn new() -> Self {
+        Self {
+            bytes: [0; SPILL_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Keeps as many of `bytes` as there is room for, dropping the rest, and
+    /// returns how many are kept.
+    pub(super) fn push(&mut self, bytes: &[u8]) -> usize {
+        let n = bytes.len().min(SPILL_SIZE - self.len);
+        self.bytes[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Returns the bytes kept.
+    pub(super) fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Writes the bytes kept to `console`, emptying the spill.
+    pub(super) fn drain_to(&mut self, console: &mut VgaTextConsole) {
+        for &c in &self.bytes[..self.len] {
+            console.write_byte(c);
+        }
+        self.len = 0;
+    }
+}
+
+/// A lock that knows the CPU holding it, so that a CPU can tell it holds the
+/// lock already, as when it writes from an exception taken while writing,
+/// rather than wait for itself.
+pub(super) struct CpuLock<T> {
+    inner: SpinNoIrq<T>,
+    /// The CPU holding the lock, or `NO_OWNER`.
+    owner: AtomicUsize,
+}
+
+impl<T> CpuLock<T> {
+    pub(super) const fn new(value: T) -> Self {
+        Self {
+            inner: SpinNoIrq::new(value),
+            owner: AtomicUsize::new(NO_OWNER),
+        }
+    }
+
+    /// Locks it for CPU `cpu`, waiting for another CPU holding it.
+    pub(super) fn lock(&self, cpu: usize) -> CpuLockGuard<'_, T> {
+        let guard = self.inner.lock();
+        self.owner.store(cpu, Ordering::Relaxed);
+        CpuLockGuard {
+            guard,
+            owner: &self.owner,
+        }
+    }
+
+    /// Locks it for CPU `cpu` if no CPU holds it.
+    pub(super) fn try_lock(&self, cpu: usize) -> Option<CpuLockGuard<'_, T>> {
+        let guard = self.inner.try_lock()?;
+        self.owner.store(cpu, Ordering::Relaxed);
+        Some(CpuLockGuard {
+            guard,
+            owner: &self.owner,
+        })
+    }
+
+    /// Returns whether CPU `cpu` holds it.
+    pub(super) fn is_held_by(&self, cpu: usize) -> bool {
+        self.owner.load(Ordering::Relaxed) == cpu
+    }
+}
+
+/// A `CpuLock` held by a CPU.
+pub(super) struct CpuLockGuard<'a, T> {
+    guard: SpinNoIrqGuard<'a, T>,
+    owner: &'a AtomicUsize,
+}
+
+impl<T> Drop for CpuLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // before the lock itself is let go
+        self.owner.store(NO_OWNER, Ordering::Relaxed);
+    }
+}
+
+impl<T> Deref for CpuLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for CpuLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, vec};
+
+    /// The rows of the screen, in the default mode.
+    const VGA_BUFFER_HEIGHT: usize = VgaMode::Mode80x25.height();
+
+    fn row(vga: &VgaTextConsole, y: usize) -> Vec<u8> {
+        vga.shadow.chars[y].iter().map(|c| c.0).collect()
+    }
+
+    /// Returns the dump of a screen showing `lines` from the top.
+    fn screen<S: AsRef<str>>(lines: &[S]) -> String {
+        let mut text = String::new();
+        let lines = lines
+            .iter()
+            .map(AsRef::as_ref)
+            .chain(core::iter::repeat(""));
+        for line in lines.take(VGA_BUFFER_HEIGHT) {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn letters(range: core::ops::RangeInclusive<u8>) -> Vec<String> {
+        range.map(|c| String::from(char::from(c))).collect()
+    }
+
+    /// Returns the rows of a blank VGA buffer, as many as in any mode.
+    fn vga_buffer() -> Vec<VgaTextRow> {
+        let color = VgaTextColorCode::new(DEFAULT_FG, DEFAULT_BG);
+        vec![[VgaTextChar(b' ', color); VGA_BUFFER_WIDTH]; VGA_MAX_HEIGHT]
+    }
+
+    fn shown(buffer: &[VgaTextRow], y: usize) -> Vec<u8> {
+        buffer[y].iter().map(|c| c.0).collect()
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut buffer = vga_buffer();
+        let mut vga = VgaTextConsole::new();
+        for &c in b"ab\n\ncd" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dirty_rows, 0b101);
+        assert_eq!(shown(&buffer, 0)[..2], *b"  ");
+        vga.flush_to(&mut buffer);
+        assert_eq!(vga.dirty_rows, 0);
+        assert_eq!(shown(&buffer, 0)[..2], *b"ab");
+        assert_eq!(shown(&buffer, 2)[..2], *b"cd");
+
+        // Only the rows changed are copied.
+        buffer[0][0].0 = b'x';
+        vga.put_cell(0, 1, b'y', vga.current_color);
+        vga.flush_to(&mut buffer);
+        assert_eq!((shown(&buffer, 0)[0], shown(&buffer, 1)[0]), (b'x', b'y'));
+
+        // Scrolling changes every row.
+        vga.current_y = VGA_BUFFER_HEIGHT - 1;
+        vga.write_byte(b'\n');
+        assert_eq!(vga.dirty_rows, (1 << VGA_BUFFER_HEIGHT) - 1);
+        vga.flush_to(&mut buffer);
+        assert_eq!(shown(&buffer, 0)[0], b'y');
+    }
+
+    #[test]
+    fn test_backspace_at_origin() {
+        let mut vga = VgaTextConsole::new();
+        vga.putchar(b'\x08');
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+        assert!(row(&vga, 0).iter().all(|&c| c == b' '));
+    }
+
+    #[test]
+    fn test_backspace_across_lines() {
+        let mut vga = VgaTextConsole::new();
+        for _ in 0..VGA_BUFFER_WIDTH {
+            vga.putchar(b'a');
+        }
+        assert_eq!((vga.current_x, vga.current_y), (0, 1));
+        vga.putchar(b'\x08');
+        assert_eq!((vga.current_x, vga.current_y), (VGA_BUFFER_WIDTH - 1, 0));
+        assert_eq!(row(&vga, 0)[VGA_BUFFER_WIDTH - 2..], [b'a', b' ']);
+
+        // Back over a line ending, to the last character before it.
+        for &c in b"\nbc\n" {
+            vga.putchar(c);
+        }
+        vga.putchar(b'\x08');
+        assert_eq!((vga.current_x, vga.current_y), (1, 1));
+        assert_eq!(row(&vga, 1)[..2], [b'b', b' ']);
+
+        // Nothing to erase on the line above.
+        for &c in b"\n\n" {
+            vga.putchar(c);
+        }
+        vga.putchar(b'\x08');
+        assert_eq!((vga.current_x, vga.current_y), (0, 2));
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut vga = VgaTextConsole::new();
+        let color = vga.current_color;
+        for (row, c) in vga.shadow.chars.iter_mut().zip(b'a'..=b'y') {
+            row[0] = VgaTextChar(c, color);
+        }
+        vga.current_y = VGA_BUFFER_HEIGHT - 1;
+        vga.scroll_up(0..VGA_BUFFER_HEIGHT, 0);
+        assert_eq!(vga.shadow.chars[0][0].0, b'a');
+
+        vga.scroll_up(0..VGA_BUFFER_HEIGHT, 3);
+        assert_eq!(vga.current_y, VGA_BUFFER_HEIGHT - 1);
+        assert_eq!(vga.dump(), screen(&letters(b'd'..=b'y')));
+
+        // Writing past the last row scrolls by one.
+        vga.current_y = VGA_BUFFER_HEIGHT - 1;
+        vga.putchar(b'\n');
+        assert_eq!(vga.current_y, VGA_BUFFER_HEIGHT - 1);
+        assert_eq!(vga.dump(), screen(&letters(b'e'..=b'y')));
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let mut vga = VgaTextConsole::new();
+        let last = VGA_BUFFER_HEIGHT - 1;
+        // a header, rows 2 to 4 scrolling, and a footer
+        for &c in b"\x1b[2;4rhead\x1b[4;1Hx\ny\nz\x1b[25;1Hfoot\n" {
+            vga.write_byte(c);
+        }
+        let mut lines = vec![String::new(); VGA_BUFFER_HEIGHT];
+        for (y, line) in [(0, "head"), (1, "x"), (2, "y"), (3, "z"), (last, "foot")] {
+            lines[y] = String::from(line);
+        }
+        assert_eq!(vga.dump(), screen(&lines));
+        assert_eq!((vga.current_x, vga.current_y), (0, last));
+
+        // Moves up and down stop at the margins from inside the region.
+        for &c in b"\x1b[3;1H\x1b[9A" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.current_y, 1);
+        for &c in b"\x1b[9B" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.current_y, 3);
+        for &c in b"\x1b[25;1H\x1b[30A" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.current_y, 0);
+
+        // In origin mode, rows count from the top of the region.
+        for &c in b"\x1b[?6h\x1b[1;2H" {
+            vga.write_byte(c);
+        }
+        assert_eq!((vga.current_x, vga.current_y), (1, 1));
+        for &c in b"\x1b[9;1H" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.current_y, 3);
+        for &c in b"\x1b[?6l" {
+            vga.write_byte(c);
+        }
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+
+        // Regions of one row are ignored, and the whole screen scrolls again
+        // once the region is reset.
+        for &c in b"\x1b[5;5r\x1b[25;1H\n" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&lines));
+        for &c in b"\x1b[r\x1b[25;1H\n" {
+            vga.write_byte(c);
+        }
+        lines.remove(0);
+        assert_eq!(vga.dump(), screen(&lines));
+        assert_eq!(vga.margins, None);
+    }
+
+    #[test]
+    fn test_insert_delete_lines() {
+        let mut vga = VgaTextConsole::new();
+        let color = vga.current_color;
+        for (row, c) in vga.shadow.chars.iter_mut().zip(b'a'..=b'y') {
+            row[0] = VgaTextChar(c, color);
+        }
+        let mut lines = letters(b'a'..=b'y');
+
+        // Lines inserted push those below down, off the bottom.
+        for &c in b"\x1b[3;5H\x1b[2L" {
+            vga.write_byte(c);
+        }
+        lines.splice(2..2, [String::new(), String::new()]);
+        lines.truncate(VGA_BUFFER_HEIGHT);
+        assert_eq!(vga.dump(), screen(&lines));
+        assert_eq!((vga.current_x, vga.current_y), (0, 2));
+
+        // Lines deleted pull those below up, blanking the bottom.
+        for &c in b"\x1b[M" {
+            vga.write_byte(c);
+        }
+        lines.remove(2);
+        lines.push(String::new());
+        assert_eq!(vga.dump(), screen(&lines));
+
+        // Inside a scroll region, only its rows move, even by more rows than
+        // there are left in it.
+        for &c in b"\x1b[4;6r\x1b[6;1H\x1b[L\x1b[4;1H\x1b[9M" {
+            vga.write_byte(c);
+        }
+        for line in &mut lines[3..6] {
+            *line = String::new();
+        }
+        assert_eq!(vga.dump(), screen(&lines));
+
+        // Outside of it, nothing moves.
+        for &c in b"\x1b[1;1H\x1b[L\x1b[25;1H\x1b[M" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&lines));
+    }
+
+    #[test]
+    fn test_tab() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"a\tb\t\tc" {
+            vga.putchar(c);
+        }
+        assert_eq!(vga.current_x, 2 * TAB_WIDTH + TAB_WIDTH + 1);
+        assert_eq!(row(&vga, 0)[TAB_WIDTH], b'b');
+        assert!(!row(&vga, 0).contains(&b'\t'));
+
+        // A tab from the last tab stop of the line wraps to the next one.
+        vga.current_x = VGA_BUFFER_WIDTH - TAB_WIDTH;
+        vga.putchar(b'\t');
+        assert_eq!((vga.current_x, vga.current_y), (0, 1));
+        vga.current_x = VGA_BUFFER_WIDTH - 1;
+        vga.putchar(b'\t');
+        assert_eq!((vga.current_x, vga.current_y), (0, 2));
+
+        vga.putchar(b'\x0b');
+        assert_eq!((vga.current_x, vga.current_y), (0, 3));
+        vga.putchar(b'\x0c');
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+        assert!(row(&vga, 0).iter().all(|&c| c == b' '));
+    }
+
+    #[test]
+    fn test_background() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"\x1b[44ma\x1b[31mb\x1b[49mc\x1b[0md\x1b[102m\x1b[mf" {
+            vga.write_byte(c);
+        }
+        let colors: Vec<u8> = vga.shadow.chars[0][..5].iter().map(|c| c.1 .0).collect();
+        let code = |fg, bg| VgaTextColorCode::new(fg, bg).0;
+        assert_eq!(
+            colors,
+            [
+                code(DEFAULT_FG, VgaTextColor::Blue),
+                code(VgaTextColor::Red, VgaTextColor::Blue),
+                code(VgaTextColor::Red, DEFAULT_BG),
+                code(DEFAULT_FG, DEFAULT_BG),
+                code(DEFAULT_FG, DEFAULT_BG),
+            ]
+        );
+        assert_eq!(
+            vga.dump_with_colors(),
+            screen(&["\x1b[97;44ma\x1b[31;44mb\x1b[31;40mc\x1b[0mdf"])
+        );
+    }
+
+    #[test]
+    fn test_sgr_params() {
+        let mut vga = VgaTextConsole::new();
+        let overflow = b"\x1b[44;1;2;3;4;5;6;7;8;9;1;2;3;4;5;6;41mf";
+        for &c in b"\x1b[1;31ma\x1b[0;37;44mb\x1b[31;1mc\x1b[;32md\x1b[22;33;1;49;0me" {
+            vga.write_byte(c);
+        }
+        for &c in overflow {
+            vga.write_byte(c);
+        }
+        let row = &vga.shadow.chars[0][..6];
+        assert_eq!(row.iter().map(|c| c.0).collect::<Vec<_>>(), b"abcdef");
+        let colors: Vec<u8> = row.iter().map(|c| c.1 .0).collect();
+        let code = |fg, bg| VgaTextColorCode::new(fg, bg).0;
+        assert_eq!(
+            colors,
+            [
+                code(VgaTextColor::LightRed, DEFAULT_BG),
+                code(VgaTextColor::Gray, VgaTextColor::Blue),
+                code(VgaTextColor::LightRed, VgaTextColor::Blue),
+                code(VgaTextColor::Green, DEFAULT_BG),
+                code(DEFAULT_FG, DEFAULT_BG),
+                // too many parameters, ignored
+                code(DEFAULT_FG, DEFAULT_BG),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sgr_default_colors() {
+        let mut vga = VgaTextConsole::new();
+        vga.default_fg = VgaTextColor::Black;
+        vga.default_bg = VgaTextColor::White;
+        for &c in b"\x1b[31;44ma\x1b[39;1mb\x1b[49;22;32mc\x1b[0;33md\x1b[44;39;49;7me" {
+            vga.write_byte(c);
+        }
+        let colors: Vec<u8> = vga.shadow.chars[0][..5].iter().map(|c| c.1 .0).collect();
+        let code = |fg, bg| VgaTextColorCode::new(fg, bg).0;
+        assert_eq!(
+            colors,
+            [
+                code(VgaTextColor::Red, VgaTextColor::Blue),
+                // only the foreground goes back to the default, then bright
+                code(VgaTextColor::DarkGray, VgaTextColor::Blue),
+                code(VgaTextColor::Green, VgaTextColor::White),
+                code(VgaTextColor::Brown, VgaTextColor::White),
+                code(VgaTextColor::White, VgaTextColor::Black),
+            ]
+        );
+        assert_eq!(vga.current_color, vga.default_color());
+    }
+
+    #[test]
+    fn test_default_colors() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"a\x1b[31mb\x1b[44m \x1b[0m" {
+            vga.write_byte(c);
+        }
+        vga.set_default_colors(VgaTextColor::Black, VgaTextColor::White);
+        vga.write_byte(b'c');
+        // only the blank cells in the old default colors are repainted
+        assert_eq!(
+            vga.dump_with_colors(),
+            screen(&["\x1b[97;40ma\x1b[31;40mb\x1b[31;44m \x1b[0mc"])
+        );
+        assert_eq!(vga.shadow.chars[5][0].1, vga.default_color());
+    }
+
+    #[test]
+    fn test_alt_screen() {
+        let mut vga = VgaTextConsole::new();
+        vga.scrollback.enable();
+        for &c in b"shell\n$ \x1b[31;1mls" {
+            vga.write_byte(c);
+        }
+        let shell = vga.dump_with_colors();
+        for &c in b"\x1b[?1049h" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen::<&str>(&[]));
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+
+        for &c in b"\x1b[0;44mmenu\n\x1b[?1049l" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump_with_colors(), shell);
+        assert_eq!((vga.current_x, vga.current_y), (4, 1));
+        assert_eq!(vga.attrs, ATTR_BOLD);
+
+        // Nothing was saved to put back.
+        for &c in b"\x1b[?1049lx" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&["shell", "$ lsx"]));
+    }
+
+    #[test]
+    fn test_invalid_escape() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"a\x1bxb\x1b[12;!\x1b\x1b[31mr\x1b[4" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&["a\x1bxb\x1b[12;!\x1br"]));
+        let code = |fg| VgaTextColorCode::new(fg, DEFAULT_BG).0;
+        assert_eq!(vga.shadow.chars[0][10].1 .0, code(DEFAULT_FG));
+        assert_eq!(vga.shadow.chars[0][11].1 .0, code(VgaTextColor::Red));
+        // The end of the text may be the start of a sequence.
+        assert_eq!(vga.current_x, 12);
+
+        // Sequences too long to keep are put as they are.
+        let mut vga = VgaTextConsole::new();
+        let mut long = Vec::from(&b"\x1b[1"[..]);
+        long.resize(MAX_ESCAPE_LEN + 1, b'0');
+        for &c in &long {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 0)[..long.len()], long);
+    }
+
+    #[test]
+    fn test_cursor_moves() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"abc\x1b[2Dx\x1b[Bd\x1b[3;5He\x1b[0Af\x1b[10Cg\x1b[Hh\x1b[2;2fi\x1b[5Zj" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&["hxc", " ij  f          g", "    e"]));
+        assert_eq!((vga.current_x, vga.current_y), (3, 1));
+
+        // Moves stop at the edges of the screen.
+        for &c in b"\x1b[99A\x1b[99D" {
+            vga.write_byte(c);
+        }
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+        for &c in b"\x1b[99B\x1b[999C" {
+            vga.write_byte(c);
+        }
+        assert_eq!(
+            (vga.current_x, vga.current_y),
+            (VGA_BUFFER_WIDTH - 1, VGA_BUFFER_HEIGHT - 1)
+        );
+        for &c in b"\x1b[30;100H" {
+            vga.write_byte(c);
+        }
+        assert_eq!(
+            (vga.current_x, vga.current_y),
+            (VGA_BUFFER_WIDTH - 1, VGA_BUFFER_HEIGHT - 1)
+        );
+    }
+
+    #[test]
+    fn test_erase() {
+        let mut vga = VgaTextConsole::new();
+        let fill = |vga: &mut VgaTextConsole| {
+            vga.clear();
+            for _ in 0..3 * VGA_BUFFER_WIDTH {
+                vga.write_byte(b'x');
+            }
+            for &c in b"\x1b[2;5H\x1b[44m" {
+                vga.write_byte(c);
+            }
+        };
+        let blank = |vga: &VgaTextConsole, y: usize, x: usize| vga.shadow.chars[y][x].0 == b' ';
+
+        fill(&mut vga);
+        for &c in b"\x1b[K" {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 1)[3..6], b"x  "[..]);
+        assert!(blank(&vga, 1, VGA_BUFFER_WIDTH - 1) && !blank(&vga, 2, 0));
+        let blue = VgaTextColorCode::new(DEFAULT_FG, VgaTextColor::Blue).0;
+        assert_eq!(vga.shadow.chars[1][4].1 .0, blue);
+
+        fill(&mut vga);
+        for &c in b"\x1b[1K" {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 1)[3..6], b"  x"[..]);
+        assert!(!blank(&vga, 0, VGA_BUFFER_WIDTH - 1));
+
+        fill(&mut vga);
+        for &c in b"\x1b[2K" {
+            vga.write_byte(c);
+        }
+        assert!(row(&vga, 1).iter().all(|&c| c == b' '));
+        assert!(!blank(&vga, 0, 0) && !blank(&vga, 2, 0));
+
+        fill(&mut vga);
+        for &c in b"\x1b[J" {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 1)[3..6], b"x  "[..]);
+        assert!(blank(&vga, 2, 0) && !blank(&vga, 0, 0));
+
+        fill(&mut vga);
+        for &c in b"\x1b[1J" {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 1)[3..6], b"  x"[..]);
+        assert!(blank(&vga, 0, 0) && !blank(&vga, 2, 0));
+        assert_eq!((vga.current_x, vga.current_y), (4, 1));
+
+        fill(&mut vga);
+        for &c in b"\x1b[2J" {
+            vga.write_byte(c);
+        }
+        assert!((0..3).all(|y| row(&vga, y).iter().all(|&c| c == b' ')));
+        assert_eq!((vga.current_x, vga.current_y), (0, 0));
+    }
+
+    #[test]
+    fn test_save_cursor() {
+        let mut vga = VgaTextConsole::new();
+        // Nothing to restore yet.
+        for &c in b"ab\x1b8\x1b[uc" {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 0)[..3], b"abc"[..]);
+
+        for &c in b"\x1b[32m\x1b7\x1b[25;1H\x1b[44mstatus\x1b8d" {
+            vga.write_byte(c);
+        }
+        for &c in b"\x1b[2;3H\x1b[s\x1b[Hx\x1b[ue" {
+            vga.write_byte(c);
+        }
+        let mut lines = [""; VGA_BUFFER_HEIGHT];
+        lines[..2].copy_from_slice(&["xbcd", "  e"]);
+        lines[VGA_BUFFER_HEIGHT - 1] = "status";
+        assert_eq!(vga.dump(), screen(&lines));
+        let green = VgaTextColorCode::new(VgaTextColor::Green, DEFAULT_BG).0;
+        assert_eq!(vga.shadow.chars[0][3].1 .0, green);
+        assert_eq!((vga.current_x, vga.current_y), (3, 1));
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let mut vga = VgaTextConsole::new();
+        vga.scrollback.enable();
+        // Lines starting with A, B, C and so on; those up to K scroll off.
+        for i in 0..VGA_BUFFER_HEIGHT + 10 {
+            vga.write_byte(b'A' + i as u8);
+            vga.write_byte(b'\n');
+        }
+        assert_eq!(vga.scrollback.lines.len(), 11);
+        let first = |vga: &VgaTextConsole| vga.shadow.chars[0][0].0;
+        assert_eq!(first(&vga), b'L');
+
+        vga.scroll_view_up(5);
+        assert_eq!(first(&vga), b'G');
+        assert_eq!(vga.shadow.chars[5][0].0, b'L');
+        // The view stays on the same lines while output goes on.
+        for &c in b"x\n" {
+            vga.write_byte(c);
+        }
+        assert_eq!(first(&vga), b'G');
+        assert_eq!(vga.scrollback.lines.len(), 12);
+
+        vga.scroll_view_up(100);
+        assert_eq!(first(&vga), b'A');
+        vga.scroll_view_down(7);
+        assert_eq!(first(&vga), b'H');
+        vga.scroll_view_down(100);
+        assert_eq!(first(&vga), b'M');
+        assert_eq!(vga.shadow.chars[VGA_BUFFER_HEIGHT - 2][0].0, b'x');
+        assert_eq!(vga.scrollback.offset, 0);
+        // The view is scrolled without allocating, from the keyboard interrupt.
+        assert!(vga.scrollback.lines.capacity() >= SCROLLBACK_LINES);
+
+        vga.scrollback.snap = true;
+        vga.scroll_view_up(1);
+        assert_eq!(first(&vga), b'L');
+        vga.write_byte(b'y');
+        assert_eq!(first(&vga), b'M');
+        assert_eq!(vga.shadow.chars[VGA_BUFFER_HEIGHT - 1][0].0, b'y');
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"\x1b[31;7ma\x1b[1mb\x1b[27mc\x1b[22md\x1b[7;1;44me\x1b[0mf" {
+            vga.write_byte(c);
+        }
+        let colors: Vec<u8> = vga.shadow.chars[0][..6].iter().map(|c| c.1 .0).collect();
+        let code = |fg, bg| VgaTextColorCode::new(fg, bg).0;
+        assert_eq!(
+            colors,
+            [
+                code(DEFAULT_BG, VgaTextColor::Red),
+                code(DEFAULT_BG, VgaTextColor::LightRed),
+                code(VgaTextColor::LightRed, DEFAULT_BG),
+                code(VgaTextColor::Red, DEFAULT_BG),
+                code(VgaTextColor::Blue, VgaTextColor::LightRed),
+                code(DEFAULT_FG, DEFAULT_BG),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        assert_eq!(VgaTextColor::from_256(9), VgaTextColor::LightRed);
+        assert_eq!(VgaTextColor::from_256(21), VgaTextColor::Blue);
+        assert_eq!(VgaTextColor::from_256(231), VgaTextColor::White);
+        assert_eq!(VgaTextColor::from_256(232), VgaTextColor::Black);
+        assert_eq!(VgaTextColor::from_256(240), VgaTextColor::DarkGray);
+
+        let mut vga = VgaTextConsole::new();
+        let text = b"\x1b[38;5;2;48;2;250;250;80ma\x1b[0;38;2;0;0m\x1b[38;5mb\
+            \x1b[38;5;300;31mc\x1b[48;9;34md\x1b[38;2;255;85;255;44me";
+        for &c in text {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 0)[..6], *b"abcde ");
+        let colors: Vec<u8> = vga.shadow.chars[0][..5].iter().map(|c| c.1 .0).collect();
+        let code = |fg, bg| VgaTextColorCode::new(fg, bg).0;
+        assert_eq!(
+            colors,
+            [
+                code(VgaTextColor::Green, VgaTextColor::Yellow),
+                code(DEFAULT_FG, DEFAULT_BG),
+                code(DEFAULT_FG, DEFAULT_BG),
+                code(DEFAULT_FG, DEFAULT_BG),
+                code(VgaTextColor::LightPurple, VgaTextColor::Blue),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"abc" {
+            vga.write_byte(c);
+        }
+        let color = VgaTextColorCode::new(VgaTextColor::Yellow, VgaTextColor::Blue);
+        vga.fill_rect(1, 1, 3, 2, b'#', color);
+        vga.fill_rect(78, 24, 5, 5, b'%', color);
+        vga.put_cell(2, 2, b'x', color);
+        vga.put_cell(VGA_BUFFER_WIDTH, 0, b'!', color);
+        assert_eq!(row(&vga, 0)[..4], *b"abc ");
+        assert_eq!(row(&vga, 1)[..5], *b" ### ");
+        assert_eq!(row(&vga, 2)[..5], *b" #x# ");
+        assert_eq!(row(&vga, 3)[..5], *b"     ");
+        assert_eq!(row(&vga, 24)[77..], *b" %%");
+        assert_eq!(vga.shadow.chars[1][1].1, color);
+        assert_eq!((vga.current_x, vga.current_y), (3, 0));
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut vga = VgaTextConsole::new();
+        assert_eq!(vga.dump(), "\n".repeat(VGA_BUFFER_HEIGHT));
+        for &c in b"a b  \n\x1b[1;31mc\x1b[44m  \x1b[0m \n\x1b[7m \x1b[0md" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&["a b", "c", " d"]));
+        assert_eq!(
+            vga.dump_with_colors(),
+            screen(&[
+                "a b",
+                "\x1b[91;40mc\x1b[91;44m  \x1b[0m",
+                "\x1b[30;107m \x1b[0md"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(CP437_LOW.chars().count(), 0x1f);
+        assert_eq!(CP437_HIGH.chars().count(), 0x80);
+
+        let mut vga = VgaTextConsole::new();
+        for &c in "é°┌─┐←x中".as_bytes() {
+            vga.write_byte(c);
+        }
+        assert_eq!(
+            row(&vga, 0)[..8],
+            [0x82, 0xf8, 0xda, 0xc4, 0xbf, 0x1b, b'x', b'?']
+        );
+
+        // Invalid bytes, sequences cut short, overlong forms and surrogates.
+        let mut vga = VgaTextConsole::new();
+        for &c in b"a\xffb\xc3(c\xe2\x82\xe0\x80\x80\xed\xa0\x80d\x80" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump(), screen(&["a?b?(c???d?"]));
+
+        // A character ends an escape sequence.
+        let mut vga = VgaTextConsole::new();
+        for &c in "\x1b[31ü\x1b[32mü".as_bytes() {
+            vga.write_byte(c);
+        }
+        assert_eq!(row(&vga, 0)[..6], *b"\x1b[31\x81\x81");
+        let green = VgaTextColorCode::new(VgaTextColor::Green, DEFAULT_BG);
+        assert_eq!(vga.shadow.chars[0][5].1, green);
+    }
+
+    #[test]
+    fn test_status_line() {
+        let mut vga = VgaTextConsole::new();
+        let last = VGA_BUFFER_HEIGHT - 1;
+        let status = VgaTextColorCode::new(VgaTextColor::Black, VgaTextColor::Gray);
+        for i in 0..VGA_BUFFER_HEIGHT {
+            vga.write_byte(b'a' + i as u8);
+            if i < last {
+                vga.write_byte(b'\n');
+            }
+        }
+        // The line of the cursor is scrolled above the status line.
+        vga.set_status_line(Some(("up 0:00:01", status)));
+        let mut lines = letters(b'b'..=b'y');
+        lines.push(String::from("up 0:00:01"));
+        assert_eq!(vga.dump(), screen(&lines));
+        assert_eq!((vga.current_x, vga.current_y), (1, last - 1));
+        assert_eq!(vga.shadow.chars[last][79].1, status);
+
+        // Text scrolls, is cleared and moved around above it.
+        for &c in b"\nz\n" {
+            vga.write_byte(c);
+        }
+        assert_eq!(vga.dump().lines().nth(last - 2), Some("z"));
+        for &c in b"\x1b[99Bx\x1b[2J\x1b[99;1Hw" {
+            vga.write_byte(c);
+        }
+        let mut lines = vec![String::new(); last];
+        lines[last - 1] = String::from("w");
+        lines.push(String::from("up 0:00:01"));
+        assert_eq!(vga.dump(), screen(&lines));
+
+        vga.set_status_line(Some(("up 0:00:02 é", status)));
+        assert_eq!(row(&vga, last)[..12], *b"up 0:00:02 \x82");
+        vga.set_status_line(None);
+        assert_eq!(vga.dump(), screen(&lines[..last]));
+        vga.write_byte(b'\n');
+        assert_eq!(vga.current_y, last);
+    }
+
+    #[test]
+    fn test_tall_mode() {
+        let mut buffer = vga_buffer();
+        let mut vga = VgaTextConsole::new();
+        // what `set_mode` sets besides the VGA registers and the font
+        vga.set_height(VgaMode::Mode80x50.height());
+        for i in 0..VGA_MAX_HEIGHT + 1 {
+            vga.write_byte(b'0' + i as u8 % 10);
+            vga.write_byte(b'\n');
+        }
+        let lines = vga.dump();
+        assert_eq!(lines.lines().count(), VGA_MAX_HEIGHT);
+        assert!(lines.starts_with("2\n3\n"), "{}", lines);
+        assert!(lines.ends_with("0\n\n"), "{}", lines);
+
+        for &c in b"\x1b[99;1Hx" {
+            vga.write_byte(c);
+        }
+        vga.flush_to(&mut buffer);
+        assert_eq!(buffer[VGA_MAX_HEIGHT - 1][0].0, b'x');
+        for &c in b"\x1b[2J" {
+            vga.write_byte(c);
+        }
+        vga.flush_to(&mut buffer);
+        assert_eq!(buffer[VGA_MAX_HEIGHT - 1][0].0, b' ');
+        vga.fill_rect(0, 0, 1, VGA_MAX_HEIGHT + 1, b'#', vga.current_color);
+        assert_eq!(vga.dump(), "#\n".repeat(VGA_MAX_HEIGHT));
+    }
+
+    #[test]
+    fn test_consoles() {
+        let mut buffer = vga_buffer();
+        let mut vga = VgaConsoles::new();
+        for &c in b"one\n\x1b[31m" {
+            vga.output_console().write_byte(c);
+        }
+        vga.flush_to(&mut buffer);
+
+        // The output to a console not shown is kept, and scrolls on its own.
+        vga.output = 1;
+        let console = vga.output_console();
+        for &c in b"a\nb" {
+            console.write_byte(c);
+        }
+        for _ in 1..VGA_BUFFER_HEIGHT {
+            console.write_byte(b'\n');
+        }
+        vga.flush_to(&mut buffer);
+        assert_eq!(shown(&buffer, 0)[..3], *b"one");
+        assert_eq!(vga.consoles[1].dump(), screen(&["b"]));
+        assert_eq!(vga.consoles[0].current_y, 1);
+        assert_eq!(vga.consoles[1].current_y, VGA_BUFFER_HEIGHT - 1);
+        let default = VgaTextColorCode::new(DEFAULT_FG, DEFAULT_BG);
+        let red = default.with_fg(VgaTextColor::Red);
+        assert_eq!(vga.consoles[0].current_color, red);
+        assert_eq!(vga.consoles[1].current_color, default);
+
+        vga.switch_console(1);
+        vga.flush_to(&mut buffer);
+        assert_eq!(shown(&buffer, 0)[..3], *b"b  ");
+        vga.switch_console(VGA_CONSOLES);
+        assert_eq!(vga.active, 1);
+        vga.switch_console(0);
+        vga.flush_to(&mut buffer);
+        assert_eq!(shown(&buffer, 0)[..3], *b"one");
+    }
+
+    #[test]
+    fn test_cursor_visible() {
+        let mut vga = VgaConsoles::new();
+        for &c in b"\x1b[?25l" {
+            vga.output_console().write_byte(c);
+        }
+        assert!(!vga.shown().cursor_visible);
+
+        // Each console shows the cursor or not as it was told.
+        vga.switch_console(1);
+        assert!(vga.shown().cursor_visible);
+        vga.switch_console(0);
+        assert!(!vga.shown().cursor_visible);
+
+        for &c in b"\x1b[?6;25h" {
+            vga.output_console().write_byte(c);
+        }
+        assert!(vga.shown().cursor_visible);
+    }
+
+    #[test]
+    fn test_print_debug() {
+        let levels = DebugLevels::new();
+        let mut vga = VgaTextConsole::new();
+        let (tag, color) = levels.style(1);
+        vga.print_debug(DebugPrefix(None), tag, color, format_args!("up {}\n", 1))
+            .unwrap();
+        let prefix = DebugPrefix(Some((Duration::new(12, 345_678_901), 1)));
+        let (tag, color) = levels.style(4);
+        vga.print_debug(prefix, tag, color, format_args!("x"))
+            .unwrap();
+        assert_eq!(
+            vga.dump_with_colors(),
+            screen(&[
+                "\x1b[92;40m[INFO]  \x1b[0mup 1",
+                "[   12.345678 cpu1] \x1b[90;40m[TRACE] \x1b[0mx"
+            ])
+        );
+        let prefix = DebugPrefix(Some((Duration::from_secs(123_456), 0)));
+        assert_eq!(format!("{}", prefix), "[123456.000000 cpu0] ");
+    }
+
+    #[test]
+    fn test_max_level() {
+        let levels = DebugLevels::new();
+        assert_eq!(levels.max(), 3);
+        assert_eq!(levels.set_max(4), Ok(()));
+        assert_eq!(levels.set_max(5), Err(5));
+        assert_eq!(levels.max(), 4);
+    }
+
+    #[test]
+    fn test_level_style() {
+        let levels = DebugLevels::new();
+        let (white, red) = (VgaTextColor::White, VgaTextColor::Red);
+        assert_eq!(levels.set_style(0, white, red, "[WARN]  "), Err(0));
+        assert_eq!(levels.set_style(5, white, red, "[WARN]  "), Err(5));
+        let (tag, color) = levels.style(2);
+        assert_eq!((tag, color.0), ("[DEV]   ", 0x09));
+        assert_eq!(levels.set_style(2, white, red, "[WARN]  "), Ok(()));
+        let (tag, color) = levels.style(2);
+        assert_eq!(
+            (tag, color),
+            ("[WARN]  ", VgaTextColorCode::new(white, red))
+        );
+    }
+
+    #[test]
+    fn test_spill() {
+        let mut spill = Spill::new();
+        assert_eq!(spill.push(b"ab"), 2);
+        let mut long = vec![b'c'; SPILL_SIZE];
+        long.extend_from_slice(b"dd");
+        assert_eq!(spill.push(&long), SPILL_SIZE - 2);
+        assert_eq!(spill.bytes().len(), SPILL_SIZE);
+
+        let mut console = VgaTextConsole::new();
+        spill.drain_to(&mut console);
+        assert!(spill.bytes().is_empty());
+        assert_eq!(&row(&console, 0)[..3], b"abc");
+        assert_eq!((console.current_x, console.current_y), (16, 3));
+    }
+
+    #[test]
+    fn test_cpu_lock() {
+        let lock = CpuLock::new(0);
+        let mut guard = lock.lock(1);
+        *guard += 1;
+        // the CPU holding the lock does not wait for itself
+        assert!(lock.is_held_by(1));
+        assert!(!lock.is_held_by(0));
+        assert!(lock.try_lock(0).is_none());
+        drop(guard);
+        assert!(!lock.is_held_by(1));
+        let guard = lock.try_lock(0).unwrap();
+        assert_eq!(*guard, 1);
+        assert!(lock.is_held_by(0));
+    }
+
+    #[test]
+    fn test_panic_banner() {
+        let mut vga = VgaTextConsole::new();
+        for &c in b"\x1b[32mok\n\x1b[3\xc3" {
+            vga.write_byte(c);
+        }
+        let long = "x".repeat(VGA_BUFFER_WIDTH + 2);
+        vga.panic_banner(format_args!("panicked at a.rs:1:2:\n{}", long));
+        let top = VGA_BUFFER_HEIGHT - PANIC_BANNER_ROWS;
+        assert_eq!((vga.current_x, vga.current_y), (2, top + 2));
+        let mut lines = vec![String::from("\x1b[32;40mok\x1b[0m")];
+        lines.resize(top, String::new());
+        let red = |text: &str| format!("\x1b[97;41m{:<1$}\x1b[0m", text, VGA_BUFFER_WIDTH);
+        lines.push(red("panicked at a.rs:1:2:"));
+        lines.push(red(&long[..VGA_BUFFER_WIDTH]));
+        lines.push(red("xx"));
+        lines.resize(VGA_BUFFER_HEIGHT, red(""));
+        assert_eq!(vga.dump_with_colors(), screen(&lines));
+    }
+}
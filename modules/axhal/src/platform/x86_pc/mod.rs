@@ -1,7 +1,9 @@
 mod apic;
 mod boot;
 mod dtables;
-//mod uart16550;
+// only written to, to mirror the VGA console
+#[allow(dead_code)]
+mod uart16550;
 mod vga_buffer;
 mod ps2_key;
 
@@ -17,6 +19,8 @@ pub mod irq {
     pub use super::apic::*;
 }
 
+/// The VGA console, which mirrors its output to the serial port itself if
+/// asked, so the serial console is not used as well.
 pub mod console {
     //pub use super::uart16550::*;
     pub use super::vga_buffer::*;
@@ -44,7 +48,7 @@ unsafe extern "C" fn rust_entry(magic: usize, _mbi: usize) {
     if magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC {
         crate::mem::clear_bss();
         crate::cpu::init_primary(current_cpu_id());
-        //self::uart16550::init_early();
+        self::uart16550::init_early();
         self::console::init_early();
         self::dtables::init_primary();
         self::time::init_early();
@@ -18,6 +18,9 @@ static KEYBOARD: SpinNoIrq<Keyboard<Us104Key, ScancodeSet1>> = SpinNoIrq::new(Ke
 const KEYBOARD_IRQ_NUM: u8 = 0x21;
 /// 键盘输入端口
 const KEYBOARD_INPUT_PORT: u16 = 0x60;
+/// Shift+PageUp/PageDown 滚动的行数（半屏，同 Linux）
+#[cfg(feature = "alloc")]
+const SCROLL_VIEW_LINES: usize = 12;
 
 use crate::console::put2stdin;
 
@@ -29,13 +32,51 @@ fn keyboard_irq_handler() {
     let mut keyboard = KEYBOARD.lock();
     if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
         if let Some(key) = keyboard.process_keyevent(key_event) {
-            if let DecodedKey::Unicode(c) = key {
-                put2stdin(c as u8);
+            match key {
+                DecodedKey::Unicode(c) => put2stdin(c as u8),
+                DecodedKey::RawKey(code) => {
+                    let modifiers = keyboard.get_modifiers();
+                    switch_console(code, modifiers);
+                    #[cfg(feature = "alloc")]
+                    scroll_view(code, modifiers);
+                }
             }
         }
     }
 }
 
+/// Alt+F1..F4 切换虚拟控制台
+fn switch_console(code: pc_keyboard::KeyCode, modifiers: &pc_keyboard::Modifiers) {
+    use pc_keyboard::KeyCode;
+
+    if !(modifiers.lalt || modifiers.ralt) {
+        return;
+    }
+    let n = match code {
+        KeyCode::F1 => 0,
+        KeyCode::F2 => 1,
+        KeyCode::F3 => 2,
+        KeyCode::F4 => 3,
+        _ => return,
+    };
+    crate::console::switch_console(n);
+}
+
+/// Shift+PageUp/PageDown 滚动查看已滚出屏幕的输出
+#[cfg(feature = "alloc")]
+fn scroll_view(code: pc_keyboard::KeyCode, modifiers: &pc_keyboard::Modifiers) {
+    use pc_keyboard::KeyCode;
+
+    if !(modifiers.lshift || modifiers.rshift) {
+        return;
+    }
+    match code {
+        KeyCode::PageUp => crate::console::scroll_view_up(SCROLL_VIEW_LINES),
+        KeyCode::PageDown => crate::console::scroll_view_down(SCROLL_VIEW_LINES),
+        _ => {}
+    }
+}
+
 /// 初始化键盘
 pub(super) fn init() {
     #[cfg(feature = "irq")]
@@ -95,6 +95,17 @@ pub fn putchar(c: u8) {
     }
 }
 
+/// Writes a slice of bytes to the console.
+pub fn write_bytes(bytes: &[u8]) {
+    let mut uart = COM1.lock();
+    for &c in bytes {
+        if c == b'\n' {
+            uart.putchar(b'\r');
+        }
+        uart.putchar(c);
+    }
+}
+
 /// Reads a byte from the console, or returns [`None`] if no input is available.
 pub fn getchar() -> Option<u8> {
     COM1.lock().getchar()
@@ -2,262 +2,326 @@
 
 extern crate alloc;
 
-use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 
 use lazy_init::LazyInit;
-use spinlock::SpinNoIrq;
+use spinlock::{SpinNoIrq, SpinNoIrqGuard};
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
 use core::fmt;
-use core::fmt::Error;
 use core::fmt::Write;
-
-use axlog::ColorCode as ConsoleColorCode;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use crate::mem::PhysAddr;
+use crate::platform::vga_text::{
+    cp437_glyph, CpuLock, CpuLockGuard, DebugLevels, DebugPrefix, Spill, VgaConsoles, VgaTextChar,
+    VgaTextConsole, VgaTextRow, PANIC_BANNER_ROWS, PANIC_COLOR, REPLACEMENT_GLYPH,
+    VGA_BUFFER_WIDTH, VGA_MAX_HEIGHT,
+};
+
+#[cfg(feature = "alloc")]
+pub use crate::platform::vga_text::ScreenSnapshot;
+pub use crate::platform::vga_text::{VgaMode, VgaTextColor, VgaTextColorCode, VGA_CONSOLES};
 
-static VGA: SpinNoIrq<VgaTextMode> = SpinNoIrq::new(VgaTextMode::new());
+static VGA: CpuLock<VgaTextMode> = CpuLock::new(VgaTextMode::new());
 static STDIN_BUFFER: SpinNoIrq<StdinBuffer> = SpinNoIrq::new(StdinBuffer::new());
 
-static mut LEVEL_DEBUG: u8 = 3;
+/// The number of rows of the screen in the current mode, known without the
+/// lock of `VGA` for the panic banner.
+static VGA_HEIGHT: AtomicUsize = AtomicUsize::new(VgaMode::Mode80x25.height());
+
+/// The bytes each CPU wrote while it held the lock of `VGA`, as when it
+/// faulted in the middle of writing.
+static SPILLS: [SpinNoIrq<Spill>; axconfig::SMP] =
+    [const { SpinNoIrq::new(Spill::new()) }; axconfig::SMP];
+/// The number of bytes kept in `SPILLS` so far.
+static DEFERRED_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// The number of bytes there was no room for in `SPILLS` so far.
+static DROPPED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The levels of the messages of `print_debug`.
+static LEVELS: DebugLevels = DebugLevels::new();
+/// Whether the messages of `print_debug` start with the time and the CPU.
+static PREFIX_DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Whether the output is written to the serial port as well.
+static SERIAL_MIRROR: AtomicBool = AtomicBool::new(cfg!(feature = "serial-mirror"));
 
-/// The height of the vga text buffer (normally 25 lines).
-const VGA_BUFFER_HEIGHT: usize = 25;
-/// The width of the vga text buffer (normally 80 columns).
-const VGA_BUFFER_WIDTH: usize = 80;
 /// The MMIO address of VGA buffer.
 const VGA_BASE_ADDR: PhysAddr = PhysAddr::from(0xb_8000);
+/// The address plane 2 of the VGA memory, holding the font, is mapped at
+/// while it is loaded, with 32 bytes for each character.
+const VGA_FONT_ADDR: PhysAddr = PhysAddr::from(0xa_0000);
+const VGA_FONT_STRIDE: usize = 32;
+/// The address of the 8x8 font of the first 128 characters in the BIOS.
+const BIOS_FONT_8X8_ADDR: PhysAddr = PhysAddr::from(0xf_fa6e);
+/// How many times a panicking CPU tries to lock the console held by another
+/// CPU, before writing the message of the panic without the lock.
+const PANIC_LOCK_TRIES: usize = 1 << 20;
 /// The size of Stdin Buffer
 const STDIN_BUFFER_SIZE: usize = 1024;
+/// The I/O port selecting a CRT controller register.
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+/// The I/O port accessing the selected CRT controller register.
+const CRTC_DATA_PORT: u16 = 0x3d5;
+/// The CRT controller register of the height of characters, less one, in
+/// the low 5 bits.
+const CRTC_MAX_SCAN_LINE: u8 = 0x09;
+/// The CRT controller registers of the cursor shape: the scanlines it starts
+/// and ends at, and whether it is disabled (bit 5 of the start register).
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_END: u8 = 0x0b;
+const CRTC_CURSOR_DISABLE: u8 = 1 << 5;
+/// The I/O ports selecting and accessing a sequencer register.
+const SEQ_INDEX_PORT: u16 = 0x3c4;
+const SEQ_DATA_PORT: u16 = 0x3c5;
+/// The sequencer registers holding it in reset, selecting the planes written
+/// to, and setting how the planes are addressed.
+const SEQ_RESET: u8 = 0x00;
+const SEQ_MAP_MASK: u8 = 0x02;
+const SEQ_MEMORY_MODE: u8 = 0x04;
+/// The I/O ports selecting and accessing a graphics controller register.
+const GC_INDEX_PORT: u16 = 0x3ce;
+const GC_DATA_PORT: u16 = 0x3cf;
+/// The graphics controller registers selecting the plane read, setting how
+/// the planes are addressed, and where the memory is mapped.
+const GC_READ_MAP: u8 = 0x04;
+const GC_MODE: u8 = 0x05;
+const GC_MISC: u8 = 0x06;
+/// The CRT controller registers of the cursor location, high byte first.
+const CRTC_CURSOR_LOCATION_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOCATION_LOW: u8 = 0x0f;
+/// The I/O port of the attribute controller, taking a register index and
+/// the value to write to it in turn.
+const ATTR_PORT: u16 = 0x3c0;
+/// The I/O port reading the selected attribute controller register.
+const ATTR_DATA_READ_PORT: u16 = 0x3c1;
+/// The I/O port of input status register 1, reading which makes `ATTR_PORT`
+/// take an index next.
+const INPUT_STATUS_PORT: u16 = 0x3da;
+/// The bit of an attribute controller index keeping the screen on: with it
+/// clear, the palette is being changed and the screen is blank.
+const ATTR_PALETTE_SOURCE: u8 = 1 << 5;
+/// The attribute mode control register, and its bit making bright
+/// backgrounds blink.
+const ATTR_MODE_CONTROL: u8 = 0x10;
+const ATTR_MODE_BLINK: u8 = 1 << 3;
+/// Whether bright backgrounds blink, as the BIOS leaves them, rather than
+/// being shown.
+const BLINK: bool = false;
 
-/// The standard color palette in VGA text mode.
-#[allow(dead_code)]
-#[derive(Clone, Copy)]
-#[repr(u8)]
-enum VgaTextColor {
-    Black = 0,
-    Blue = 1,
-    Green = 2,
-    Cyan = 3,
-    Red = 4,
-    Purple = 5,
-    Brown = 6,
-    Gray = 7,
-    DarkGray = 8,
-    LightBlue = 9,
-    LightGreen = 10,
-    LightCyan = 11,
-    LightRed = 12,
-    LightPurple = 13,
-    Yellow = 14,
-    White = 15,
-}
-
-impl VgaTextColor {
-    fn from_console_color(color: ConsoleColorCode) -> VgaTextColor {
-        match color {
-            ConsoleColorCode::Black => VgaTextColor::Black,
-            ConsoleColorCode::Red => VgaTextColor::Red,
-            ConsoleColorCode::Green => VgaTextColor::Green,
-            ConsoleColorCode::Yellow => VgaTextColor::Brown,
-            ConsoleColorCode::Blue => VgaTextColor::Blue,
-            ConsoleColorCode::Magenta => VgaTextColor::Purple,
-            ConsoleColorCode::Cyan => VgaTextColor::Cyan,
-            ConsoleColorCode::White => VgaTextColor::Gray,
-            ConsoleColorCode::BrightBlack => VgaTextColor::Gray,
-            ConsoleColorCode::BrightRed => VgaTextColor::LightRed,
-            ConsoleColorCode::BrightGreen => VgaTextColor::LightGreen,
-            ConsoleColorCode::BrightYellow => VgaTextColor::Yellow,
-            ConsoleColorCode::BrightBlue => VgaTextColor::LightBlue,
-            ConsoleColorCode::BrightMagenta => VgaTextColor::LightPurple,
-            ConsoleColorCode::BrightCyan => VgaTextColor::LightCyan,
-            ConsoleColorCode::BrightWhite => VgaTextColor::White,
-        }
-    }
+struct VgaTextMode {
+    /// The virtual consoles, and which of them are shown and written to.
+    consoles: VgaConsoles,
+    /// The rows of the VGA buffer, as many as in any mode.
+    buffer: LazyInit<&'static mut [VgaTextRow]>,
+    mode: VgaMode,
+    /// Whether the hardware cursor is shown, as last set.
+    cursor_shown: bool,
+    /// The 8x16 font of the characters, kept while another is loaded.
+    saved_font: [[u8; 16]; 256],
+    crtc_index: PortWriteOnly<u8>,
+    crtc_data: Port<u8>,
+    seq_index: PortWriteOnly<u8>,
+    seq_data: PortWriteOnly<u8>,
+    gc_index: PortWriteOnly<u8>,
+    gc_data: PortWriteOnly<u8>,
+    attr: PortWriteOnly<u8>,
+    attr_data: PortReadOnly<u8>,
+    input_status: PortReadOnly<u8>,
 }
 
-/// A combination of a foreground and a background color.
-#[derive(Clone, Copy)]
-#[repr(transparent)]
-struct VgaTextColorCode(u8);
-
-impl VgaTextColorCode {
-    /// Create a new `VgaTextColorCode` with the given foreground and background colors.
-    const fn new(fg: VgaTextColor, bg: VgaTextColor) -> VgaTextColorCode {
-        VgaTextColorCode((bg as u8) << 4 | (fg as u8))
+impl VgaTextMode {
+    const fn new() -> Self {
+        Self {
+            consoles: VgaConsoles::new(),
+            buffer: LazyInit::new(),
+            mode: VgaMode::Mode80x25,
+            cursor_shown: true,
+            saved_font: [[0; 16]; 256],
+            crtc_index: PortWriteOnly::new(CRTC_INDEX_PORT),
+            crtc_data: Port::new(CRTC_DATA_PORT),
+            seq_index: PortWriteOnly::new(SEQ_INDEX_PORT),
+            seq_data: PortWriteOnly::new(SEQ_DATA_PORT),
+            gc_index: PortWriteOnly::new(GC_INDEX_PORT),
+            gc_data: PortWriteOnly::new(GC_DATA_PORT),
+            attr: PortWriteOnly::new(ATTR_PORT),
+            attr_data: PortReadOnly::new(ATTR_DATA_READ_PORT),
+            input_status: PortReadOnly::new(INPUT_STATUS_PORT),
+        }
     }
-}
-
-/// Character for the VGA text buffer, including an ASCII character and a `VgaTextColorCode`.
-struct VgaTextChar(u8, VgaTextColorCode);
 
-/// A structure representing the VGA text buffer.
-#[repr(transparent)]
-struct VgaTextBuffer {
-    chars: [[VgaTextChar; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
-}
+    /// Returns the console shown.
+    fn active_console(&mut self) -> &mut VgaTextConsole {
+        self.consoles.active_console()
+    }
 
-#[derive(Clone, Copy)]
-enum VgaTextSetColor {
-    // \x1b, to LeftBrackets
-    Start,
-    // [, to value or end
-    LeftBrackets,
-    // number
-    Value(u8),
-    // m, end
-    End,
-}
+    /// Returns the console written to by `putchar` and the like.
+    fn output_console(&mut self) -> &mut VgaTextConsole {
+        self.consoles.output_console()
+    }
 
-#[derive(Clone, Copy)]
-enum VgaTextState {
-    PutChar,
-    SetColor(VgaTextSetColor),
-}
+    fn write_crtc(&mut self, index: u8, value: u8) {
+        unsafe {
+            self.crtc_index.write(index);
+            self.crtc_data.write(value);
+        }
+    }
 
-struct VgaTextMode {
-    current_x: usize,
-    current_y: usize,
-    current_color: VgaTextColorCode,
-    state: VgaTextState,
-    buffer: LazyInit<&'static mut VgaTextBuffer>,
-}
+    fn read_crtc(&mut self, index: u8) -> u8 {
+        unsafe {
+            self.crtc_index.write(index);
+            self.crtc_data.read()
+        }
+    }
 
-impl VgaTextMode {
-    const fn new() -> Self {
-        Self {
-            current_x: 0,
-            current_y: 0,
-            current_color: VgaTextColorCode::new(VgaTextColor::White, VgaTextColor::Black),
-            state: VgaTextState::PutChar,
-            buffer: LazyInit::new(),
+    fn write_seq(&mut self, index: u8, value: u8) {
+        unsafe {
+            self.seq_index.write(index);
+            self.seq_data.write(value);
         }
     }
 
-    fn scroll_up(&mut self, line: usize) {
-        if line > VGA_BUFFER_HEIGHT {
-            return;
+    fn write_gc(&mut self, index: u8, value: u8) {
+        unsafe {
+            self.gc_index.write(index);
+            self.gc_data.write(value);
         }
+    }
 
-        let buffer = &mut self.buffer.chars;
+    /// Copies the rows of the console shown changed to the VGA buffer and
+    /// moves the hardware cursor.
+    fn flush(&mut self) {
+        self.consoles.flush_to(&mut self.buffer);
+        self.update_cursor();
+    }
 
-        let size =
-            (VGA_BUFFER_HEIGHT - line) * VGA_BUFFER_WIDTH * core::mem::size_of::<VgaTextChar>();
-        let src = &buffer[line][0] as *const VgaTextChar;
-        let dst = &mut buffer[0][0] as *mut VgaTextChar;
+    /// Makes bright backgrounds blink, or shows them as they are.
+    fn set_blink(&mut self, blink: bool) {
         unsafe {
-            core::ptr::copy(src, dst, size);
+            // `ATTR_PORT` takes an index and a value in turn, from an index
+            // after input status 1 is read. The index keeps the palette
+            // source bit set, or the screen would stay blank.
+            self.input_status.read();
+            self.attr.write(ATTR_MODE_CONTROL | ATTR_PALETTE_SOURCE);
+            let mode = self.attr_data.read();
+            let mode = if blink {
+                mode | ATTR_MODE_BLINK
+            } else {
+                mode & !ATTR_MODE_BLINK
+            };
+            // writing the value makes the port take an index again
+            self.attr.write(mode);
         }
-        self.current_y -= line;
     }
 
-    fn process_char(&mut self, ch: u8) -> VgaTextState {
-        match &self.state {
-            VgaTextState::PutChar => {
-                if ch == 0x1b {
-                    self.state = VgaTextState::SetColor(VgaTextSetColor::Start);
-                }
-            }
-            VgaTextState::SetColor(state) => {
-                match state {
-                    VgaTextSetColor::Start => {
-                        if ch == b'[' {
-                            self.state = VgaTextState::SetColor(VgaTextSetColor::LeftBrackets);
-                        } else {
-                            // ignore invalid state and put it
-                            self.state = VgaTextState::PutChar;
-                        }
-                    }
-                    VgaTextSetColor::LeftBrackets => {
-                        match ch {
-                            b'm' => {
-                                self.set_color(None);
-                                self.state = VgaTextState::SetColor(VgaTextSetColor::End);
-                            }
-                            ch_val @ b'0'..=b'9' => {
-                                self.state =
-                                    VgaTextState::SetColor(VgaTextSetColor::Value(ch_val - b'0'));
-                            }
-                            _ => {
-                                // ignore invalid state and put it
-                                self.state = VgaTextState::PutChar;
-                            }
-                        }
-                    }
-                    VgaTextSetColor::Value(v) => {
-                        match ch {
-                            b'm' => {
-                                let color = match (*v).try_into() {
-                                    Ok(c) => Some(VgaTextColorCode::new(
-                                        VgaTextColor::from_console_color(c),
-                                        VgaTextColor::Black,
-                                    )),
-                                    Err(_) => None,
-                                };
-                                self.set_color(color);
-                                self.state = VgaTextState::SetColor(VgaTextSetColor::End);
-                            }
-                            ch_val @ b'0'..=b'9' => {
-                                self.state = VgaTextState::SetColor(VgaTextSetColor::Value(
-                                    v * 10 + (ch_val - b'0'),
-                                ));
-                            }
-                            _ => {
-                                // ignore invalid state and put it
-                                self.state = VgaTextState::PutChar;
-                            }
-                        }
-                    }
-                    VgaTextSetColor::End => {
-                        if ch == 0x1b {
-                            self.state = VgaTextState::SetColor(VgaTextSetColor::Start);
-                        } else {
-                            self.state = VgaTextState::PutChar;
-                        }
-                    }
-                }
-            }
+    /// Moves the hardware cursor to that of the console shown, and shows or
+    /// hides it as the console does.
+    fn update_cursor(&mut self) {
+        let visible = self.consoles.shown().cursor_visible;
+        if visible != self.cursor_shown {
+            self.cursor_enable(visible);
         }
+        let console = self.consoles.shown();
+        #[cfg(feature = "alloc")]
+        if console.scrollback.offset > 0 {
+            return;
+        }
+        let pos = (console.current_y * VGA_BUFFER_WIDTH + console.current_x) as u16;
+        self.write_crtc(CRTC_CURSOR_LOCATION_HIGH, (pos >> 8) as u8);
+        self.write_crtc(CRTC_CURSOR_LOCATION_LOW, pos as u8);
+    }
 
-        self.state
+    /// Shows the hardware cursor as an underline, or hides it.
+    fn cursor_enable(&mut self, enable: bool) {
+        self.cursor_shown = enable;
+        if enable {
+            let bottom = self.mode.char_height() - 1;
+            self.write_crtc(CRTC_CURSOR_START, bottom - 1);
+            self.write_crtc(CRTC_CURSOR_END, bottom);
+        } else {
+            self.write_crtc(CRTC_CURSOR_START, CRTC_CURSOR_DISABLE);
+        }
     }
 
-    fn set_color(&mut self, color: Option<VgaTextColorCode>) {
-        self.current_color = color.unwrap_or(VgaTextColorCode::new(
-            VgaTextColor::White,
-            VgaTextColor::Black,
-        ));
+    /// Maps plane 2 of the VGA memory, holding the font, at `VGA_FONT_ADDR`
+    /// if `font` is set, or maps the text back at `VGA_BASE_ADDR`.
+    fn map_font(&mut self, font: bool) {
+        // the sequencer is held in reset while the addressing changes
+        self.write_seq(SEQ_RESET, 0x01);
+        if font {
+            // plane 2 alone, addressed sequentially, at 0xa0000
+            self.write_seq(SEQ_MAP_MASK, 0x04);
+            self.write_seq(SEQ_MEMORY_MODE, 0x07);
+            self.write_seq(SEQ_RESET, 0x03);
+            self.write_gc(GC_READ_MAP, 0x02);
+            self.write_gc(GC_MODE, 0x00);
+            self.write_gc(GC_MISC, 0x04);
+        } else {
+            // planes 0 and 1, the characters and their colors, in turn at
+            // 0xb8000
+            self.write_seq(SEQ_MAP_MASK, 0x03);
+            self.write_seq(SEQ_MEMORY_MODE, 0x03);
+            self.write_seq(SEQ_RESET, 0x03);
+            self.write_gc(GC_READ_MAP, 0x00);
+            self.write_gc(GC_MODE, 0x10);
+            self.write_gc(GC_MISC, 0x0e);
+        }
     }
 
-    fn putchar(&mut self, ch: u8) {
-        match ch {
-            b'\r' => {
-                self.current_x = 0;
-            }
-            b'\n' => {
-                // treat it as \r\n
-                self.current_x = 0;
-                self.current_y += 1;
-            }
-            b'\x08' => {
-                // handle backspace
-                self.current_x -= 1;
-                self.buffer.chars[self.current_y][self.current_x] = 
-                    VgaTextChar(b' ' as u8, self.current_color);
+    /// Loads the font of `mode`, keeping the 8x16 one to load it back.
+    ///
+    /// The 8x8 font of the first 128 characters is that of the BIOS, and the
+    /// others are the 8x16 ones with each pair of scanlines merged.
+    fn load_font(&mut self, mode: VgaMode) {
+        let font = mapped(VGA_FONT_ADDR) as *mut u8;
+        self.map_font(true);
+        unsafe {
+            if self.mode == VgaMode::Mode80x25 {
+                for (c, glyph) in self.saved_font.iter_mut().enumerate() {
+                    for (i, line) in glyph.iter_mut().enumerate() {
+                        *line = font.add(c * VGA_FONT_STRIDE + i).read_volatile();
+                    }
+                }
             }
-            _ => {
-                self.buffer.chars[self.current_y][self.current_x] =
-                    VgaTextChar(ch, self.current_color);
-                self.current_x += 1;
+            let bios_font = mapped(BIOS_FONT_8X8_ADDR) as *const u8;
+            for (c, saved) in self.saved_font.iter().enumerate() {
+                for i in 0..mode.char_height() as usize {
+                    let line = match mode {
+                        VgaMode::Mode80x25 => saved[i],
+                        VgaMode::Mode80x50 if c < 128 => bios_font.add(c * 8 + i).read_volatile(),
+                        VgaMode::Mode80x50 => saved[2 * i] | saved[2 * i + 1],
+                    };
+                    font.add(c * VGA_FONT_STRIDE + i).write_volatile(line);
+                }
             }
         }
+        self.map_font(false);
+    }
 
-        if self.current_x >= VGA_BUFFER_WIDTH {
-            self.current_x = 0;
-            self.current_y += 1;
+    /// Switches to text mode `mode`, clearing the screens of all consoles.
+    fn set_mode(&mut self, mode: VgaMode) {
+        if mode == self.mode {
+            return;
         }
-        if self.current_y >= VGA_BUFFER_HEIGHT {
-            self.scroll_up(self.current_y - VGA_BUFFER_HEIGHT + 1);
+        self.load_font(mode);
+        let max_scan_line = self.read_crtc(CRTC_MAX_SCAN_LINE) & !0x1f;
+        self.write_crtc(CRTC_MAX_SCAN_LINE, max_scan_line | (mode.char_height() - 1));
+        self.mode = mode;
+        VGA_HEIGHT.store(mode.height(), Ordering::Relaxed);
+        // the cursor lines depend on the height of the characters
+        self.cursor_enable(self.cursor_shown);
+        for console in self.consoles.iter_mut() {
+            console.set_height(mode.height());
         }
     }
+
+    /// Shows the panic message `args` in white on red at the bottom of the
+    /// console shown, whatever it was in the middle of.
+    fn panic_banner(&mut self, args: fmt::Arguments) {
+        self.active_console().panic_banner(args);
+    }
 }
 
 /// 标准输入的缓存块
@@ -298,105 +362,602 @@ impl StdinBuffer {
     }
 }
 
+/// Keeps `bytes` in `spill`, counting those kept and those there is no room
+/// for.
+fn spill_bytes(spill: &mut Spill, bytes: &[u8]) {
+    let kept = spill.push(bytes);
+    DEFERRED_BYTES.fetch_add(kept, Ordering::Relaxed);
+    DROPPED_BYTES.fetch_add(bytes.len() - kept, Ordering::Relaxed);
+}
+
+impl Write for Spill {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        spill_bytes(self, s.as_bytes());
+        Ok(())
+    }
+}
+
+/// The console locked by the current CPU.
+///
+/// The CPU is known while it holds the lock, so that it does not wait for
+/// itself when it writes from an exception taken while writing.
+struct VgaLock(CpuLockGuard<'static, VgaTextMode>);
+
+impl VgaLock {
+    /// Locks the console, waiting for another CPU holding it, and writes the
+    /// bytes spilled in the meantime.
+    fn lock() -> Self {
+        let mut vga = VgaLock(VGA.lock(crate::cpu::this_cpu_id()));
+        for spill in SPILLS.iter() {
+            // one being written by its CPU is written next time
+            if let Some(mut spill) = spill.try_lock() {
+                #[cfg(feature = "log-buffer")]
+                crate::log_buffer::write_bytes(spill.bytes());
+                mirror_to_serial(spill.bytes());
+                spill.drain_to(vga.output_console());
+            }
+        }
+        vga
+    }
+
+    /// Locks the console like [`VgaLock::lock`], unless the current CPU holds
+    /// it already.
+    fn try_lock() -> Option<Self> {
+        if VGA.is_held_by(crate::cpu::this_cpu_id()) {
+            return None;
+        }
+        Some(Self::lock())
+    }
+}
+
+impl Deref for VgaLock {
+    type Target = VgaTextMode;
+
+    fn deref(&self) -> &VgaTextMode {
+        &self.0
+    }
+}
+
+impl DerefMut for VgaLock {
+    fn deref_mut(&mut self) -> &mut VgaTextMode {
+        &mut self.0
+    }
+}
+
+/// Counts the bytes written to it as dropped.
+struct Dropped;
+
+impl Write for Dropped {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        DROPPED_BYTES.fetch_add(s.len(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Writes the message of a panic straight to the VGA memory, without the
+/// lock of `VGA`, wrapping long lines and dropping those past the bottom of
+/// the screen.
+struct RawBanner {
+    cells: *mut VgaTextChar,
+    x: usize,
+    y: usize,
+    bottom: usize,
+}
+
+impl RawBanner {
+    /// Clears the rows `top..bottom` of the screen for the message.
+    fn new(top: usize, bottom: usize) -> Self {
+        let cells = mapped(VGA_BASE_ADDR) as *mut VgaTextChar;
+        for i in top * VGA_BUFFER_WIDTH..bottom * VGA_BUFFER_WIDTH {
+            unsafe { cells.add(i).write_volatile(VgaTextChar(b' ', PANIC_COLOR)) };
+        }
+        Self {
+            cells,
+            x: 0,
+            y: top,
+            bottom,
+        }
+    }
+
+    fn put(&mut self, glyph: u8) {
+        if self.x == VGA_BUFFER_WIDTH {
+            self.x = 0;
+            self.y += 1;
+        }
+        if self.y < self.bottom {
+            let cell = unsafe { self.cells.add(self.y * VGA_BUFFER_WIDTH + self.x) };
+            unsafe { cell.write_volatile(VgaTextChar(glyph, PANIC_COLOR)) };
+        }
+        self.x += 1;
+    }
+}
+
+impl Write for RawBanner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '\n' => {
+                    self.x = 0;
+                    self.y += 1;
+                }
+                '\r' => self.x = 0,
+                _ => self.put(cp437_glyph(c).unwrap_or(REPLACEMENT_GLYPH)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes to the serial port, with the escape sequences as they are, for a
+/// terminal to show.
+struct SerialWriter;
+
+impl Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        super::uart16550::write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Writes `bytes` to the serial port if the output is mirrored there.
+///
+/// It is called with the console locked, so that an exception taken while
+/// writing to the serial port does not write to it again.
+fn mirror_to_serial(bytes: &[u8]) {
+    if SERIAL_MIRROR.load(Ordering::Relaxed) {
+        super::uart16550::write_bytes(bytes);
+    }
+}
+
+impl DebugPrefix {
+    /// Returns the prefix of a message printed now.
+    fn now() -> Self {
+        Self(
+            PREFIX_DEBUG
+                .load(Ordering::Relaxed)
+                .then(|| (crate::time::current_time(), crate::cpu::this_cpu_id())),
+        )
+    }
+}
+
+/// Writes `args` after `prefix` and the tag of debug level `level`, from 1,
+/// with the SGR sequences of its color, for a terminal.
+fn write_debug_sgr(
+    w: &mut dyn Write,
+    level: u8,
+    prefix: DebugPrefix,
+    args: fmt::Arguments,
+) -> fmt::Result {
+    let (tag, color) = LEVELS.style(level);
+    let (fg, bg) = color.sgr_params();
+    write!(w, "{}\x1b[{};{}m{}\x1b[0m{}", prefix, fg, bg, tag, args)
+}
+
+/// Returns the spill of the current CPU, unless it is being written already,
+/// by the code an exception interrupted or as it is written to the console.
+fn this_spill() -> Option<SpinNoIrqGuard<'static, Spill>> {
+    SPILLS[crate::cpu::this_cpu_id()].try_lock()
+}
+
 pub fn put2stdin(c: u8) {
     STDIN_BUFFER.lock().push(c);
 }
 
 pub fn putchar(c: u8) {
-    let mut vga = VGA.lock();
+    write_bytes(&[c]);
+}
+
+/// Write a slice of bytes to the console, updating the screen and the cursor
+/// once at the end.
+///
+/// The bytes are written once the console is unlocked if this CPU holds the
+/// lock already, as when it writes from an exception taken while writing.
+pub fn write_bytes(bytes: &[u8]) {
+    let Some(mut vga) = VgaLock::try_lock() else {
+        match this_spill() {
+            Some(mut spill) => spill_bytes(&mut spill, bytes),
+            None => {
+                DROPPED_BYTES.fetch_add(bytes.len(), Ordering::Relaxed);
+            }
+        }
+        return;
+    };
+    #[cfg(feature = "log-buffer")]
+    crate::log_buffer::write_bytes(bytes);
+    mirror_to_serial(bytes);
+    let console = vga.output_console();
+    for c in bytes {
+        console.write_byte(*c);
+    }
+    vga.flush();
+}
+
+/// Clear the screen and move the cursor to the top left.
+pub fn clear() {
+    let mut vga = VgaLock::lock();
+    vga.output_console().clear();
+    vga.flush();
+}
+
+/// Use `fg` on `bg` as the default colors of all the consoles, e.g. black on
+/// white for a "paper" theme, rather than white on black.
+///
+/// They are the colors of the text after `\x1b[0m` and of the screen after
+/// [`clear`]. The blank cells in the old default colors are repainted at
+/// once, the text written before is left as it is.
+pub fn set_default_colors(fg: VgaTextColor, bg: VgaTextColor) {
+    let mut vga = VgaLock::lock();
+    for console in vga.consoles.iter_mut() {
+        console.set_default_colors(fg, bg);
+    }
+    vga.flush();
+}
+
+/// Save the text, colors and cursor of the output console, e.g. before a
+/// full-screen program draws over them, to be put back by
+/// [`restore_screen`].
+///
+/// Programs may also write `\x1b[?1049h` and `\x1b[?1049l` to do so, as
+/// for the alternate screen of xterm.
+#[cfg(feature = "alloc")]
+pub fn save_screen() -> ScreenSnapshot {
+    VgaLock::lock().output_console().save_screen()
+}
+
+/// Put back the text, colors and cursor saved by [`save_screen`] in the
+/// output console.
+///
+/// If the screen has fewer rows of text than when it was saved, the rows at
+/// the bottom are lost.
+#[cfg(feature = "alloc")]
+pub fn restore_screen(snapshot: &ScreenSnapshot) {
+    let mut vga = VgaLock::lock();
+    vga.output_console().restore_screen(snapshot);
+    vga.flush();
+}
+
+/// Show the cells written by [`write_at`], [`put_cell`] and [`fill_rect`]
+/// since the screen was last updated.
+///
+/// Writing text updates the screen by itself.
+pub fn flush() {
+    VgaLock::lock().flush();
+}
+
+/// Show `lines` more lines scrolled off the top of the screen, as far as
+/// they are kept.
+///
+/// Output is not shown until the view is back to live output, unless
+/// [`set_scroll_view_snap`] is set.
+#[cfg(feature = "alloc")]
+pub fn scroll_view_up(lines: usize) {
+    let mut vga = VgaLock::lock();
+    vga.active_console().scroll_view_up(lines);
+    vga.flush();
+}
+
+/// Show `lines` more recent lines, back to live output at most.
+#[cfg(feature = "alloc")]
+pub fn scroll_view_down(lines: usize) {
+    let mut vga = VgaLock::lock();
+    vga.active_console().scroll_view_down(lines);
+    vga.flush();
+}
+
+/// Show live output again.
+#[cfg(feature = "alloc")]
+pub fn scroll_view_reset() {
+    let mut vga = VgaLock::lock();
+    vga.active_console().scroll_view_reset();
+    vga.flush();
+}
+
+/// Set whether new output shows live output again while the view is scrolled
+/// back, rather than being shown once it is.
+#[cfg(feature = "alloc")]
+pub fn set_scroll_view_snap(snap: bool) {
+    for console in VgaLock::lock().consoles.iter_mut() {
+        console.scrollback.snap = snap;
+    }
+}
+
+/// Write `text` from column `x` of row `y` in the given colors, without
+/// moving the cursor, to be shown by [`flush`].
+///
+/// The bytes are put as they are, control characters included, and those
+/// past the end of the row are dropped rather than wrapped.
+pub fn write_at(x: usize, y: usize, text: &str, fg: VgaTextColor, bg: VgaTextColor) {
+    let color = VgaTextColorCode::new(fg, bg);
+    let mut vga = VgaLock::lock();
+    let console = vga.output_console();
+    for (i, &ch) in text.as_bytes().iter().enumerate() {
+        console.put_cell(x.saturating_add(i), y, ch, color);
+    }
+}
+
+/// Put `ch` in `color` at column `x` of row `y`, without moving the cursor,
+/// to be shown by [`flush`].
+///
+/// Cells off the screen are ignored.
+pub fn put_cell(x: usize, y: usize, ch: u8, color: VgaTextColorCode) {
+    VgaLock::lock().output_console().put_cell(x, y, ch, color);
+}
+
+/// Fill the `w` by `h` cells from column `x` of row `y` with `ch` in
+/// `color`, without moving the cursor, to be shown by [`flush`].
+///
+/// The part off the screen is ignored.
+pub fn fill_rect(x: usize, y: usize, w: usize, h: usize, ch: u8, color: VgaTextColorCode) {
+    VgaLock::lock()
+        .output_console()
+        .fill_rect(x, y, w, h, ch, color);
+}
+
+/// Return the lines of text on the screen, without trailing spaces.
+///
+/// The bytes are taken as Latin-1 characters, though the VGA font shows
+/// code page 437 for those above ASCII.
+#[cfg(feature = "alloc")]
+pub fn dump() -> String {
+    VgaLock::lock().active_console().dump()
+}
+
+/// Return the lines of text on the screen like [`dump`], with the SGR
+/// sequences setting the colors of the characters.
+#[cfg(feature = "alloc")]
+pub fn dump_with_colors() -> String {
+    VgaLock::lock().active_console().dump_with_colors()
+}
+
+/// Switch to text mode `mode`, clearing the screen.
+pub fn set_mode(mode: VgaMode) {
+    let mut vga = VgaLock::lock();
+    vga.set_mode(mode);
+    vga.flush();
+}
+
+/// Keep the last row of the screen of every console for `text` in `color`,
+/// as a status line the text scrolls above, or show the new text if it is
+/// already kept.
+///
+/// The characters past the end of the row are dropped.
+pub fn set_status_line(text: &str, color: VgaTextColorCode) {
+    let mut vga = VgaLock::lock();
+    for console in vga.consoles.iter_mut() {
+        console.set_status_line(Some((text, color)));
+    }
+    vga.flush();
+}
 
-    if matches!(vga.process_char(c), VgaTextState::PutChar) {
-        vga.putchar(c);
+/// Give the row of the status line back to the text, blanking it.
+pub fn clear_status_line() {
+    let mut vga = VgaLock::lock();
+    for console in vga.consoles.iter_mut() {
+        console.set_status_line(None);
     }
+    vga.flush();
+}
+
+/// Show virtual console `n`, counting from 0, with the text written to it
+/// while another was shown.
+///
+/// There is no console `n` if it is not less than [`VGA_CONSOLES`].
+pub fn switch_console(n: usize) {
+    let mut vga = VgaLock::lock();
+    vga.consoles.switch_console(n);
+    vga.flush();
+}
+
+/// Write the output of [`putchar`], [`write_bytes`], [`clear`] and the cells
+/// of [`write_at`] and the like to virtual console `n`, shown or not.
+pub fn set_output_console(n: usize) {
+    if n < VGA_CONSOLES {
+        VgaLock::lock().consoles.output = n;
+    }
+}
+
+/// Write the output of [`print_debug`] to virtual console `n`, shown or not.
+pub fn set_debug_console(n: usize) {
+    if n < VGA_CONSOLES {
+        VgaLock::lock().consoles.debug_output = n;
+    }
+}
+
+/// Show or hide the blinking cursor while the output console is shown, as
+/// `\x1b[?25h` and `\x1b[?25l` do.
+///
+/// Each console keeps whether its cursor is shown, through switches between
+/// consoles and modes.
+pub fn set_cursor_visible(visible: bool) {
+    let mut vga = VgaLock::lock();
+    vga.output_console().cursor_visible = visible;
+    vga.flush();
+}
+
+/// Show or hide the blinking cursor, as [`set_cursor_visible`] does.
+pub fn cursor_enable(enable: bool) {
+    set_cursor_visible(enable);
 }
 
 pub fn getchar() -> Option<u8> {
     STDIN_BUFFER.lock().pop()
 }
 
-impl Write for VgaTextMode {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        let bytes = s.as_bytes();
-        for c in bytes {
-            putchar(*c);
-        }
-        Ok(())
-    }
+/// Returns the address the physical address `paddr` is mapped at.
+fn mapped(paddr: PhysAddr) -> usize {
+    #[cfg(feature = "paging")]
+    let addr = crate::mem::phys_to_virt(paddr);
+    #[cfg(not(feature = "paging"))]
+    let addr = paddr;
+    addr.as_usize()
+}
+
+/// Returns the rows of the VGA buffer, mapped at `addr`.
+///
+/// # Safety
+///
+/// `addr` must be where the VGA buffer is mapped.
+unsafe fn vga_buffer_at(addr: usize) -> &'static mut [VgaTextRow] {
+    core::slice::from_raw_parts_mut(addr as *mut VgaTextRow, VGA_MAX_HEIGHT)
 }
 
 pub(super) fn init_early() {
-    let mut vga = VGA.lock();
+    let mut vga = VgaLock::lock();
     unsafe {
-        vga.buffer
-            .init_by(&mut *(VGA_BASE_ADDR.as_usize() as *mut VgaTextBuffer));
-    }
-    for y in 0..VGA_BUFFER_HEIGHT {
-        for x in 0..VGA_BUFFER_WIDTH {
-            vga.buffer.chars[y][x] = VgaTextChar(b' ', vga.current_color);
-        }
+        vga.buffer.init_by(vga_buffer_at(VGA_BASE_ADDR.as_usize()));
     }
+    vga.active_console().clear();
+    vga.cursor_enable(true);
+    vga.set_blink(BLINK);
+    vga.flush();
 }
 
 pub(super) fn init() {
     #[cfg(feature = "paging")]
     {
-        use crate::mem::phys_to_virt;
-
-        let mut vga = VGA.lock();
+        let mut vga = VgaLock::lock();
         vga.buffer = LazyInit::new();
         unsafe {
-            vga.buffer
-                .init_by(&mut *(phys_to_virt(VGA_BASE_ADDR).as_usize() as *mut VgaTextBuffer));
+            vga.buffer.init_by(vga_buffer_at(mapped(VGA_BASE_ADDR)));
         }
     }
+    // the heap is ready by now
+    #[cfg(feature = "alloc")]
+    for console in VgaLock::lock().consoles.iter_mut() {
+        console.scrollback.enable();
+    }
 }
 
-/// Set the maximum debug level.
+/// Show the panic message `args` in white on red at the bottom of the screen,
+/// with the cursor after it.
 ///
-/// `level` should be one of 0, 1, 2, 3.
-pub fn set_max_level(level: u8) {
-    unsafe {
-        if level > 3 {
-            panic!("LEVEL_DEBUG INPUT WRONG RANGE!");
+/// The console is locked if it can be, waiting a while for another CPU
+/// holding it. If this CPU holds it, as when it panicked while writing, or
+/// the other CPU does not let it go, the message is written straight to the
+/// VGA memory instead, over the bottom rows of the screen. The console and
+/// the cursor are then left as they are, since the code holding the lock may
+/// be in the middle of changing them.
+pub fn panic_banner(args: fmt::Arguments) {
+    let this_cpu = crate::cpu::this_cpu_id();
+    if !VGA.is_held_by(this_cpu) {
+        for _ in 0..PANIC_LOCK_TRIES {
+            if let Some(guard) = VGA.try_lock(this_cpu) {
+                let mut vga = VgaLock(guard);
+                vga.panic_banner(args);
+                vga.flush();
+                return;
+            }
+            core::hint::spin_loop();
         }
-        LEVEL_DEBUG = level;
     }
+    // Another CPU still holding the lock may write to the VGA memory at the
+    // same time, garbling the message; `VGA` itself is not touched.
+    let height = VGA_HEIGHT.load(Ordering::Relaxed);
+    let mut banner = RawBanner::new(height.saturating_sub(PANIC_BANNER_ROWS), height);
+    let _ = banner.write_fmt(args);
 }
 
-pub fn print_debug(level: u8, args: fmt::Arguments) -> fmt::Result{
-    unsafe {
-        if level > LEVEL_DEBUG {
-            return Err(Error);
-        }
+/// Return the number of bytes written while this CPU held the lock of the
+/// console, as from an exception taken while writing, and so written to the
+/// output console once it was unlocked.
+pub fn deferred_bytes() -> usize {
+    DEFERRED_BYTES.load(Ordering::Relaxed)
+}
+
+/// Return the number of bytes written while this CPU held the lock of the
+/// console that there was no room to keep, and so were lost.
+pub fn dropped_bytes() -> usize {
+    DROPPED_BYTES.load(Ordering::Relaxed)
+}
+
+/// Set whether the output is written to the serial port as well, as for a
+/// machine without a screen.
+///
+/// It is by default if the `serial-mirror` feature is enabled.
+pub fn set_serial_mirror(enable: bool) {
+    SERIAL_MIRROR.store(enable, Ordering::Relaxed);
+}
+
+/// Return whether the output is written to the serial port as well.
+pub fn serial_mirror() -> bool {
+    SERIAL_MIRROR.load(Ordering::Relaxed)
+}
+
+/// Set the maximum debug level.
+///
+/// `level` should be one of 0, 1, 2, 3, 4; another is given back as the
+/// error, and the level is left as it was.
+pub fn set_max_level(level: u8) -> Result<(), u8> {
+    LEVELS.set_max(level)
+}
+
+/// Return the maximum debug level.
+pub fn max_level() -> u8 {
+    LEVELS.max()
+}
+
+/// Write the messages of [`print_debug`] of `level` after `tag` in `fg` on
+/// `bg`, rather than the tag of the level and its color, e.g. for level 2 to
+/// be warnings as `"[WARN]  "` in red.
+///
+/// The tag is written as it is, with the spaces after it. `level` should be
+/// one of 1, 2, 3, 4; another is given back as the error.
+pub fn set_level_style(
+    level: u8,
+    fg: VgaTextColor,
+    bg: VgaTextColor,
+    tag: &'static str,
+) -> Result<(), u8> {
+    LEVELS.set_style(level, fg, bg, tag)
+}
+
+/// Set whether the messages of [`print_debug`] start with the time since
+/// boot and the current CPU, as `[   12.345678 cpu0] `.
+pub fn set_debug_prefix(enable: bool) {
+    PREFIX_DEBUG.store(enable, Ordering::Relaxed);
+}
+
+/// Return whether the messages of [`print_debug`] start with the time since
+/// boot and the current CPU.
+pub fn debug_prefix() -> bool {
+    PREFIX_DEBUG.load(Ordering::Relaxed)
+}
+
+/// Whether [`print_debug`] printed a message or left it out for its level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugPrint {
+    Printed,
+    Filtered,
+}
+
+/// Print `args` to the debug console after the tag of `level` in its color,
+/// unless `level` is 0 or above the maximum debug level.
+///
+/// The errors are those formatting `args`; messages left out are not.
+pub fn print_debug(level: u8, args: fmt::Arguments) -> Result<DebugPrint, fmt::Error> {
+    if level == 0 || level > LEVELS.max() {
+        return Ok(DebugPrint::Filtered);
     }
-    let mut vga = VGA.lock();
-    match level {
-        1 => {
-            vga.set_color(Some(VgaTextColorCode::new(
-                VgaTextColor::LightGreen,
-                VgaTextColor::Black,
-            )));
-            let _ = vga.write_str("[INFO]  ");
+    let Some(mut vga) = VgaLock::try_lock() else {
+        let prefix = DebugPrefix::now();
+        return match this_spill() {
+            Some(mut spill) => write_debug_sgr(&mut *spill, level, prefix, args),
+            None => write_debug_sgr(&mut Dropped, level, prefix, args),
         }
-        2 => {
-            vga.set_color(Some(VgaTextColorCode::new(
-                VgaTextColor::LightBlue,
-                VgaTextColor::Black,
-            )));
-            let _ = vga.write_str("[DEV]   ");
-        }
-        3 => {
-            vga.set_color(Some(VgaTextColorCode::new(
-                VgaTextColor::Yellow,
-                VgaTextColor::Black,
-            )));
-            let _ = vga.write_str("[DEBUG] ");
-        },
-        _ => return Err(Error)
-    }
-    vga.set_color(Some(VgaTextColorCode::new(
-        VgaTextColor::White,
-        VgaTextColor::Black,
-    )));
-    vga.write_fmt(args)
-}
\ No newline at end of file
+        .map(|()| DebugPrint::Printed);
+    };
+    // taken with the console locked, for the times to be in order
+    let prefix = DebugPrefix::now();
+    #[cfg(feature = "log-buffer")]
+    let _ = crate::log_buffer::write_message(level, format_args!("{}{}", prefix, args));
+    if SERIAL_MIRROR.load(Ordering::Relaxed) {
+        let _ = write_debug_sgr(&mut SerialWriter, level, prefix, args);
+    }
+    let (tag, color) = LEVELS.style(level);
+    let result = vga
+        .consoles
+        .debug_console()
+        .print_debug(prefix, tag, color, args);
+    vga.flush();
+    result.map(|()| DebugPrint::Printed)
+}
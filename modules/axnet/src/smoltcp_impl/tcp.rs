@@ -1,8 +1,10 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
-use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use core::time::Duration;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axhal::time::current_time;
 use axio::PollState;
 use axsync::Mutex;
 
@@ -41,6 +43,10 @@ pub struct TcpSocket {
     local_addr: UnsafeCell<IpEndpoint>,
     peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
+    /// Timeout of `recv` in nanoseconds, or 0 for none.
+    recv_timeout: AtomicU64,
+    /// Timeout of `send` in nanoseconds, or 0 for none.
+    send_timeout: AtomicU64,
 }
 
 unsafe impl Sync for TcpSocket {}
@@ -54,6 +60,8 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
+            recv_timeout: AtomicU64::new(0),
+            send_timeout: AtomicU64::new(0),
         }
     }
 
@@ -69,6 +77,8 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
+            recv_timeout: AtomicU64::new(0),
+            send_timeout: AtomicU64::new(0),
         }
     }
 
@@ -115,9 +125,50 @@ impl TcpSocket {
         self.nonblock.store(nonblocking, Ordering::Release);
     }
 
+    /// Returns the timeout of [`recv`](Self::recv), if any.
+    #[inline]
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        nanos_to_timeout(self.recv_timeout.load(Ordering::Acquire))
+    }
+
+    /// Sets the timeout of [`recv`](Self::recv), and of
+    /// [`accept`](Self::accept) on a listening socket.
+    ///
+    /// A blocking `recv` or `accept` that cannot complete within `timeout`
+    /// fails with [`Err(TimedOut)`](AxError::TimedOut). `None` means to wait
+    /// forever.
+    #[inline]
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) {
+        self.recv_timeout
+            .store(timeout_to_nanos(timeout), Ordering::Release);
+    }
+
+    /// Returns the timeout of [`send`](Self::send), if any.
+    #[inline]
+    pub fn send_timeout(&self) -> Option<Duration> {
+        nanos_to_timeout(self.send_timeout.load(Ordering::Acquire))
+    }
+
+    /// Sets the timeout of [`send`](Self::send), and of
+    /// [`connect`](Self::connect).
+    ///
+    /// A blocking `send` or `connect` that cannot complete within `timeout`
+    /// fails with [`Err(TimedOut)`](AxError::TimedOut). `None` means to wait
+    /// forever.
+    #[inline]
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) {
+        self.send_timeout
+            .store(timeout_to_nanos(timeout), Ordering::Release);
+    }
+
     /// Connects to the given address and port.
     ///
     /// The local port is generated automatically.
+    ///
+    /// A blocking `connect` waits for the connection to be established up to
+    /// the [send timeout](Self::set_send_timeout). If it times out, the
+    /// connection is still being established, as after a non-blocking
+    /// `connect`.
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
             // SAFETY: no other threads can read or write these fields.
@@ -160,7 +211,7 @@ impl TcpSocket {
         if self.is_nonblocking() {
             Err(AxError::WouldBlock)
         } else {
-            self.block_on(|| {
+            self.block_on(self.send_timeout(), || {
                 let PollState { writable, .. } = self.poll_connect()?;
                 if !writable {
                     Err(AxError::WouldBlock)
@@ -179,7 +230,13 @@ impl TcpSocket {
     ///
     /// It's must be called before [`listen`](Self::listen) and
     /// [`accept`](Self::accept).
+    ///
+    /// IPv6 addresses are rejected with
+    /// [`Err(Unsupported)`](AxError::Unsupported), as the stack only speaks IPv4.
     pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
+        if local_addr.is_ipv6() {
+            return ax_err!(Unsupported, "socket bind() failed: IPv6 not supported");
+        }
         self.update_state(STATE_CLOSED, STATE_CLOSED, || {
             // TODO: check addr is available
             if local_addr.port() == 0 {
@@ -219,7 +276,9 @@ impl TcpSocket {
     /// Accepts a new connection.
     ///
     /// This function will block the calling thread until a new TCP connection
-    /// is established. When established, a new [`TcpSocket`] is returned.
+    /// is established, or the [receive timeout](Self::set_recv_timeout) of
+    /// this socket has elapsed. When established, a new [`TcpSocket`] is
+    /// returned.
     ///
     /// It's must be called after [`bind`](Self::bind) and [`listen`](Self::listen).
     pub fn accept(&self) -> AxResult<TcpSocket> {
@@ -229,7 +288,7 @@ impl TcpSocket {
 
         // SAFETY: `self.local_addr` should be initialized after `bind()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
+        self.block_on(self.recv_timeout(), || {
             let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
             Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
@@ -279,7 +338,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(self.recv_timeout(), || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() {
                     // not open
@@ -312,7 +371,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(self.send_timeout(), || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() || !socket.may_send() {
                     // closed by remote
@@ -471,21 +530,31 @@ impl TcpSocket {
 
     /// Block the current thread until the given function completes or fails.
     ///
-    /// If the socket is non-blocking, it calls the function once and returns
-    /// immediately. Otherwise, it may call the function multiple times if it
-    /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// If the socket is non-blocking, it polls the interfaces, calls the
+    /// function once and returns immediately. Otherwise, it may call the
+    /// function multiple times if it returns
+    /// [`Err(WouldBlock)`](AxError::WouldBlock), and fails with
+    /// [`Err(TimedOut)`](AxError::TimedOut) once `timeout` (if any) has elapsed.
+    fn block_on<F, T>(&self, timeout: Option<Duration>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
+            // Nothing else drives the interfaces while the caller polls.
+            SOCKET_SET.poll_interfaces();
             f()
         } else {
+            let deadline = timeout.map(|t| current_time() + t);
             loop {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if deadline.is_some_and(|d| current_time() >= d) {
+                            return Err(AxError::TimedOut);
+                        }
+                        axtask::yield_now()
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -493,6 +562,18 @@ impl TcpSocket {
     }
 }
 
+fn timeout_to_nanos(timeout: Option<Duration>) -> u64 {
+    timeout.map_or(0, |t| (t.as_nanos() as u64).max(1))
+}
+
+fn nanos_to_timeout(nanos: u64) -> Option<Duration> {
+    if nanos == 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(nanos))
+    }
+}
+
 impl Drop for TcpSocket {
     fn drop(&mut self) {
         self.shutdown().ok();
@@ -73,7 +73,13 @@ impl UdpSocket {
     ///
     /// It's must be called before [`send_to`](Self::send_to) and
     /// [`recv_from`](Self::recv_from).
+    ///
+    /// IPv6 addresses are rejected with
+    /// [`Err(Unsupported)`](AxError::Unsupported), as the stack only speaks IPv4.
     pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
+        if local_addr.is_ipv6() {
+            return ax_err!(Unsupported, "socket bind() failed: IPv6 not supported");
+        }
         let mut self_local_addr = self.local_addr.write();
 
         if local_addr.port() == 0 {
@@ -257,6 +263,8 @@ impl UdpSocket {
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
+            // Nothing else drives the interfaces while the caller polls.
+            SOCKET_SET.poll_interfaces();
             f()
         } else {
             loop {
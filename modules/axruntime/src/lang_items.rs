@@ -3,5 +3,7 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("{}", info);
+    // last, so that no output scrolls the banner away or writes over it
+    axhal::console::panic_banner(format_args!("{}", info));
     axhal::misc::terminate()
 }
@@ -126,10 +126,17 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     axlog::init();
     axlog::set_max_level(option_env!("AX_LOG").unwrap_or("")); // no effect if set `log-level-*` features
     info!("Logging is enabled.");
-    axhal::console::set_max_level(option_env!("AX_DEBUG")
-                            .unwrap_or("3")
-                            .parse::<u8>()
-                            .expect("Wanted a number"));
+    let debug_level = option_env!("AX_DEBUG").unwrap_or("3");
+    if !matches!(
+        debug_level.parse().map(axhal::console::set_max_level),
+        Ok(Ok(()))
+    ) {
+        warn!(
+            "Invalid debug level {:?}, keeping {}.",
+            debug_level,
+            axhal::console::max_level()
+        );
+    }
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
 
     info!("Found physcial memory regions:");
@@ -173,8 +173,8 @@ pub fn __print_impl(args: core::fmt::Arguments) {
     }
 }
 
-
 #[doc(hidden)]
-pub fn __print_impl_debug(level:u8, args: core::fmt::Arguments) {
+pub fn __print_impl_debug(level: u8, args: core::fmt::Arguments) {
+    // a message that fails to format is dropped, rather than panicking
     let _ = arceos_api::stdio::ax_console_write_fmt_debug(level, args);
 }
@@ -43,6 +43,8 @@
 //!     - `log-level-off`: Disable all logging.
 //!     - `log-level-error`, `log-level-warn`, `log-level-info`, `log-level-debug`,
 //!       `log-level-trace`: Keep logging only at the specified level or higher.
+//!     - `log-buffer`: Keep the console output in a ring buffer to be read later.
+//!     - `serial-mirror`: Write the output of the VGA console to the serial port as well.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 
@@ -46,3 +46,11 @@ macro_rules! pdebug {
         $crate::io::__print_impl_debug(3, format_args!("{}\n", format_args!($($arg)*)));
     }
 }
+
+/// Prints to the standard output with ptrace log.
+#[macro_export]
+macro_rules! ptrace {
+    ($($arg:tt)*) => {
+        $crate::io::__print_impl_debug(4, format_args!("{}\n", format_args!($($arg)*)));
+    }
+}
@@ -1,5 +1,6 @@
 use super::{SocketAddr, ToSocketAddrs};
 use crate::io::{self, prelude::*};
+use crate::time::Duration;
 
 use arceos_api::net::{self as api, AxTcpSocketHandle};
 
@@ -29,6 +30,24 @@ impl TcpStream {
         })
     }
 
+    /// Opens a TCP connection to a remote host with a timeout.
+    ///
+    /// Unlike [`connect`](Self::connect), `connect_timeout` takes a single
+    /// [`SocketAddr`] since a timeout must be applied to each address. It
+    /// fails with an error of kind `TimedOut` if the connection is not
+    /// established within `timeout`. An [`Err`] is returned if the zero
+    /// [`Duration`] is passed to this method.
+    pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        if timeout == Duration::ZERO {
+            return Err(io::Error::InvalidInput);
+        }
+        let socket = api::ax_tcp_socket();
+        api::ax_tcp_set_send_timeout(&socket, Some(timeout))?;
+        api::ax_tcp_connect(&socket, *addr)?;
+        api::ax_tcp_set_send_timeout(&socket, None)?;
+        Ok(TcpStream(socket))
+    }
+
     /// Returns the socket address of the local half of this TCP connection.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         api::ax_tcp_socket_addr(&self.0)
@@ -43,6 +62,44 @@ impl TcpStream {
     pub fn shutdown(&self) -> io::Result<()> {
         api::ax_tcp_shutdown(&self.0)
     }
+
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then [`read`] calls will block
+    /// indefinitely. Otherwise a [`read`] that cannot complete in time fails
+    /// with an error of kind `TimedOut`. An [`Err`] is returned if the zero
+    /// [`Duration`] is passed to this method.
+    ///
+    /// [`read`]: Read::read
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        if dur == Some(Duration::ZERO) {
+            return Err(io::Error::InvalidInput);
+        }
+        api::ax_tcp_set_recv_timeout(&self.0, dur)
+    }
+
+    /// Sets the write timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then [`write`] calls will block
+    /// indefinitely. Otherwise a [`write`] that cannot complete in time fails
+    /// with an error of kind `TimedOut`. An [`Err`] is returned if the zero
+    /// [`Duration`] is passed to this method.
+    ///
+    /// [`write`]: Write::write
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        if dur == Some(Duration::ZERO) {
+            return Err(io::Error::InvalidInput);
+        }
+        api::ax_tcp_set_send_timeout(&self.0, dur)
+    }
+
+    /// Moves this TCP stream into or out of nonblocking mode.
+    ///
+    /// In nonblocking mode, `read` and `write` return an error of kind
+    /// `WouldBlock` instead of waiting when they cannot make progress.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        api::ax_tcp_set_nonblocking(&self.0, nonblocking)
+    }
 }
 
 impl Read for TcpStream {
@@ -102,4 +159,23 @@ impl TcpListener {
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
         api::ax_tcp_accept(&self.0).map(|(a, b)| (TcpStream(a), b))
     }
+
+    /// Returns whether a connection is waiting to be accepted, so that
+    /// [`TcpListener::accept`] would not block.
+    ///
+    /// The network interfaces are polled first, so that connections which
+    /// have just arrived are seen.
+    pub fn accept_ready(&self) -> io::Result<bool> {
+        api::ax_poll_interfaces()?;
+        Ok(api::ax_tcp_poll(&self.0)?.readable)
+    }
+
+    /// Moves this TCP listener into or out of nonblocking mode.
+    ///
+    /// In nonblocking mode, [`TcpListener::accept`] returns an error of kind
+    /// `WouldBlock` instead of waiting when there is no pending connection.
+    /// Accepted streams are always in blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        api::ax_tcp_set_nonblocking(&self.0, nonblocking)
+    }
 }
@@ -0,0 +1,86 @@
+//! A condition variable that blocks in a wait queue.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use arceos_api::task::{self as api, AxWaitQueueHandle};
+
+use super::MutexGuard;
+
+/// A condition variable, similar to
+/// [`std::sync::Condvar`](https://doc.rust-lang.org/std/sync/struct.Condvar.html).
+///
+/// Waiting tasks block in a wait queue until they are notified. Each
+/// notification bumps a counter that waiting tasks check before they block,
+/// so a notification sent between unlocking the mutex and blocking is not
+/// lost. Like the `std` one, waits may end without a notification, so the
+/// condition must be checked again.
+pub struct Condvar {
+    wq: AxWaitQueueHandle,
+    notifications: AtomicU32,
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            notifications: AtomicU32::new(0),
+        }
+    }
+
+    /// Unlocks the mutex of `guard` and blocks the current task until this
+    /// condition variable is notified, then locks the mutex again.
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex();
+        let seen = self.notifications.load(Ordering::Acquire);
+        drop(guard);
+        api::ax_wait_queue_wait(
+            &self.wq,
+            || self.notifications.load(Ordering::Acquire) != seen,
+            None,
+        );
+        mutex.lock()
+    }
+
+    /// Blocks the current task while `condition` returns `true` for the value
+    /// guarded by `guard`, waiting for a notification before each check
+    /// after the first.
+    pub fn wait_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Wakes up one task blocked on this condition variable.
+    pub fn notify_one(&self) {
+        self.notifications.fetch_add(1, Ordering::Release);
+        api::ax_wait_queue_wake(&self.wq, 1);
+    }
+
+    /// Wakes up all tasks blocked on this condition variable.
+    pub fn notify_all(&self) {
+        self.notifications.fetch_add(1, Ordering::Release);
+        api::ax_wait_queue_wake(&self.wq, u32::MAX);
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Condvar { .. }")
+    }
+}